@@ -1,12 +1,243 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Directory containing libmpv, for installs pkg-config doesn't know about
+const MPV_LIB_DIR: &str = "MPV_LIB_DIR";
+
+// Where Homebrew lives on Apple Silicon and on Intel Macs
+const HOMEBREW_PREFIXES: &[&str] = &["/opt/homebrew", "/usr/local"];
+
+// The runtime library from the Windows libmpv dev package, and where we stage it so
+// tauri.windows.conf.json can bundle it next to the executable. The bundler only looks
+// in the source tree, so this is the one output that can't go in OUT_DIR.
+const MPV_DLL: &str = "libmpv-2.dll";
+const BUNDLED_DLL_DIR: &str = "lib";
+
+// Merged over tauri.conf.json by tauri-build, as the Tauri CLI's --config is
+const TAURI_CONFIG: &str = "TAURI_CONFIG";
+
 fn main() {
     // Tell Rust about the mobile cfg
     println!("cargo::rustc-check-cfg=cfg(mobile)");
+    // objc 0.2's msg_send!/sel! expand to checks of the retired `cargo-clippy` feature
+    println!("cargo::rustc-check-cfg=cfg(feature, values(\"cargo-clippy\"))");
 
-    // libmpv-sys should handle the linking automatically
-    // but we can help it find the right path
-    if cfg!(target_os = "macos") {
-        println!("cargo:rustc-link-search=native=/opt/homebrew/Cellar/mpv/0.40.0_1/lib");
+    let windows = env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows");
+    // The stub backend doesn't link libmpv
+    if env::var_os("CARGO_FEATURE_REAL_MPV").is_some() {
+        println!("cargo:rerun-if-env-changed={}", MPV_LIB_DIR);
+        let lib_dir = find_libmpv();
+        if windows {
+            prepare_windows(lib_dir.as_deref());
+        }
+    } else if windows {
+        skip_bundled_dll();
     }
 
     tauri_build::build();
 }
+
+// libmpv-sys emits `-lmpv` itself; all we do is point the linker at the directory,
+// which is returned when known. Build scripts run on the host, so the target OS comes
+// from Cargo, not cfg!.
+fn find_libmpv() -> Option<PathBuf> {
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let mut tried = Vec::new();
+
+    match pkg_config::Config::new().cargo_metadata(false).probe("mpv") {
+        Ok(library) => {
+            for dir in &library.link_paths {
+                link_search(dir);
+            }
+            return library.link_paths.into_iter().next();
+        }
+        Err(e) => tried.push(format!(
+            "pkg-config mpv: {}",
+            e.to_string().lines().next().unwrap_or("failed")
+        )),
+    }
+
+    match env::var_os(MPV_LIB_DIR) {
+        Some(dir) => {
+            let dir = PathBuf::from(dir);
+            if has_libmpv(&dir) {
+                link_search(&dir);
+                return Some(dir);
+            }
+            tried.push(format!(
+                "{}={}: no libmpv there",
+                MPV_LIB_DIR,
+                dir.display()
+            ));
+        }
+        None => tried.push(format!("{}: not set", MPV_LIB_DIR)),
+    }
+
+    if target_os == "macos" {
+        if let Some(dir) = homebrew_lib_dir(&mut tried) {
+            link_search(&dir);
+            return Some(dir);
+        }
+    }
+
+    panic!(
+        "\n\nCould not find libmpv. Tried:\n  - {}\n\n\
+         Install mpv (e.g. `brew install mpv` or your distro's libmpv-dev package) or set \
+         {} to the directory containing libmpv (on Windows, the unpacked \
+         mpv-dev package).\n",
+        tried.join("\n  - "),
+        MPV_LIB_DIR
+    );
+}
+
+fn link_search(dir: &Path) {
+    println!("cargo:rustc-link-search=native={}", dir.display());
+}
+
+fn has_libmpv(dir: &Path) -> bool {
+    [
+        "libmpv.dylib",
+        "libmpv.so",
+        "libmpv.a",
+        "mpv.lib",
+        "libmpv.dll.a",
+    ]
+    .iter()
+    .any(|name| dir.join(name).exists())
+}
+
+// `brew --prefix mpv` first, then the newest version in either Cellar
+fn homebrew_lib_dir(tried: &mut Vec<String>) -> Option<PathBuf> {
+    let output = Command::new("brew").args(["--prefix", "mpv"]).output();
+    match output {
+        Ok(output) if output.status.success() => {
+            let prefix = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            let dir = Path::new(&prefix).join("lib");
+            if has_libmpv(&dir) {
+                return Some(dir);
+            }
+            tried.push(format!("brew --prefix mpv: no libmpv in {}", dir.display()));
+        }
+        Ok(_) => tried.push("brew --prefix mpv: mpv is not installed".to_string()),
+        Err(e) => tried.push(format!("brew --prefix mpv: {}", e)),
+    }
+
+    for prefix in HOMEBREW_PREFIXES {
+        let cellar = Path::new(prefix).join("Cellar/mpv");
+        let mut versions: Vec<PathBuf> = std::fs::read_dir(&cellar)
+            .map(|entries| entries.filter_map(|e| e.ok().map(|e| e.path())).collect())
+            .unwrap_or_default();
+        versions.sort_by_key(|path| version_key(path));
+        if let Some(dir) = versions
+            .into_iter()
+            .rev()
+            .map(|version| version.join("lib"))
+            .find(|dir| has_libmpv(dir))
+        {
+            return Some(dir);
+        }
+        tried.push(format!("{}: no mpv versions found", cellar.display()));
+    }
+    None
+}
+
+// "0.40.0_2" -> [0, 40, 0, 2], so versions sort numerically
+fn version_key(path: &Path) -> Vec<u64> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+        .split(|c: char| !c.is_ascii_digit())
+        .filter_map(|part| part.parse().ok())
+        .collect()
+}
+
+// MSVC links against mpv.lib. The Windows dev package ships libmpv.dll.a (enough for the
+// GNU toolchain) and mpv.def, from which lib.exe can build the import library.
+fn prepare_windows(lib_dir: Option<&Path>) {
+    let Some(lib_dir) = lib_dir else {
+        panic!(
+            "\n\nlibmpv was found, but not its directory; set {} to the unpacked \
+             mpv-dev package so {} can be bundled.\n",
+            MPV_LIB_DIR, MPV_DLL
+        );
+    };
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    if env::var("CARGO_CFG_TARGET_ENV").as_deref() == Ok("msvc")
+        && !lib_dir.join("mpv.lib").exists()
+    {
+        generate_import_lib(lib_dir, &out_dir);
+        link_search(&out_dir);
+    }
+
+    // MinGW-style packages keep the DLL in bin/ next to lib/
+    let candidates = [lib_dir.join(MPV_DLL), lib_dir.join("../bin").join(MPV_DLL)];
+    let Some(dll) = candidates.iter().find(|path| path.exists()) else {
+        panic!(
+            "\n\nCould not find {} in {} or its ../bin; it has to ship next to the \
+             executable.\n",
+            MPV_DLL,
+            lib_dir.display()
+        );
+    };
+    println!("cargo:rerun-if-changed={}", dll.display());
+
+    // Bundled by `tauri build`; tauri-build copies bundle resources beside the binary
+    // too, so `tauri dev` runs as well
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let dir = manifest_dir.join(BUNDLED_DLL_DIR);
+    if let Err(e) =
+        std::fs::create_dir_all(&dir).and_then(|_| std::fs::copy(dll, dir.join(MPV_DLL)))
+    {
+        panic!("Failed to copy {} to {}: {}", MPV_DLL, dir.display(), e);
+    }
+}
+
+// The stub has no DLL to stage, so tauri.windows.conf.json's resource isn't there. Drop
+// it from what tauri-build sees, keeping anything already passed in TAURI_CONFIG.
+fn skip_bundled_dll() {
+    let mut config: serde_json::Value = env::var(TAURI_CONFIG)
+        .ok()
+        .and_then(|config| serde_json::from_str(&config).ok())
+        .filter(serde_json::Value::is_object)
+        .unwrap_or_else(|| serde_json::json!({}));
+    // A null removes the key when tauri-build merges this over the config files
+    config["bundle"]["resources"] = serde_json::Value::Null;
+    env::set_var(TAURI_CONFIG, config.to_string());
+}
+
+fn generate_import_lib(lib_dir: &Path, out_dir: &Path) {
+    let def = lib_dir.join("mpv.def");
+    if !def.exists() {
+        panic!(
+            "\n\nNeither mpv.lib nor mpv.def is in {}. Generate the import library from a \
+             Developer Command Prompt with\n  lib /def:mpv.def /name:{} /out:mpv.lib \
+             /machine:x64\n",
+            lib_dir.display(),
+            MPV_DLL
+        );
+    }
+
+    let machine = match env::var("CARGO_CFG_TARGET_ARCH").as_deref() {
+        Ok("aarch64") => "ARM64",
+        Ok("x86") => "X86",
+        _ => "X64",
+    };
+    let status = Command::new("lib")
+        .arg(format!("/def:{}", def.display()))
+        .arg(format!("/name:{}", MPV_DLL))
+        .arg(format!("/out:{}", out_dir.join("mpv.lib").display()))
+        .arg(format!("/machine:{}", machine))
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => panic!("lib.exe failed to build mpv.lib from mpv.def: {}", status),
+        Err(e) => panic!(
+            "Failed to run lib.exe to build mpv.lib ({}); run cargo from a Developer \
+             Command Prompt or put a prebuilt mpv.lib in {}",
+            e,
+            lib_dir.display()
+        ),
+    }
+}
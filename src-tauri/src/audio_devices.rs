@@ -0,0 +1,97 @@
+// Audio outputs: which devices mpv can play to, and following a chosen one as it's
+// unplugged and plugged back in. While the chosen device is gone, audio goes to the
+// system default ("auto") so playback carries on; once it's back, audio returns to it.
+
+use serde::Serialize;
+
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// Observed for hot-plugging; mpv refreshes the list as devices come and go
+pub const LIST_PROPERTY: &str = "audio-device-list";
+pub const DEVICE_PROPERTY: &str = "audio-device";
+
+// mpv's name for whatever the system's default output is
+pub const AUTO: &str = "auto";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AudioDevice {
+    // What audio-device takes, e.g. "pulse/alsa_output.usb-headset"
+    pub name: String,
+    pub description: String,
+    pub current: bool,
+}
+
+// Read field by field, like the track list
+pub fn read(handle: &MpvHandle) -> Vec<AudioDevice> {
+    let current = handle
+        .get::<String>(DEVICE_PROPERTY)
+        .unwrap_or_else(|_| AUTO.to_string());
+    let count = handle
+        .get::<i64>(&format!("{}/count", LIST_PROPERTY))
+        .unwrap_or(0);
+    (0..count.max(0))
+        .filter_map(|i| {
+            let field = |field: &str| {
+                handle
+                    .get::<String>(&format!("{}/{}/{}", LIST_PROPERTY, i, field))
+                    .ok()
+            };
+            let name = field("name")?;
+            Some(AudioDevice {
+                description: field("description").unwrap_or_else(|| name.clone()),
+                current: name == current,
+                name,
+            })
+        })
+        .collect()
+}
+
+// The device to switch to now that the list is `devices`, if any: away from the
+// `preferred` one while it's missing, and back to it once it returns
+pub fn follow<'a>(
+    preferred: Option<&'a str>,
+    current: &str,
+    devices: &[AudioDevice],
+) -> Option<&'a str> {
+    let preferred = preferred.filter(|&preferred| preferred != AUTO)?;
+    let present = devices.iter().any(|device| device.name == preferred);
+    if present && current != preferred {
+        Some(preferred)
+    } else if !present && current == preferred {
+        Some(AUTO)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_chosen_device_is_followed_through_replugging() {
+        let device = |name: &str| AudioDevice {
+            name: name.to_string(),
+            description: name.to_string(),
+            current: false,
+        };
+        let plugged = [device("auto"), device("pulse/headphones")];
+        let unplugged = [device("auto")];
+
+        assert_eq!(
+            follow(Some("pulse/headphones"), "pulse/headphones", &unplugged),
+            Some("auto")
+        );
+        assert_eq!(follow(Some("pulse/headphones"), "auto", &unplugged), None);
+        assert_eq!(
+            follow(Some("pulse/headphones"), "auto", &plugged),
+            Some("pulse/headphones")
+        );
+        assert_eq!(
+            follow(Some("pulse/headphones"), "pulse/headphones", &plugged),
+            None
+        );
+        assert_eq!(follow(None, "auto", &unplugged), None);
+        assert_eq!(follow(Some("auto"), "auto", &plugged), None);
+    }
+}
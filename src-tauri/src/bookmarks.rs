@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+const BOOKMARKS_FILE: &str = "bookmarks.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub id: u64,
+    pub path: String,
+    pub time: f64,
+    pub label: Option<String>,
+    // Seconds since the Unix epoch
+    pub created_at: u64,
+}
+
+// A bookmark as listed to the frontend
+#[derive(Debug, Clone, Serialize)]
+pub struct BookmarkEntry {
+    #[serde(flatten)]
+    pub bookmark: Bookmark,
+    // The file no longer exists at its recorded path
+    pub missing: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct BookmarkFile {
+    // Ids are never reused, even after removal
+    next_id: u64,
+    bookmarks: Vec<Bookmark>,
+}
+
+fn bookmarks_path(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map(|dir| dir.join(BOOKMARKS_FILE))
+        .map_err(|e| format!("No app data directory: {}", e))
+}
+
+fn read(app: &AppHandle) -> Result<BookmarkFile, String> {
+    let path = bookmarks_path(app)?;
+    match std::fs::read_to_string(&path) {
+        Ok(contents) => {
+            serde_json::from_str(&contents).map_err(|e| format!("Bookmarks file is corrupt: {}", e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BookmarkFile::default()),
+        Err(e) => Err(format!("Failed to read bookmarks: {}", e)),
+    }
+}
+
+fn write(app: &AppHandle, file: &BookmarkFile) -> Result<(), String> {
+    let path = bookmarks_path(app)?;
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    let contents = serde_json::to_string_pretty(file)
+        .map_err(|e| format!("Failed to serialize bookmarks: {}", e))?;
+    std::fs::write(path, contents).map_err(|e| format!("Failed to write bookmarks: {}", e))
+}
+
+pub fn add(
+    app: &AppHandle,
+    path: String,
+    time: f64,
+    label: Option<String>,
+) -> Result<Bookmark, String> {
+    let mut file = read(app)?;
+    let created_at = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+
+    let bookmark = Bookmark {
+        id: file.next_id,
+        path,
+        time,
+        label,
+        created_at,
+    };
+    file.next_id += 1;
+    file.bookmarks.push(bookmark.clone());
+    write(app, &file)?;
+    Ok(bookmark)
+}
+
+// Bookmarks for `path` ordered by position in the file
+pub fn list(app: &AppHandle, path: &str) -> Result<Vec<BookmarkEntry>, String> {
+    let mut bookmarks: Vec<Bookmark> = read(app)?
+        .bookmarks
+        .into_iter()
+        .filter(|b| b.path == path)
+        .collect();
+    bookmarks.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+    Ok(bookmarks
+        .into_iter()
+        .map(|bookmark| BookmarkEntry {
+            missing: !is_reachable(&bookmark.path),
+            bookmark,
+        })
+        .collect())
+}
+
+pub fn get(app: &AppHandle, id: u64) -> Result<Bookmark, String> {
+    read(app)?
+        .bookmarks
+        .into_iter()
+        .find(|b| b.id == id)
+        .ok_or_else(|| format!("No bookmark with id {}", id))
+}
+
+pub fn remove(app: &AppHandle, id: u64) -> Result<Bookmark, String> {
+    let mut file = read(app)?;
+    let index = file
+        .bookmarks
+        .iter()
+        .position(|b| b.id == id)
+        .ok_or_else(|| format!("No bookmark with id {}", id))?;
+    let removed = file.bookmarks.remove(index);
+    write(app, &file)?;
+    Ok(removed)
+}
+
+// URLs can't be checked cheaply, so only local paths can be reported missing
+pub fn is_reachable(path: &str) -> bool {
+    path.contains("://") || Path::new(path).exists()
+}
@@ -0,0 +1,153 @@
+// A frame from the start of each chapter, for a visual chapter menu. They're grabbed in
+// the background when a local video with chapters loads, cached like the seekbar
+// previews (see seek_preview::file_cache_dir), and handed out with the chapter list by
+// get_chapter_thumbnails; chapter-thumbnails://ready says when they're all there.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::commands::navigation::Chapter;
+use crate::cover_art::data_url;
+use crate::library::grab_frame;
+use crate::seek_preview::file_cache_dir;
+
+pub const CHAPTERS_DIR: &str = "chapters";
+
+const WIDTH: u32 = 240;
+
+// Chapters often open with a fade from black; a moment in shows what they're about
+const OFFSET: f64 = 2.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChapterThumbnail {
+    #[serde(flatten)]
+    pub chapter: Chapter,
+    // A data: URL; None until it's been made, or if it couldn't be
+    pub thumbnail: Option<String>,
+}
+
+// Payload of chapter-thumbnails://ready
+#[derive(Debug, Clone, Serialize)]
+struct ThumbnailsReady {
+    path: String,
+}
+
+pub struct ChapterThumbnails {
+    // None without a cache dir
+    dir: Option<PathBuf>,
+    // Bumped for each file loaded, so a file left behind stops getting frames
+    generation: Arc<AtomicU64>,
+}
+
+impl ChapterThumbnails {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Stops grabbing frames for the previous file
+    pub fn cancel(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // Called by the event thread as a video loads, with its chapters
+    pub fn prepare<R: Runtime>(&self, app: &AppHandle<R>, path: String, chapters: &[Chapter]) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let Some(dir) = self.dir.clone() else {
+            return;
+        };
+        if chapters.is_empty() {
+            return;
+        }
+        let times = grab_times(chapters);
+        let app = app.clone();
+        let current = self.generation.clone();
+        std::thread::spawn(move || {
+            let Some(frames) = file_cache_dir(&dir, Path::new(&path)) else {
+                return;
+            };
+            if let Err(e) = std::fs::create_dir_all(&frames) {
+                eprintln!("Failed to create the chapter thumbnails directory: {}", e);
+                return;
+            }
+            let mut made = false;
+            for (index, at) in times.into_iter().enumerate() {
+                if current.load(Ordering::SeqCst) != generation {
+                    return;
+                }
+                let file = frame_file(&frames, index);
+                if file.exists() {
+                    continue;
+                }
+                match grab_frame(&path, at, WIDTH, &file) {
+                    Ok(()) => made = true,
+                    Err(e) => {
+                        eprintln!("No thumbnail for chapter {} of {}: {}", index + 1, path, e)
+                    }
+                }
+            }
+            if made {
+                if let Err(e) = app.emit("chapter-thumbnails://ready", ThumbnailsReady { path }) {
+                    eprintln!("Failed to emit chapter-thumbnails://ready: {}", e);
+                }
+            }
+        });
+    }
+
+    pub fn read(&self, path: &str, chapters: Vec<Chapter>) -> Vec<ChapterThumbnail> {
+        let frames = self
+            .dir
+            .as_deref()
+            .and_then(|dir| file_cache_dir(dir, Path::new(path)));
+        chapters
+            .into_iter()
+            .map(|chapter| {
+                let thumbnail = frames
+                    .as_ref()
+                    .and_then(|frames| std::fs::read(frame_file(frames, chapter.index)).ok())
+                    .map(|bytes| data_url("image/jpeg", &bytes));
+                ChapterThumbnail { chapter, thumbnail }
+            })
+            .collect()
+    }
+}
+
+// Where to grab each chapter's frame: OFFSET in, or halfway to the next chapter if
+// that comes sooner
+fn grab_times(chapters: &[Chapter]) -> Vec<f64> {
+    chapters
+        .iter()
+        .enumerate()
+        .map(|(i, chapter)| {
+            let room = chapters
+                .get(i + 1)
+                .map_or(OFFSET, |next| (next.start - chapter.start) / 2.0);
+            chapter.start + OFFSET.min(room.max(0.0))
+        })
+        .collect()
+}
+
+fn frame_file(frames: &Path, index: usize) -> PathBuf {
+    frames.join(format!("chapter-{}.jpg", index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_grabbed_just_inside_each_chapter() {
+        let chapter = |index, start| Chapter {
+            index,
+            title: None,
+            start,
+        };
+        let chapters = [chapter(0, 0.0), chapter(1, 3.0), chapter(2, 300.0)];
+        assert_eq!(grab_times(&chapters), [1.5, 5.0, 302.0]);
+    }
+}
@@ -0,0 +1,193 @@
+use serde::Serialize;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+
+use crate::mpv::{EndFileReason, Event, MpvHandle, PlayerBackend};
+
+// Everything the export thread needs, captured while the player lock is held
+pub struct ClipJob {
+    pub input: String,
+    pub output: String,
+    pub start: f64,
+    pub end: f64,
+    pub reencode: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ClipProgress {
+    output: String,
+    // 0.0 - 1.0
+    progress: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ClipExported {
+    path: String,
+    size: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ClipExportError {
+    output: String,
+    message: String,
+}
+
+// Run the export on a background thread; `running` is cleared when it finishes
+pub fn spawn_export(app: AppHandle, job: ClipJob, running: Arc<AtomicBool>) {
+    std::thread::spawn(move || {
+        let result = if job.reencode {
+            export_with_ffmpeg(&app, &job)
+        } else {
+            export_with_stream_copy(&app, &job)
+        };
+
+        let outcome = result.and_then(|_| {
+            let size = std::fs::metadata(&job.output)
+                .map_err(|e| format!("Export finished but output is missing: {}", e))?
+                .len();
+            Ok(ClipExported {
+                path: job.output.clone(),
+                size,
+            })
+        });
+
+        let emitted = match outcome {
+            Ok(done) => app.emit("clip-export://complete", done),
+            Err(message) => app.emit(
+                "clip-export://error",
+                ClipExportError {
+                    output: job.output.clone(),
+                    message,
+                },
+            ),
+        };
+        if let Err(e) = emitted {
+            eprintln!("Failed to emit clip export result: {}", e);
+        }
+
+        running.store(false, Ordering::SeqCst);
+    });
+}
+
+fn emit_progress(app: &AppHandle, job: &ClipJob, position: f64) {
+    let progress = ((position - job.start) / (job.end - job.start)).clamp(0.0, 1.0);
+    let _ = app.emit(
+        "clip-export://progress",
+        ClipProgress {
+            output: job.output.clone(),
+            progress,
+        },
+    );
+}
+
+// Stream-copy the segment with a second, headless mpv instance using `stream-record`
+fn export_with_stream_copy(app: &AppHandle, job: &ClipJob) -> Result<(), String> {
+    // Dropping the handle on any early return destroys the instance
+    let handle = MpvHandle::create()
+        .map_err(|e| format!("Failed to create MPV handle for export: {}", e))?;
+
+    let start = job.start.to_string();
+    let end = job.end.to_string();
+    let options = [
+        ("vo", "null"),
+        ("ao", "null"),
+        // Demux as fast as possible instead of in real time
+        ("untimed", "yes"),
+        ("idle", "no"),
+        ("start", start.as_str()),
+        ("end", end.as_str()),
+        ("stream-record", job.output.as_str()),
+    ];
+    for (name, value) in options {
+        handle
+            .set_option_string(name, value)
+            .map_err(|e| format!("Failed to set export option {}={}: {}", name, value, e))?;
+    }
+
+    handle
+        .initialize()
+        .map_err(|e| format!("Failed to initialize export MPV: {}", e))?;
+    handle
+        .command(&["loadfile", &job.input])
+        .map_err(|e| format!("Failed to open {}: {}", job.input, e))?;
+
+    loop {
+        match handle.wait_event(0.25) {
+            Event::EndFile {
+                reason: EndFileReason::Error(e),
+                ..
+            } => return Err(format!("Export failed: {}", e)),
+            Event::EndFile { .. } | Event::Shutdown => return Ok(()),
+            _ => {
+                if let Ok(position) = handle.get::<f64>("time-pos") {
+                    emit_progress(app, job, position);
+                }
+            }
+        }
+    }
+}
+
+pub fn ffmpeg_available() -> bool {
+    Command::new("ffmpeg")
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn export_with_ffmpeg(app: &AppHandle, job: &ClipJob) -> Result<(), String> {
+    if !ffmpeg_available() {
+        return Err("Re-encoding needs ffmpeg on PATH".to_string());
+    }
+
+    let mut child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostats", "-loglevel", "error", "-y"])
+        .args(["-ss", &job.start.to_string(), "-to", &job.end.to_string()])
+        .args(["-i", &job.input])
+        .args(["-c:v", "libx264", "-preset", "medium", "-crf", "18"])
+        .args(["-c:a", "aac", "-b:a", "192k"])
+        .args(["-progress", "pipe:1"])
+        .arg(&job.output)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    // Read stderr alongside stdout so neither pipe can fill up and stall ffmpeg;
+    // only the last line is kept for the error message
+    let last_error = child.stderr.take().map(|stderr| {
+        std::thread::spawn(move || {
+            BufReader::new(stderr)
+                .lines()
+                .map_while(Result::ok)
+                .filter(|line| !line.trim().is_empty())
+                .last()
+        })
+    });
+
+    // `-progress` writes key=value lines; out_time_us is relative to the clip start
+    if let Some(stdout) = child.stdout.take() {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if let Some(micros) = line
+                .strip_prefix("out_time_us=")
+                .and_then(|v| v.parse::<f64>().ok())
+            {
+                emit_progress(app, job, job.start + micros / 1_000_000.0);
+            }
+        }
+    }
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("ffmpeg did not finish: {}", e))?;
+    let last_error = last_error.and_then(|reader| reader.join().ok().flatten());
+    if !status.success() {
+        let reason = last_error.as_deref().unwrap_or("unknown error");
+        return Err(format!("ffmpeg failed: {}", reason));
+    }
+    Ok(())
+}
@@ -0,0 +1,320 @@
+// The audio processing the app manages: the equalizer, loudness normalization, the
+// channel layout and downmix, and passthrough to a receiver
+
+use serde::Deserialize;
+
+use super::off_thread;
+use crate::config::{AudioPassthrough, EqualizerPreset, LoudnessNormalization};
+use crate::downmix::{self, ChannelsState, LAYOUTS, MAX_LEVEL};
+use crate::equalizer::{self, EqualizerState, BANDS, MAX_GAIN};
+use crate::error::PlayerError;
+use crate::loudness::{self, MAX_TARGET_LUFS, MIN_TARGET_LUFS};
+use crate::player::{PlayerId, Players};
+use crate::spdif;
+
+#[tauri::command]
+pub async fn get_equalizer(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<EqualizerState, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let player = instance.player.lock().unwrap();
+        Ok(EqualizerState::new(&player.config.values.equalizer))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_equalizer_enabled(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<EqualizerState, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let mut settings = player.config.values.equalizer.clone();
+            settings.enabled = enabled;
+            equalizer::apply(handle, &settings)?;
+            player.config.values.equalizer = settings;
+            player.config.save_or_warn();
+            Ok(EqualizerState::new(&player.config.values.equalizer))
+        })
+        .await
+}
+
+// Switch to `preset`'s curve, as the user last left it. Turns the equalizer on, since
+// picking a preset is asking to hear it.
+#[tauri::command]
+pub async fn set_equalizer_preset(
+    preset: EqualizerPreset,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<EqualizerState, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let mut settings = player.config.values.equalizer.clone();
+            let was_enabled = settings.enabled;
+            settings.preset = preset;
+            settings.enabled = true;
+            if was_enabled {
+                equalizer::update(handle, &settings)?;
+            } else {
+                equalizer::apply(handle, &settings)?;
+            }
+            player.config.values.equalizer = settings;
+            player.config.save_or_warn();
+            Ok(EqualizerState::new(&player.config.values.equalizer))
+        })
+        .await
+}
+
+// Set one band (0 = lowest) of the current preset, live, e.g. while its slider is
+// dragged. The change is kept with the preset.
+#[tauri::command]
+pub async fn set_equalizer_band(
+    band: usize,
+    gain: f64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<EqualizerState, PlayerError> {
+    let instance = players.get(player_id)?;
+    if band >= BANDS.len() {
+        return Err(PlayerError::InvalidArgument(format!(
+            "There are only {} equalizer bands",
+            BANDS.len()
+        )));
+    }
+    if !(-MAX_GAIN..=MAX_GAIN).contains(&gain) {
+        return Err(PlayerError::InvalidArgument(format!(
+            "Equalizer gains must be between -{0} and {0} dB",
+            MAX_GAIN
+        )));
+    }
+    instance
+        .run(move |player, handle| {
+            let mut settings = player.config.values.equalizer.clone();
+            let mut gains = equalizer::gains(&settings, settings.preset);
+            gains[band] = gain;
+            settings.gains.insert(settings.preset, gains);
+            equalizer::update(handle, &settings)?;
+            player.config.values.equalizer = settings;
+            player.config.save_or_warn();
+            Ok(EqualizerState::new(&player.config.values.equalizer))
+        })
+        .await
+}
+
+// Put the current preset back to its built-in curve
+#[tauri::command]
+pub async fn reset_equalizer_preset(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<EqualizerState, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let mut settings = player.config.values.equalizer.clone();
+            settings.gains.remove(&settings.preset);
+            equalizer::update(handle, &settings)?;
+            player.config.values.equalizer = settings;
+            player.config.save_or_warn();
+            Ok(EqualizerState::new(&player.config.values.equalizer))
+        })
+        .await
+}
+
+// Even out the volume between files. `target_lufs` is kept from last time when omitted;
+// -16 suits most listening, -23 is the broadcast standard.
+#[tauri::command]
+pub async fn set_loudness_normalization(
+    enabled: bool,
+    target_lufs: Option<f64>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<LoudnessNormalization, PlayerError> {
+    let instance = players.get(player_id)?;
+    if let Some(target) = target_lufs {
+        if !(MIN_TARGET_LUFS..=MAX_TARGET_LUFS).contains(&target) {
+            return Err(PlayerError::InvalidArgument(format!(
+                "The loudness target must be between {} and {} LUFS",
+                MIN_TARGET_LUFS, MAX_TARGET_LUFS
+            )));
+        }
+    }
+    instance
+        .run(move |player, handle| {
+            let mut settings = player.config.values.loudness_normalization.clone();
+            settings.enabled = enabled;
+            if let Some(target) = target_lufs {
+                settings.target_lufs = target;
+            }
+            loudness::apply(handle, &settings)?;
+            player.config.values.loudness_normalization = settings.clone();
+            player.config.save_or_warn();
+            Ok(settings)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_loudness_normalization(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<LoudnessNormalization, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let player = instance.player.lock().unwrap();
+        Ok(player.config.values.loudness_normalization.clone())
+    })
+    .await
+}
+
+// Play to `layout` (one of downmix::LAYOUTS); "stereo" forces a downmix of surround
+// files, for laptop speakers and headphones
+#[tauri::command]
+pub async fn set_audio_channels(
+    layout: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<ChannelsState, PlayerError> {
+    let instance = players.get(player_id)?;
+    if !LAYOUTS.contains(&layout.as_str()) {
+        return Err(PlayerError::InvalidArgument(format!(
+            "Unknown channel layout {}; expected one of {}",
+            layout,
+            LAYOUTS.join(", ")
+        )));
+    }
+    instance
+        .run(move |player, handle| {
+            let mut settings = player.config.values.audio_channels.clone();
+            settings.layout = layout;
+            downmix::apply(handle, &settings)?;
+            player.config.values.audio_channels = settings;
+            player.config.save_or_warn();
+            Ok(ChannelsState::read(
+                handle,
+                &player.config.values.audio_channels,
+            ))
+        })
+        .await
+}
+
+// Partial update of the downmix: only the levels given change. A centre level above the
+// default of 0.707 makes dialogue easier to follow.
+#[tauri::command]
+pub async fn set_downmix(
+    center_level: Option<f64>,
+    lfe_level: Option<f64>,
+    normalize: Option<bool>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<ChannelsState, PlayerError> {
+    let instance = players.get(player_id)?;
+    for level in [center_level, lfe_level].into_iter().flatten() {
+        if !(0.0..=MAX_LEVEL).contains(&level) {
+            return Err(PlayerError::InvalidArgument(format!(
+                "Downmix levels must be between 0 and {}",
+                MAX_LEVEL
+            )));
+        }
+    }
+    instance
+        .run(move |player, handle| {
+            let mut settings = player.config.values.audio_channels.clone();
+            if let Some(level) = center_level {
+                settings.center_level = level;
+            }
+            if let Some(level) = lfe_level {
+                settings.lfe_level = level;
+            }
+            if let Some(normalize) = normalize {
+                settings.normalize = normalize;
+            }
+            downmix::apply(handle, &settings)?;
+            downmix::restart_output(handle);
+            player.config.values.audio_channels = settings;
+            player.config.save_or_warn();
+            Ok(ChannelsState::read(
+                handle,
+                &player.config.values.audio_channels,
+            ))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_audio_channels(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<ChannelsState, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            Ok(ChannelsState::read(
+                handle,
+                &player.config.values.audio_channels,
+            ))
+        })
+        .await
+}
+
+// Partial update: only the fields that are present change
+#[derive(Debug, Deserialize)]
+pub struct AudioPassthroughUpdate {
+    enabled: Option<bool>,
+    ac3: Option<bool>,
+    eac3: Option<bool>,
+    dts: Option<bool>,
+    dts_hd: Option<bool>,
+    truehd: Option<bool>,
+}
+
+// Bitstream surround tracks to an AV receiver. Only turn on the codecs the receiver
+// decodes: one it doesn't gets silence or noise.
+#[tauri::command]
+pub async fn set_audio_passthrough(
+    options: AudioPassthroughUpdate,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<AudioPassthrough, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let mut values = player.config.values.audio_passthrough.clone();
+            let fields = [
+                (options.enabled, &mut values.enabled),
+                (options.ac3, &mut values.ac3),
+                (options.eac3, &mut values.eac3),
+                (options.dts, &mut values.dts),
+                (options.dts_hd, &mut values.dts_hd),
+                (options.truehd, &mut values.truehd),
+            ];
+            for (update, value) in fields {
+                if let Some(update) = update {
+                    *value = update;
+                }
+            }
+            spdif::apply(handle, &values)?;
+            player.config.values.audio_passthrough = values.clone();
+            player.config.save_or_warn();
+            Ok(values)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_audio_passthrough(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<AudioPassthrough, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let player = instance.player.lock().unwrap();
+        Ok(player.config.values.audio_passthrough.clone())
+    })
+    .await
+}
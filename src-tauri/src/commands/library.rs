@@ -0,0 +1,121 @@
+// The media library: watched folders, queries over what scanning them found, and the
+// watch history. All of it is database or disk work, so every command runs off_thread.
+
+use tauri::Manager;
+
+use super::off_thread;
+use crate::error::PlayerError;
+use crate::library::{
+    HistoryEntry, HistoryRange, Library, LibraryFilters, LibraryFolder, LibraryItem, LibraryQuery,
+    ScanSummary, SearchPage, Thumbnailer,
+};
+
+#[tauri::command]
+pub async fn library_add_folder(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<LibraryFolder, PlayerError> {
+    off_thread(move || {
+        app.state::<Library>()
+            .add_folder(std::path::Path::new(&path))
+            .map_err(PlayerError::InvalidArgument)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn library_remove_folder(app: tauri::AppHandle, id: i64) -> Result<(), PlayerError> {
+    off_thread(move || match app.state::<Library>().remove_folder(id)? {
+        true => Ok(()),
+        false => Err(PlayerError::NotFound(format!("No watched folder {}", id))),
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn library_folders(app: tauri::AppHandle) -> Result<Vec<LibraryFolder>, PlayerError> {
+    off_thread(move || Ok(app.state::<Library>().folders()?)).await
+}
+
+#[tauri::command]
+pub async fn library_list(
+    app: tauri::AppHandle,
+    query: Option<LibraryQuery>,
+) -> Result<Vec<LibraryItem>, PlayerError> {
+    off_thread(move || Ok(app.state::<Library>().list(&query.unwrap_or_default())?)).await
+}
+
+// Ranked by how well the title matches `query`; an empty query lists everything the
+// filters let through, by title
+#[tauri::command]
+pub async fn library_search(
+    app: tauri::AppHandle,
+    query: String,
+    filters: Option<LibraryFilters>,
+) -> Result<SearchPage, PlayerError> {
+    off_thread(move || {
+        Ok(app
+            .state::<Library>()
+            .search(&query, &filters.unwrap_or_default())?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn library_get(app: tauri::AppHandle, id: i64) -> Result<LibraryItem, PlayerError> {
+    off_thread(move || {
+        app.state::<Library>()
+            .get(id)?
+            .ok_or_else(|| PlayerError::NotFound(format!("No library item {}", id)))
+    })
+    .await
+}
+
+// Probing opens each new or changed file, so a first scan of a big folder takes a while.
+// Thumbnails come after, one library://thumbnail event per video as each is made.
+#[tauri::command]
+pub async fn library_rescan(app: tauri::AppHandle) -> Result<ScanSummary, PlayerError> {
+    off_thread(move || {
+        let library = app.state::<Library>();
+        let summary = match library.rescan() {
+            Some(summary) => summary?,
+            None => {
+                return Err(PlayerError::Other(
+                    "A library scan is already running".to_string(),
+                ))
+            }
+        };
+        if let Err(e) = app.state::<Thumbnailer>().queue_missing(&library) {
+            eprintln!("Failed to queue library thumbnails: {}", e);
+        }
+        Ok(summary)
+    })
+    .await
+}
+
+// The latest playback of each file, most recent first, with how far it got
+#[tauri::command]
+pub async fn get_recent(
+    app: tauri::AppHandle,
+    limit: Option<u32>,
+) -> Result<Vec<HistoryEntry>, PlayerError> {
+    off_thread(move || Ok(app.state::<Library>().recent(limit.unwrap_or(20))?)).await
+}
+
+// Every playback in the range, including repeats of the same file
+#[tauri::command]
+pub async fn get_history(
+    app: tauri::AppHandle,
+    range: Option<HistoryRange>,
+) -> Result<Vec<HistoryEntry>, PlayerError> {
+    off_thread(move || Ok(app.state::<Library>().history(&range.unwrap_or_default())?)).await
+}
+
+#[tauri::command]
+pub async fn clear_history(app: tauri::AppHandle) -> Result<String, PlayerError> {
+    off_thread(move || {
+        let removed = app.state::<Library>().clear_history()?;
+        Ok(format!("🧹 Cleared {} history entries", removed))
+    })
+    .await
+}
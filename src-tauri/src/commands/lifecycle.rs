@@ -0,0 +1,419 @@
+// Creating, initializing and destroying players, and queueing the files they open
+
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{Emitter, Manager};
+
+use super::off_thread;
+use super::playback::apply_precise_seeking;
+use super::settings::apply_session_settings;
+use super::subtitles::apply_subtitle_style;
+use super::video::{apply_smooth_motion, apply_tone_mapping, apply_video_enhancements};
+use crate::error::{Context, PlayerError};
+use crate::loadfile::{quote_option_value, LoadOptions};
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::player::channel::PlayerCommand;
+use crate::player::{self, teardown_player, MpvPlayer, PlayerId, PlayerInstance, Players};
+use crate::rendering::video_window;
+use crate::{config, downmix, equalizer, events, loudness, media, spdif};
+
+// Load the first file and append the rest to the playlist, returning per-file failures.
+// Sidecar subtitles are passed as per-file options so they attach when each file plays.
+pub(crate) fn queue_files(handle: &MpvHandle, files: &[media::MediaFile]) -> Vec<String> {
+    load_files(handle, files, "replace", "append")
+}
+
+// Add to the end of the playlist, starting the first one if nothing is playing
+pub(crate) fn append_files(handle: &MpvHandle, files: &[media::MediaFile]) -> Vec<String> {
+    load_files(handle, files, "append-play", "append")
+}
+
+fn load_files(
+    handle: &MpvHandle,
+    files: &[media::MediaFile],
+    first_mode: &str,
+    mode: &str,
+) -> Vec<String> {
+    let mut failures = Vec::new();
+    for (i, file) in files.iter().enumerate() {
+        let mode = if i == 0 { first_mode } else { mode };
+        let options = file
+            .subtitles
+            .iter()
+            .map(|sub| format!("sub-files-append={}", quote_option_value(sub)))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        if let Err(e) = handle.loadfile(&file.path, mode, &options) {
+            failures.push(format!("Failed to load {}: {}", file.path, e));
+        }
+    }
+    failures
+}
+
+// How many times mpv may be restarted within RESTART_WINDOW after stopping on its own,
+// so one that dies straight away again isn't restarted forever
+const MAX_RESTARTS: usize = 3;
+const RESTART_WINDOW: Duration = Duration::from_secs(60);
+
+// Where playback was when mpv last reported it, for picking up after a restart
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ResumePoint {
+    pub(crate) path: String,
+    pub(crate) position: Option<f64>,
+    pub(crate) paused: bool,
+}
+
+// Payload of player-restarted, so the page can say so and set up rendering again
+#[derive(Debug, Clone, Serialize)]
+struct PlayerRestarted {
+    path: Option<String>,
+    position: Option<f64>,
+}
+
+const SCRIPTS_DIR: &str = "scripts";
+
+const SCRIPT_EXTENSIONS: &[&str] = &["lua", "js"];
+
+// Scripts in the app config dir's scripts/ folder, in name order. Directories are
+// included too - mpv loads them as multi-file scripts via their main.lua/main.js.
+fn user_scripts<R: tauri::Runtime>(app: &tauri::AppHandle<R>) -> Vec<std::path::PathBuf> {
+    let Ok(dir) = app.path().app_config_dir() else {
+        return Vec::new();
+    };
+    let Ok(entries) = std::fs::read_dir(dir.join(SCRIPTS_DIR)) else {
+        return Vec::new();
+    };
+
+    let mut scripts: Vec<_> = entries
+        .filter_map(|e| e.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.is_dir()
+                || path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| SCRIPT_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()))
+        })
+        .collect();
+    scripts.sort();
+    scripts
+}
+
+// A new mpv instance with `values` and the user's `options` applied, on the event thread
+fn create_mpv(
+    values: &config::AppConfig,
+    options: &[(String, String)],
+    control: &events::EventControl,
+) -> Result<MpvHandle, PlayerError> {
+    let handle = MpvHandle::create().context("Failed to create MPV handle")?;
+
+    // CRITICAL: Set up MPV for embedding BEFORE initialization
+    #[cfg(not(target_os = "windows"))]
+    handle.set_option_string("vo", "libmpv")?;
+    // On Windows mpv draws into the `wid` child window itself, through Direct3D 11,
+    // unless frames are to be streamed to the page
+    #[cfg(target_os = "windows")]
+    if values.video_backend == config::VideoBackend::Software {
+        handle.set_option_string("vo", "libmpv")?;
+    } else {
+        for (name, value) in [("vo", "gpu"), ("gpu-context", "d3d11")] {
+            handle.set_option_string(name, value)?;
+        }
+    }
+    // Disable video output initially (no separate window)
+    handle.set_option_string("vid", "no")?;
+
+    for (name, value) in options {
+        if let Err(e) = handle.set_option_string(name, value) {
+            eprintln!("Failed to apply option {}={}: {}", name, value, e);
+        }
+    }
+
+    // Initialize MPV; dropping the handle on failure destroys it
+    handle.initialize().context("Failed to initialize MPV")?;
+
+    // Basic configuration. d3d11va decodes on the device the d3d11 context renders with,
+    // so frames never leave the GPU.
+    let hwdec = if cfg!(target_os = "windows") {
+        "d3d11va"
+    } else {
+        "auto"
+    };
+    if let Err(e) = handle.set_option_string("hwdec", hwdec) {
+        eprintln!("Failed to set hwdec: {}", e);
+    }
+
+    if let Err(e) = apply_video_enhancements(&handle, &values.video_enhancements) {
+        eprintln!("Failed to restore video enhancements: {}", e);
+    }
+
+    if values.smooth_motion {
+        if let Err(e) = apply_smooth_motion(&handle, true) {
+            eprintln!("Failed to restore smooth motion: {}", e);
+        }
+    }
+
+    if values.precise_seeking {
+        if let Err(e) = apply_precise_seeking(&handle, true) {
+            eprintln!("Failed to restore precise seeking: {}", e);
+        }
+    }
+
+    if let Err(e) = apply_tone_mapping(&handle, &values.tone_mapping) {
+        eprintln!("Failed to restore tone mapping: {}", e);
+    }
+
+    if let Err(e) = apply_subtitle_style(&handle, &values.subtitle_style) {
+        eprintln!("Failed to restore subtitle style: {}", e);
+    }
+
+    if let Err(e) = apply_session_settings(&handle, &values.session) {
+        eprintln!("Failed to restore session settings: {}", e);
+    }
+
+    if let Err(e) = equalizer::apply(&handle, &values.equalizer) {
+        eprintln!("Failed to restore the equalizer: {}", e);
+    }
+
+    if let Err(e) = loudness::apply(&handle, &values.loudness_normalization) {
+        eprintln!("Failed to restore loudness normalization: {}", e);
+    }
+
+    if let Err(e) = downmix::apply(&handle, &values.audio_channels) {
+        eprintln!("Failed to restore the channel layout: {}", e);
+    }
+
+    if let Err(e) = spdif::apply(&handle, &values.audio_passthrough) {
+        eprintln!("Failed to restore audio passthrough: {}", e);
+    }
+
+    if let Err(e) = control.reset_end_hold(&handle) {
+        eprintln!("Failed to hold keep-open for stop after current: {}", e);
+    }
+    control.set_prevent_sleep(&handle, values.prevent_sleep);
+    control.set_dock_progress(&handle, values.dock_progress);
+
+    Ok(handle)
+}
+
+// Create and initialize an mpv instance for `instance` with its saved settings, and start
+// its event thread. False if one is already running, which is left as it is.
+pub(crate) fn start_player<R: tauri::Runtime>(
+    app: &tauri::AppHandle<R>,
+    instance: &Arc<PlayerInstance>,
+) -> Result<bool, PlayerError> {
+    let mut player = instance.player.lock().unwrap();
+    if player.running() {
+        return Ok(false);
+    }
+
+    player.event_control.restore_per_file_settings.store(
+        player.config.values.restore_per_file_settings,
+        Ordering::SeqCst,
+    );
+    player
+        .event_control
+        .auto_advance
+        .store(player.config.values.auto_advance, Ordering::SeqCst);
+    player
+        .event_control
+        .restore_queue
+        .store(player.config.values.restore_queue, Ordering::SeqCst);
+    player
+        .event_control
+        .remember_position
+        .store(player.config.values.remember_position, Ordering::SeqCst);
+    // Until teardown says otherwise, mpv shutting down means it stopped on its own
+    player.event_control.quitting.store(false, Ordering::SeqCst);
+
+    // mpv is created on the event thread, which keeps it to itself from then on
+    let values = player.config.values.clone();
+    let options = player.pre_init_options.clone();
+    let control = player.event_control.clone();
+    let channel = &instance.channel;
+    let thread = events::spawn_event_loop(
+        app.clone(),
+        instance.id,
+        move || create_mpv(&values, &options, &control),
+        player.event_control.clone(),
+        channel.connect(),
+        player.pause_fade.clone(),
+        channel.shared_snapshot(),
+    );
+    match thread {
+        Ok(thread) => player.event_thread = Some(thread),
+        Err(e) => {
+            channel.disconnect();
+            return Err(e);
+        }
+    }
+
+    // The rest is the event thread's to do, once this lets go of the player
+    let scripts = user_scripts(app);
+    let pending = std::mem::take(&mut player.pending_files);
+    let queued = instance.post(move |player, handle| {
+        for script in scripts {
+            let script = script.to_string_lossy().into_owned();
+            match handle.command(&["load-script", &script]) {
+                Ok(()) => player.loaded_scripts.push(script),
+                Err(e) => eprintln!("Failed to load script {}: {}", script, e),
+            }
+        }
+        if !pending.is_empty() {
+            for failure in queue_files(handle, &pending) {
+                eprintln!("{}", failure);
+            }
+        }
+    });
+    if let Err(e) = queued {
+        eprintln!("Failed to load scripts and dropped files: {}", e);
+    }
+
+    Ok(true)
+}
+
+// mpv shut down without teardown asking it to, e.g. after a fatal error: start a fresh
+// instance with the same settings and pick the current file back up where it was. The
+// rest of the playlist is lost. Called from the event thread, so the restart runs as a
+// task of its own that can wait for that thread to finish.
+pub(crate) fn recover<R: tauri::Runtime>(
+    app: tauri::AppHandle<R>,
+    player_id: PlayerId,
+    resume: Option<ResumePoint>,
+) {
+    tauri::async_runtime::spawn(async move {
+        // The player may have been removed in the meantime
+        let Ok(instance) = app.state::<Players>().get(Some(player_id)) else {
+            return;
+        };
+        let name = player::event_name(player_id, "player-restarted");
+        match restart(&app, &instance, resume.as_ref()).await {
+            Ok(()) => {
+                let payload = PlayerRestarted {
+                    path: resume.as_ref().map(|r| r.path.clone()),
+                    position: resume.as_ref().and_then(|r| r.position),
+                };
+                if let Err(e) = app.emit(&name, payload) {
+                    eprintln!("Failed to emit player-restarted: {}", e);
+                }
+            }
+            Err(e) => {
+                eprintln!("Failed to restart player {}: {}", player_id, e);
+                let name = player::event_name(player_id, "player-restart-failed");
+                if let Err(e) = app.emit(&name, e) {
+                    eprintln!("Failed to emit player-restart-failed: {}", e);
+                }
+            }
+        }
+    });
+}
+
+async fn restart<R: tauri::Runtime>(
+    app: &tauri::AppHandle<R>,
+    instance: &Arc<PlayerInstance>,
+    resume: Option<&ResumePoint>,
+) -> Result<(), PlayerError> {
+    let gave_up = {
+        let mut player = instance.player.lock().unwrap();
+        let now = Instant::now();
+        player
+            .restarts
+            .retain(|restart| now.duration_since(*restart) < RESTART_WINDOW);
+        if player.restarts.len() < MAX_RESTARTS {
+            player.restarts.push(now);
+            false
+        } else {
+            true
+        }
+    };
+
+    teardown_player(app, instance).await?;
+    if gave_up {
+        return Err(PlayerError::Other(
+            "mpv keeps stopping, so it wasn't restarted".to_string(),
+        ));
+    }
+    let (app, started) = (app.clone(), instance.clone());
+    off_thread(move || start_player(&app, &started)).await?;
+
+    let Some(resume) = resume else {
+        return Ok(());
+    };
+    let options = LoadOptions {
+        start: resume.position,
+        pause_on_load: resume.paused,
+        ..LoadOptions::default()
+    }
+    .encode()?;
+    let path = resume.path.clone();
+    instance
+        .channel
+        .request(|reply| PlayerCommand::Load {
+            path,
+            options: Some(options).filter(|o| !o.is_empty()),
+            reply,
+        })
+        .await
+}
+
+// Tauri commands
+#[tauri::command]
+pub async fn init_mpv_player(
+    app: tauri::AppHandle,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        if start_player(&app, &instance)? {
+            Ok("MPV initialized for embedding - no popup windows".to_string())
+        } else {
+            Ok("MPV already initialized".to_string())
+        }
+    })
+    .await
+}
+
+// Tear a player down without quitting the app, e.g. before switching layouts. The main
+// player can be started again with init_mpv_player; any other one is gone for good.
+#[tauri::command]
+pub async fn destroy_player<R: tauri::Runtime>(
+    app: tauri::AppHandle<R>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let stopped = teardown_player(&app, &instance).await?;
+    // A window the video was popped out into has nothing left to show
+    if let Some(window) = app.get_webview_window(&video_window::label(instance.id)) {
+        if let Err(e) = window.destroy() {
+            eprintln!("Failed to close the video window: {}", e);
+        }
+    }
+    if players.remove(instance.id).is_some() {
+        Ok(format!("🛑 Player {} removed", instance.id))
+    } else if stopped {
+        Ok("🛑 Player destroyed".to_string())
+    } else {
+        Err(PlayerError::NotInitialized)
+    }
+}
+
+// A second player alongside the main one, to be started with init_mpv_player and
+// addressed by the returned id. It starts from the main player's settings, but changing
+// its own doesn't save them.
+#[tauri::command]
+pub fn create_player(players: tauri::State<Players>) -> Result<PlayerId, PlayerError> {
+    let mut player = MpvPlayer::new();
+    player.config = players.main().player.lock().unwrap().config.detached();
+    // The Dock icon / taskbar button only has room for one player's progress
+    player.config.values.dock_progress = false;
+    Ok(players.add(player))
+}
+
+#[tauri::command]
+pub fn list_players(players: tauri::State<Players>) -> Result<Vec<PlayerId>, PlayerError> {
+    Ok(players.all().iter().map(|instance| instance.id).collect())
+}
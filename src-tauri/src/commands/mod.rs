@@ -0,0 +1,48 @@
+// Tauri commands, grouped by what they act on. Each takes an optional player id and
+// falls back to the main player when it is omitted. Commands that talk to mpv are async
+// and hand that work to the player's event thread (PlayerInstance::run and
+// PlayerChannel::run), which is the only thread driving mpv once it's up; anything slow
+// that doesn't need mpv, like file or network access, goes off_thread instead. The few
+// that move native views stay sync, since views belong to the main thread, and post
+// whatever mpv has to do to the event thread without waiting for it.
+
+pub mod audio;
+pub mod library;
+pub mod lifecycle;
+pub mod navigation;
+pub mod passthrough;
+pub mod playback;
+pub mod queue;
+pub mod settings;
+pub mod subtitles;
+pub mod tracks;
+pub mod video;
+pub mod window;
+
+use crate::error::PlayerError;
+
+// Runs a command's blocking work on the blocking pool. Sync commands run on the main
+// thread, where a slow file or network access would freeze the whole UI.
+pub(crate) async fn off_thread<T: Send + 'static>(
+    work: impl FnOnce() -> Result<T, PlayerError> + Send + 'static,
+) -> Result<T, PlayerError> {
+    tauri::async_runtime::spawn_blocking(work)
+        .await
+        .map_err(|e| PlayerError::Other(format!("Command failed: {}", e)))?
+}
+
+// Runs work on the main thread, which native views belong to, and awaits its result
+pub(crate) async fn on_main_thread<R: tauri::Runtime, T: Send + 'static>(
+    app: &tauri::AppHandle<R>,
+    work: impl FnOnce() -> Result<T, PlayerError> + Send + 'static,
+) -> Result<T, PlayerError> {
+    let (reply, result) = tokio::sync::oneshot::channel();
+    app.run_on_main_thread(move || {
+        let _ = reply.send(work());
+    })
+    .map_err(|e| PlayerError::Other(format!("Failed to reach the main thread: {}", e)))?;
+    result.await.unwrap_or(Err(PlayerError::ShutDown))
+}
+
+#[cfg(all(test, not(feature = "real-mpv")))]
+mod tests;
@@ -0,0 +1,233 @@
+// Chapters (with their thumbnails) and bookmarks: jumping around within the current file
+
+use serde::Serialize;
+use tauri::Manager;
+
+use super::off_thread;
+use crate::bookmarks;
+use crate::chapter_thumbnails::{ChapterThumbnail, ChapterThumbnails};
+use crate::error::{Context, PlayerError};
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::player::{PlayerId, Players};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Chapter {
+    pub(crate) index: usize,
+    pub(crate) title: Option<String>,
+    pub(crate) start: f64,
+}
+
+pub(crate) fn read_chapters(handle: &MpvHandle) -> Vec<Chapter> {
+    let count = handle.get::<i64>("chapter-list/count").unwrap_or(0);
+    (0..count.max(0) as usize)
+        .map(|i| Chapter {
+            index: i,
+            title: handle
+                .get::<String>(&format!("chapter-list/{}/title", i))
+                .ok(),
+            start: handle
+                .get::<f64>(&format!("chapter-list/{}/time", i))
+                .unwrap_or(0.0),
+        })
+        .collect()
+}
+
+#[tauri::command]
+pub async fn get_chapters(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<Chapter>, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| Ok(read_chapters(handle)))
+        .await
+}
+
+// get_chapters with a frame from each chapter, for a visual chapter menu. Thumbnails that
+// aren't made yet are None; chapter-thumbnails://ready says when to ask again.
+#[tauri::command]
+pub async fn get_chapter_thumbnails(
+    app: tauri::AppHandle,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<ChapterThumbnail>, PlayerError> {
+    let instance = players.get(player_id)?;
+    let (path, chapters) = instance
+        .channel
+        .run(move |handle| {
+            let path = handle
+                .get::<String>("path")
+                .map_err(|_| PlayerError::NoFile)?;
+            Ok((path, read_chapters(handle)))
+        })
+        .await?;
+    off_thread(move || Ok(app.state::<ChapterThumbnails>().read(&path, chapters))).await
+}
+
+#[tauri::command]
+pub async fn set_chapter(
+    index: usize,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            let count = handle.get::<i64>("chapter-list/count").unwrap_or(0);
+            if index as i64 >= count {
+                return Err(PlayerError::InvalidArgument(format!(
+                    "Chapter {} doesn't exist; this file has {}",
+                    index, count
+                )));
+            }
+            handle
+                .set("chapter", index as i64)
+                .context("Failed to change chapter")?;
+            Ok(format!("📖 Chapter {}", index + 1))
+        })
+        .await
+}
+
+// Past the last chapter mpv moves on to the next file. Going back within the first few
+// seconds of a chapter (chapter-seek-threshold) goes to the previous one, otherwise to
+// the start of the current one.
+#[tauri::command]
+pub async fn next_chapter(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| step_chapter(handle, 1))
+        .await
+}
+
+#[tauri::command]
+pub async fn prev_chapter(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| step_chapter(handle, -1))
+        .await
+}
+
+fn step_chapter(handle: &MpvHandle, delta: i64) -> Result<String, PlayerError> {
+    if handle.get::<i64>("chapter-list/count").unwrap_or(0) == 0 {
+        return Err(PlayerError::NotFound(
+            "This file has no chapters".to_string(),
+        ));
+    }
+    handle
+        .command(&["add", "chapter", &delta.to_string()])
+        .context("Failed to change chapter")?;
+    Ok(if delta > 0 {
+        "⏭️ Next chapter"
+    } else {
+        "⏮️ Previous chapter"
+    }
+    .to_string())
+}
+
+#[tauri::command]
+pub async fn add_bookmark(
+    app: tauri::AppHandle,
+    label: Option<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<bookmarks::Bookmark, PlayerError> {
+    let instance = players.get(player_id)?;
+    let (path, time) = instance
+        .channel
+        .run(move |handle| {
+            let path = handle
+                .get::<String>("path")
+                .map_err(|_| PlayerError::NoFile)?;
+            let time = handle.get::<f64>("time-pos").map_err(|_| {
+                PlayerError::NotFound("Playback position is not available yet".to_string())
+            })?;
+            Ok((path, time))
+        })
+        .await?;
+
+    off_thread(move || Ok(bookmarks::add(&app, path, time, label)?)).await
+}
+
+#[tauri::command]
+pub async fn get_bookmarks(
+    app: tauri::AppHandle,
+    path: Option<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<bookmarks::BookmarkEntry>, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let path = match path {
+            Some(path) => path,
+            None => instance
+                .channel
+                .snapshot()
+                .and_then(|snapshot| snapshot.path)
+                .ok_or(PlayerError::NoFile)?,
+        };
+
+        Ok(bookmarks::list(&app, &path)?)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn remove_bookmark(app: tauri::AppHandle, id: u64) -> Result<String, PlayerError> {
+    off_thread(move || {
+        let removed = bookmarks::remove(&app, id)?;
+        Ok(format!("🗑️ Removed bookmark at {:.1}s", removed.time))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn jump_to_bookmark(
+    app: tauri::AppHandle,
+    id: u64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    // Checking the file is there can be slow, e.g. on a network share, so it's done
+    // before the event thread gets involved
+    let bookmark = off_thread(move || {
+        let bookmark = bookmarks::get(&app, id)?;
+        if !bookmarks::is_reachable(&bookmark.path) {
+            return Err(PlayerError::NotFound(format!(
+                "Bookmarked file is missing: {}",
+                bookmark.path
+            )));
+        }
+        Ok(bookmark)
+    })
+    .await?;
+    instance
+        .channel
+        .run(move |handle| {
+            let time = bookmark.time.to_string();
+            let current = handle.get::<String>("path").ok();
+            let result = if current.as_deref() == Some(bookmark.path.as_str()) {
+                handle.command(&["seek", &time, "absolute"])
+            } else {
+                let options = format!("start={}", time);
+                handle.command(&["loadfile", &bookmark.path, "replace", "-1", &options])
+            };
+            result.context("Failed to jump to bookmark")?;
+
+            Ok(format!(
+                "🔖 Jumped to {}",
+                bookmark.label.as_deref().unwrap_or(&time)
+            ))
+        })
+        .await
+}
@@ -0,0 +1,557 @@
+// Raw access to mpv properties, options, commands and scripts for power users
+
+use std::path::Path;
+
+use super::off_thread;
+use crate::config::MpvAllowlist;
+use crate::error::{Context, PlayerError};
+use crate::mpv::PlayerBackend;
+use crate::mpv_log::LogEntry;
+use crate::player::{MpvPlayer, PlayerId, Players};
+use crate::{events, mpv};
+
+// Properties the app manages itself; changing them would break the embedding or the
+// event thread's view of the player
+const MANAGED_PROPERTIES: &[&str] = &[
+    "wid",
+    "vo",
+    "vid",
+    "force-window",
+    "gpu-context",
+    "idle",
+    "input-ipc-server",
+];
+
+// The same option is reachable as "--vo", "options/vo" or, for the current file,
+// "file-local-options/vo"; normalize before comparing
+fn bare_name(name: &str) -> &str {
+    let bare = name.trim_start_matches("--");
+    ["options/", "file-local-options/"]
+        .iter()
+        .find_map(|prefix| bare.strip_prefix(prefix))
+        .unwrap_or(bare)
+}
+
+// Options that load scripts or config, or name programs mpv runs (youtube-dl and its
+// arguments, through ytdl_hook's script-opts), which page content mustn't reach
+const CODE_OPTIONS: &[&str] = &[
+    "script",
+    "scripts",
+    "load-scripts",
+    "script-opts",
+    "include",
+    "profile",
+    "config",
+    "config-dir",
+    "input-conf",
+    "input-ipc-client",
+    "ytdl-path",
+    "ytdl-raw-options",
+];
+
+pub(crate) fn check_unmanaged(name: &str) -> Result<(), PlayerError> {
+    if name.is_empty() {
+        return Err(PlayerError::InvalidArgument(
+            "Property name must not be empty".to_string(),
+        ));
+    }
+    let bare = bare_name(name);
+    if MANAGED_PROPERTIES.contains(&bare) {
+        return Err(PlayerError::InvalidArgument(format!(
+            "{} is managed by the app and can't be changed directly",
+            name
+        )));
+    }
+    if CODE_OPTIONS.contains(&bare) {
+        return Err(PlayerError::InvalidArgument(format!(
+            "{} can load code, so it can't be changed from the frontend",
+            name
+        )));
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn set_mpv_property(
+    name: String,
+    value: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    check_unmanaged(&name)?;
+    instance
+        .channel
+        .run(move |handle| {
+            handle
+                .set(&name, &value)
+                .context(format!("Failed to set {}", name))?;
+            // mpv may normalize the value (e.g. clamp or reformat it)
+            Ok(handle.get::<String>(&name).unwrap_or(value))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_mpv_property_string(
+    name: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            handle
+                .get::<String>(&name)
+                .context(format!("Failed to get {}", name))
+        })
+        .await
+}
+
+// Store an option for init_mpv_player; some options only take effect before initialization
+#[tauri::command]
+pub async fn set_mpv_option(
+    name: String,
+    value: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        check_unmanaged(&name)?;
+        let mut player = instance.player.lock().unwrap();
+
+        if player.running() {
+            return Err(PlayerError::InvalidArgument(
+                "MPV is already initialized - use set_mpv_property instead".to_string(),
+            ));
+        }
+        if name.contains('\0') || value.contains('\0') {
+            return Err(PlayerError::InvalidArgument(
+                "Option names and values must not contain NUL".to_string(),
+            ));
+        }
+
+        store_pre_init_option(&mut player, name, value.clone());
+        Ok(value)
+    })
+    .await
+}
+
+fn store_pre_init_option(player: &mut MpvPlayer, name: String, value: String) {
+    let options = &mut player.pre_init_options;
+    match options.iter_mut().find(|(n, _)| *n == name) {
+        Some(existing) => existing.1 = value,
+        None => options.push((name, value)),
+    }
+}
+
+// Commands that would end the session or otherwise desync the app's model of the player,
+// and those that start processes or load scripts or config, which page content mustn't
+// reach. Every command that takes another command to run later (bindings, input
+// sections) or hands arguments to scripts is here too, so nothing nested gets past.
+const DENIED_COMMANDS: &[&str] = &[
+    "quit",
+    "quit-watch-later",
+    "run",
+    "subprocess",
+    "load-script",
+    "load-config-file",
+    "load-input-conf",
+    "apply-profile",
+    "keybind",
+    "keypress",
+    "keydown",
+    "keyup",
+    "define-section",
+    "enable-section",
+    "script-message",
+    "script-message-to",
+    "script-binding",
+];
+
+// Commands whose first argument names a property
+const PROPERTY_COMMANDS: &[&str] = &[
+    "set",
+    "add",
+    "cycle",
+    "cycle-values",
+    "multiply",
+    "change-list",
+];
+
+fn check_command(args: &[String]) -> Result<(), PlayerError> {
+    let Some(name) = args.first() else {
+        return Err(PlayerError::InvalidArgument(
+            "Command must not be empty".to_string(),
+        ));
+    };
+    if args.iter().any(|a| a.contains('\0')) {
+        return Err(PlayerError::InvalidArgument(
+            "Command arguments must not contain NUL".to_string(),
+        ));
+    }
+
+    if DENIED_COMMANDS.contains(&name.as_str()) {
+        return Err(PlayerError::InvalidArgument(format!(
+            "{} is not allowed from the frontend",
+            name
+        )));
+    }
+    if PROPERTY_COMMANDS.contains(&name.as_str()) {
+        if let Some(property) = args.get(1) {
+            check_unmanaged(property)?;
+        }
+    }
+    // Per-file options ride along as "key=value,..." after the URL
+    if name == "loadfile" || name == "loadlist" {
+        for options in args.iter().skip(2) {
+            for option in options.split(',') {
+                if let Some((key, _)) = option.split_once('=') {
+                    check_unmanaged(key)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn send_mpv_command(
+    args: Vec<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    if !cfg!(feature = "mpv-command-passthrough") {
+        return Err(PlayerError::Other(
+            "mpv command pass-through is disabled in this build".to_string(),
+        ));
+    }
+    check_command(&args)?;
+    instance
+        .channel
+        .run(move |handle| {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            handle
+                .command(&args)
+                .context(format!("{} failed", args[0]))?;
+            Ok(format!("Ran {}", args[0]))
+        })
+        .await
+}
+
+fn check_allowed_option(allowlist: &MpvAllowlist, name: &str) -> Result<(), PlayerError> {
+    check_unmanaged(name)?;
+    let bare = bare_name(name);
+    if !allowlist.options.iter().any(|allowed| allowed == bare) {
+        return Err(PlayerError::InvalidArgument(format!(
+            "{} is not in the mpv option allowlist",
+            bare
+        )));
+    }
+    Ok(())
+}
+
+fn check_allowed_command(allowlist: &MpvAllowlist, args: &[String]) -> Result<(), PlayerError> {
+    check_command(args)?;
+    let name = &args[0];
+    if !allowlist.commands.contains(name) {
+        return Err(PlayerError::InvalidArgument(format!(
+            "{} is not in the mpv command allowlist",
+            name
+        )));
+    }
+    if PROPERTY_COMMANDS.contains(&name.as_str()) {
+        let property = args.get(1).ok_or_else(|| {
+            PlayerError::InvalidArgument(format!("{} needs a property name", name))
+        })?;
+        check_allowed_option(allowlist, property)?;
+    }
+    Ok(())
+}
+
+// Set an allowlisted option (see config::MpvAllowlist), for settings pages exposing mpv
+// options the app has no command of its own for. Before init_mpv_player it's kept for
+// initialization, like set_mpv_option; after, it's set as a property right away.
+#[tauri::command]
+pub async fn mpv_set_option(
+    name: String,
+    value: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let pending = {
+        let instance = instance.clone();
+        let value = value.clone();
+        off_thread(move || {
+            let mut player = instance.player.lock().unwrap();
+            check_allowed_option(&player.config.values.mpv_allowlist, &name)?;
+            let name = bare_name(&name).to_string();
+            if player.running() {
+                return Ok(Some(name));
+            }
+            if value.contains('\0') {
+                return Err(PlayerError::InvalidArgument(
+                    "Option values must not contain NUL".to_string(),
+                ));
+            }
+            store_pre_init_option(&mut player, name, value);
+            Ok(None)
+        })
+        .await?
+    };
+    let Some(name) = pending else {
+        return Ok(value);
+    };
+    instance
+        .channel
+        .run(move |handle| {
+            handle
+                .set(&name, &value)
+                .context(format!("Failed to set {}", name))?;
+            Ok(handle.get::<String>(&name).unwrap_or(value))
+        })
+        .await
+}
+
+// Run an allowlisted mpv command. Unlike send_mpv_command this is in every build.
+#[tauri::command]
+pub async fn mpv_run_command(
+    args: Vec<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let allowlist = get_mpv_allowlist(player_id, players).await?;
+    check_allowed_command(&allowlist, &args)?;
+    instance
+        .channel
+        .run(move |handle| {
+            let args: Vec<&str> = args.iter().map(String::as_str).collect();
+            handle
+                .command(&args)
+                .context(format!("{} failed", args[0]))?;
+            Ok(format!("Ran {}", args[0]))
+        })
+        .await
+}
+
+// What mpv_set_option and mpv_run_command accept, for building the settings page
+#[tauri::command]
+pub async fn get_mpv_allowlist(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<MpvAllowlist, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let allowlist = instance
+            .player
+            .lock()
+            .unwrap()
+            .config
+            .values
+            .mpv_allowlist
+            .clone();
+        Ok(allowlist)
+    })
+    .await
+}
+
+// Syntax or runtime errors in the script arrive later as mpv-log-message events
+#[tauri::command]
+pub async fn load_script(
+    path: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            if !Path::new(&path).exists() {
+                return Err(PlayerError::NotFound(format!("Script not found: {}", path)));
+            }
+            handle
+                .command(&["load-script", &path])
+                .context(format!("Failed to load script {}", path))?;
+            player.loaded_scripts.push(path.clone());
+            Ok(format!("📜 Loaded {}", path))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_loaded_scripts(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<String>, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let scripts = instance.player.lock().unwrap().loaded_scripts.clone();
+        Ok(scripts)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn send_script_message(
+    target: String,
+    args: Vec<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            let mut command = vec!["script-message-to", target.as_str()];
+            command.extend(args.iter().map(String::as_str));
+            handle
+                .command(&command)
+                .context(format!("Failed to message {}", target))?;
+            Ok(format!("Sent message to {}", target))
+        })
+        .await
+}
+
+// What mpv logged recently, oldest first, including lines from before a restart. Only
+// warnings and errors arrive as mpv-log-message events; this has info lines too.
+#[tauri::command]
+pub async fn get_player_logs(
+    limit: Option<usize>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<LogEntry>, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let control = instance.player.lock().unwrap().event_control.clone();
+        Ok(control.log.recent(limit))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn clear_player_logs(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<(), PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        instance.player.lock().unwrap().event_control.log.clear();
+        Ok(())
+    })
+    .await
+}
+
+// Report changes to `name` (e.g. time-pos, pause, track-list) as mpv://property-change
+// events tagged with the returned id, instead of polling. `format` defaults to string,
+// which gives node properties such as track-list as JSON; "none" only notifies.
+#[tauri::command]
+pub async fn observe_property(
+    name: String,
+    format: Option<mpv::Format>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<u64, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let id = player.event_control.next_frontend_observer();
+            handle
+                .observe(id, &name, format.unwrap_or(mpv::Format::String))
+                .context(format!("Failed to observe {}", name))?;
+            Ok(id)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn unobserve_property(
+    id: u64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            // Our own observers have ids below the frontend's
+            if id < events::FRONTEND_OBSERVER_BASE
+                || !handle.unobserve(id).context("Failed to unobserve")?
+            {
+                return Err(PlayerError::NotFound(format!(
+                    "No property observer with id {}",
+                    id
+                )));
+            }
+            Ok(format!("Stopped observer {}", id))
+        })
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    #[test]
+    fn managed_properties_are_caught_under_every_prefix() {
+        for name in ["vo", "--vo", "options/vo", "file-local-options/wid"] {
+            assert!(check_unmanaged(name).is_err(), "{}", name);
+        }
+        assert!(check_command(&command(&["set", "file-local-options/vo", "null"])).is_err());
+        assert!(check_unmanaged("file-local-options/sub-scale").is_ok());
+    }
+
+    #[test]
+    fn commands_that_start_processes_are_denied() {
+        assert!(check_command(&command(&["sub-seek", "1"])).is_ok());
+        for name in ["run", "subprocess", "load-script", "quit"] {
+            assert!(
+                check_command(&command(&[name, "/tmp/x"])).is_err(),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn commands_that_run_other_commands_or_load_config_are_denied() {
+        let denied = [
+            &["keybind", "F1", "run sh -c true"][..],
+            &["keypress", "F1"],
+            &["keydown", "F1"],
+            &["define-section", "s", "F1 run sh -c true"],
+            &["load-config-file", "/tmp/mpv.conf"],
+            &["load-input-conf", "/tmp/input.conf"],
+            &["apply-profile", "evil"],
+            &["script-message", "evil"],
+            &["script-binding", "console/enable"],
+        ];
+        for args in denied {
+            assert!(check_command(&command(args)).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn options_that_load_code_are_denied() {
+        for args in [
+            &["set", "script-opts", "ytdl_hook-ytdl_path=/tmp/x"][..],
+            &["change-list", "scripts", "append", "/tmp/x.lua"],
+            &[
+                "loadfile",
+                "/videos/a.mkv",
+                "replace",
+                "include=/tmp/mpv.conf",
+            ],
+        ] {
+            assert!(check_command(&command(args)).is_err(), "{:?}", args);
+        }
+        assert!(check_unmanaged("--ytdl-raw-options").is_err());
+    }
+}
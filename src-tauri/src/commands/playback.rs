@@ -0,0 +1,779 @@
+// Transport, seeking (with hover previews and a waveform), speed, volume and audio output
+// of the current file
+
+use serde::Serialize;
+use tauri::Manager;
+
+use super::off_thread;
+use crate::audio_devices::{self, AudioDevice};
+use crate::error::{Context, PlayerError};
+use crate::file_probe::{self, FileReport};
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::player::channel::{self, PlayerCommand};
+use crate::player::{PlayerId, Players};
+use crate::seek_preview::{PreviewThumbnail, SeekPreviews};
+use crate::waveform::{self, Waveform};
+use crate::{loadfile, pitch, resume_position};
+
+// Diagnostics shown in the stats overlay; None until mpv can report a value
+#[derive(Debug, Clone, Serialize)]
+pub struct PlaybackStats {
+    frame_drop_count: Option<i64>,
+    decoder_frame_drop_count: Option<i64>,
+    hwdec_current: Option<String>,
+    estimated_vf_fps: Option<f64>,
+    avsync: Option<f64>,
+    video_bitrate: Option<f64>,
+    audio_bitrate: Option<f64>,
+    cache_duration: Option<f64>,
+    vo: Option<String>,
+}
+
+pub(crate) fn read_playback_stats(handle: &MpvHandle) -> PlaybackStats {
+    PlaybackStats {
+        frame_drop_count: handle.get::<i64>("frame-drop-count").ok(),
+        decoder_frame_drop_count: handle.get::<i64>("decoder-frame-drop-count").ok(),
+        hwdec_current: handle.get::<String>("hwdec-current").ok(),
+        estimated_vf_fps: handle.get::<f64>("estimated-vf-fps").ok(),
+        avsync: handle.get::<f64>("avsync").ok(),
+        video_bitrate: handle.get::<f64>("video-bitrate").ok(),
+        audio_bitrate: handle.get::<f64>("audio-bitrate").ok(),
+        cache_duration: handle.get::<f64>("demuxer-cache-duration").ok(),
+        vo: handle.get::<String>("current-vo").ok(),
+    }
+}
+
+// hr-seek and hr-seek-framedrop with precise seeking on and off (off = mpv defaults).
+// Dropping frames speeds up exact seeks but can skip the target frame with filters that
+// change timestamps.
+const PRECISE_SEEKING_ON: [(&str, &str); 2] = [("hr-seek", "yes"), ("hr-seek-framedrop", "no")];
+
+const PRECISE_SEEKING_OFF: [(&str, &str); 2] =
+    [("hr-seek", "default"), ("hr-seek-framedrop", "yes")];
+
+pub(crate) fn apply_precise_seeking(handle: &MpvHandle, enabled: bool) -> Result<(), PlayerError> {
+    let properties = if enabled {
+        PRECISE_SEEKING_ON
+    } else {
+        PRECISE_SEEKING_OFF
+    };
+    for (name, value) in properties {
+        handle
+            .set(name, value)
+            .context(format!("Failed to set {}", name))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PreciseSeekingStatus {
+    pub(crate) enabled: bool,
+    pub(crate) hr_seek: Option<String>,
+    pub(crate) hr_seek_framedrop: Option<bool>,
+}
+
+fn read_precise_seeking(handle: &MpvHandle, enabled: bool) -> PreciseSeekingStatus {
+    PreciseSeekingStatus {
+        enabled,
+        hr_seek: handle.get::<String>("hr-seek").ok(),
+        hr_seek_framedrop: handle.get::<bool>("hr-seek-framedrop").ok(),
+    }
+}
+
+#[tauri::command]
+pub async fn load_video(
+    file_path: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    channel
+        .request(|reply| PlayerCommand::Load {
+            path: file_path.clone(),
+            options: None,
+            reply,
+        })
+        .await?;
+    Ok(format!("🎬 Loading video: {}", file_path))
+}
+
+// Whether `path` can be played, checked without loading it, so a bad file gets a clear
+// reason instead of load_video's mpv error
+#[tauri::command]
+pub async fn probe_file(path: String) -> Result<FileReport, PlayerError> {
+    off_thread(move || Ok(file_probe::probe_file(&path))).await
+}
+
+#[tauri::command]
+pub async fn load_video_with_options<R: tauri::Runtime>(
+    app: tauri::AppHandle<R>,
+    path: String,
+    mut options: loadfile::LoadOptions,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    if options.resume && options.start.is_none() {
+        let file = path.clone();
+        options.start = off_thread(move || Ok(resume_position::resume_from(&app, &file))).await?;
+    }
+    let encoded = options.encode()?;
+    channel
+        .request(|reply| PlayerCommand::Load {
+            path: path.clone(),
+            options: Some(encoded),
+            reply,
+        })
+        .await?;
+    Ok(format!("🎬 Loading video: {}", path))
+}
+
+#[tauri::command]
+pub async fn play_pause(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    let paused = channel
+        .request(|reply| PlayerCommand::TogglePause { reply })
+        .await?;
+    Ok(if paused {
+        "⏸️ Paused"
+    } else {
+        "▶️ Playing"
+    }
+    .to_string())
+}
+
+// Unlike play_pause, doing nothing when playback is already in that state
+#[tauri::command]
+pub async fn set_paused(
+    paused: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    let paused = channel
+        .request(|reply| PlayerCommand::SetPause { paused, reply })
+        .await?;
+    Ok(if paused {
+        "⏸️ Paused"
+    } else {
+        "▶️ Playing"
+    }
+    .to_string())
+}
+
+// Advance one frame and stay paused, for reviewing footage frame by frame
+#[tauri::command]
+pub async fn frame_step(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    channel
+        .request(|reply| PlayerCommand::FrameStep {
+            backward: false,
+            reply,
+        })
+        .await?;
+    Ok("⏭️ Next frame".to_string())
+}
+
+// Going back decodes from the previous keyframe, so it is slower than stepping forward
+#[tauri::command]
+pub async fn frame_back_step(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    channel
+        .request(|reply| PlayerCommand::FrameStep {
+            backward: true,
+            reply,
+        })
+        .await?;
+    Ok("⏮️ Previous frame".to_string())
+}
+
+pub(crate) const MAX_PAUSE_FADE_MS: u64 = 5000;
+
+#[tauri::command]
+pub async fn set_pause_fade(
+    enabled: bool,
+    duration_ms: u64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .configure(move |player, handle| {
+            if !enabled {
+                player.pause_fade.set_duration(None);
+                if let Some(handle) = handle {
+                    player.pause_fade.cancel(handle);
+                }
+                return Ok("Pause fade disabled".to_string());
+            }
+            if !(1..=MAX_PAUSE_FADE_MS).contains(&duration_ms) {
+                return Err(PlayerError::InvalidArgument(format!(
+                    "Fade duration must be between 1 and {} ms",
+                    MAX_PAUSE_FADE_MS
+                )));
+            }
+
+            player
+                .pause_fade
+                .set_duration(Some(std::time::Duration::from_millis(duration_ms)));
+            Ok(format!("🔉 Pause fade set to {} ms", duration_ms))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn stop_video(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    channel
+        .request(|reply| PlayerCommand::Stop { reply })
+        .await?;
+    Ok("⏹️ Stopped".to_string())
+}
+
+// Seek to `position` seconds. `exact` overrides the precise seeking setting for this
+// seek; the result says which kind of seek was done.
+#[tauri::command]
+pub async fn seek(
+    position: f64,
+    exact: Option<bool>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<channel::SeekResult, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    if !position.is_finite() || position < 0.0 {
+        return Err(PlayerError::InvalidArgument(
+            "Position must be a non-negative number of seconds".to_string(),
+        ));
+    }
+    channel
+        .request(|reply| PlayerCommand::Seek {
+            target: channel::SeekTarget::Seconds(position),
+            exact,
+            reply,
+        })
+        .await
+}
+
+// Seek to a fraction of the duration as mpv itself knows it; `exact` as for seek
+#[tauri::command]
+pub async fn seek_percent(
+    percent: f64,
+    exact: Option<bool>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<channel::SeekResult, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    if !(0.0..=100.0).contains(&percent) {
+        return Err(PlayerError::InvalidArgument(
+            "Percent must be between 0 and 100".to_string(),
+        ));
+    }
+    channel
+        .request(|reply| PlayerCommand::Seek {
+            target: channel::SeekTarget::Percent(percent),
+            exact,
+            reply,
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn set_playback_speed(
+    speed: f64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    // Validate speed range
+    if !(0.25..=4.0).contains(&speed) {
+        return Err(PlayerError::InvalidArgument(
+            "Speed must be between 0.25x and 4.0x".to_string(),
+        ));
+    }
+    let speed = channel
+        .request(|reply| PlayerCommand::SetSpeed { speed, reply })
+        .await?;
+    Ok(format!("🏃 Speed set to {:.2}x", speed))
+}
+
+#[tauri::command]
+pub async fn get_playback_speed(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<f64, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let channel = &instance.channel;
+        channel
+            .snapshot()
+            .map(|snapshot| snapshot.speed)
+            .ok_or(PlayerError::NotInitialized)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn speed_preset(
+    speed: f64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    set_playback_speed(speed, player_id, players).await
+}
+
+// Keep the pitch when playing faster or slower (on by default). Saved with the session,
+// like the speed itself.
+#[tauri::command]
+pub async fn set_pitch_correction(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            handle
+                .set(pitch::PROPERTY, enabled)
+                .context("Failed to set pitch correction")?;
+            pitch::sync(handle)?;
+            Ok(if enabled {
+                "🎵 Pitch kept at any speed".to_string()
+            } else {
+                "🎵 Pitch follows the speed".to_string()
+            })
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_pitch_correction(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<bool, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            handle
+                .get::<bool>(pitch::PROPERTY)
+                .context("Failed to get pitch correction")
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_position(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Option<f64>, PlayerError> {
+    get_progress(player_id, players)
+        .await
+        .map(|progress| progress.position)
+}
+
+#[tauri::command]
+pub async fn get_duration(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Option<f64>, PlayerError> {
+    get_progress(player_id, players)
+        .await
+        .map(|progress| progress.duration)
+}
+
+// Both at once, for progress bars that poll every tick. None while nothing is loaded
+// (and duration also for live streams that don't have one).
+#[derive(Debug, Clone, Serialize)]
+pub struct Progress {
+    position: Option<f64>,
+    duration: Option<f64>,
+}
+
+#[tauri::command]
+pub async fn get_progress(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Progress, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let channel = &instance.channel;
+        channel
+            .snapshot()
+            .map(|snapshot| Progress {
+                position: snapshot.position,
+                duration: snapshot.duration,
+            })
+            .ok_or(PlayerError::NotInitialized)
+    })
+    .await
+}
+
+// The frame to show above the seekbar at `time`; None until the previews are made (see
+// the seek-preview://ready event), and for streams and audio
+#[tauri::command]
+pub async fn get_preview_thumbnail(
+    app: tauri::AppHandle,
+    time: f64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Option<PreviewThumbnail>, PlayerError> {
+    let instance = players.get(player_id)?;
+    let path = instance
+        .channel
+        .run(move |handle| {
+            handle
+                .get::<String>("path")
+                .map_err(|_| PlayerError::NoFile)
+        })
+        .await?;
+    off_thread(move || Ok(app.state::<SeekPreviews>().frame(&path, time)?)).await
+}
+
+// `points` peaks (default 1000) of the audio of `path`, or of the current file, for a
+// waveform seekbar
+#[tauri::command]
+pub async fn get_waveform(
+    path: Option<String>,
+    points: Option<usize>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Waveform, PlayerError> {
+    let points = points.unwrap_or(waveform::DEFAULT_POINTS);
+    if !(1..=waveform::MAX_POINTS).contains(&points) {
+        return Err(PlayerError::InvalidArgument(format!(
+            "A waveform has 1 to {} points",
+            waveform::MAX_POINTS
+        )));
+    }
+    let path = match path {
+        Some(path) => path,
+        None => {
+            let instance = players.get(player_id)?;
+            instance
+                .channel
+                .run(move |handle| {
+                    handle
+                        .get::<String>("path")
+                        .map_err(|_| PlayerError::NoFile)
+                })
+                .await?
+        }
+    };
+    // A stream would have to be downloaded in full first
+    if path.contains("://") && !path.starts_with("file://") {
+        return Err(PlayerError::InvalidArgument(
+            "Waveforms are only made for local files".to_string(),
+        ));
+    }
+    off_thread(move || Ok(waveform::extract(&path, points)?)).await
+}
+
+#[tauri::command]
+pub async fn set_volume(
+    volume: f64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<f64, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    if !volume.is_finite() {
+        return Err(PlayerError::InvalidArgument(
+            "Volume must be a finite number".to_string(),
+        ));
+    }
+    channel
+        .request(|reply| PlayerCommand::SetVolume { volume, reply })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_volume(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<channel::Volume, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let channel = &instance.channel;
+        let snapshot = channel.snapshot().ok_or(PlayerError::NotInitialized)?;
+        Ok(channel::Volume {
+            volume: snapshot.volume.unwrap_or(100.0),
+            muted: snapshot.muted,
+        })
+    })
+    .await
+}
+
+// Returns whether audio is now muted
+#[tauri::command]
+pub async fn toggle_mute(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<bool, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    channel
+        .request(|reply| PlayerCommand::ToggleMute { reply })
+        .await
+}
+
+// Positive values play the audio later, for a picture that lags behind (Bluetooth
+// headphones); negative ones play it earlier. Remembered per file like the subtitle delay.
+#[tauri::command]
+pub async fn set_audio_delay(
+    seconds: f64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    if !seconds.is_finite() {
+        return Err(PlayerError::InvalidArgument(
+            "Audio delay must be a number of seconds".to_string(),
+        ));
+    }
+    instance
+        .channel
+        .run(move |handle| {
+            handle
+                .set("audio-delay", seconds)
+                .context("Failed to set audio delay")?;
+            Ok(format!("🎧 Audio delay {:+.0} ms", seconds * 1000.0))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_audio_delay(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<f64, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            handle
+                .get::<f64>("audio-delay")
+                .context("Failed to get audio delay")
+        })
+        .await
+}
+
+// The outputs audio can go to, "auto" (the system default) first
+#[tauri::command]
+pub async fn list_audio_devices(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<AudioDevice>, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| Ok(audio_devices::read(handle)))
+        .await
+}
+
+// Route audio to `name` from list_audio_devices, straight away. If the device is
+// unplugged, audio falls back to the system default until it's back.
+#[tauri::command]
+pub async fn set_audio_device(
+    name: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            let devices = audio_devices::read(handle);
+            let Some(device) = devices.iter().find(|device| device.name == name) else {
+                return Err(PlayerError::NotFound(format!(
+                    "No audio device named {}",
+                    name
+                )));
+            };
+            handle
+                .set(audio_devices::DEVICE_PROPERTY, &device.name)
+                .context("Failed to switch audio device")?;
+            Ok(format!("🔊 Audio output: {}", device.description))
+        })
+        .await
+}
+
+// Step the speed by `delta` (e.g. from the +/- buttons), returning the new speed.
+// The event thread reads and writes it in one go, so rapid presses can't lose an update.
+#[tauri::command]
+pub async fn adjust_playback_speed(
+    delta: f64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<f64, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    if !delta.is_finite() {
+        return Err(PlayerError::InvalidArgument(
+            "Speed change must be a finite number".to_string(),
+        ));
+    }
+    channel
+        .request(|reply| PlayerCommand::AdjustSpeed { delta, reply })
+        .await
+}
+
+#[tauri::command]
+pub async fn reset_playback_speed(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<f64, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    channel
+        .request(|reply| PlayerCommand::SetSpeed { speed: 1.0, reply })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_playback_stats(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<PlaybackStats, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| Ok(read_playback_stats(handle)))
+        .await
+}
+
+#[tauri::command]
+pub async fn subscribe_playback_stats(
+    interval_ms: u64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            if !(100..=10_000).contains(&interval_ms) {
+                return Err(PlayerError::InvalidArgument(
+                    "Interval must be between 100ms and 10000ms".to_string(),
+                ));
+            }
+            player.event_control.set_stats_interval(handle, interval_ms);
+            Ok(format!("📊 Sending playback stats every {}ms", interval_ms))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn unsubscribe_playback_stats(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            player.event_control.set_stats_interval(handle, 0);
+            Ok("📊 Stopped playback stats".to_string())
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn set_precise_seeking(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<PreciseSeekingStatus, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            apply_precise_seeking(handle, enabled)?;
+            player.config.values.precise_seeking = enabled;
+            player.config.save_or_warn();
+            Ok(read_precise_seeking(handle, enabled))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_precise_seeking(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<PreciseSeekingStatus, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            Ok(read_precise_seeking(
+                handle,
+                player.config.values.precise_seeking,
+            ))
+        })
+        .await
+}
+
+// Everything the UI needs to redraw its controls, e.g. after a webview reload
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerState {
+    pub(crate) initialized: bool,
+    pub(crate) path: Option<String>,
+    pub(crate) paused: bool,
+    pub(crate) position: Option<f64>,
+    pub(crate) duration: Option<f64>,
+    pub(crate) speed: f64,
+    pub(crate) volume: Option<f64>,
+    pub(crate) muted: bool,
+    // "no" when no subtitle track is selected
+    pub(crate) subtitle_track: Option<String>,
+    pub(crate) subtitle_visible: bool,
+    pub(crate) video_layer_visible: bool,
+}
+
+// Reads the event thread's snapshot, so it never waits on a playback command
+#[tauri::command]
+pub async fn get_player_state(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<PlayerState, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let channel = &instance.channel;
+        let video_layer_visible = instance.player.lock().unwrap().video_layer_visible;
+        let snapshot = channel.snapshot();
+        let initialized = snapshot.is_some();
+        let snapshot = snapshot.unwrap_or_default();
+        Ok(PlayerState {
+            initialized,
+            path: snapshot.path,
+            paused: snapshot.paused,
+            position: snapshot.position,
+            duration: snapshot.duration,
+            speed: snapshot.speed,
+            volume: snapshot.volume,
+            muted: snapshot.muted,
+            subtitle_track: snapshot.subtitle_track,
+            subtitle_visible: snapshot.subtitle_visible,
+            video_layer_visible,
+        })
+    })
+    .await
+}
@@ -0,0 +1,732 @@
+// The playlist, dropped files, and what happens when a file ends
+
+use serde::Serialize;
+use std::path::Path;
+use std::sync::Arc;
+use tauri::{Emitter, Manager, Window};
+
+use super::lifecycle::{append_files, queue_files};
+use super::off_thread;
+use crate::error::{Context, PlayerError};
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::player::{MpvPlayer, PlayerId, PlayerInstance, Players};
+use crate::saved_queue::{self, SavedQueue};
+use crate::{events, loop_mode, m3u, media, playlist, pls, tracks};
+
+// What the queue's shuffle and repeat buttons show
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QueueModes {
+    shuffle: bool,
+    repeat: loop_mode::RepeatMode,
+}
+
+fn read_queue_modes(player: &MpvPlayer, handle: &MpvHandle) -> Result<QueueModes, PlayerError> {
+    Ok(QueueModes {
+        shuffle: player.shuffled,
+        repeat: loop_mode::RepeatMode::of(loop_mode::LoopMode::read(handle)?),
+    })
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlaylistImport {
+    loaded: usize,
+    warnings: Vec<String>,
+}
+
+// Written as PLS when `path` ends in .pls, and as extended M3U otherwise
+#[tauri::command]
+pub async fn save_playlist(
+    path: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let entries = instance
+        .run(move |player, handle| {
+            let items = player.event_control.playlist.read(handle);
+            Ok(items.into_iter().map(m3u_entry).collect::<Vec<_>>())
+        })
+        .await?;
+    if entries.is_empty() {
+        return Err(PlayerError::NotFound(
+            "Playlist is empty - nothing to save".to_string(),
+        ));
+    }
+
+    off_thread(move || {
+        let contents = if pls::is_pls(Path::new(&path)) {
+            pls::write(&entries)
+        } else {
+            m3u::write(&entries)
+        };
+        std::fs::write(&path, contents)
+            .map_err(|e| PlayerError::Other(format!("Failed to write playlist: {}", e)))?;
+        Ok(format!("💾 Saved {} entries to {}", entries.len(), path))
+    })
+    .await
+}
+
+fn m3u_entry(item: playlist::PlaylistItem) -> m3u::PlaylistEntry {
+    m3u::PlaylistEntry {
+        path: item.path,
+        title: item.title,
+        duration: item.duration,
+    }
+}
+
+// Queue an .m3u, .m3u8 or .pls file's entries, relative ones resolved against its
+// directory; `replace` starts from the first instead of appending to the queue
+#[tauri::command]
+pub async fn load_playlist(
+    path: String,
+    replace: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<PlaylistImport, PlayerError> {
+    let instance = players.get(player_id)?;
+    let (parsed, mut warnings) = off_thread(move || {
+        let bytes = std::fs::read(&path)
+            .map_err(|e| PlayerError::Other(format!("Failed to read playlist: {}", e)))?;
+        let mut warnings = Vec::new();
+        // Plain .m3u files are often Latin-1 rather than UTF-8
+        let contents = String::from_utf8(bytes).unwrap_or_else(|e| {
+            warnings.push("Playlist is not valid UTF-8, some names may be garbled".to_string());
+            String::from_utf8_lossy(e.as_bytes()).into_owned()
+        });
+
+        let base_dir = Path::new(&path).parent().unwrap_or(Path::new("."));
+        let parsed = if pls::is_pls(Path::new(&path)) {
+            pls::parse(&contents, base_dir)
+        } else {
+            m3u::parse(&contents, base_dir)
+        };
+        warnings.extend(parsed.warnings);
+
+        if parsed.entries.is_empty() {
+            return Err(PlayerError::NotFound(
+                "Playlist contains no playable entries".to_string(),
+            ));
+        }
+        Ok((parsed.entries, warnings))
+    })
+    .await?;
+
+    instance
+        .run(move |player, handle| {
+            let mut loaded = 0;
+            for entry in &parsed {
+                // Shown in the queue until the file's own tags are known
+                player.event_control.playlist.remember(entry);
+                let mode = if replace && loaded == 0 {
+                    "replace"
+                } else {
+                    "append-play"
+                };
+                match handle.command(&["loadfile", &entry.path, mode]) {
+                    Ok(()) => loaded += 1,
+                    Err(e) => warnings.push(format!("Failed to queue {}: {}", entry.path, e)),
+                }
+            }
+
+            Ok(PlaylistImport { loaded, warnings })
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn load_directory(
+    path: String,
+    recursive: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<media::MediaFile>, PlayerError> {
+    let instance = players.get(player_id)?;
+    // Scanning can take a while on a big tree, so it's done before queueing anything
+    let files = off_thread(move || {
+        let dir = Path::new(&path);
+        if !dir.is_dir() {
+            return Err(PlayerError::InvalidArgument(format!(
+                "Not a directory: {}",
+                path
+            )));
+        }
+
+        let files = media::scan_directory(dir, recursive);
+        if files.is_empty() {
+            return Err(PlayerError::NotFound(format!(
+                "No media files found in {}",
+                path
+            )));
+        }
+        Ok(files)
+    })
+    .await?;
+
+    instance
+        .channel
+        .run(move |handle| {
+            let failures = queue_files(handle, &files);
+            if failures.len() == files.len() {
+                return Err(PlayerError::Other(failures.join("; ")));
+            }
+            for failure in failures {
+                eprintln!("{}", failure);
+            }
+            Ok(files)
+        })
+        .await
+}
+
+fn check_index(handle: &MpvHandle, index: usize) -> Result<(), PlayerError> {
+    let count = playlist::count(handle);
+    if index >= count {
+        return Err(PlayerError::InvalidArgument(format!(
+            "No playlist entry {} - the playlist has {}",
+            index, count
+        )));
+    }
+    Ok(())
+}
+
+// Runs `change` against the playlist, then reads it back for the page
+async fn edit_playlist(
+    instance: Arc<PlayerInstance>,
+    change: impl FnOnce(&MpvHandle) -> Result<(), PlayerError> + Send + 'static,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    instance
+        .run(move |player, handle| {
+            change(handle)?;
+            Ok(player.event_control.playlist.read(handle))
+        })
+        .await
+}
+
+// Put back the main player's queue from the last session, if restore_queue is on and
+// one was saved; what was playing picks up where it was. For the page to call once the
+// player is initialized.
+#[tauri::command]
+pub async fn restore_session(
+    app: tauri::AppHandle,
+    players: tauri::State<'_, Players>,
+) -> Result<Option<SavedQueue>, PlayerError> {
+    let instance = players.main();
+    let queue = off_thread(move || {
+        let enabled = instance.player.lock().unwrap().config.values.restore_queue;
+        Ok(saved_queue::load(&app).filter(|q| enabled && !q.entries.is_empty()))
+    })
+    .await?;
+    let Some(queue) = queue else {
+        return Ok(None);
+    };
+    players
+        .main()
+        .channel
+        .run(move |handle| {
+            queue.restore(handle)?;
+            Ok(Some(queue))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_playlist(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    edit_playlist(players.get(player_id)?, |_| Ok(())).await
+}
+
+// Queue files after the last entry; the first starts playing if nothing is
+#[tauri::command]
+pub async fn playlist_add(
+    paths: Vec<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    let instance = players.get(player_id)?;
+    if paths.is_empty() {
+        return Err(PlayerError::InvalidArgument("No files to add".to_string()));
+    }
+    // Looking for sidecar subtitles reads each file's directory
+    let files = off_thread(move || {
+        Ok(paths
+            .iter()
+            .map(|path| media::MediaFile::new(Path::new(path)))
+            .collect::<Vec<_>>())
+    })
+    .await?;
+    edit_playlist(instance, move |handle| {
+        let failures = append_files(handle, &files);
+        if failures.len() == files.len() {
+            return Err(PlayerError::Other(failures.join("; ")));
+        }
+        for failure in failures {
+            eprintln!("{}", failure);
+        }
+        Ok(())
+    })
+    .await
+}
+
+// Removing the entry that's playing moves on to the next one
+#[tauri::command]
+pub async fn playlist_remove(
+    index: usize,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    edit_playlist(players.get(player_id)?, move |handle| {
+        check_index(handle, index)?;
+        handle
+            .command(&["playlist-remove", &index.to_string()])
+            .context("Failed to remove the playlist entry")
+    })
+    .await
+}
+
+// Move the entry at `from` so it ends up at index `to`
+#[tauri::command]
+pub async fn playlist_move(
+    from: usize,
+    to: usize,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    edit_playlist(players.get(player_id)?, move |handle| {
+        check_index(handle, from)?;
+        check_index(handle, to)?;
+        if from == to {
+            return Ok(());
+        }
+        let target = playlist::move_target(from, to).to_string();
+        handle
+            .command(&["playlist-move", &from.to_string(), &target])
+            .context("Failed to move the playlist entry")
+    })
+    .await
+}
+
+// Everything but what's playing, as in mpv; stop_video ends that too
+#[tauri::command]
+pub async fn playlist_clear(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    edit_playlist(players.get(player_id)?, |handle| {
+        handle
+            .command(&["playlist-clear"])
+            .context("Failed to clear the playlist")
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn playlist_next(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    edit_playlist(players.get(player_id)?, |handle| {
+        // mpv fails it at the end of the playlist, which is nothing to report
+        handle
+            .command(&["playlist-next"])
+            .map_err(|_| PlayerError::NotFound("Already at the last entry".to_string()))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn playlist_prev(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    edit_playlist(players.get(player_id)?, |handle| {
+        handle
+            .command(&["playlist-prev"])
+            .map_err(|_| PlayerError::NotFound("Already at the first entry".to_string()))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn playlist_play_index(
+    index: usize,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<playlist::PlaylistItem>, PlayerError> {
+    edit_playlist(players.get(player_id)?, move |handle| {
+        check_index(handle, index)?;
+        handle
+            .command(&["playlist-play-index", &index.to_string()])
+            .context("Failed to play the playlist entry")
+    })
+    .await
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FilesDropped {
+    accepted: Vec<String>,
+    // Subtitle files, attached to the playing video or loading with the first accepted one
+    subtitles: Vec<String>,
+    rejected: Vec<String>,
+    // True when mpv wasn't running yet and the files will load on init
+    queued: bool,
+}
+
+// What was dropped, sorted into what can play, subtitles and the rest
+struct DroppedFiles {
+    accepted: Vec<media::MediaFile>,
+    subtitles: Vec<String>,
+    rejected: Vec<String>,
+}
+
+impl DroppedFiles {
+    // Reads dropped folders, so not on the main thread
+    fn sort(paths: &[std::path::PathBuf]) -> Self {
+        let mut dropped = Self {
+            accepted: Vec::new(),
+            subtitles: Vec::new(),
+            rejected: Vec::new(),
+        };
+        for path in paths {
+            if path.is_dir() {
+                let files = media::scan_directory(path, false);
+                if files.is_empty() {
+                    dropped.rejected.push(path.to_string_lossy().into_owned());
+                }
+                dropped.accepted.extend(files);
+            } else if media::is_media_file(path) {
+                dropped.accepted.push(media::MediaFile::new(path));
+            } else if media::is_subtitle_file(path) {
+                dropped.subtitles.push(path.to_string_lossy().into_owned());
+            } else {
+                dropped.rejected.push(path.to_string_lossy().into_owned());
+            }
+        }
+        dropped
+    }
+
+    // With nothing playing to attach them to, subtitles load with the first accepted file
+    fn attach_subtitles_to_first(&mut self) {
+        if let Some(first) = self.accepted.first_mut() {
+            first.subtitles.extend(self.subtitles.iter().cloned());
+        } else {
+            self.rejected.append(&mut self.subtitles);
+        }
+    }
+
+    fn into_payload(self, queued: bool) -> FilesDropped {
+        FilesDropped {
+            accepted: self.accepted.into_iter().map(|f| f.path).collect(),
+            subtitles: self.subtitles,
+            rejected: self.rejected,
+            queued,
+        }
+    }
+}
+
+// The folder scan runs on the blocking pool and the files are queued on the event
+// thread, so a large drop doesn't hold up the window
+pub(crate) fn handle_file_drop(window: &Window, paths: &[std::path::PathBuf]) {
+    let window = window.clone();
+    let paths = paths.to_vec();
+    tauri::async_runtime::spawn(async move {
+        // Drops always go to the main player
+        let main = window.state::<Players>().main();
+        let result = match off_thread(move || Ok(DroppedFiles::sort(&paths))).await {
+            Ok(dropped) => queue_drop(&main, dropped).await,
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(payload) => {
+                if let Err(e) = window.emit("files-dropped", payload) {
+                    eprintln!("Failed to emit files-dropped: {}", e);
+                }
+            }
+            Err(e) => eprintln!("Failed to queue dropped files: {}", e),
+        }
+    });
+}
+
+async fn queue_drop(
+    instance: &Arc<PlayerInstance>,
+    mut dropped: DroppedFiles,
+) -> Result<FilesDropped, PlayerError> {
+    {
+        // init_mpv_player takes pending files under this lock, so none are missed
+        let mut player = instance.player.lock().unwrap();
+        if !player.running() {
+            dropped.attach_subtitles_to_first();
+            player
+                .pending_files
+                .extend(dropped.accepted.iter().cloned());
+            let queued = !dropped.accepted.is_empty();
+            return Ok(dropped.into_payload(queued));
+        }
+    }
+    instance
+        .channel
+        .run(move |handle| {
+            if handle.get::<String>("path").is_ok() {
+                let DroppedFiles {
+                    subtitles,
+                    rejected,
+                    ..
+                } = &mut dropped;
+                subtitles.retain(|path| {
+                    match tracks::add_subtitle(
+                        handle,
+                        path,
+                        tracks::SubtitleFlag::Select,
+                        None,
+                        None,
+                    ) {
+                        Ok(_) => true,
+                        Err(e) => {
+                            eprintln!("{}", e);
+                            rejected.push(path.clone());
+                            false
+                        }
+                    }
+                });
+            } else {
+                dropped.attach_subtitles_to_first();
+            }
+            for failure in queue_files(handle, &dropped.accepted) {
+                eprintln!("{}", failure);
+            }
+            Ok(dropped.into_payload(false))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn set_sleep_timer(
+    minutes: u64,
+    action: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let action = match action.as_str() {
+                "pause" => events::SleepAction::Pause,
+                "stop" => events::SleepAction::Stop,
+                "quit_app" => events::SleepAction::QuitApp,
+                "end_of_file" => events::SleepAction::EndOfFile,
+                _ => {
+                    return Err(PlayerError::InvalidArgument(
+                        "Action must be 'pause', 'stop', 'quit_app' or 'end_of_file'".to_string(),
+                    ))
+                }
+            };
+
+            let deadline = if action == events::SleepAction::EndOfFile {
+                None
+            } else {
+                if !(1..=1440).contains(&minutes) {
+                    return Err(PlayerError::InvalidArgument(
+                        "Sleep timer must be between 1 and 1440 minutes".to_string(),
+                    ));
+                }
+                Some(std::time::Instant::now() + std::time::Duration::from_secs(minutes * 60))
+            };
+
+            player
+                .event_control
+                .set_sleep_timer(handle, Some(events::SleepTimer { action, deadline }));
+
+            Ok(match deadline {
+                Some(_) => format!("😴 Sleep timer set for {} minutes", minutes),
+                None => "😴 Playback will stop when this file ends".to_string(),
+            })
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn cancel_sleep_timer(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            if player.event_control.set_sleep_timer(handle, None) {
+                Ok("Sleep timer cancelled".to_string())
+            } else {
+                Ok("No sleep timer was set".to_string())
+            }
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_sleep_timer(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Option<events::SleepTimerStatus>, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let player = instance.player.lock().unwrap();
+        Ok(player.event_control.sleep_timer_status())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_end_behavior(
+    mode: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            if !["no", "yes", "always"].contains(&mode.as_str()) {
+                return Err(PlayerError::InvalidArgument(
+                    "End behavior must be 'no', 'yes' or 'always'".to_string(),
+                ));
+            }
+
+            player
+                .event_control
+                .set_end_behavior(handle, &mode)
+                .context("Failed to set keep-open")?;
+            Ok(format!("🎞️ keep-open set to {}", mode))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_end_behavior(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            player
+                .event_control
+                .end_behavior(handle)
+                .context("Failed to get keep-open")
+        })
+        .await
+}
+
+// Only the loops given change; returns the resulting mode
+#[tauri::command]
+pub async fn set_loop_mode(
+    mode: loop_mode::LoopModeUpdate,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<loop_mode::LoopMode, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            loop_mode::LoopMode::apply(&mode, handle)?;
+            Ok(loop_mode::LoopMode::read(handle)?)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_loop_mode(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<loop_mode::LoopMode, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| Ok(loop_mode::LoopMode::read(handle)?))
+        .await
+}
+
+// Turning it off puts the playlist back in the order it had, with anything added since
+// at the end. A playlist loaded while it's on keeps its own order until it's toggled.
+#[tauri::command]
+pub async fn set_shuffle(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<QueueModes, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            if enabled != player.shuffled {
+                let command = if enabled {
+                    "playlist-shuffle"
+                } else {
+                    "playlist-unshuffle"
+                };
+                handle
+                    .command(&[command])
+                    .context(format!("Failed to run {}", command))?;
+                player.shuffled = enabled;
+            }
+            read_queue_modes(player, handle)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn set_repeat(
+    mode: loop_mode::RepeatMode,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<QueueModes, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            loop_mode::LoopMode::apply(&mode.update(), handle)?;
+            read_queue_modes(player, handle)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_queue_modes(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<QueueModes, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(|player, handle| read_queue_modes(player, handle))
+        .await
+}
+
+#[tauri::command]
+pub async fn stop_after_current(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            player
+                .event_control
+                .set_stop_after_current(handle, enabled)
+                .context("Failed to hold keep-open")?;
+            Ok(if enabled {
+                "⏹️ Will stop after the current file".to_string()
+            } else {
+                "Playback will continue after the current file".to_string()
+            })
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_stop_after_current(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<bool, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let player = instance.player.lock().unwrap();
+        Ok(player.event_control.stop_after_current())
+    })
+    .await
+}
@@ -0,0 +1,315 @@
+// Settings saved per file and for the app, and the session state they restore
+
+use std::sync::atomic::Ordering;
+
+use super::off_thread;
+use crate::error::PlayerError;
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::player::{PlayerId, Players};
+use crate::{config, file_settings, pitch, resume_position, saved_queue};
+
+pub(crate) fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
+// Session settings are applied one by one so a bad value doesn't block the rest
+pub(crate) fn apply_session_settings(
+    handle: &MpvHandle,
+    values: &config::SessionSettings,
+) -> Result<(), PlayerError> {
+    let properties = [
+        ("speed", values.speed.clamp(0.25, 4.0).to_string()),
+        ("volume", values.volume.max(0.0).to_string()),
+        ("mute", yes_no(values.mute).to_string()),
+        (
+            "sub-visibility",
+            yes_no(values.subtitle_visible).to_string(),
+        ),
+        (pitch::PROPERTY, yes_no(values.pitch_correction).to_string()),
+    ];
+    let mut failures: Vec<String> = properties
+        .iter()
+        .filter_map(|(name, value)| {
+            let e = handle.set(name, value).err()?;
+            Some(format!("{}={}: {}", name, value, e))
+        })
+        .collect();
+    if let Err(e) = pitch::sync(handle) {
+        failures.push(e.to_string());
+    }
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(PlayerError::Other(format!(
+            "Failed to set {}",
+            failures.join(", ")
+        )))
+    }
+}
+
+pub(crate) fn read_session_settings(handle: &MpvHandle) -> config::SessionSettings {
+    let defaults = config::SessionSettings::default();
+    config::SessionSettings {
+        speed: handle.get::<f64>("speed").unwrap_or(defaults.speed),
+        volume: handle.get::<f64>("volume").unwrap_or(defaults.volume),
+        mute: handle.get::<bool>("mute").unwrap_or(defaults.mute),
+        subtitle_visible: handle
+            .get::<bool>("sub-visibility")
+            .unwrap_or(defaults.subtitle_visible),
+        pitch_correction: handle
+            .get::<bool>(pitch::PROPERTY)
+            .unwrap_or(defaults.pitch_correction),
+    }
+}
+
+#[tauri::command]
+pub async fn get_file_settings(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<Option<file_settings::FileSettings>, PlayerError> {
+    off_thread(move || Ok(file_settings::load(&app, &path))).await
+}
+
+#[tauri::command]
+pub async fn clear_file_settings(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<String, PlayerError> {
+    off_thread(move || {
+        if file_settings::clear(&app, &path)? {
+            Ok(format!("🧹 Cleared saved settings for {}", path))
+        } else {
+            Ok(format!("No saved settings for {}", path))
+        }
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_saved_position(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<Option<resume_position::SavedPosition>, PlayerError> {
+    off_thread(move || Ok(resume_position::load(&app, &path))).await
+}
+
+#[tauri::command]
+pub async fn clear_saved_position(
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<String, PlayerError> {
+    off_thread(move || {
+        if resume_position::clear(&app, &path)? {
+            Ok(format!("🧹 {} will start from the beginning", path))
+        } else {
+            Ok(format!("No saved position for {}", path))
+        }
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_restore_per_file_settings(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let mut player = instance.player.lock().unwrap();
+
+        player.config.values.restore_per_file_settings = enabled;
+        player
+            .event_control
+            .restore_per_file_settings
+            .store(enabled, Ordering::SeqCst);
+        player.config.save_or_warn();
+
+        Ok(if enabled {
+            "Per-file settings will be restored".to_string()
+        } else {
+            "Per-file settings will not be restored".to_string()
+        })
+    })
+    .await
+}
+
+// Whether the next file in the folder plays once the queue has run out (see the
+// auto-advance event)
+#[tauri::command]
+pub async fn set_auto_advance(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let mut player = instance.player.lock().unwrap();
+
+        player.config.values.auto_advance = enabled;
+        player
+            .event_control
+            .auto_advance
+            .store(enabled, Ordering::SeqCst);
+        player.config.save_or_warn();
+
+        Ok(if enabled {
+            "⏭️ The next file in the folder will play when the queue ends".to_string()
+        } else {
+            "Playback will stop when the queue ends".to_string()
+        })
+    })
+    .await
+}
+
+// Whether the event thread keeps saving where files were left. Positions already saved
+// stay until cleared.
+#[tauri::command]
+pub async fn set_remember_position(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let mut player = instance.player.lock().unwrap();
+
+        player.config.values.remember_position = enabled;
+        player
+            .event_control
+            .remember_position
+            .store(enabled, Ordering::SeqCst);
+        player.config.save_or_warn();
+
+        Ok(if enabled {
+            "📍 Playback positions will be remembered".to_string()
+        } else {
+            "Playback positions will not be remembered".to_string()
+        })
+    })
+    .await
+}
+
+// Turning it off forgets the saved queue too
+#[tauri::command]
+pub async fn set_restore_queue(
+    app: tauri::AppHandle,
+    enabled: bool,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.main();
+    off_thread(move || {
+        let mut player = instance.player.lock().unwrap();
+
+        player.config.values.restore_queue = enabled;
+        player
+            .event_control
+            .restore_queue
+            .store(enabled, Ordering::SeqCst);
+        player.config.save_or_warn();
+        if !enabled {
+            saved_queue::clear(&app);
+        }
+
+        Ok(if enabled {
+            "💾 The queue will be kept for next time".to_string()
+        } else {
+            "The queue won't be kept".to_string()
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_app_settings(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<config::SessionSettings, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let session = instance
+            .player
+            .lock()
+            .unwrap()
+            .config
+            .values
+            .session
+            .clone();
+        Ok(session)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_app_settings(
+    settings: config::SessionSettings,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<config::SessionSettings, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .configure(move |player, handle| {
+            // Applied first so the saved values are the ones mpv accepted
+            if let Some(handle) = handle {
+                apply_session_settings(handle, &settings)?;
+                player.config.values.session = read_session_settings(handle);
+            } else {
+                player.config.values.session = settings;
+            }
+            player.config.save()?;
+            Ok(player.config.values.session.clone())
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn set_prevent_sleep(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .configure(move |player, handle| {
+            player.config.values.prevent_sleep = enabled;
+            if let Some(handle) = handle {
+                player.event_control.set_prevent_sleep(handle, enabled);
+            }
+            player.config.save_or_warn();
+
+            Ok(if enabled {
+                "☀️ Display will stay awake during playback".to_string()
+            } else {
+                "Display may sleep during playback".to_string()
+            })
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn set_dock_progress_enabled(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .configure(move |player, handle| {
+            player.config.values.dock_progress = enabled;
+            if let Some(handle) = handle {
+                player.event_control.set_dock_progress(handle, enabled);
+            }
+            player.config.save_or_warn();
+
+            Ok(if enabled {
+                "Playback progress shown on the Dock icon".to_string()
+            } else {
+                "Dock progress hidden".to_string()
+            })
+        })
+        .await
+}
@@ -0,0 +1,321 @@
+// Subtitle styling, visibility and OpenSubtitles downloads
+
+use serde::Deserialize;
+use std::path::Path;
+use tauri::Manager;
+
+use super::off_thread;
+use crate::error::{Context, PlayerError};
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::player::channel::PlayerCommand;
+use crate::player::{MpvPlayer, PlayerId, Players};
+use crate::{config, opensubtitles, subtitle_text, tracks};
+
+pub(crate) fn apply_subtitle_style(
+    handle: &MpvHandle,
+    style: &config::SubtitleStyle,
+) -> Result<(), PlayerError> {
+    let border_style = if style.background_box {
+        "background-box"
+    } else {
+        "outline-and-shadow"
+    };
+    let properties = [
+        ("sub-font", style.font.clone()),
+        ("sub-font-size", style.font_size.to_string()),
+        ("sub-color", style.color.clone()),
+        ("sub-border-size", style.border_size.to_string()),
+        ("sub-border-color", style.border_color.clone()),
+        ("sub-border-style", border_style.to_string()),
+        ("sub-back-color", style.background_color.clone()),
+        ("sub-pos", style.position.to_string()),
+    ];
+    for (name, value) in properties {
+        handle
+            .set(name, &value)
+            .context(format!("Failed to set {}", name))?;
+    }
+    Ok(())
+}
+
+// mpv also takes r/g/b/a floats, but hex is what a color picker gives us
+fn is_hex_color(value: &str) -> bool {
+    value
+        .strip_prefix('#')
+        .is_some_and(|hex| matches!(hex.len(), 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubtitleStyleUpdate {
+    font: Option<String>,
+    font_size: Option<f64>,
+    color: Option<String>,
+    border_size: Option<f64>,
+    border_color: Option<String>,
+    background_box: Option<bool>,
+    background_color: Option<String>,
+    position: Option<i64>,
+}
+
+#[tauri::command]
+pub async fn set_subtitle_style(
+    options: SubtitleStyleUpdate,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<config::SubtitleStyle, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let mut values = player.config.values.subtitle_style.clone();
+
+            if let Some(font) = options.font {
+                if font.trim().is_empty() {
+                    return Err(PlayerError::InvalidArgument(
+                        "Font name can't be empty".to_string(),
+                    ));
+                }
+                values.font = font;
+            }
+            if let Some(size) = options.font_size {
+                if !(1.0..=200.0).contains(&size) {
+                    return Err(PlayerError::InvalidArgument(
+                        "Font size must be between 1 and 200".to_string(),
+                    ));
+                }
+                values.font_size = size;
+            }
+            if let Some(size) = options.border_size {
+                if !(0.0..=20.0).contains(&size) {
+                    return Err(PlayerError::InvalidArgument(
+                        "Border size must be between 0 and 20".to_string(),
+                    ));
+                }
+                values.border_size = size;
+            }
+            if let Some(position) = options.position {
+                if !(0..=100).contains(&position) {
+                    return Err(PlayerError::InvalidArgument(
+                        "Subtitle position must be between 0 and 100".to_string(),
+                    ));
+                }
+                values.position = position;
+            }
+            if let Some(background_box) = options.background_box {
+                values.background_box = background_box;
+            }
+            let colors = [
+                (options.color, &mut values.color),
+                (options.border_color, &mut values.border_color),
+                (options.background_color, &mut values.background_color),
+            ];
+            for (color, value) in colors {
+                if let Some(color) = color {
+                    if !is_hex_color(&color) {
+                        return Err(PlayerError::InvalidArgument(format!(
+                            "Invalid color '{}'. Use #RRGGBB or #AARRGGBB",
+                            color
+                        )));
+                    }
+                    *value = color;
+                }
+            }
+
+            apply_subtitle_style(handle, &values)?;
+            player.config.values.subtitle_style = values.clone();
+            player.config.save_or_warn();
+            Ok(values)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_subtitle_style(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<config::SubtitleStyle, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let player = instance.player.lock().unwrap();
+        Ok(player.config.values.subtitle_style.clone())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn set_opensubtitles_api_key(
+    api_key: Option<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let mut player = instance.player.lock().unwrap();
+
+        let api_key = api_key.filter(|key| !key.trim().is_empty());
+        let message = if api_key.is_some() {
+            "OpenSubtitles API key saved"
+        } else {
+            "OpenSubtitles API key removed"
+        };
+        player.config.values.opensubtitles_api_key = api_key;
+        player.config.save_or_warn();
+        Ok(message.to_string())
+    })
+    .await
+}
+
+fn opensubtitles_api_key(player: &MpvPlayer) -> Result<String, PlayerError> {
+    player
+        .config
+        .values
+        .opensubtitles_api_key
+        .clone()
+        .ok_or_else(|| PlayerError::NotFound("Set an OpenSubtitles API key first".to_string()))
+}
+
+// Look the current file up on OpenSubtitles by hash and by name. `languages` is a
+// comma-separated list of codes, English by default.
+#[tauri::command]
+pub async fn search_subtitles(
+    languages: Option<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<opensubtitles::SubtitleCandidate>, PlayerError> {
+    let instance = players.get(player_id)?;
+    let path = instance
+        .channel
+        .snapshot()
+        .ok_or(PlayerError::NotInitialized)?
+        .path
+        .ok_or(PlayerError::NoFile)?;
+
+    off_thread(move || {
+        let api_key = opensubtitles_api_key(&instance.player.lock().unwrap())?;
+        let path = Path::new(&path);
+        // Streams, and files too small to hash, are only searched by name
+        let hash = opensubtitles::movie_hash(path).ok();
+        let name = path
+            .file_stem()
+            .map_or_else(|| path.to_string_lossy(), |stem| stem.to_string_lossy());
+        Ok(opensubtitles::search(
+            &api_key,
+            hash.as_deref(),
+            &name,
+            languages.as_deref().unwrap_or("en"),
+        )?)
+    })
+    .await
+}
+
+// Fetch a search result into the app's cache and attach it to the current file
+#[tauri::command]
+pub async fn download_subtitle(
+    file_id: i64,
+    app: tauri::AppHandle,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<tracks::Track, PlayerError> {
+    let instance = players.get(player_id)?;
+    let dir = app
+        .path()
+        .app_cache_dir()
+        .map_err(|e| PlayerError::Other(format!("No cache directory: {}", e)))?
+        .join("subtitles");
+
+    let target = instance.clone();
+    let path = off_thread(move || {
+        let api_key = opensubtitles_api_key(&target.player.lock().unwrap())?;
+        Ok(opensubtitles::download(&api_key, file_id, &dir)?)
+    })
+    .await?;
+
+    instance
+        .channel
+        .run(move |handle| {
+            Ok(tracks::add_subtitle(
+                handle,
+                &path.to_string_lossy(),
+                tracks::SubtitleFlag::Cached,
+                None,
+                None,
+            )?)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn toggle_subtitle_visibility(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<bool, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    channel
+        .request(|reply| PlayerCommand::SetSubtitleVisibility {
+            visible: None,
+            reply,
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn set_subtitle_visibility(
+    visible: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<bool, PlayerError> {
+    let instance = players.get(player_id)?;
+    let channel = &instance.channel;
+    channel
+        .request(|reply| PlayerCommand::SetSubtitleVisibility {
+            visible: Some(visible),
+            reply,
+        })
+        .await
+}
+
+// Turn mpv's own subtitle rendering on or off. The subtitle-text-changed events keep
+// coming either way, for frontends that draw the text themselves.
+#[tauri::command]
+pub async fn set_native_subtitle_rendering(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            for name in ["sub-visibility", "secondary-sub-visibility"] {
+                handle
+                    .set(name, enabled)
+                    .context(format!("Failed to set {}", name))?;
+            }
+            Ok(if enabled {
+                "Subtitles rendered by mpv"
+            } else {
+                "Subtitles rendered by the frontend"
+            }
+            .to_string())
+        })
+        .await
+}
+
+// The line currently showing; `raw` keeps the ASS styling tags
+#[tauri::command]
+pub async fn get_current_subtitle(
+    raw: Option<bool>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<subtitle_text::SubtitleText, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            Ok(subtitle_text::SubtitleText::read(
+                handle,
+                raw.unwrap_or(false),
+            ))
+        })
+        .await
+}
@@ -0,0 +1,1308 @@
+// Command logic against the stub backend; run with `cargo test --no-default-features`
+
+use std::sync::mpsc;
+use std::time::Duration;
+use tauri::{Listener, Manager};
+
+use super::audio::*;
+use super::lifecycle::*;
+use super::navigation::*;
+use super::passthrough::*;
+use super::playback::*;
+use super::queue::*;
+use super::subtitles::*;
+use super::tracks::*;
+use super::video::*;
+use crate::error::PlayerError;
+use crate::mpv::{EndFileReason, Event, MpvHandle, PlayerBackend};
+use crate::player::channel;
+use crate::player::{self, MpvPlayer, Players};
+use crate::{config, events, loadfile, loop_mode, mpv, tracks};
+use tauri::async_runtime::block_on;
+use tauri::test::{mock_app, MockRuntime};
+
+// A player whose mpv instance is up, as after init_mpv_player, with a thread
+// serving playback commands in place of the event loop
+fn app_with_player() -> tauri::App<MockRuntime> {
+    let app = mock_app();
+    let players = Players::with_main(MpvPlayer::new());
+    let main = players.main();
+    let mut player = main.player.lock().unwrap();
+    let server = channel::spawn_test_server(&main.channel, player.pause_fade.clone());
+    player.event_thread = Some(server);
+    drop(player);
+    app.manage(players);
+    app
+}
+
+// A player started the way init_mpv_player does, with the real event loop driving
+// the stub. Settings that would read or write the app data dir are off.
+fn app_with_event_loop() -> tauri::App<MockRuntime> {
+    let app = mock_app();
+    let mut player = MpvPlayer::new();
+    let values = &mut player.config.values;
+    values.restore_per_file_settings = false;
+    values.remember_position = false;
+    values.restore_queue = false;
+    app.manage(Players::with_main(player));
+    start_player(app.handle(), &app.state::<Players>().main()).unwrap();
+    app
+}
+
+// Payloads of `name` as the event loop emits them
+fn listen(app: &tauri::App<MockRuntime>, name: &str) -> mpsc::Receiver<serde_json::Value> {
+    let (sender, receiver) = mpsc::channel();
+    app.listen_any(name, move |event| {
+        let _ = sender.send(serde_json::from_str(event.payload()).unwrap());
+    });
+    receiver
+}
+
+fn next_event(receiver: &mpsc::Receiver<serde_json::Value>) -> serde_json::Value {
+    receiver
+        .recv_timeout(Duration::from_secs(5))
+        .expect("event not emitted")
+}
+
+// Play the current file to its end and let the event loop see it
+fn finish_current_file(app: &tauri::App<MockRuntime>) {
+    on_mpv(app, |handle| {
+        handle.elapse(Duration::from_secs_f64(mpv::stub::FAKE_DURATION))
+    });
+}
+
+// Run `work` on the thread that owns the main player's mpv instance
+fn on_mpv<T: Send + 'static>(
+    app: &tauri::App<MockRuntime>,
+    work: impl FnOnce(&MpvHandle) -> T + Send + 'static,
+) -> T {
+    let main = app.state::<Players>().main();
+    block_on(main.channel.run(move |handle| Ok(work(handle)))).unwrap()
+}
+
+fn get<T: mpv::GetProperty + Send + 'static>(
+    app: &tauri::App<MockRuntime>,
+    name: &str,
+) -> mpv::Result<T> {
+    let name = name.to_string();
+    on_mpv(app, move |handle| handle.get::<T>(&name))
+}
+
+#[test]
+fn commands_need_an_initialized_player() {
+    let app = mock_app();
+    app.manage(Players::default());
+    assert!(block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).is_err());
+    assert!(block_on(play_pause(None, app.state())).is_err());
+    assert!(block_on(set_playback_speed(1.5, None, app.state())).is_err());
+    assert!(
+        !block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .initialized
+    );
+}
+
+#[test]
+fn speed_is_validated_rounded_and_clamped() {
+    let app = app_with_player();
+    assert!(block_on(set_playback_speed(5.0, None, app.state())).is_err());
+    block_on(set_playback_speed(1.5, None, app.state())).unwrap();
+    assert_eq!(
+        block_on(get_playback_speed(None, app.state())).unwrap(),
+        1.5
+    );
+    assert_eq!(
+        block_on(adjust_playback_speed(0.1, None, app.state())).unwrap(),
+        1.6
+    );
+    assert_eq!(
+        block_on(adjust_playback_speed(10.0, None, app.state())).unwrap(),
+        4.0
+    );
+    assert!(block_on(adjust_playback_speed(f64::NAN, None, app.state())).is_err());
+    assert_eq!(
+        block_on(reset_playback_speed(None, app.state())).unwrap(),
+        1.0
+    );
+}
+
+#[test]
+fn subtitle_style_updates_are_validated() {
+    let app = app_with_player();
+    let update = |json: serde_json::Value| serde_json::from_value(json).unwrap();
+    let style = block_on(set_subtitle_style(
+        update(serde_json::json!({"font_size": 50.0, "color": "#FFFF00", "background_box": true})),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert_eq!(style.font_size, 50.0);
+    assert_eq!(
+        style,
+        block_on(get_subtitle_style(None, app.state())).unwrap()
+    );
+    assert_eq!(
+        get::<String>(&app, "sub-border-style").unwrap(),
+        "background-box"
+    );
+
+    assert!(block_on(set_subtitle_style(
+        update(serde_json::json!({"color": "yellow"})),
+        None,
+        app.state()
+    ))
+    .is_err());
+    assert!(block_on(set_subtitle_style(
+        update(serde_json::json!({"position": 120})),
+        None,
+        app.state()
+    ))
+    .is_err());
+    assert_eq!(
+        block_on(get_subtitle_style(None, app.state()))
+            .unwrap()
+            .color,
+        "#FFFF00"
+    );
+}
+
+#[test]
+fn sub_codepage_reloads_external_subtitles() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert_eq!(
+        block_on(set_sub_codepage("cp1256".to_string(), None, app.state())).unwrap(),
+        "💬 Subtitle encoding set to cp1256"
+    );
+    assert!(block_on(set_sub_codepage("cp 1256".to_string(), None, app.state())).is_err());
+
+    on_mpv(&app, |handle| {
+        tracks::add_subtitle(
+            handle,
+            "/subs/a.srt",
+            tracks::SubtitleFlag::Select,
+            None,
+            None,
+        )
+    })
+    .unwrap();
+    assert_eq!(
+        block_on(set_sub_codepage("auto".to_string(), None, app.state())).unwrap(),
+        "💬 Subtitles reloaded as auto"
+    );
+    assert_eq!(get::<String>(&app, "sub-codepage").unwrap(), "auto");
+}
+
+#[test]
+fn subtitle_search_needs_a_file_and_an_api_key() {
+    let app = app_with_player();
+    assert_eq!(
+        block_on(search_subtitles(None, None, app.state())).unwrap_err(),
+        PlayerError::NoFile
+    );
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert_eq!(
+        block_on(search_subtitles(None, None, app.state()))
+            .unwrap_err()
+            .to_string(),
+        "Set an OpenSubtitles API key first"
+    );
+}
+
+#[test]
+fn audio_delay_round_trips() {
+    let app = app_with_player();
+    assert_eq!(
+        block_on(set_audio_delay(-0.25, None, app.state())).unwrap(),
+        "🎧 Audio delay -250 ms"
+    );
+    assert_eq!(block_on(get_audio_delay(None, app.state())).unwrap(), -0.25);
+    assert!(block_on(set_audio_delay(f64::NAN, None, app.state())).is_err());
+}
+
+#[test]
+fn destroying_the_player_cuts_every_command_off() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert_eq!(
+        block_on(destroy_player(app.handle().clone(), None, app.state())).unwrap(),
+        "🛑 Player destroyed"
+    );
+    {
+        let main = app.state::<Players>().main();
+        let player = main.player.lock().unwrap();
+        assert!(!player.running());
+        // Asked for, so mpv shutting down isn't taken for a crash to recover from
+        assert!(player
+            .event_control
+            .quitting
+            .load(std::sync::atomic::Ordering::SeqCst));
+    }
+    // The channel is cut too, so transport commands fail rather than hang
+    assert!(block_on(load_video("/videos/b.mkv".to_string(), None, app.state())).is_err());
+    assert!(block_on(destroy_player(app.handle().clone(), None, app.state())).is_err());
+}
+
+#[test]
+fn starting_a_running_player_again_keeps_its_instance() {
+    let app = app_with_event_loop();
+    on_mpv(&app, |handle| handle.set("volume", 42.0)).unwrap();
+    let main = app.state::<Players>().main();
+    assert!(!start_player(app.handle(), &main).unwrap());
+    // A new instance would have come up at the default volume
+    assert_eq!(get::<f64>(&app, "volume"), Ok(42.0));
+    assert!(block_on(player::teardown_player(app.handle(), &main)).unwrap());
+}
+
+#[test]
+fn players_are_addressed_by_id() {
+    let app = app_with_player();
+    let second = create_player(app.state()).unwrap();
+    assert_eq!(
+        list_players(app.state()).unwrap(),
+        [player::MAIN_PLAYER, second]
+    );
+    // Not started yet, while the main player is
+    assert!(block_on(play_pause(Some(second), app.state())).is_err());
+    assert!(
+        block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .initialized
+    );
+    assert!(
+        !block_on(get_player_state(Some(second), app.state()))
+            .unwrap()
+            .initialized
+    );
+
+    assert_eq!(
+        block_on(destroy_player(
+            app.handle().clone(),
+            Some(second),
+            app.state()
+        ))
+        .unwrap(),
+        format!("🛑 Player {} removed", second)
+    );
+    assert_eq!(list_players(app.state()).unwrap(), [player::MAIN_PLAYER]);
+    assert_eq!(
+        block_on(get_player_state(Some(second), app.state())).unwrap_err(),
+        PlayerError::NoPlayer(second)
+    );
+    // Ids aren't handed out again
+    assert_ne!(create_player(app.state()).unwrap(), second);
+}
+
+#[test]
+fn video_backend_only_takes_what_the_platform_has() {
+    use config::VideoBackend;
+
+    let app = app_with_player();
+    let status = block_on(get_video_backend(None, app.state())).unwrap();
+    assert_eq!(status.configured, VideoBackend::Auto);
+    assert_ne!(status.resolved, VideoBackend::Auto);
+
+    let unavailable = if cfg!(target_os = "linux") {
+        VideoBackend::Native
+    } else {
+        VideoBackend::OpenGl
+    };
+    assert!(block_on(set_video_backend(unavailable, None, app.state())).is_err());
+    assert_eq!(
+        block_on(get_video_backend(None, app.state()))
+            .unwrap()
+            .configured,
+        VideoBackend::Auto
+    );
+    let status = block_on(set_video_backend(status.resolved, None, app.state())).unwrap();
+    assert_eq!(status.configured, status.resolved);
+}
+
+#[test]
+fn volume_is_validated_and_mute_toggles() {
+    let app = app_with_player();
+    assert_eq!(block_on(set_volume(40.0, None, app.state())).unwrap(), 40.0);
+    assert!(block_on(set_volume(-1.0, None, app.state())).is_err());
+    assert!(block_on(set_volume(131.0, None, app.state())).is_err());
+    assert!(block_on(set_volume(f64::INFINITY, None, app.state())).is_err());
+    assert!(block_on(toggle_mute(None, app.state())).unwrap());
+    assert_eq!(
+        block_on(get_volume(None, app.state())).unwrap(),
+        channel::Volume {
+            volume: 40.0,
+            muted: true,
+        }
+    );
+    assert!(!block_on(toggle_mute(None, app.state())).unwrap());
+}
+
+#[test]
+fn play_pause_toggles() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert_eq!(
+        block_on(play_pause(None, app.state())).unwrap(),
+        "⏸️ Paused"
+    );
+    assert!(
+        block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .paused
+    );
+    assert_eq!(
+        block_on(play_pause(None, app.state())).unwrap(),
+        "▶️ Playing"
+    );
+    assert!(
+        !block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .paused
+    );
+}
+
+#[test]
+fn set_paused_leaves_a_matching_state_or_fade_alone() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    block_on(set_paused(false, None, app.state())).unwrap();
+    assert!(!get::<bool>(&app, "pause").unwrap());
+    block_on(set_paused(true, None, app.state())).unwrap();
+    assert!(get::<bool>(&app, "pause").unwrap());
+    block_on(set_paused(false, None, app.state())).unwrap();
+
+    block_on(set_pause_fade(true, 5000, None, app.state())).unwrap();
+    let pause_fade = app
+        .state::<Players>()
+        .main()
+        .player
+        .lock()
+        .unwrap()
+        .pause_fade
+        .clone();
+    block_on(set_paused(true, None, app.state())).unwrap();
+    assert_eq!(pause_fade.target(), Some(true));
+    // Asking again mustn't turn the fade-out around
+    block_on(set_paused(true, None, app.state())).unwrap();
+    assert_eq!(pause_fade.target(), Some(true));
+    assert_eq!(
+        block_on(set_paused(false, None, app.state())).unwrap(),
+        "▶️ Playing"
+    );
+    assert_eq!(pause_fade.target(), Some(false));
+    block_on(set_pause_fade(false, 0, None, app.state())).unwrap();
+}
+
+#[test]
+fn frame_steps_leave_playback_paused() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert!(
+        !block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .paused
+    );
+    block_on(frame_step(None, app.state())).unwrap();
+    assert!(
+        block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .paused
+    );
+
+    block_on(play_pause(None, app.state())).unwrap();
+    block_on(frame_back_step(None, app.state())).unwrap();
+    assert!(
+        block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .paused
+    );
+}
+
+#[test]
+fn chapter_navigation_needs_chapters() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert!(block_on(get_chapters(None, app.state()))
+        .unwrap()
+        .is_empty());
+    assert!(block_on(set_chapter(0, None, app.state())).is_err());
+    assert!(block_on(next_chapter(None, app.state())).is_err());
+    assert!(block_on(prev_chapter(None, app.state())).is_err());
+}
+
+#[test]
+fn loop_mode_changes_only_the_given_loops() {
+    use loop_mode::{LoopMode, LoopModeUpdate, Repeat};
+
+    let app = app_with_player();
+    let update = LoopModeUpdate {
+        playlist: Some(Repeat::Inf),
+        ..Default::default()
+    };
+    assert_eq!(
+        block_on(set_loop_mode(update, None, app.state())).unwrap(),
+        LoopMode {
+            file: Repeat::Off,
+            playlist: Repeat::Inf,
+        }
+    );
+    let update = LoopModeUpdate {
+        file: Some(Repeat::Times(3)),
+        ..Default::default()
+    };
+    block_on(set_loop_mode(update, None, app.state())).unwrap();
+    assert_eq!(get::<String>(&app, "loop-file").unwrap(), "3");
+    assert_eq!(
+        block_on(get_loop_mode(None, app.state())).unwrap().playlist,
+        Repeat::Inf
+    );
+}
+
+#[test]
+fn frontend_observers_get_their_own_ids() {
+    let app = app_with_player();
+    let first = block_on(observe_property(
+        "pause".to_string(),
+        None,
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    let second = block_on(observe_property(
+        "volume".to_string(),
+        Some(mpv::Format::Double),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert!(first >= events::FRONTEND_OBSERVER_BASE);
+    assert_eq!(second, first + 1);
+
+    block_on(unobserve_property(first, None, app.state())).unwrap();
+    assert!(block_on(unobserve_property(first, None, app.state())).is_err());
+    assert!(block_on(unobserve_property(1, None, app.state())).is_err());
+}
+
+#[test]
+fn tracks_are_listed_with_their_flags() {
+    let app = app_with_player();
+    assert!(block_on(get_tracks(None, app.state())).unwrap().is_empty());
+
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    let tracks = block_on(get_tracks(None, app.state())).unwrap();
+    let kinds: Vec<_> = tracks.iter().map(|t| t.kind.as_str()).collect();
+    assert_eq!(kinds, ["video", "audio", "audio", "sub", "sub"]);
+    assert_eq!(
+        tracks[1],
+        tracks::Track {
+            id: 1,
+            kind: "audio".to_string(),
+            language: Some("eng".to_string()),
+            title: Some("Stereo".to_string()),
+            codec: Some("aac".to_string()),
+            default: true,
+            forced: false,
+            external: false,
+            selected: true,
+        }
+    );
+    assert!(tracks[4].forced);
+    assert!(!tracks[3].selected);
+}
+
+#[test]
+fn audio_tracks_are_validated_and_cycle() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    block_on(set_audio_track(2, None, app.state())).unwrap();
+    assert_eq!(get::<String>(&app, "aid").unwrap(), "2");
+    assert!(block_on(set_audio_track(3, None, app.state())).is_err());
+
+    assert_eq!(
+        block_on(cycle_audio_track(None, app.state())).unwrap(),
+        None
+    );
+    assert_eq!(
+        block_on(cycle_audio_track(None, app.state())).unwrap(),
+        Some(1)
+    );
+}
+
+#[test]
+fn subtitles_switch_toggle_and_cycle() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    // No track selected yet, so toggling on picks the first
+    assert_eq!(
+        block_on(toggle_subtitles(None, app.state())).unwrap(),
+        Some(1)
+    );
+    assert_eq!(block_on(toggle_subtitles(None, app.state())).unwrap(), None);
+    assert!(!get::<bool>(&app, "sub-visibility").unwrap());
+
+    block_on(set_subtitle_track(Some(2), None, app.state())).unwrap();
+    assert!(get::<bool>(&app, "sub-visibility").unwrap());
+    assert!(block_on(set_subtitle_track(Some(5), None, app.state())).is_err());
+    assert_eq!(
+        block_on(cycle_subtitle_track(None, app.state())).unwrap(),
+        None
+    );
+    assert_eq!(
+        block_on(cycle_subtitle_track(None, app.state())).unwrap(),
+        Some(1)
+    );
+
+    block_on(set_subtitle_track(None, None, app.state())).unwrap();
+    assert_eq!(get::<String>(&app, "sid").unwrap(), "no");
+    // Jobs on the event thread refresh the snapshot before replying
+    let state = block_on(get_player_state(None, app.state())).unwrap();
+    assert_eq!(state.subtitle_track.as_deref(), Some("no"));
+}
+
+#[test]
+fn external_subtitles_are_attached() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert!(block_on(load_subtitle(
+        "/no/such.srt".to_string(),
+        None,
+        None,
+        None,
+        None,
+        app.state()
+    ))
+    .is_err());
+
+    let track = on_mpv(&app, |handle| {
+        tracks::add_subtitle(
+            handle,
+            "/subs/a.srt",
+            tracks::SubtitleFlag::Select,
+            None,
+            Some("fra"),
+        )
+    })
+    .unwrap();
+    assert_eq!((track.id, track.external, track.selected), (3, true, true));
+    assert_eq!(track.title.as_deref(), Some("a.srt"));
+    assert_eq!(track.language.as_deref(), Some("fra"));
+
+    let track = on_mpv(&app, |handle| {
+        tracks::add_subtitle(
+            handle,
+            "/subs/b.ass",
+            tracks::SubtitleFlag::Auto,
+            Some("Signs"),
+            None,
+        )
+    })
+    .unwrap();
+    assert_eq!((track.id, track.selected), (4, false));
+}
+
+#[test]
+fn seek_percent_validates_and_reports_the_position() {
+    let app = app_with_player();
+    assert!(block_on(seek_percent(150.0, None, None, app.state())).is_err());
+    // Nothing loaded yet
+    assert!(block_on(seek_percent(50.0, None, None, app.state())).is_err());
+
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    let result = block_on(seek_percent(50.0, Some(true), None, app.state())).unwrap();
+    assert!(
+        (300.0..301.0).contains(&result.position),
+        "{}",
+        result.position
+    );
+    assert!(result.precise);
+}
+
+#[test]
+fn precise_seeking_sets_the_default_and_exact_overrides_it() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert!(
+        !block_on(seek(10.0, None, None, app.state()))
+            .unwrap()
+            .precise
+    );
+
+    let status = block_on(set_precise_seeking(true, None, app.state())).unwrap();
+    assert_eq!(status.hr_seek.as_deref(), Some("yes"));
+    assert_eq!(status.hr_seek_framedrop, Some(false));
+    let result = block_on(seek(20.0, None, None, app.state())).unwrap();
+    assert!(result.precise);
+    assert!(
+        (20.0..21.0).contains(&result.position),
+        "{}",
+        result.position
+    );
+    assert!(
+        !block_on(seek(30.0, Some(false), None, app.state()))
+            .unwrap()
+            .precise
+    );
+    assert!(block_on(seek(-1.0, None, None, app.state())).is_err());
+
+    block_on(set_precise_seeking(false, None, app.state())).unwrap();
+    assert!(
+        !block_on(get_precise_seeking(None, app.state()))
+            .unwrap()
+            .enabled
+    );
+    assert!(
+        block_on(seek(40.0, Some(true), None, app.state()))
+            .unwrap()
+            .precise
+    );
+}
+
+#[test]
+fn load_options_apply_to_the_file() {
+    let app = app_with_player();
+    let options = loadfile::LoadOptions {
+        start: Some(30.0),
+        pause_on_load: true,
+        ..Default::default()
+    };
+    block_on(load_video_with_options(
+        app.handle().clone(),
+        "/videos/a.mkv".to_string(),
+        options,
+        None,
+        app.state(),
+    ))
+    .unwrap();
+
+    let state = block_on(get_player_state(None, app.state())).unwrap();
+    assert_eq!(state.path.as_deref(), Some("/videos/a.mkv"));
+    assert_eq!(state.position, Some(30.0));
+    assert_eq!(state.duration, Some(mpv::stub::FAKE_DURATION));
+    assert!(state.paused);
+}
+
+#[test]
+fn stop_ends_the_file_and_idles() {
+    let app = app_with_player();
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    on_mpv(&app, |handle| handle.drain_events());
+
+    block_on(stop_video(None, app.state())).unwrap();
+    let events = on_mpv(&app, |handle| handle.drain_events());
+    assert!(events.contains(&Event::EndFile {
+        reason: EndFileReason::Stop,
+        playlist_entry_id: 1,
+    }));
+    assert!(events.contains(&Event::Idle));
+    assert_eq!(
+        block_on(get_player_state(None, app.state())).unwrap().path,
+        None
+    );
+}
+
+#[test]
+fn subtitle_visibility_toggles() {
+    let app = app_with_player();
+    assert!(!block_on(toggle_subtitle_visibility(None, app.state())).unwrap());
+    assert!(block_on(toggle_subtitle_visibility(None, app.state())).unwrap());
+    assert!(!block_on(set_subtitle_visibility(false, None, app.state())).unwrap());
+    assert!(
+        !block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .subtitle_visible
+    );
+}
+
+#[test]
+fn hiding_the_video_without_a_view_turns_vid_off() {
+    let app = app_with_player();
+    on_mpv(&app, |handle| handle.set("vid", "1")).unwrap();
+
+    block_on(set_video_layer_visible(
+        app.handle().clone(),
+        false,
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert_eq!(get::<String>(&app, "vid").unwrap(), "no");
+    assert!(
+        !block_on(get_player_state(None, app.state()))
+            .unwrap()
+            .video_layer_visible
+    );
+
+    block_on(set_video_layer_visible(
+        app.handle().clone(),
+        true,
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert_eq!(get::<String>(&app, "vid").unwrap(), "1");
+}
+
+#[test]
+fn pause_fade_duration_is_validated() {
+    let app = app_with_player();
+    assert!(block_on(set_pause_fade(true, 0, None, app.state())).is_err());
+    assert!(block_on(set_pause_fade(
+        true,
+        MAX_PAUSE_FADE_MS + 1,
+        None,
+        app.state()
+    ))
+    .is_err());
+    block_on(set_pause_fade(true, 300, None, app.state())).unwrap();
+    block_on(set_pause_fade(false, 0, None, app.state())).unwrap();
+}
+
+#[test]
+fn passthrough_only_reaches_allowlisted_options_and_commands() {
+    let app = app_with_player();
+
+    block_on(mpv_set_option(
+        "--deband".to_string(),
+        "yes".to_string(),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert!(get::<bool>(&app, "deband").unwrap());
+    let denied = block_on(mpv_set_option(
+        "vo".to_string(),
+        "null".to_string(),
+        None,
+        app.state(),
+    ));
+    assert!(matches!(denied, Err(PlayerError::InvalidArgument(_))));
+    assert!(block_on(mpv_set_option(
+        "input-conf".to_string(),
+        "/tmp/input.conf".to_string(),
+        None,
+        app.state()
+    ))
+    .is_err());
+
+    let run = |args: &[&str]| {
+        let args = args.iter().map(|a| a.to_string()).collect();
+        block_on(mpv_run_command(args, None, app.state()))
+    };
+    run(&["set", "sub-scale", "1.5"]).unwrap();
+    assert_eq!(get::<f64>(&app, "sub-scale").unwrap(), 1.5);
+    // set is allowed, but only for allowlisted properties
+    assert!(run(&["set", "input-conf", "/tmp/input.conf"]).is_err());
+    assert!(run(&["quit"]).is_err());
+    assert!(run(&["load-script", "/tmp/evil.lua"]).is_err());
+}
+
+#[test]
+fn playlist_commands_edit_mpvs_playlist() {
+    let app = app_with_player();
+    let paths = |items: &[crate::playlist::PlaylistItem]| {
+        items.iter().map(|i| i.path.clone()).collect::<Vec<_>>()
+    };
+    let files = ["/videos/a.mkv", "/videos/b.mkv", "/videos/c.mkv"];
+
+    let items = block_on(playlist_add(
+        files.map(String::from).to_vec(),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert_eq!(paths(&items), files);
+    // Nothing was playing, so the first one started
+    assert!(items[0].current);
+
+    let items = block_on(playlist_move(0, 2, None, app.state())).unwrap();
+    assert_eq!(
+        paths(&items),
+        ["/videos/b.mkv", "/videos/c.mkv", "/videos/a.mkv"]
+    );
+    assert!(items[2].current);
+    assert!(block_on(playlist_move(0, 3, None, app.state())).is_err());
+
+    let items = block_on(playlist_play_index(0, None, app.state())).unwrap();
+    assert!(items[0].current);
+    let items = block_on(playlist_next(None, app.state())).unwrap();
+    assert!(items[1].current);
+
+    let items = block_on(playlist_remove(0, None, app.state())).unwrap();
+    assert_eq!(paths(&items), ["/videos/c.mkv", "/videos/a.mkv"]);
+    assert!(items[0].current);
+    assert!(block_on(playlist_prev(None, app.state())).is_err());
+
+    let items = block_on(playlist_clear(None, app.state())).unwrap();
+    assert_eq!(paths(&items), ["/videos/c.mkv"]);
+    assert_eq!(block_on(get_playlist(None, app.state())).unwrap(), items);
+}
+
+#[test]
+fn unshuffling_restores_the_playlist_order() {
+    let app = app_with_player();
+    let paths = || {
+        block_on(get_playlist(None, app.state()))
+            .unwrap()
+            .into_iter()
+            .map(|i| i.path)
+            .collect::<Vec<_>>()
+    };
+    let files = ["/videos/a.mkv", "/videos/b.mkv", "/videos/c.mkv"];
+    block_on(playlist_add(
+        files.map(String::from).to_vec(),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+
+    let modes = block_on(set_shuffle(true, None, app.state())).unwrap();
+    assert_eq!(modes, block_on(get_queue_modes(None, app.state())).unwrap());
+    assert_ne!(paths(), files);
+    block_on(playlist_add(
+        vec!["/videos/d.mkv".to_string()],
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    block_on(set_shuffle(false, None, app.state())).unwrap();
+    assert_eq!(
+        paths(),
+        [
+            "/videos/a.mkv",
+            "/videos/b.mkv",
+            "/videos/c.mkv",
+            "/videos/d.mkv"
+        ]
+    );
+
+    block_on(set_repeat(loop_mode::RepeatMode::One, None, app.state())).unwrap();
+    assert_eq!(get::<String>(&app, "loop-file").unwrap(), "inf");
+    block_on(set_repeat(loop_mode::RepeatMode::All, None, app.state())).unwrap();
+    let loops = block_on(get_loop_mode(None, app.state())).unwrap();
+    assert_eq!(loops.file, loop_mode::Repeat::Off);
+    assert_eq!(loops.playlist, loop_mode::Repeat::Inf);
+}
+
+#[test]
+fn a_saved_queue_restores_in_order_at_its_position() {
+    let app = app_with_player();
+    let queue = crate::saved_queue::SavedQueue {
+        entries: ["/videos/a.mkv", "/videos/b.mkv", "/videos/c.mkv"]
+            .map(String::from)
+            .to_vec(),
+        index: Some(1),
+        position: Some(42.0),
+    };
+
+    let entries = queue.entries.clone();
+    let restored = on_mpv(&app, move |handle| {
+        queue.restore(handle)?;
+        Ok::<_, PlayerError>(crate::saved_queue::SavedQueue::read(handle))
+    })
+    .unwrap();
+    assert_eq!(restored.entries, entries);
+    assert_eq!(restored.index, Some(1));
+    assert!((restored.position.unwrap() - 42.0).abs() < 0.5);
+}
+
+#[test]
+fn media_info_lists_the_files_streams() {
+    let app = app_with_player();
+    assert!(matches!(
+        block_on(get_media_info(None, app.state())),
+        Err(PlayerError::NoFile)
+    ));
+
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    let info = block_on(get_media_info(None, app.state())).unwrap();
+    assert_eq!(info.path, "/videos/a.mkv");
+    assert_eq!(info.duration, Some(mpv::stub::FAKE_DURATION));
+    assert_eq!(info.video.len(), 1);
+    assert_eq!(info.video[0].codec.as_deref(), Some("h264"));
+    let languages: Vec<_> = info.audio.iter().map(|a| a.language.as_deref()).collect();
+    assert_eq!(languages, [Some("eng"), Some("jpn")]);
+    assert!(info.audio[0].selected);
+    assert!(!info.subtitles.is_empty());
+}
+
+#[test]
+fn metadata_needs_a_loaded_file() {
+    let app = app_with_player();
+    assert!(matches!(
+        block_on(get_metadata(None, app.state())),
+        Err(PlayerError::NoFile)
+    ));
+
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    let metadata = block_on(get_metadata(None, app.state())).unwrap();
+    assert_eq!(metadata.path, "/videos/a.mkv");
+    // The stub's files have no tags
+    assert_eq!(metadata.title, None);
+    assert!(metadata.tags.is_empty());
+}
+
+#[test]
+fn cover_art_is_not_found_for_a_file_without_any() {
+    let app = app_with_player();
+    assert!(matches!(
+        block_on(get_cover_art(None, app.state())),
+        Err(PlayerError::NoFile)
+    ));
+
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    assert!(matches!(
+        block_on(get_cover_art(None, app.state())),
+        Err(PlayerError::NotFound(_))
+    ));
+}
+
+#[test]
+fn contact_sheet_options_are_checked_before_anything_runs() {
+    let app = app_with_player();
+    let export = |columns, rows, output: &str| {
+        block_on(export_contact_sheet(
+            output.to_string(),
+            columns,
+            rows,
+            None,
+            None,
+            app.state(),
+        ))
+    };
+    assert!(matches!(
+        export(Some(0), None, "/tmp/sheet.jpg"),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        export(None, Some(13), "/tmp/sheet.jpg"),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        export(None, None, "/tmp/sheet.gif"),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        export(None, None, "/tmp/sheet.jpg"),
+        Err(PlayerError::NoFile)
+    ));
+}
+
+#[test]
+fn probe_file_reports_a_missing_file() {
+    let report = block_on(probe_file("/videos/not-there.mkv".to_string())).unwrap();
+    assert_eq!(report.status, crate::file_probe::FileStatus::Missing);
+    assert!(report.message.is_some());
+}
+
+#[test]
+fn waveforms_are_only_made_for_local_files() {
+    let app = app_with_player();
+    let waveform = |path: Option<&str>, points| {
+        block_on(get_waveform(
+            path.map(str::to_string),
+            points,
+            None,
+            app.state(),
+        ))
+    };
+    assert!(matches!(waveform(None, None), Err(PlayerError::NoFile)));
+    assert!(matches!(
+        waveform(Some("/music/a.flac"), Some(0)),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        waveform(Some("https://example.com/podcast.mp3"), None),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn captured_frames_come_back_as_png() {
+    use tauri::ipc::{InvokeResponseBody, IpcResponse};
+
+    let app = app_with_player();
+    assert!(matches!(
+        block_on(capture_frame(None, app.state())),
+        Err(PlayerError::NoFile)
+    ));
+
+    block_on(load_video("/videos/a.mkv".to_string(), None, app.state())).unwrap();
+    let response = block_on(capture_frame(None, app.state())).unwrap();
+    match response.body().unwrap() {
+        InvokeResponseBody::Raw(png) => assert!(png.starts_with(b"\x89PNG\r\n\x1a\n")),
+        InvokeResponseBody::Json(_) => panic!("the frame should be sent as raw bytes"),
+    }
+}
+
+#[test]
+fn audio_can_be_routed_to_a_listed_device() {
+    let app = app_with_player();
+    let current = |app: &tauri::App<MockRuntime>| {
+        block_on(list_audio_devices(None, app.state()))
+            .unwrap()
+            .into_iter()
+            .find(|device| device.current)
+            .map(|device| device.name)
+    };
+    assert_eq!(current(&app).as_deref(), Some("auto"));
+
+    block_on(set_audio_device("null".to_string(), None, app.state())).unwrap();
+    assert_eq!(current(&app).as_deref(), Some("null"));
+    assert!(matches!(
+        block_on(set_audio_device("hdmi".to_string(), None, app.state())),
+        Err(PlayerError::NotFound(_))
+    ));
+    assert_eq!(current(&app).as_deref(), Some("null"));
+}
+
+#[test]
+fn equalizer_presets_remember_their_adjustments() {
+    use crate::config::EqualizerPreset;
+
+    let app = app_with_player();
+    let state = block_on(get_equalizer(None, app.state())).unwrap();
+    assert!(!state.enabled);
+    assert_eq!(state.preset, EqualizerPreset::Flat);
+    assert_eq!(state.gains, [0.0; 10]);
+
+    let state = block_on(set_equalizer_band(0, 3.0, None, app.state())).unwrap();
+    assert_eq!(state.gains[0], 3.0);
+    assert!(state.adjusted);
+
+    let state = block_on(set_equalizer_preset(
+        EqualizerPreset::BassBoost,
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert!(state.enabled);
+    assert_eq!(state.gains[0], 6.0);
+    let state = block_on(set_equalizer_preset(
+        EqualizerPreset::Flat,
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert_eq!(state.gains[0], 3.0);
+
+    let state = block_on(reset_equalizer_preset(None, app.state())).unwrap();
+    assert_eq!(state.gains[0], 0.0);
+    assert!(!state.adjusted);
+
+    assert!(matches!(
+        block_on(set_equalizer_band(10, 0.0, None, app.state())),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+    assert!(matches!(
+        block_on(set_equalizer_band(0, 13.0, None, app.state())),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn loudness_normalization_keeps_its_target() {
+    let app = app_with_player();
+    let settings = block_on(set_loudness_normalization(
+        true,
+        Some(-23.0),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert!(settings.enabled);
+    assert_eq!(settings.target_lufs, -23.0);
+
+    let settings = block_on(set_loudness_normalization(false, None, None, app.state())).unwrap();
+    assert!(!settings.enabled);
+    assert_eq!(settings.target_lufs, -23.0);
+    assert_eq!(
+        block_on(get_loudness_normalization(None, app.state())).unwrap(),
+        settings
+    );
+
+    assert!(matches!(
+        block_on(set_loudness_normalization(
+            true,
+            Some(0.0),
+            None,
+            app.state()
+        )),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn pitch_correction_can_be_turned_off() {
+    let app = app_with_player();
+    assert!(block_on(get_pitch_correction(None, app.state())).unwrap());
+    block_on(set_pitch_correction(false, None, app.state())).unwrap();
+    assert!(!block_on(get_pitch_correction(None, app.state())).unwrap());
+    // Speed changes go on without it
+    block_on(set_playback_speed(1.8, None, app.state())).unwrap();
+    assert_eq!(get::<f64>(&app, "speed"), Ok(1.8));
+}
+
+#[test]
+fn surround_can_be_downmixed_with_louder_dialogue() {
+    let app = app_with_player();
+    let state = block_on(set_audio_channels("stereo".to_string(), None, app.state())).unwrap();
+    assert_eq!(state.settings.layout, "stereo");
+    assert_eq!(
+        get::<String>(&app, "audio-channels").as_deref(),
+        Ok("stereo")
+    );
+    assert!(matches!(
+        block_on(set_audio_channels("22.2".to_string(), None, app.state())),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+
+    let state = block_on(set_downmix(Some(1.4), None, None, None, app.state())).unwrap();
+    assert_eq!(state.settings.center_level, 1.4);
+    assert_eq!(state.settings.lfe_level, 0.0);
+    assert_eq!(
+        get::<String>(&app, "audio-swresample-o").as_deref(),
+        Ok("clev=1.400,lfe_mix_level=0.000")
+    );
+    assert!(matches!(
+        block_on(set_downmix(None, Some(-1.0), None, None, app.state())),
+        Err(PlayerError::InvalidArgument(_))
+    ));
+}
+
+#[test]
+fn passthrough_sends_only_the_chosen_codecs() {
+    let app = app_with_player();
+    let update = |json: serde_json::Value| {
+        block_on(set_audio_passthrough(
+            serde_json::from_value(json).unwrap(),
+            None,
+            app.state(),
+        ))
+        .unwrap()
+    };
+    let spdif = || get::<String>(&app, "audio-spdif").unwrap();
+
+    let values = update(serde_json::json!({ "enabled": true }));
+    assert!(values.enabled && values.ac3 && !values.truehd);
+    assert_eq!(spdif(), "ac3,dts");
+    update(serde_json::json!({ "truehd": true, "dts": false }));
+    assert_eq!(spdif(), "ac3,truehd");
+    update(serde_json::json!({ "enabled": false }));
+    assert_eq!(spdif(), "");
+    assert!(
+        block_on(get_audio_passthrough(None, app.state()))
+            .unwrap()
+            .truehd
+    );
+}
+
+#[test]
+fn event_loop_reports_the_end_of_a_file_and_moves_on() {
+    let app = app_with_event_loop();
+    let end_file = listen(&app, "mpv://end-file");
+    let loaded = listen(&app, "file-load-succeeded");
+    let files = ["/videos/a.mkv", "/videos/b.mkv"];
+    block_on(playlist_add(
+        files.map(String::from).to_vec(),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert_eq!(next_event(&loaded)["path"], files[0]);
+
+    finish_current_file(&app);
+    let ended = next_event(&end_file);
+    assert_eq!(ended["reason"], "eof");
+    assert_eq!(ended["playlist_entry_id"], 1);
+    assert_eq!(next_event(&loaded)["path"], files[1]);
+
+    let items = block_on(get_playlist(None, app.state())).unwrap();
+    assert_eq!(items.len(), 2);
+    assert!(items[1].current);
+    block_on(player::teardown_player(
+        app.handle(),
+        &app.state::<Players>().main(),
+    ))
+    .unwrap();
+}
+
+#[test]
+fn stop_after_current_keeps_the_queue_and_skips_the_next_file() {
+    for end_behavior in ["no", "always"] {
+        let app = app_with_event_loop();
+        let loaded = listen(&app, "file-load-succeeded");
+        let fired = listen(&app, "stop-after-current-fired");
+        block_on(set_end_behavior(
+            end_behavior.to_string(),
+            None,
+            app.state(),
+        ))
+        .unwrap();
+        let files = ["/videos/a.mkv", "/videos/b.mkv"];
+        block_on(playlist_add(
+            files.map(String::from).to_vec(),
+            None,
+            app.state(),
+        ))
+        .unwrap();
+        next_event(&loaded);
+        block_on(stop_after_current(true, None, app.state())).unwrap();
+        // The user's setting is what's reported while it's held
+        assert_eq!(
+            block_on(get_end_behavior(None, app.state())).unwrap(),
+            end_behavior
+        );
+
+        finish_current_file(&app);
+        next_event(&fired);
+        let items = block_on(get_playlist(None, app.state())).unwrap();
+        assert_eq!(items.len(), 2);
+        assert!(items.iter().all(|item| !item.current));
+        assert!(loaded.try_recv().is_err(), "the next file started");
+        assert!(!block_on(get_stop_after_current(None, app.state())).unwrap());
+        assert_eq!(get::<String>(&app, "keep-open").unwrap(), end_behavior);
+        assert!(!get::<bool>(&app, "pause").unwrap());
+        block_on(player::teardown_player(
+            app.handle(),
+            &app.state::<Players>().main(),
+        ))
+        .unwrap();
+    }
+}
+
+#[test]
+fn end_of_file_sleep_timer_keeps_the_queue_and_skips_the_next_file() {
+    let app = app_with_event_loop();
+    let loaded = listen(&app, "file-load-succeeded");
+    let fired = listen(&app, "sleep-timer-fired");
+    let files = ["/videos/a.mkv", "/videos/b.mkv"];
+    block_on(playlist_add(
+        files.map(String::from).to_vec(),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    next_event(&loaded);
+    block_on(set_sleep_timer(
+        0,
+        "end_of_file".to_string(),
+        None,
+        app.state(),
+    ))
+    .unwrap();
+    assert_eq!(get::<String>(&app, "keep-open").unwrap(), "always");
+
+    finish_current_file(&app);
+    assert_eq!(next_event(&fired)["action"], "end_of_file");
+    let items = block_on(get_playlist(None, app.state())).unwrap();
+    assert_eq!(items.len(), 2);
+    assert!(loaded.try_recv().is_err(), "the next file started");
+    assert!(block_on(get_sleep_timer(None, app.state()))
+        .unwrap()
+        .is_none());
+    assert_eq!(get::<String>(&app, "keep-open").unwrap(), "no");
+    block_on(player::teardown_player(
+        app.handle(),
+        &app.state::<Players>().main(),
+    ))
+    .unwrap();
+}
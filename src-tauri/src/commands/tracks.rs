@@ -0,0 +1,275 @@
+// Choosing between a file's audio and subtitle tracks, loading external subtitles, and
+// describing the file: its streams, its tags and its cover art
+
+use std::path::Path;
+
+use super::off_thread;
+use crate::cover_art::{self, CoverArt};
+use crate::error::{Context, PlayerError};
+use crate::media_info::{self, MediaInfo};
+use crate::metadata::Metadata;
+use crate::mpv::PlayerBackend;
+use crate::player::{PlayerId, Players};
+use crate::tracks;
+
+// Every video, audio and subtitle track of the current file, for the track menus
+#[tauri::command]
+pub async fn get_tracks(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Vec<tracks::Track>, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| Ok(tracks::read_tracks(handle)))
+        .await
+}
+
+// Container, codecs and stream details of the current file, for a "media info" panel
+#[tauri::command]
+pub async fn get_media_info(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<MediaInfo, PlayerError> {
+    let instance = players.get(player_id)?;
+    let info = instance
+        .channel
+        .run(move |handle| MediaInfo::read(handle).ok_or(PlayerError::NoFile))
+        .await?;
+    if !info.is_incomplete() {
+        return Ok(info);
+    }
+    off_thread(move || {
+        let mut info = info;
+        // Without ffprobe there's only what mpv said
+        match media_info::ffprobe(&info.path) {
+            Ok(json) => info.fill_from_ffprobe(&json),
+            Err(e) => eprintln!("{}", e),
+        }
+        Ok(info)
+    })
+    .await
+}
+
+// Title, artist, album and the rest of the current file's tags; metadata-update sends
+// them again when they change
+#[tauri::command]
+pub async fn get_metadata(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Metadata, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| Metadata::read(handle).ok_or(PlayerError::NoFile))
+        .await
+}
+
+// The album art of the current file, attached or from its folder, as a data: URL
+#[tauri::command]
+pub async fn get_cover_art(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<CoverArt, PlayerError> {
+    let instance = players.get(player_id)?;
+    let (path, source) = instance
+        .channel
+        .run(move |handle| {
+            let path = handle
+                .get::<String>("path")
+                .map_err(|_| PlayerError::NoFile)?;
+            let source = cover_art::locate(handle, &path);
+            Ok((path, source))
+        })
+        .await?;
+    let source =
+        source.ok_or_else(|| PlayerError::NotFound(format!("No cover art for {}", path)))?;
+    off_thread(move || Ok(cover_art::read(source)?)).await
+}
+
+#[tauri::command]
+pub async fn set_audio_track(
+    id: i64,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            tracks::select(handle, "audio", Some(id))?;
+            Ok(format!("🔊 Audio track {}", id))
+        })
+        .await
+}
+
+// Returns the new audio track id, or None once the cycle reaches "no audio"
+#[tauri::command]
+pub async fn cycle_audio_track(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Option<i64>, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| Ok(tracks::cycle(handle, "audio")?))
+        .await
+}
+
+// None switches subtitles off; picking a track also unhides them
+#[tauri::command]
+pub async fn set_subtitle_track(
+    id: Option<i64>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            tracks::select(handle, "sub", id)?;
+            match id {
+                Some(id) => {
+                    handle
+                        .set("sub-visibility", true)
+                        .context("Failed to show subtitles")?;
+                    Ok(format!("💬 Subtitle track {}", id))
+                }
+                None => Ok("💬 Subtitles off".to_string()),
+            }
+        })
+        .await
+}
+
+// The subtitles button: hides subtitles that are showing, and otherwise shows them,
+// selecting the default (or first) track if none is. Returns the track now showing.
+#[tauri::command]
+pub async fn toggle_subtitles(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Option<i64>, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            let visible = handle
+                .get::<bool>("sub-visibility")
+                .context("Failed to get subtitle visibility")?;
+            let current = tracks::selected(handle, "sub");
+            if visible && current.is_some() {
+                handle
+                    .set("sub-visibility", false)
+                    .context("Failed to hide subtitles")?;
+                return Ok(None);
+            }
+
+            let id = match current {
+                Some(id) => id,
+                None => {
+                    let subs: Vec<_> = tracks::read_tracks(handle)
+                        .into_iter()
+                        .filter(|t| t.kind == "sub")
+                        .collect();
+                    let track = subs
+                        .iter()
+                        .find(|t| t.default)
+                        .or(subs.first())
+                        .ok_or_else(|| {
+                            PlayerError::NotFound("This file has no subtitles".to_string())
+                        })?;
+                    tracks::select(handle, "sub", Some(track.id))?;
+                    track.id
+                }
+            };
+            handle
+                .set("sub-visibility", true)
+                .context("Failed to show subtitles")?;
+            Ok(Some(id))
+        })
+        .await
+}
+
+// Returns the new subtitle track id, or None once the cycle reaches "no subtitles"
+#[tauri::command]
+pub async fn cycle_subtitle_track(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<Option<i64>, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| Ok(tracks::cycle(handle, "sub")?))
+        .await
+}
+
+// Attach a subtitle file to the current video, e.g. one dropped onto the player.
+// Without a flag the new track is selected straight away.
+#[tauri::command]
+pub async fn load_subtitle(
+    path: String,
+    flag: Option<tracks::SubtitleFlag>,
+    title: Option<String>,
+    language: Option<String>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<tracks::Track, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            if !Path::new(&path).is_file() {
+                return Err(PlayerError::NotFound(format!("File not found: {}", path)));
+            }
+            Ok(tracks::add_subtitle(
+                handle,
+                &path,
+                flag.unwrap_or_default(),
+                title.as_deref(),
+                language.as_deref(),
+            )?)
+        })
+        .await
+}
+
+// The encoding external text subtitles are read in, e.g. "cp1256" or "gbk"; "auto"
+// guesses it from the contents. mpv only applies this when a file is loaded, so a
+// selected external track is re-read straight away. Embedded tracks are always UTF-8.
+#[tauri::command]
+pub async fn set_sub_codepage(
+    encoding: String,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let valid = !encoding.is_empty()
+        && encoding
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'));
+    if !valid {
+        return Err(PlayerError::InvalidArgument(format!(
+            "Invalid encoding name '{}'",
+            encoding
+        )));
+    }
+    instance
+        .channel
+        .run(move |handle| {
+            handle
+                .set("sub-codepage", &encoding)
+                .context("Failed to set subtitle encoding")?;
+
+            let current = tracks::selected(handle, "sub");
+            let external = tracks::read_tracks(handle)
+                .iter()
+                .any(|t| t.kind == "sub" && Some(t.id) == current && t.external);
+            if external {
+                handle
+                    .command(&["sub-reload"])
+                    .context("Failed to reload subtitles")?;
+                Ok(format!("💬 Subtitles reloaded as {}", encoding))
+            } else {
+                Ok(format!("💬 Subtitle encoding set to {}", encoding))
+            }
+        })
+        .await
+}
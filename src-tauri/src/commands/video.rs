@@ -0,0 +1,813 @@
+// Video rendering, pop-out windows, picture adjustments, frame capture and contact sheets
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::Ordering;
+use tauri::Window;
+
+use super::settings::yes_no;
+use super::{off_thread, on_main_thread};
+use crate::contact_sheet::{self, ContactSheet};
+use crate::error::{Context, PlayerError};
+use crate::frame_capture;
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::player::{PlayerId, Players};
+#[cfg(target_os = "linux")]
+use crate::rendering::gl_view;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use crate::rendering::native_view;
+use crate::rendering::{
+    layout, place_video, resolve_video_backend, video_window, VideoArea, VideoPlacement,
+};
+use crate::{clip, config};
+
+pub(crate) fn apply_video_enhancements(
+    handle: &MpvHandle,
+    values: &config::VideoEnhancements,
+) -> Result<(), PlayerError> {
+    let properties = [
+        ("deband", yes_no(values.deband).to_string()),
+        ("deband-iterations", values.deband_iterations.to_string()),
+        ("deband-threshold", values.deband_threshold.to_string()),
+        ("sharpen", values.sharpen.to_string()),
+    ];
+    for (name, value) in properties {
+        handle
+            .set(name, &value)
+            .context(format!("Failed to set {}", name))?;
+    }
+    Ok(())
+}
+
+// video-sync, interpolation, tscale for smooth motion on and off (off = mpv defaults)
+const SMOOTH_MOTION_ON: [(&str, &str); 3] = [
+    ("video-sync", "display-resample"),
+    ("interpolation", "yes"),
+    ("tscale", "oversample"),
+];
+
+const SMOOTH_MOTION_OFF: [(&str, &str); 3] = [
+    ("video-sync", "audio"),
+    ("interpolation", "no"),
+    ("tscale", "oversample"),
+];
+
+pub(crate) fn apply_smooth_motion(handle: &MpvHandle, enabled: bool) -> Result<(), PlayerError> {
+    let properties = if enabled {
+        SMOOTH_MOTION_ON
+    } else {
+        SMOOTH_MOTION_OFF
+    };
+    for (name, value) in properties {
+        handle
+            .set(name, value)
+            .context(format!("Failed to set {}", name))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SmoothMotionStatus {
+    enabled: bool,
+    video_sync: Option<String>,
+    interpolation: Option<String>,
+    tscale: Option<String>,
+    // interpolation only runs while display sync is actually in effect
+    interpolation_active: bool,
+    avsync: Option<f64>,
+}
+
+fn read_smooth_motion(handle: &MpvHandle, enabled: bool) -> SmoothMotionStatus {
+    let interpolation = handle.get::<String>("interpolation").ok();
+    let display_sync_active = handle.get::<bool>("display-sync-active").unwrap_or(false);
+
+    SmoothMotionStatus {
+        enabled,
+        video_sync: handle.get::<String>("video-sync").ok(),
+        interpolation_active: display_sync_active && interpolation.as_deref() == Some("yes"),
+        interpolation,
+        tscale: handle.get::<String>("tscale").ok(),
+        avsync: handle.get::<f64>("avsync").ok(),
+    }
+}
+
+const TONE_MAPPING_ALGORITHMS: &[&str] = &[
+    "auto",
+    "clip",
+    "mobius",
+    "reinhard",
+    "hable",
+    "gamma",
+    "linear",
+    "spline",
+    "bt.2390",
+    "bt.2446a",
+    "st2094-40",
+    "st2094-10",
+];
+
+const GAMUT_MAPPING_MODES: &[&str] = &[
+    "auto",
+    "clip",
+    "perceptual",
+    "relative",
+    "saturation",
+    "absolute",
+    "desaturate",
+    "darken",
+    "warn",
+    "linear",
+];
+
+const HDR_COMPUTE_PEAK_VALUES: &[&str] = &["auto", "yes", "no"];
+
+pub(crate) fn apply_tone_mapping(
+    handle: &MpvHandle,
+    values: &config::ToneMapping,
+) -> Result<(), PlayerError> {
+    let properties = [
+        ("tone-mapping", &values.tone_mapping),
+        ("target-peak", &values.target_peak),
+        ("hdr-compute-peak", &values.hdr_compute_peak),
+        ("gamut-mapping-mode", &values.gamut_mapping_mode),
+    ];
+    for (name, value) in properties {
+        handle
+            .set(name, value)
+            .context(format!("Failed to set {}", name))?;
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HdrInfo {
+    is_hdr: bool,
+    primaries: Option<String>,
+    gamma: Option<String>,
+    // Peak brightness relative to SDR white (1.0 for SDR content)
+    sig_peak: Option<f64>,
+}
+
+// Async: views are set up on the main thread and mpv is pointed at them on its event
+// thread, which a sync command on the main thread would wait on
+#[tauri::command]
+pub async fn setup_video_rendering(
+    window: Window,
+    video_area: VideoArea,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<VideoPlacement, PlayerError> {
+    let instance = players.get(player_id)?;
+    let visible = {
+        let player = instance.player.lock().unwrap();
+        if !player.running() {
+            return Err(PlayerError::NotInitialized);
+        }
+        player.video_layer_visible
+    };
+
+    // Everything downstream of here works in logical pixels
+    let scale = window
+        .scale_factor()
+        .map_err(|e| PlayerError::Other(format!("Failed to get scale factor: {}", e)))?;
+    let size = layout::window_size(&window)?;
+    let video_area = layout::clamp_to_window(&video_area.to_logical(scale), size)?;
+    instance.player.lock().unwrap().video_area = Some(video_area.clone());
+
+    // Enable video output now that we have a target, unless it's meant to be hidden
+    if visible {
+        instance
+            .run(|_, handle| {
+                if let Err(e) = handle.set("vid", "auto") {
+                    eprintln!("Failed to enable video output: {}", e);
+                }
+                Ok(())
+            })
+            .await?;
+    }
+
+    place_video(&window, &instance, video_area, size, scale).await
+}
+
+// The frontend's layout changed; `layout` also sets how the area follows later resizes
+#[tauri::command]
+pub fn update_video_area(
+    window: Window,
+    video_area: VideoArea,
+    layout: Option<layout::LayoutRule>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<Players>,
+) -> Result<VideoPlacement, PlayerError> {
+    let instance = players.get(player_id)?;
+    let mut player = instance.player.lock().unwrap();
+
+    if !player.running() {
+        return Err(PlayerError::NotInitialized);
+    }
+    let Some(current) = &player.video_layout else {
+        return Err(PlayerError::RenderingNotSetUp);
+    };
+
+    let rule = layout.unwrap_or(current.rule());
+    let scale = window
+        .scale_factor()
+        .map_err(|e| PlayerError::Other(format!("Failed to get scale factor: {}", e)))?;
+    let size = layout::window_size(&window)?;
+    let video_area = layout::clamp_to_window(&video_area.to_logical(scale), size)?;
+    let video_layout = layout::VideoLayout::new(video_area, size, rule);
+    let area = video_layout.area_for(size);
+    // The main page keeps laying out its container while the video is in its own
+    // window; that is where the video goes back to
+    if player.video_window.is_some() && window.label() == video_window::MAIN_WINDOW {
+        player.docked_layout = Some(video_layout);
+        return Ok(VideoPlacement {
+            message: format!(
+                "Video area set to {}x{} for when the video is docked",
+                area.width, area.height
+            ),
+            area,
+            backend: player.video_backend.unwrap_or_default(),
+        });
+    }
+    layout::apply_area(&instance, &player, &area, size, scale);
+
+    player.video_layout = Some(video_layout);
+    player.video_area = Some(area.clone());
+    Ok(VideoPlacement {
+        message: format!("Video area set to {}x{}", area.width, area.height),
+        area,
+        backend: player.video_backend.unwrap_or_default(),
+    })
+}
+
+// Hide the video without stopping playback, e.g. while a settings screen is open
+#[tauri::command]
+pub async fn set_video_layer_visible<R: tauri::Runtime>(
+    app: tauri::AppHandle<R>,
+    visible: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    let message = if visible {
+        "Video layer shown"
+    } else {
+        "Video layer hidden"
+    };
+    {
+        let player = instance.player.lock().unwrap();
+        if !player.running() {
+            return Err(PlayerError::NotInitialized);
+        }
+        if player.video_layer_visible == visible {
+            return Ok(message.to_string());
+        }
+    }
+
+    // Views are only touched from the main thread
+    let target = instance.clone();
+    let hidden_view = on_main_thread(&app, move || {
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        if let Some(view) = crate::rendering::native_view(target.id) {
+            let player = target.player.lock().unwrap();
+            unsafe {
+                if visible {
+                    // The window may have been resized while hidden
+                    if let Some(area) = &player.video_area {
+                        native_view::set_frame(view.0, area);
+                    }
+                }
+                native_view::set_hidden(view.0, !visible);
+            }
+            return Ok(true);
+        }
+        #[cfg(target_os = "linux")]
+        let hidden = gl_view::set_hidden(target.id, !visible);
+        #[cfg(not(target_os = "linux"))]
+        let hidden = false;
+        Ok(hidden)
+    })
+    .await?;
+    if hidden_view {
+        instance.player.lock().unwrap().video_layer_visible = visible;
+        return Ok(message.to_string());
+    }
+
+    // No view of our own to hide: switch the video track off, which leaves audio playing
+    instance
+        .run(move |player, handle| {
+            if visible {
+                let vid = player
+                    .hidden_vid
+                    .take()
+                    .unwrap_or_else(|| "auto".to_string());
+                handle
+                    .set("vid", &vid)
+                    .context("Failed to restore video track")?;
+            } else {
+                player.hidden_vid = handle.get::<String>("vid").ok();
+                handle.set("vid", "no").context("Failed to hide video")?;
+            }
+            player.video_layer_visible = visible;
+            Ok(message.to_string())
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn export_clip(
+    app: tauri::AppHandle,
+    output_path: String,
+    reencode: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let input = handle
+                .get::<String>("path")
+                .map_err(|_| PlayerError::NoFile)?;
+            // Unset loop points read back as "no", which fails as a double
+            let (Ok(start), Ok(end)) = (
+                handle.get::<f64>("ab-loop-a"),
+                handle.get::<f64>("ab-loop-b"),
+            ) else {
+                return Err(PlayerError::InvalidArgument(
+                    "Set both A-B loop points before exporting a clip".to_string(),
+                ));
+            };
+            if end <= start {
+                return Err(PlayerError::InvalidArgument(
+                    "Loop point B must be after point A".to_string(),
+                ));
+            }
+
+            if player.clip_export_running.swap(true, Ordering::SeqCst) {
+                return Err(PlayerError::Other(
+                    "A clip export is already running".to_string(),
+                ));
+            }
+
+            let job = clip::ClipJob {
+                input,
+                output: output_path.clone(),
+                start,
+                end,
+                reencode,
+            };
+            clip::spawn_export(app, job, player.clip_export_running.clone());
+
+            Ok(format!(
+                "✂️ Exporting {:.1}s - {:.1}s to {}",
+                start, end, output_path
+            ))
+        })
+        .await
+}
+
+// A `columns` × `rows` grid of frames from across the current file, saved to
+// `output_path` (.jpg or .png). Reading through a long recording takes a few seconds.
+#[tauri::command]
+pub async fn export_contact_sheet(
+    output_path: String,
+    columns: Option<u32>,
+    rows: Option<u32>,
+    tile_width: Option<u32>,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let (columns, rows) = (columns.unwrap_or(4), rows.unwrap_or(4));
+    let tile_width = tile_width.unwrap_or(320);
+    if !(1..=contact_sheet::MAX_GRID).contains(&columns)
+        || !(1..=contact_sheet::MAX_GRID).contains(&rows)
+    {
+        return Err(PlayerError::InvalidArgument(format!(
+            "A contact sheet has 1 to {} columns and rows",
+            contact_sheet::MAX_GRID
+        )));
+    }
+    if !(64..=1920).contains(&tile_width) {
+        return Err(PlayerError::InvalidArgument(
+            "Frames must be 64 to 1920 pixels wide".to_string(),
+        ));
+    }
+    let extension = std::path::Path::new(&output_path)
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    if !matches!(extension.as_deref(), Some("jpg" | "jpeg" | "png")) {
+        return Err(PlayerError::InvalidArgument(
+            "Save contact sheets as .jpg or .png".to_string(),
+        ));
+    }
+
+    let instance = players.get(player_id)?;
+    let (input, duration) = instance
+        .channel
+        .run(move |handle| {
+            let input = handle
+                .get::<String>("path")
+                .map_err(|_| PlayerError::NoFile)?;
+            match handle.get::<f64>("duration") {
+                Ok(duration) if duration > 0.0 => Ok((input, duration)),
+                _ => Err(PlayerError::InvalidArgument(
+                    "The current file has no duration to spread frames over".to_string(),
+                )),
+            }
+        })
+        .await?;
+
+    off_thread(move || {
+        let sheet = ContactSheet {
+            input,
+            output: output_path,
+            duration,
+            columns,
+            rows,
+            tile_width,
+        };
+        sheet.render()?;
+        Ok(format!(
+            "🎞️ Saved a {}x{} contact sheet to {}",
+            columns, rows, sheet.output
+        ))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn copy_frame_to_clipboard(
+    app: tauri::AppHandle,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<String, PlayerError> {
+    let instance = players.get(player_id)?;
+    use tauri_plugin_clipboard_manager::ClipboardExt;
+
+    let temp_path = instance
+        .channel
+        .run(move |handle| {
+            if handle.get::<String>("path").is_err() {
+                return Err(PlayerError::NoFile);
+            }
+            // Audio-only files have no frame to grab
+            if handle.get::<String>("video-params/w").is_err() {
+                return Err(PlayerError::NotFound(
+                    "The current file has no video frame to copy".to_string(),
+                ));
+            }
+
+            let nanos = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos())
+                .unwrap_or(0);
+            let temp_path = std::env::temp_dir().join(format!("mpv-frame-{}.png", nanos));
+            let temp_str = temp_path.to_string_lossy().into_owned();
+
+            if let Err(e) = handle.command(&["screenshot-to-file", &temp_str, "subtitles"]) {
+                let _ = std::fs::remove_file(&temp_path);
+                return Err(PlayerError::Mpv {
+                    action: Some("Failed to capture frame".to_string()),
+                    error: e,
+                });
+            }
+            Ok(temp_path)
+        })
+        .await?;
+
+    // Decoding the capture and handing it to the clipboard stay off the event thread
+    off_thread(move || {
+        let image = tauri::image::Image::from_path(&temp_path);
+        let _ = std::fs::remove_file(&temp_path);
+        let image = image
+            .map_err(|e| PlayerError::Other(format!("Failed to read captured frame: {}", e)))?;
+
+        app.clipboard()
+            .write_image(&image)
+            .map_err(|e| PlayerError::Other(format!("Failed to copy frame to clipboard: {}", e)))?;
+
+        Ok(format!(
+            "📋 Copied {}x{} frame to clipboard",
+            image.width(),
+            image.height()
+        ))
+    })
+    .await
+}
+
+// The current frame, subtitles included, as PNG bytes the page receives as an
+// ArrayBuffer: an instant "frame captured" preview, or an image to drop into a note,
+// without saving a file anywhere
+#[tauri::command]
+pub async fn capture_frame(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<tauri::ipc::Response, PlayerError> {
+    let instance = players.get(player_id)?;
+    let frame = instance
+        .channel
+        .run(move |handle| {
+            if handle.get::<String>("path").is_err() {
+                return Err(PlayerError::NoFile);
+            }
+            // Audio-only files have no frame; mpv's command fails for them
+            handle
+                .screenshot_raw("subtitles")
+                .context("Failed to capture frame")
+        })
+        .await?;
+
+    let png =
+        off_thread(move || frame_capture::encode_png(&frame).map_err(PlayerError::Other)).await?;
+    Ok(tauri::ipc::Response::new(png))
+}
+
+// Partial update: only the fields that are present change
+#[derive(Debug, Deserialize)]
+pub struct VideoEnhancementsUpdate {
+    deband: Option<bool>,
+    deband_iterations: Option<i64>,
+    deband_threshold: Option<f64>,
+    sharpen: Option<f64>,
+}
+
+#[tauri::command]
+pub async fn set_video_enhancements(
+    options: VideoEnhancementsUpdate,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<config::VideoEnhancements, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let mut values = player.config.values.video_enhancements.clone();
+
+            if let Some(deband) = options.deband {
+                values.deband = deband;
+            }
+            if let Some(iterations) = options.deband_iterations {
+                if !(1..=16).contains(&iterations) {
+                    return Err(PlayerError::InvalidArgument(
+                        "Deband iterations must be between 1 and 16".to_string(),
+                    ));
+                }
+                values.deband_iterations = iterations;
+            }
+            if let Some(threshold) = options.deband_threshold {
+                if !(0.0..=4096.0).contains(&threshold) {
+                    return Err(PlayerError::InvalidArgument(
+                        "Deband threshold must be between 0 and 4096".to_string(),
+                    ));
+                }
+                values.deband_threshold = threshold;
+            }
+            if let Some(sharpen) = options.sharpen {
+                if !(-1.0..=1.0).contains(&sharpen) {
+                    return Err(PlayerError::InvalidArgument(
+                        "Sharpen must be between -1.0 and 1.0".to_string(),
+                    ));
+                }
+                values.sharpen = sharpen;
+            }
+
+            apply_video_enhancements(handle, &values)?;
+            player.config.values.video_enhancements = values.clone();
+            player.config.save_or_warn();
+            Ok(values)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_video_enhancements(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<config::VideoEnhancements, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let player = instance.player.lock().unwrap();
+        Ok(player.config.values.video_enhancements.clone())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn reset_video_enhancements(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<config::VideoEnhancements, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let values = config::VideoEnhancements::default();
+            apply_video_enhancements(handle, &values)?;
+            player.config.values.video_enhancements = values.clone();
+            player.config.save_or_warn();
+            Ok(values)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn set_smooth_motion(
+    enabled: bool,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<SmoothMotionStatus, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            apply_smooth_motion(handle, enabled)?;
+            player.config.values.smooth_motion = enabled;
+            player.config.save_or_warn();
+            Ok(read_smooth_motion(handle, enabled))
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_smooth_motion(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<SmoothMotionStatus, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            Ok(read_smooth_motion(
+                handle,
+                player.config.values.smooth_motion,
+            ))
+        })
+        .await
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoBackendStatus {
+    // What the settings ask for
+    pub(crate) configured: config::VideoBackend,
+    // What that comes to on this platform
+    pub(crate) resolved: config::VideoBackend,
+}
+
+// Takes effect the next time the video is set up, i.e. from the next launch
+#[tauri::command]
+pub async fn set_video_backend(
+    backend: config::VideoBackend,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<VideoBackendStatus, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let mut player = instance.player.lock().unwrap();
+
+        let resolved = resolve_video_backend(backend)?;
+        player.config.values.video_backend = backend;
+        player.config.save_or_warn();
+        Ok(VideoBackendStatus {
+            configured: backend,
+            resolved,
+        })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_video_backend(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<VideoBackendStatus, PlayerError> {
+    let instance = players.get(player_id)?;
+    off_thread(move || {
+        let player = instance.player.lock().unwrap();
+
+        let configured = player.config.values.video_backend;
+        Ok(VideoBackendStatus {
+            configured,
+            resolved: resolve_video_backend(configured)?,
+        })
+    })
+    .await
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ToneMappingUpdate {
+    tone_mapping: Option<String>,
+    target_peak: Option<String>,
+    hdr_compute_peak: Option<String>,
+    gamut_mapping_mode: Option<String>,
+}
+
+#[tauri::command]
+pub async fn set_tone_mapping(
+    options: ToneMappingUpdate,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<config::ToneMapping, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .run(move |player, handle| {
+            let mut values = player.config.values.tone_mapping.clone();
+
+            if let Some(algorithm) = options.tone_mapping {
+                if !TONE_MAPPING_ALGORITHMS.contains(&algorithm.as_str()) {
+                    return Err(PlayerError::InvalidArgument(format!(
+                        "Unknown tone mapping '{}'. Use one of: {}",
+                        algorithm,
+                        TONE_MAPPING_ALGORITHMS.join(", ")
+                    )));
+                }
+                values.tone_mapping = algorithm;
+            }
+            if let Some(peak) = options.target_peak {
+                let valid = peak == "auto"
+                    || peak
+                        .parse::<f64>()
+                        .is_ok_and(|nits| (10.0..=10000.0).contains(&nits));
+                if !valid {
+                    return Err(PlayerError::InvalidArgument(
+                        "Target peak must be 'auto' or between 10 and 10000 nits".to_string(),
+                    ));
+                }
+                values.target_peak = peak;
+            }
+            if let Some(compute_peak) = options.hdr_compute_peak {
+                if !HDR_COMPUTE_PEAK_VALUES.contains(&compute_peak.as_str()) {
+                    return Err(PlayerError::InvalidArgument(
+                        "hdr_compute_peak must be 'auto', 'yes' or 'no'".to_string(),
+                    ));
+                }
+                values.hdr_compute_peak = compute_peak;
+            }
+            if let Some(mode) = options.gamut_mapping_mode {
+                if !GAMUT_MAPPING_MODES.contains(&mode.as_str()) {
+                    return Err(PlayerError::InvalidArgument(format!(
+                        "Unknown gamut mapping mode '{}'. Use one of: {}",
+                        mode,
+                        GAMUT_MAPPING_MODES.join(", ")
+                    )));
+                }
+                values.gamut_mapping_mode = mode;
+            }
+
+            apply_tone_mapping(handle, &values)?;
+            player.config.values.tone_mapping = values.clone();
+            player.config.save_or_warn();
+            Ok(values)
+        })
+        .await
+}
+
+#[tauri::command]
+pub async fn get_hdr_info(
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<HdrInfo, PlayerError> {
+    let instance = players.get(player_id)?;
+    instance
+        .channel
+        .run(move |handle| {
+            let primaries = handle.get::<String>("video-params/primaries").ok();
+            let gamma = handle.get::<String>("video-params/gamma").ok();
+            let sig_peak = handle.get::<f64>("video-params/sig-peak").ok();
+
+            // PQ and HLG transfer functions are what make a file HDR; BT.2020 alone is not
+            let is_hdr = matches!(gamma.as_deref(), Some("pq") | Some("hlg"))
+                || sig_peak.is_some_and(|peak| peak > 1.0);
+
+            Ok(HdrInfo {
+                is_hdr,
+                primaries,
+                gamma,
+                sig_peak,
+            })
+        })
+        .await
+}
+
+// Pop the video out into a window of its own, e.g. to put it on another monitor while
+// the controls stay here. Closing that window, or dock_video_window, brings it back.
+// Async, as building a window from a sync command deadlocks on Windows.
+#[tauri::command]
+pub async fn detach_video_window(
+    app: tauri::AppHandle,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<VideoPlacement, PlayerError> {
+    let instance = players.get(player_id)?;
+    let window = video_window::open(&app, &instance)?;
+
+    let placed = video_window::detach(&window, &instance).await;
+    if placed.is_err() {
+        let _ = window.destroy();
+    }
+    placed
+}
+
+#[tauri::command]
+pub async fn dock_video_window(
+    app: tauri::AppHandle,
+    player_id: Option<PlayerId>,
+    players: tauri::State<'_, Players>,
+) -> Result<VideoPlacement, PlayerError> {
+    let instance = players.get(player_id)?;
+    video_window::close(&app, &instance).await
+}
@@ -0,0 +1,54 @@
+// Placing the main window on a monitor
+
+use tauri::Manager;
+
+use crate::error::PlayerError;
+
+// Window positioning commands (from your existing code)
+#[tauri::command]
+pub fn move_to_monitor(window: tauri::Window, monitor_index: u32) -> Result<String, PlayerError> {
+    use tauri::{LogicalPosition, Position};
+
+    let (x, y) = match monitor_index {
+        0 => (0, 100),
+        1 => (1920, 100),
+        2 => (3840, 100),
+        _ => {
+            return Err(PlayerError::InvalidArgument(
+                "Invalid monitor index. Use 0, 1, or 2.".to_string(),
+            ))
+        }
+    };
+
+    window
+        .set_position(Position::Logical(LogicalPosition {
+            x: x as f64,
+            y: y as f64,
+        }))
+        .map_err(|e| PlayerError::Other(format!("Failed to move window: {}", e)))?;
+
+    Ok(format!("Moved window to monitor {}", monitor_index))
+}
+
+pub(crate) fn setup_window_position(
+    app: &mut tauri::App,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let window = app.get_webview_window("main").unwrap();
+    let preferred_monitor = 2;
+
+    let (x, y) = match preferred_monitor {
+        0 => (0, 100),
+        1 => (1920, 100),
+        2 => (3840, 100),
+        _ => (0, 100),
+    };
+
+    use tauri::{LogicalPosition, Position};
+    window.set_position(Position::Logical(LogicalPosition {
+        x: x as f64,
+        y: y as f64,
+    }))?;
+
+    println!("Window moved to monitor {}", preferred_monitor);
+    Ok(())
+}
@@ -0,0 +1,399 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+const CONFIG_FILE: &str = "config.json";
+
+// Video filter settings applied through vo_gpu options
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct VideoEnhancements {
+    pub deband: bool,
+    pub deband_iterations: i64,
+    pub deband_threshold: f64,
+    pub sharpen: f64,
+}
+
+impl Default for VideoEnhancements {
+    // mpv's own defaults
+    fn default() -> Self {
+        Self {
+            deband: false,
+            deband_iterations: 1,
+            deband_threshold: 48.0,
+            sharpen: 0.0,
+        }
+    }
+}
+
+// HDR -> SDR conversion; values are passed to mpv verbatim
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToneMapping {
+    pub tone_mapping: String,
+    // "auto" or a peak in nits
+    pub target_peak: String,
+    pub hdr_compute_peak: String,
+    pub gamut_mapping_mode: String,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        Self {
+            tone_mapping: "auto".to_string(),
+            target_peak: "auto".to_string(),
+            hdr_compute_peak: "auto".to_string(),
+            gamut_mapping_mode: "auto".to_string(),
+        }
+    }
+}
+
+// Look of text subtitles. ASS subtitles keep their own styling unless mpv is told to
+// override it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SubtitleStyle {
+    pub font: String,
+    // In scaled pixels, relative to a 720px tall window
+    pub font_size: f64,
+    // "#RRGGBB" or "#AARRGGBB"
+    pub color: String,
+    pub border_size: f64,
+    pub border_color: String,
+    // Draw a box behind the text instead of an outline
+    pub background_box: bool,
+    pub background_color: String,
+    // Vertical position, 0 (top) to 100 (bottom)
+    pub position: i64,
+}
+
+impl Default for SubtitleStyle {
+    // mpv's own defaults
+    fn default() -> Self {
+        Self {
+            font: "sans-serif".to_string(),
+            font_size: 38.0,
+            color: "#FFFFFF".to_string(),
+            border_size: 3.0,
+            border_color: "#000000".to_string(),
+            background_box: false,
+            background_color: "#C0000000".to_string(),
+            position: 100,
+        }
+    }
+}
+
+// How mpv's frames reach the screen. Read when the video is first set up, so a change
+// applies from the next launch.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VideoBackend {
+    // Native where the platform has it, OpenGL elsewhere
+    #[default]
+    Auto,
+    // mpv draws into a child view of the window, handed to it as `wid`
+    Native,
+    // mpv renders through its render API into a GL surface under the webview
+    #[serde(rename = "opengl")]
+    OpenGl,
+    // mpv renders on the CPU and the page draws the frames into a canvas. Slow, but
+    // needs nothing from the platform; also the fallback when the others fail.
+    Software,
+}
+
+// Starting curves for the equalizer; see equalizer::preset_gains
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EqualizerPreset {
+    #[default]
+    Flat,
+    BassBoost,
+    Voice,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqualizerSettings {
+    pub enabled: bool,
+    pub preset: EqualizerPreset,
+    // Band gains in dB for the presets the user has adjusted; the others play with their
+    // built-in curve
+    pub gains: BTreeMap<EqualizerPreset, Vec<f64>>,
+}
+
+// Evening out loudness between files; see loudness.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoudnessNormalization {
+    pub enabled: bool,
+    // Integrated loudness to aim for, in LUFS
+    pub target_lufs: f64,
+}
+
+impl Default for LoudnessNormalization {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            // What most streaming services play at
+            target_lufs: -16.0,
+        }
+    }
+}
+
+// Which speakers to play to and how surround is folded down onto fewer; see downmix.rs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioChannels {
+    // audio-channels: "auto-safe", "stereo", "5.1", ...
+    pub layout: String,
+    // Level of the centre channel, where the dialogue is, in a downmix
+    pub center_level: f64,
+    // Level of the LFE (subwoofer) channel in a downmix; 0 leaves it out
+    pub lfe_level: f64,
+    // Scale the downmix so it can't clip, at the cost of it being quieter
+    pub normalize: bool,
+}
+
+impl Default for AudioChannels {
+    // mpv's and swresample's own defaults
+    fn default() -> Self {
+        Self {
+            layout: "auto-safe".to_string(),
+            center_level: std::f64::consts::FRAC_1_SQRT_2,
+            lfe_level: 0.0,
+            normalize: false,
+        }
+    }
+}
+
+// Sending compressed surround to an AV receiver as-is (S/PDIF or HDMI) instead of
+// decoding it; see spdif.rs. Each codec is only sent if the receiver can take it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioPassthrough {
+    pub enabled: bool,
+    pub ac3: bool,
+    pub eac3: bool,
+    pub dts: bool,
+    pub dts_hd: bool,
+    pub truehd: bool,
+}
+
+impl Default for AudioPassthrough {
+    // What any receiver with an optical input takes; the rest need HDMI
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ac3: true,
+            eac3: false,
+            dts: true,
+            dts_hd: false,
+            truehd: false,
+        }
+    }
+}
+
+// Playback state carried over from the last session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSettings {
+    pub speed: f64,
+    pub volume: f64,
+    pub mute: bool,
+    pub subtitle_visible: bool,
+    // Keep the pitch unchanged when playing faster or slower
+    pub pitch_correction: bool,
+}
+
+impl Default for SessionSettings {
+    // mpv's own defaults
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            volume: 100.0,
+            mute: false,
+            subtitle_visible: true,
+            pitch_correction: true,
+        }
+    }
+}
+
+// What mpv_set_option and mpv_run_command may touch. Only editable in the config file,
+// so a settings page can offer these without being able to widen them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MpvAllowlist {
+    // Option/property names, without a leading "--" or "options/"
+    pub options: Vec<String>,
+    // Command names; set, add and the like still need their property in `options`
+    pub commands: Vec<String>,
+}
+
+impl Default for MpvAllowlist {
+    fn default() -> Self {
+        let options = [
+            "deband",
+            "deinterlace",
+            "hwdec",
+            "interpolation",
+            "video-sync",
+            "scale",
+            "cscale",
+            "dscale",
+            "dither-depth",
+            "audio-channels",
+            "audio-normalize-downmix",
+            "volume-max",
+            "alang",
+            "slang",
+            "sub-auto",
+            "sub-ass-override",
+            "sub-font-size",
+            "sub-scale",
+            "sub-pos",
+            "cache-secs",
+            "demuxer-max-bytes",
+            "demuxer-readahead-secs",
+            "screenshot-format",
+        ];
+        let commands = [
+            "set",
+            "add",
+            "cycle",
+            "cycle-values",
+            "multiply",
+            "show-text",
+            "screenshot",
+            "sub-reload",
+            "audio-reload",
+            "rescan-external-files",
+        ];
+        Self {
+            options: options.map(String::from).to_vec(),
+            commands: commands.map(String::from).to_vec(),
+        }
+    }
+}
+
+// Preferences persisted between sessions. Unknown or missing keys fall back to defaults
+// so older config files keep loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppConfig {
+    pub video_enhancements: VideoEnhancements,
+    pub smooth_motion: bool,
+    pub tone_mapping: ToneMapping,
+    // Re-apply speed, tracks and delays remembered for a file when it loads
+    pub restore_per_file_settings: bool,
+    // Keep the display awake while a file is playing
+    pub prevent_sleep: bool,
+    // When the last file in the queue ends, play the next one in its folder
+    pub auto_advance: bool,
+    // Keep the main player's queue on disk for restore_session after a restart
+    pub restore_queue: bool,
+    // Save where each file was left, for LoadOptions::resume
+    pub remember_position: bool,
+    // Show playback progress on the Dock icon / taskbar button
+    pub dock_progress: bool,
+    // Frame-exact seeks by default instead of keyframe ones
+    pub precise_seeking: bool,
+    pub video_backend: VideoBackend,
+    pub subtitle_style: SubtitleStyle,
+    pub equalizer: EqualizerSettings,
+    pub loudness_normalization: LoudnessNormalization,
+    pub audio_channels: AudioChannels,
+    pub audio_passthrough: AudioPassthrough,
+    // For subtitle search; each user brings their own from opensubtitles.com
+    pub opensubtitles_api_key: Option<String>,
+    pub session: SessionSettings,
+    pub mpv_allowlist: MpvAllowlist,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        Self {
+            video_enhancements: VideoEnhancements::default(),
+            smooth_motion: false,
+            tone_mapping: ToneMapping::default(),
+            restore_per_file_settings: true,
+            prevent_sleep: true,
+            auto_advance: false,
+            restore_queue: false,
+            remember_position: true,
+            dock_progress: true,
+            precise_seeking: false,
+            video_backend: VideoBackend::default(),
+            subtitle_style: SubtitleStyle::default(),
+            equalizer: EqualizerSettings::default(),
+            loudness_normalization: LoudnessNormalization::default(),
+            audio_channels: AudioChannels::default(),
+            audio_passthrough: AudioPassthrough::default(),
+            opensubtitles_api_key: None,
+            session: SessionSettings::default(),
+            mpv_allowlist: MpvAllowlist::default(),
+        }
+    }
+}
+
+// The loaded config plus where it lives on disk
+#[derive(Debug, Default)]
+pub struct ConfigStore {
+    path: Option<PathBuf>,
+    pub values: AppConfig,
+}
+
+impl ConfigStore {
+    // A copy of the values that never touches the file
+    pub fn detached(&self) -> Self {
+        Self {
+            path: None,
+            values: self.values.clone(),
+        }
+    }
+
+    // Read the config from the app config dir; a missing or corrupt file gives defaults
+    pub fn load(app: &AppHandle) -> Self {
+        let path = match app.path().app_config_dir() {
+            Ok(dir) => dir.join(CONFIG_FILE),
+            Err(e) => {
+                eprintln!("No config directory, settings won't persist: {}", e);
+                return Self::default();
+            }
+        };
+
+        let values = match std::fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                eprintln!("Ignoring unreadable config {}: {}", path.display(), e);
+                AppConfig::default()
+            }),
+            Err(_) => AppConfig::default(),
+        };
+
+        Self {
+            path: Some(path),
+            values,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create config directory: {}", e))?;
+        }
+        let contents = serde_json::to_string_pretty(&self.values)
+            .map_err(|e| format!("Failed to serialize config: {}", e))?;
+        std::fs::write(path, contents).map_err(|e| format!("Failed to write config: {}", e))
+    }
+
+    // Save, logging instead of failing - the setting has already taken effect
+    pub fn save_or_warn(&self) {
+        if let Err(e) = self.save() {
+            eprintln!("{}", e);
+        }
+    }
+}
@@ -0,0 +1,107 @@
+// Contact sheets: a grid of frames spread evenly over a file, each labelled with its
+// time, saved as one image for skimming a long recording without playing it. ffmpeg
+// makes it in one pass over the keyframes, like the seekbar previews.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::clip::ffmpeg_available;
+
+// Columns and rows; beyond this the frames are too small to tell apart
+pub const MAX_GRID: u32 = 12;
+
+// Everything the ffmpeg run needs, read from the player beforehand
+pub struct ContactSheet {
+    pub input: String,
+    pub output: String,
+    pub duration: f64,
+    pub columns: u32,
+    pub rows: u32,
+    // Pixels per frame; the height follows the video's aspect ratio
+    pub tile_width: u32,
+}
+
+impl ContactSheet {
+    // Seconds between frames, each frame in the middle of its share of the file
+    fn step(&self) -> f64 {
+        self.duration / f64::from(self.columns * self.rows)
+    }
+
+    fn filter(&self, labels: bool) -> String {
+        let mut filter = format!("fps=1/{:.3},scale={}:-2", self.step(), self.tile_width);
+        if labels {
+            filter.push_str(&format!(
+                ",drawtext=text='%{{pts\\:hms}}':x=6:y=h-th-6:fontsize={}:fontcolor=white\
+                 :box=1:boxcolor=black@0.6:boxborderw=3",
+                (self.tile_width / 16).max(10)
+            ));
+        }
+        filter.push_str(&format!(
+            ",tile={}x{}:padding=4:margin=4",
+            self.columns, self.rows
+        ));
+        filter
+    }
+
+    pub fn render(&self) -> Result<(), String> {
+        if !ffmpeg_available() {
+            return Err("Contact sheets need ffmpeg on PATH".to_string());
+        }
+        match self.run_ffmpeg(true) {
+            // ffmpeg builds without freetype have no drawtext; the frames alone still help
+            Err(e) if e.contains("drawtext") => self.run_ffmpeg(false),
+            result => result,
+        }
+    }
+
+    fn run_ffmpeg(&self, labels: bool) -> Result<(), String> {
+        let output = Command::new("ffmpeg")
+            .args(["-hide_banner", "-nostdin", "-loglevel", "error", "-y"])
+            .args(["-skip_frame", "nokey"])
+            // Past the first half step, and keeping the file's timestamps for the labels
+            .args(["-ss", &format!("{:.3}", self.step() / 2.0), "-copyts"])
+            .args(["-i", &self.input])
+            .args(["-an", "-sn"])
+            .args(["-vf", &self.filter(labels)])
+            .args(["-frames:v", "1", "-update", "1", "-q:v", "3"])
+            .arg(&self.output)
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+        if !output.status.success() || !Path::new(&self.output).exists() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            let reason = stderr
+                .lines()
+                .find(|line| line.contains("No such filter"))
+                .or_else(|| stderr.lines().last())
+                .unwrap_or("no frames decoded");
+            return Err(format!("ffmpeg failed: {}", reason));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_spread_over_the_file() {
+        let sheet = ContactSheet {
+            input: "/videos/a.mkv".to_string(),
+            output: "/tmp/a.jpg".to_string(),
+            duration: 3600.0,
+            columns: 4,
+            rows: 3,
+            tile_width: 320,
+        };
+        assert_eq!(sheet.step(), 300.0);
+        assert_eq!(
+            sheet.filter(false),
+            "fps=1/300.000,scale=320:-2,tile=4x3:padding=4:margin=4"
+        );
+        let labelled = sheet.filter(true);
+        assert!(labelled.contains(",drawtext=text='%{pts\\:hms}':"));
+        assert!(labelled.ends_with(",tile=4x3:padding=4:margin=4"));
+    }
+}
@@ -0,0 +1,133 @@
+// Album art for the music view. MP3, FLAC and M4A files usually carry theirs as an
+// attached picture, which mpv shows as a still video track; that's captured with a
+// screenshot, as mpv has no property with the picture's bytes. Files without one fall
+// back to a cover image in their folder (see media::cover_art). The page gets the image
+// as a data: URL, since the asset protocol isn't enabled.
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+use crate::media;
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CoverArt {
+    pub mime_type: &'static str,
+    pub data_url: String,
+    // Attached to the file rather than found next to it
+    pub embedded: bool,
+}
+
+// Where the current file's art is, before it's read
+pub enum Source {
+    // A screenshot of the attached picture, to delete once read
+    Capture(PathBuf),
+    Folder(PathBuf),
+}
+
+// Whether the file has an attached picture that's showing, so a screenshot captures it
+fn showing_attached_picture(handle: &MpvHandle) -> bool {
+    let count = handle.get::<i64>("track-list/count").unwrap_or(0);
+    let attached = (0..count.max(0)).any(|i| {
+        let flag = |name: &str| {
+            handle
+                .get::<bool>(&format!("track-list/{}/{}", i, name))
+                .unwrap_or(false)
+        };
+        flag("albumart") && flag("selected")
+    });
+    attached && handle.get::<i64>("video-params/w").is_ok()
+}
+
+// Runs on the event thread; reading and encoding the image is left to `read`
+pub fn locate(handle: &MpvHandle, path: &str) -> Option<Source> {
+    if showing_attached_picture(handle) {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        let capture = std::env::temp_dir().join(format!("mpv-cover-{}.png", nanos));
+        let capture_str = capture.to_string_lossy().into_owned();
+        match handle.command(&["screenshot-to-file", &capture_str, "video"]) {
+            Ok(()) => return Some(Source::Capture(capture)),
+            Err(e) => {
+                let _ = std::fs::remove_file(&capture);
+                eprintln!("Failed to capture the attached picture: {}", e);
+            }
+        }
+    }
+    media::cover_art(Path::new(path)).map(|art| Source::Folder(PathBuf::from(art)))
+}
+
+pub fn read(source: Source) -> Result<CoverArt, String> {
+    let (file, embedded) = match &source {
+        Source::Capture(file) => (file, true),
+        Source::Folder(file) => (file, false),
+    };
+    let bytes = std::fs::read(file);
+    if embedded {
+        let _ = std::fs::remove_file(file);
+    }
+    let bytes = bytes.map_err(|e| format!("Failed to read cover art: {}", e))?;
+    let mime_type = mime_type(file);
+    Ok(CoverArt {
+        mime_type,
+        data_url: data_url(mime_type, &bytes),
+        embedded,
+    })
+}
+
+fn mime_type(file: &Path) -> &'static str {
+    let extension = file
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        Some("bmp") => "image/bmp",
+        _ => "image/jpeg",
+    }
+}
+
+// How the page is handed images it can't load from the filesystem
+pub fn data_url(mime_type: &str, bytes: &[u8]) -> String {
+    format!("data:{};base64,{}", mime_type, base64(bytes))
+}
+
+// Standard alphabet, with padding
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = u32::from(b[0]) << 16 | u32::from(b[1]) << 8 | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_pads_the_last_group() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xff, 0xfe, 0x00]), "//4A");
+    }
+}
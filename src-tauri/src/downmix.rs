@@ -0,0 +1,98 @@
+// Output channel layout, and the downmix for when a file has more channels than that.
+// mpv folds surround down with swresample, which by default takes the centre channel
+// at -3 dB and drops the LFE, so 5.1 dialogue is lost under the effects on laptop
+// speakers; raising the centre level brings it forward.
+
+use serde::Serialize;
+
+use crate::config::AudioChannels;
+use crate::error::{Context, PlayerError};
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// What audio-channels can be set to from the app. "auto-safe" asks the device what it
+// supports and falls back to stereo; "auto" passes through whatever the file has.
+pub const LAYOUTS: &[&str] = &[
+    "auto-safe",
+    "auto",
+    "mono",
+    "stereo",
+    "2.1",
+    "4.0",
+    "5.1",
+    "7.1",
+];
+
+// Centre and LFE levels, as swresample multipliers; 2.0 is +6 dB
+pub const MAX_LEVEL: f64 = 2.0;
+
+// For the audio settings panel: the settings, and what they come to for this file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ChannelsState {
+    #[serde(flatten)]
+    pub settings: AudioChannels,
+    // e.g. "5.1" for the file's audio and "stereo" for what reaches the device
+    pub source_layout: Option<String>,
+    pub output_layout: Option<String>,
+}
+
+impl ChannelsState {
+    pub fn read(handle: &MpvHandle, settings: &AudioChannels) -> Self {
+        Self {
+            settings: settings.clone(),
+            source_layout: handle.get::<String>("audio-params/channels").ok(),
+            output_layout: handle.get::<String>("audio-out-params/channels").ok(),
+        }
+    }
+}
+
+fn swresample_options(settings: &AudioChannels) -> String {
+    format!(
+        "clev={:.3},lfe_mix_level={:.3}",
+        settings.center_level, settings.lfe_level
+    )
+}
+
+pub fn apply(handle: &MpvHandle, settings: &AudioChannels) -> Result<(), PlayerError> {
+    handle
+        .set("audio-channels", &settings.layout)
+        .context("Failed to set the channel layout")?;
+    handle
+        .set("audio-normalize-downmix", settings.normalize)
+        .context("Failed to set downmix normalization")?;
+    handle
+        .set("audio-swresample-o", &swresample_options(settings))
+        .context("Failed to set the downmix levels")
+}
+
+// The resampler only reads its levels when the audio output starts; reopen it so new
+// levels are heard straight away, rather than from the next file
+pub fn restart_output(handle: &MpvHandle) {
+    if handle.get::<String>("path").is_ok() {
+        if let Err(e) = handle.command(&["ao-reload"]) {
+            eprintln!("Failed to restart audio output: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_go_to_swresample() {
+        let defaults = AudioChannels::default();
+        assert_eq!(
+            swresample_options(&defaults),
+            "clev=0.707,lfe_mix_level=0.000"
+        );
+        let louder_dialogue = AudioChannels {
+            center_level: 1.4,
+            lfe_level: 0.5,
+            ..defaults
+        };
+        assert_eq!(
+            swresample_options(&louder_dialogue),
+            "clev=1.400,lfe_mix_level=0.500"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+// A ten-band graphic equalizer, as lavfi's firequalizer in mpv's audio filter chain. It
+// goes in under its own label so the user's other filters stay as they are. Gains change
+// live through af-command, which firequalizer takes without restarting the audio; only
+// switching it on or off re-adds the filter.
+
+use serde::Serialize;
+
+use crate::config::{EqualizerPreset, EqualizerSettings};
+use crate::error::{Context, PlayerError};
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// Centre frequencies in Hz, an octave apart
+pub const BANDS: [u32; 10] = [31, 62, 125, 250, 500, 1000, 2000, 4000, 8000, 16000];
+
+// dB either way; more than this mostly clips
+pub const MAX_GAIN: f64 = 12.0;
+
+const LABEL: &str = "equalizer";
+
+// Everything the equalizer panel shows
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EqualizerState {
+    pub enabled: bool,
+    pub preset: EqualizerPreset,
+    pub bands: [u32; 10],
+    // dB, one per band
+    pub gains: Vec<f64>,
+    // Whether the user has changed the preset's curve, so a reset would do something
+    pub adjusted: bool,
+}
+
+impl EqualizerState {
+    pub fn new(settings: &EqualizerSettings) -> Self {
+        Self {
+            enabled: settings.enabled,
+            preset: settings.preset,
+            bands: BANDS,
+            gains: gains(settings, settings.preset),
+            adjusted: settings.gains.contains_key(&settings.preset),
+        }
+    }
+}
+
+pub fn preset_gains(preset: EqualizerPreset) -> [f64; 10] {
+    match preset {
+        EqualizerPreset::Flat => [0.0; 10],
+        EqualizerPreset::BassBoost => [6.0, 5.0, 4.0, 2.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+        // Less rumble and hiss, more of the 1-4 kHz where speech is made out
+        EqualizerPreset::Voice => [-6.0, -4.0, -2.0, 0.0, 2.0, 3.0, 4.0, 3.0, 0.0, -2.0],
+    }
+}
+
+// The gains `preset` plays with: the user's, or its built-in curve. A saved curve that
+// doesn't have a gain for each band (a hand-edited config) is ignored.
+pub fn gains(settings: &EqualizerSettings, preset: EqualizerPreset) -> Vec<f64> {
+    settings
+        .gains
+        .get(&preset)
+        .filter(|gains| gains.len() == BANDS.len())
+        .cloned()
+        .unwrap_or_else(|| preset_gains(preset).to_vec())
+}
+
+// firequalizer's gain_entry: the curve passes through each band's gain
+fn gain_entries(gains: &[f64]) -> String {
+    BANDS
+        .iter()
+        .zip(gains)
+        .map(|(band, gain)| format!("entry({},{:.1})", band, gain))
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+fn filter(gains: &[f64]) -> String {
+    format!(
+        "@{}:lavfi=[firequalizer=gain_entry='{}']",
+        LABEL,
+        gain_entries(gains)
+    )
+}
+
+// Put the filter in (re-adding a label replaces the filter), or take it out when the
+// equalizer is off
+pub fn apply(handle: &MpvHandle, settings: &EqualizerSettings) -> Result<(), PlayerError> {
+    if !settings.enabled {
+        // Fails if it was never added, which is as good as removed
+        let _ = handle.command(&["af", "remove", &format!("@{}", LABEL)]);
+        return Ok(());
+    }
+    handle
+        .command(&["af", "add", &filter(&gains(settings, settings.preset))])
+        .context("Failed to set up the equalizer")
+}
+
+// New gains for the filter that's in place, without a gap in the audio
+pub fn update(handle: &MpvHandle, settings: &EqualizerSettings) -> Result<(), PlayerError> {
+    if !settings.enabled {
+        return Ok(());
+    }
+    let entries = gain_entries(&gains(settings, settings.preset));
+    match handle.command(&["af-command", LABEL, "gain_entry", &entries]) {
+        Ok(()) => Ok(()),
+        // No audio playing yet, so no filter to send it to
+        Err(_) => apply(handle, settings),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adjusted_presets_keep_their_own_gains() {
+        let mut settings = EqualizerSettings {
+            preset: EqualizerPreset::BassBoost,
+            ..EqualizerSettings::default()
+        };
+        assert_eq!(gains(&settings, EqualizerPreset::BassBoost)[0], 6.0);
+
+        let mut adjusted = preset_gains(EqualizerPreset::BassBoost).to_vec();
+        adjusted[0] = 9.0;
+        settings.gains.insert(EqualizerPreset::BassBoost, adjusted);
+        settings.gains.insert(EqualizerPreset::Voice, vec![1.0]);
+        assert_eq!(gains(&settings, EqualizerPreset::BassBoost)[0], 9.0);
+        // Too few bands to use
+        assert_eq!(gains(&settings, EqualizerPreset::Voice)[0], -6.0);
+        assert!(EqualizerState::new(&settings).adjusted);
+
+        let mut flat = vec![0.0; 10];
+        flat[9] = -1.5;
+        assert_eq!(
+            filter(&flat),
+            "@equalizer:lavfi=[firequalizer=gain_entry='entry(31,0.0);entry(62,0.0);\
+             entry(125,0.0);entry(250,0.0);entry(500,0.0);entry(1000,0.0);entry(2000,0.0);\
+             entry(4000,0.0);entry(8000,0.0);entry(16000,-1.5)']"
+        );
+    }
+}
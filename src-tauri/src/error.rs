@@ -0,0 +1,154 @@
+// What commands return when they fail. The page gets `{ kind, message }`, plus the
+// mpv status for mpv failures, so it can branch on `kind` and still show `message`.
+
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+
+use crate::mpv::{self, MpvError};
+use crate::player::PlayerId;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerError {
+    // init_mpv_player hasn't run for this player, or it was torn down
+    NotInitialized,
+    // setup_video_rendering hasn't run, so there's no video surface to work with
+    RenderingNotSetUp,
+    // The event thread is gone, e.g. because the app is closing
+    ShutDown,
+    NoPlayer(PlayerId),
+    NoFile,
+    // Something else the command needs isn't there, e.g. a subtitle track
+    NotFound(String),
+    InvalidArgument(String),
+    UnsupportedPlatform(String),
+    // An mpv call failed; `action` says what was being attempted
+    Mpv {
+        action: Option<String>,
+        error: MpvError,
+    },
+    // Anything else, e.g. a window or file system operation failing
+    Other(String),
+}
+
+impl PlayerError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::NotInitialized => "not_initialized",
+            Self::RenderingNotSetUp => "rendering_not_set_up",
+            Self::ShutDown => "shut_down",
+            Self::NoPlayer(_) => "no_player",
+            Self::NoFile => "no_file",
+            Self::NotFound(_) => "not_found",
+            Self::InvalidArgument(_) => "invalid_argument",
+            Self::UnsupportedPlatform(_) => "unsupported_platform",
+            Self::Mpv { .. } => "mpv",
+            Self::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for PlayerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotInitialized => write!(f, "MPV not initialized"),
+            Self::RenderingNotSetUp => write!(
+                f,
+                "Video rendering not set up - call setup_video_rendering first"
+            ),
+            Self::ShutDown => write!(f, "MPV has shut down"),
+            Self::NoPlayer(id) => write!(f, "No player with id {}", id),
+            Self::NoFile => write!(f, "No file loaded"),
+            Self::NotFound(message)
+            | Self::InvalidArgument(message)
+            | Self::UnsupportedPlatform(message)
+            | Self::Other(message) => write!(f, "{}", message),
+            Self::Mpv {
+                action: Some(action),
+                error,
+            } => write!(f, "{}: {}", action, error),
+            Self::Mpv {
+                action: None,
+                error,
+            } => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for PlayerError {}
+
+impl Serialize for PlayerError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PlayerError", 4)?;
+        state.serialize_field("kind", self.kind())?;
+        state.serialize_field("message", &self.to_string())?;
+        match self {
+            Self::NoPlayer(id) => state.serialize_field("player_id", id)?,
+            Self::Mpv { error, .. } => {
+                state.serialize_field("code", &error.code())?;
+                state.serialize_field("name", error.name())?;
+            }
+            _ => {}
+        }
+        state.end()
+    }
+}
+
+impl From<MpvError> for PlayerError {
+    fn from(error: MpvError) -> Self {
+        Self::Mpv {
+            action: None,
+            error,
+        }
+    }
+}
+
+// Helpers that still report plain strings, e.g. window and view setup
+impl From<String> for PlayerError {
+    fn from(message: String) -> Self {
+        Self::Other(message)
+    }
+}
+
+// Says what an mpv call was for, e.g. `handle.command(&["stop"]).context("Failed to stop")?`
+pub trait Context<T> {
+    fn context(self, action: impl Into<String>) -> Result<T, PlayerError>;
+}
+
+impl<T> Context<T> for mpv::Result<T> {
+    fn context(self, action: impl Into<String>) -> Result<T, PlayerError> {
+        self.map_err(|error| PlayerError::Mpv {
+            action: Some(action.into()),
+            error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mpv_errors_carry_their_status() {
+        let error: Result<(), _> = Err(MpvError::PropertyUnavailable);
+        let error = error.context("Failed to seek").unwrap_err();
+        let payload = serde_json::to_value(&error).unwrap();
+        assert_eq!(payload["kind"], "mpv");
+        assert_eq!(payload["code"], -10);
+        assert_eq!(payload["name"], "property_unavailable");
+        assert!(payload["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("Failed to seek: "));
+    }
+
+    #[test]
+    fn other_kinds_serialize_their_message() {
+        let payload = serde_json::to_value(PlayerError::NoPlayer(3)).unwrap();
+        assert_eq!(payload["kind"], "no_player");
+        assert_eq!(payload["message"], "No player with id 3");
+        assert_eq!(payload["player_id"], 3);
+        let payload = serde_json::to_value(PlayerError::NotInitialized).unwrap();
+        assert_eq!(payload["kind"], "not_initialized");
+        assert!(payload.get("code").is_none());
+    }
+}
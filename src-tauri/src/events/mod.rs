@@ -0,0 +1,1430 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tauri::window::{ProgressBarState, ProgressBarStatus};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::audio_devices;
+use crate::chapter_thumbnails::ChapterThumbnails;
+use crate::commands::lifecycle::{self, append_files, ResumePoint};
+use crate::commands::navigation::{read_chapters, Chapter};
+use crate::commands::off_thread;
+use crate::commands::playback::read_playback_stats;
+use crate::commands::settings::read_session_settings;
+use crate::config::SessionSettings;
+use crate::error::PlayerError;
+use crate::fade::PauseFade;
+use crate::file_settings::{self, FileSettings};
+use crate::library::Library;
+use crate::media;
+use crate::metadata::{self, Metadata};
+use crate::mpv::{self, EndFileReason, Event, Format, MpvHandle, PlayerBackend, PropertyValue};
+use crate::mpv_log::{LogEntry, LogThrottle, MpvLog};
+use crate::player::channel;
+use crate::player::{self, PlayerId, Players, MAIN_PLAYER};
+use crate::playlist::{self, PlaylistMetadata};
+use crate::power::SleepInhibitor;
+use crate::resume_position::{self, Progress, SavedPosition};
+use crate::saved_queue::{self, SavedQueue};
+use crate::seek_preview::SeekPreviews;
+use crate::subtitle_text::{self, SubtitleText};
+
+#[cfg(target_os = "linux")]
+pub mod mpris;
+#[cfg(target_os = "macos")]
+pub mod now_playing;
+
+// reply_userdata values identifying our property observers
+const OBSERVE_CHAPTER: u64 = 1;
+const OBSERVE_FILE_SETTING: u64 = 2;
+const OBSERVE_PLAYBACK_STATE: u64 = 5;
+const OBSERVE_PROGRESS: u64 = 6;
+const OBSERVE_SESSION: u64 = 7;
+const OBSERVE_SNAPSHOT: u64 = 8;
+const OBSERVE_SUBTITLE_TEXT: u64 = 9;
+const OBSERVE_VOLUME: u64 = 10;
+const OBSERVE_EOF_REACHED: u64 = 11;
+const OBSERVE_QUEUE: u64 = 12;
+const OBSERVE_METADATA: u64 = 13;
+const OBSERVE_AUDIO_DEVICES: u64 = 14;
+
+// Observers the frontend registers get ids from here up, clear of our own
+pub const FRONTEND_OBSERVER_BASE: u64 = 1000;
+
+// The Dock/taskbar indicator only needs to move now and then
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+#[cfg(target_os = "macos")]
+const OBSERVE_NOW_PLAYING: u64 = 3;
+#[cfg(target_os = "linux")]
+const OBSERVE_MPRIS: u64 = 4;
+
+// Properties remembered per file, observed without a value (we re-read them all)
+const FILE_SETTING_PROPERTIES: &[&str] = &["speed", "aid", "sid", "sub-delay", "audio-delay"];
+
+// Carried over to the next session (see config::SessionSettings)
+const SESSION_PROPERTIES: &[&str] = &[
+    "speed",
+    "volume",
+    "mute",
+    "sub-visibility",
+    "audio-pitch-correction",
+];
+
+// Volume drags and pause fades change these many times a second; save at most this often
+const SESSION_SAVE_DELAY: Duration = Duration::from_secs(1);
+
+// A change to these saves the main player's queue (see saved_queue), as does playing
+// for QUEUE_POSITION_INTERVAL, so a crash loses at most that much of the position
+const QUEUE_PROPERTIES: &[&str] = &["playlist-count", "playlist-pos", "pause"];
+const QUEUE_POSITION_INTERVAL: Duration = Duration::from_secs(10);
+
+// How often the position in the playing file is saved (see resume_position); pausing,
+// stopping and reaching the end save it straight away
+const POSITION_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// Together these say whether something is actively playing
+const PLAYBACK_STATE_PROPERTIES: &[&str] = &["pause", "idle-active"];
+
+// Properties shown in the macOS Now Playing widget
+#[cfg(target_os = "macos")]
+const NOW_PLAYING_PROPERTIES: &[&str] = &["media-title", "duration", "pause", "speed"];
+
+// Reported as volume-changed when changed by anything but a pause fade
+const VOLUME_PROPERTIES: &[&str] = &["volume", "mute"];
+
+// Settings the Tauri commands change while the event thread is running
+#[derive(Default)]
+pub struct EventControl {
+    // 0 disables the periodic playback-stats event
+    stats_interval_ms: AtomicU64,
+    pub restore_per_file_settings: AtomicBool,
+    sleep_timer: Mutex<Option<SleepTimer>>,
+    // One-shot: stop instead of advancing when the current file ends
+    stop_after_current: AtomicBool,
+    // The user's keep-open while it's held at "always" (see update_end_hold)
+    held_end_behavior: Mutex<Option<String>>,
+    prevent_sleep: AtomicBool,
+    dock_progress: AtomicBool,
+    frontend_observers: AtomicU64,
+    // Set while teardown quits mpv; a shutdown without it means mpv stopped on its own
+    pub quitting: AtomicBool,
+    // Written by the event thread, read by get_player_logs
+    pub log: MpvLog,
+    // Titles and durations of queued files, recorded as each one loads
+    pub playlist: PlaylistMetadata,
+    // Queue the next file in the folder when the queue runs out
+    pub auto_advance: AtomicBool,
+    pub restore_queue: AtomicBool,
+    pub remember_position: AtomicBool,
+}
+
+impl EventControl {
+    pub fn set_stats_interval(&self, handle: &MpvHandle, interval_ms: u64) {
+        self.stats_interval_ms.store(interval_ms, Ordering::SeqCst);
+        // Wake the event thread so it picks up the new timer immediately
+        handle.wakeup();
+    }
+
+    // Replace (or with None, cancel) the sleep timer; returns whether one was armed before.
+    // On the event thread, like set_stop_after_current.
+    pub fn set_sleep_timer(&self, handle: &MpvHandle, timer: Option<SleepTimer>) -> bool {
+        let previous = std::mem::replace(&mut *self.sleep_timer.lock().unwrap(), timer);
+        if let Err(e) = self.update_end_hold(handle) {
+            eprintln!("Failed to hold keep-open for the sleep timer: {}", e);
+        }
+        handle.wakeup();
+        previous.is_some()
+    }
+
+    pub fn stop_after_current(&self) -> bool {
+        self.stop_after_current.load(Ordering::SeqCst)
+    }
+
+    // On the event thread, so the hold is in place before mpv can reach the end
+    pub fn set_stop_after_current(&self, handle: &MpvHandle, enabled: bool) -> mpv::Result<()> {
+        self.stop_after_current.store(enabled, Ordering::SeqCst);
+        self.update_end_hold(handle)
+    }
+
+    // Whether the current file is to stop at its end instead of giving way to the next
+    fn stop_armed(&self) -> bool {
+        self.stop_after_current()
+            || self
+                .sleep_timer
+                .lock()
+                .unwrap()
+                .as_ref()
+                .is_some_and(|t| t.action == SleepAction::EndOfFile)
+    }
+
+    // Stopping on END_FILE is too late: mpv has already moved on to the next entry.
+    // While a stop is armed keep-open is held at "always", so every file pauses on its
+    // last frame and reports eof-reached, and the event loop stops it there.
+    fn update_end_hold(&self, handle: &MpvHandle) -> mpv::Result<()> {
+        let mut held = self.held_end_behavior.lock().unwrap();
+        if self.stop_armed() {
+            if held.is_none() {
+                let mode = handle.get::<String>("keep-open")?;
+                handle.set("keep-open", "always")?;
+                *held = Some(mode);
+            }
+        } else if let Some(mode) = held.take() {
+            handle.set("keep-open", mode.as_str())?;
+        }
+        Ok(())
+    }
+
+    // For a new mpv instance, which starts with its own keep-open
+    pub fn reset_end_hold(&self, handle: &MpvHandle) -> mpv::Result<()> {
+        self.held_end_behavior.lock().unwrap().take();
+        self.update_end_hold(handle)
+    }
+
+    // keep-open as the user set it; while it's held only the value to restore changes
+    pub fn end_behavior(&self, handle: &MpvHandle) -> mpv::Result<String> {
+        match &*self.held_end_behavior.lock().unwrap() {
+            Some(mode) => Ok(mode.clone()),
+            None => handle.get::<String>("keep-open"),
+        }
+    }
+
+    pub fn set_end_behavior(&self, handle: &MpvHandle, mode: &str) -> mpv::Result<()> {
+        match &mut *self.held_end_behavior.lock().unwrap() {
+            Some(held) => {
+                *held = mode.to_string();
+                Ok(())
+            }
+            None => handle.set("keep-open", mode),
+        }
+    }
+
+    pub fn set_prevent_sleep(&self, handle: &MpvHandle, enabled: bool) {
+        self.prevent_sleep.store(enabled, Ordering::SeqCst);
+        // The event thread owns the inhibitor; wake it to acquire/release
+        handle.wakeup();
+    }
+
+    pub fn set_dock_progress(&self, handle: &MpvHandle, enabled: bool) {
+        self.dock_progress.store(enabled, Ordering::SeqCst);
+        // Lets the event thread clear the indicator right away when disabled
+        handle.wakeup();
+    }
+
+    // A fresh reply_userdata for an observer registered by the frontend
+    pub fn next_frontend_observer(&self) -> u64 {
+        FRONTEND_OBSERVER_BASE + self.frontend_observers.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub fn sleep_timer_status(&self) -> Option<SleepTimerStatus> {
+        self.sleep_timer
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|timer| SleepTimerStatus {
+                action: timer.action,
+                remaining_seconds: timer
+                    .deadline
+                    .map(|d| d.saturating_duration_since(Instant::now()).as_secs()),
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SleepAction {
+    Pause,
+    Stop,
+    QuitApp,
+    // Stop when the current file ends instead of after a fixed time
+    EndOfFile,
+}
+
+// Wall-clock deadline, so pausing or seeking doesn't move it
+#[derive(Debug, Clone)]
+pub struct SleepTimer {
+    pub action: SleepAction,
+    pub deadline: Option<Instant>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SleepTimerStatus {
+    action: SleepAction,
+    // None for the end-of-file mode
+    remaining_seconds: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct SleepTimerFired {
+    action: SleepAction,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FileLoadSucceeded {
+    path: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct FileLoadFailed {
+    path: String,
+    // mpv's error string, e.g. "unrecognized file format"
+    reason: String,
+}
+
+// Sent before the next file in the folder is queued after the queue ran out
+#[derive(Debug, Clone, Serialize)]
+struct AutoAdvance {
+    // The file that ended, and the one that'll play next
+    ended: String,
+    next: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LogMessage {
+    #[serde(flatten)]
+    entry: LogEntry,
+    // Lines held back by the throttle since the last event; get_player_logs has them
+    dropped: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ChapterChanged {
+    index: i64,
+    title: Option<String>,
+    start: f64,
+}
+
+// A property the frontend asked to observe changed
+#[derive(Debug, Clone, Serialize)]
+struct PropertyChanged {
+    id: u64,
+    name: String,
+    value: PropertyValue,
+}
+
+// Payloads of the mpv://* events, which mirror mpv's own event stream one to one
+#[derive(Debug, Clone, Serialize)]
+struct MpvStartFile {
+    playlist_entry_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MpvFileLoaded {
+    path: Option<String>,
+    duration: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MpvEndFile {
+    // eof, stop (e.g. another file was loaded), quit, error or redirect
+    reason: &'static str,
+    // mpv's error string when the reason is error
+    error: Option<String>,
+    playlist_entry_id: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MpvEof {
+    playlist_entry_id: i64,
+}
+
+// With keep-open the file never ends; mpv pauses on the last frame instead
+#[derive(Debug, Clone, Serialize)]
+struct MpvEofReached {
+    position: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct MpvPlaybackRestart {
+    // Where playback resumed, e.g. the target of the seek that caused it
+    position: Option<f64>,
+}
+
+// State owned by the event thread
+struct EventLoop<R: Runtime> {
+    app: AppHandle<R>,
+    player_id: PlayerId,
+    handle: Rc<MpvHandle>,
+    control: Arc<EventControl>,
+    // Playback commands from the Tauri side, served between mpv events
+    inbox: channel::Inbox,
+    server: channel::Server,
+    chapters: Vec<Chapter>,
+    last_chapter: Option<i64>,
+    // Playlist entry id and path of the file mpv started but hasn't finished opening
+    loading: Option<(i64, String)>,
+    // Per-file settings as last loaded/saved for the current file
+    file_settings: Option<FileSettings>,
+    // Interval the current deadline was scheduled with, and that deadline
+    stats_interval_ms: u64,
+    next_stats: Option<Instant>,
+    sleep_inhibitor: SleepInhibitor,
+    // When the Dock/taskbar progress was last set, if it's currently shown
+    progress_updated: Option<Instant>,
+    // Latest session settings not yet written to the config, and when to write them
+    pending_session: Option<(Instant, SessionSettings)>,
+    // When to save the queue next, and what was saved last
+    queue_due: Option<Instant>,
+    saved_queue: Option<SavedQueue>,
+    // Where the current file is, as last seen in the snapshot, and when to save it next
+    position: Option<SavedPosition>,
+    position_due: Option<Instant>,
+    // The library's history entry for the current file, and the file's path
+    playback: Option<(i64, String)>,
+    // Last subtitle-text-changed payload, so a change to only one of the observed
+    // properties that leaves the text as it was isn't re-sent
+    subtitle_text: SubtitleText,
+    // Last volume-changed payload
+    volume: Option<channel::Volume>,
+    // Last metadata-update payload
+    metadata: Option<Metadata>,
+    // The output picked with set_audio_device, kept while it's unplugged so audio can
+    // go back to it
+    preferred_audio_device: Option<String>,
+    log_throttle: LogThrottle,
+    // What to reload if mpv stops on its own; cleared once there's nothing left to play
+    resume: Option<ResumePoint>,
+    #[cfg(target_os = "linux")]
+    mpris: Option<mpris::MprisServer<R>>,
+}
+
+// Start the thread that creates mpv with `create`, then pumps its events and serves
+// `inbox` until it shuts down. mpv lives and dies on that thread; returns once it has
+// been created, or with why it couldn't be.
+pub fn spawn_event_loop<R: Runtime>(
+    app: AppHandle<R>,
+    player_id: PlayerId,
+    create: impl FnOnce() -> Result<MpvHandle, PlayerError> + Send + 'static,
+    control: Arc<EventControl>,
+    inbox: channel::Inbox,
+    pause_fade: Arc<PauseFade>,
+    snapshot: Arc<RwLock<channel::PlayerSnapshot>>,
+) -> Result<std::thread::JoinHandle<()>, PlayerError> {
+    let (started, result) = mpsc::channel();
+    let thread = std::thread::spawn(move || {
+        let mut handle = match create() {
+            Ok(handle) => handle,
+            Err(e) => {
+                let _ = started.send(Err(e));
+                return;
+            }
+        };
+        let wakeup = inbox.wakeup.clone();
+        handle.set_wakeup_callback(move || wakeup.ring());
+        subscribe(&handle, player_id);
+        let handle = Rc::new(handle);
+        let server = channel::Server::new(handle.clone(), pause_fade, snapshot);
+        let _ = started.send(Ok(()));
+
+        let mut event_loop = EventLoop {
+            app: app.clone(),
+            player_id,
+            handle,
+            control,
+            inbox,
+            server,
+            chapters: Vec::new(),
+            last_chapter: None,
+            loading: None,
+            file_settings: None,
+            stats_interval_ms: 0,
+            next_stats: None,
+            sleep_inhibitor: SleepInhibitor::default(),
+            progress_updated: None,
+            pending_session: None,
+            queue_due: None,
+            saved_queue: None,
+            position: None,
+            position_due: None,
+            playback: None,
+            subtitle_text: SubtitleText::default(),
+            volume: None,
+            metadata: None,
+            preferred_audio_device: None,
+            log_throttle: LogThrottle::default(),
+            resume: None,
+            #[cfg(target_os = "linux")]
+            mpris: if player_id == MAIN_PLAYER {
+                mpris::MprisServer::start(&app)
+            } else {
+                None
+            },
+        };
+        event_loop.run();
+
+        #[cfg(target_os = "linux")]
+        if let Some(mpris) = event_loop.mpris.take() {
+            mpris.stop();
+        }
+    });
+
+    match result.recv() {
+        Ok(Ok(())) => Ok(thread),
+        Ok(Err(e)) => {
+            let _ = thread.join();
+            Err(e)
+        }
+        Err(_) => Err(PlayerError::Other("The event thread exited".to_string())),
+    }
+}
+
+// Ask `handle` for the log messages and property changes the event loop handles
+fn subscribe(handle: &MpvHandle, player_id: PlayerId) {
+    if let Err(e) = handle.request_log_messages(crate::mpv_log::BUFFER_LEVEL) {
+        eprintln!("Failed to request mpv log messages: {}", e);
+    }
+
+    observe(handle, OBSERVE_CHAPTER, "chapter", Format::Int64);
+    observe(handle, OBSERVE_EOF_REACHED, "eof-reached", Format::Flag);
+    for name in FILE_SETTING_PROPERTIES {
+        observe(handle, OBSERVE_FILE_SETTING, name, Format::None);
+    }
+    observe(handle, OBSERVE_PROGRESS, "percent-pos", Format::None);
+    for name in SESSION_PROPERTIES {
+        observe(handle, OBSERVE_SESSION, name, Format::None);
+    }
+    for name in PLAYBACK_STATE_PROPERTIES {
+        observe(handle, OBSERVE_PLAYBACK_STATE, name, Format::None);
+    }
+    for name in channel::SNAPSHOT_PROPERTIES {
+        observe(handle, OBSERVE_SNAPSHOT, name, Format::None);
+    }
+    for name in VOLUME_PROPERTIES {
+        observe(handle, OBSERVE_VOLUME, name, Format::None);
+    }
+    for name in subtitle_text::OBSERVED_PROPERTIES {
+        observe(handle, OBSERVE_SUBTITLE_TEXT, name, Format::None);
+    }
+    observe(
+        handle,
+        OBSERVE_METADATA,
+        metadata::OBSERVED_PROPERTY,
+        Format::None,
+    );
+    observe(
+        handle,
+        OBSERVE_AUDIO_DEVICES,
+        audio_devices::LIST_PROPERTY,
+        Format::None,
+    );
+    observe(
+        handle,
+        OBSERVE_AUDIO_DEVICES,
+        audio_devices::DEVICE_PROPERTY,
+        Format::None,
+    );
+    // The system's media controls follow the main player only
+    #[cfg(target_os = "macos")]
+    if player_id == MAIN_PLAYER {
+        for name in NOW_PLAYING_PROPERTIES {
+            observe(handle, OBSERVE_NOW_PLAYING, name, Format::None);
+        }
+    }
+    if player_id == MAIN_PLAYER {
+        for name in QUEUE_PROPERTIES {
+            observe(handle, OBSERVE_QUEUE, name, Format::None);
+        }
+    }
+    #[cfg(target_os = "linux")]
+    if player_id == MAIN_PLAYER {
+        for name in mpris::OBSERVED_PROPERTIES {
+            observe(handle, OBSERVE_MPRIS, name, Format::None);
+        }
+    }
+}
+
+// Scanning the folder can be slow, e.g. on a network share, so it's done off the event
+// thread, which is handed the result like any command
+fn queue_next_in_directory<R: Runtime>(app: AppHandle<R>, player_id: PlayerId, ended: String) {
+    tauri::async_runtime::spawn(async move {
+        let path = ended.clone();
+        let next = off_thread(move || Ok(media::next_in_directory(Path::new(&path)))).await;
+        let Ok(Some(next)) = next else {
+            return;
+        };
+        let Ok(instance) = app.state::<Players>().get(Some(player_id)) else {
+            return;
+        };
+        let event = AutoAdvance {
+            ended: ended.clone(),
+            next: next.path.clone(),
+        };
+        let queued = instance
+            .channel
+            .run(move |handle| {
+                // Unless something else was queued in the meantime
+                let count = playlist::count(handle);
+                let last = count.checked_sub(1).and_then(|i| {
+                    handle
+                        .get::<String>(&format!("playlist/{}/filename", i))
+                        .ok()
+                });
+                if last.as_deref() != Some(ended.as_str()) {
+                    return Ok(false);
+                }
+                match append_files(handle, &[next]).pop() {
+                    Some(failure) => Err(PlayerError::Other(failure)),
+                    None => Ok(true),
+                }
+            })
+            .await;
+        match queued {
+            Ok(true) => {
+                if let Err(e) = app.emit(&player::event_name(player_id, "auto-advance"), event) {
+                    eprintln!("Failed to emit auto-advance: {}", e);
+                }
+            }
+            Ok(false) => {}
+            Err(e) => eprintln!("Failed to queue the next file: {}", e),
+        }
+    });
+}
+
+fn observe(handle: &MpvHandle, id: u64, name: &str, format: Format) {
+    if let Err(e) = handle.observe(id, name, format) {
+        eprintln!("Failed to observe {}: {}", name, e);
+    }
+}
+
+impl<R: Runtime> EventLoop<R> {
+    fn run(&mut self) {
+        loop {
+            // Senders and mpv both ring the inbox's wakeup, which also covers anything
+            // arriving between this drain and the wait below
+            while let Ok(command) = self.inbox.commands.try_recv() {
+                self.server.serve(command);
+            }
+            let timeout = self.run_timers();
+            let mut event = self.handle.wait_event(0.0);
+            if let Event::None = event {
+                self.inbox.wakeup.wait(timeout);
+                event = self.handle.wait_event(0.0);
+            }
+            self.forward(&event);
+            match event {
+                Event::Shutdown => {
+                    self.save_session();
+                    if !self.control.quitting.load(Ordering::SeqCst) {
+                        lifecycle::recover(self.app.clone(), self.player_id, self.resume.take());
+                    }
+                    break;
+                }
+                // Timeout or mpv_wakeup; a command may have changed a setting
+                Event::None => {
+                    self.update_sleep_inhibitor();
+                    if !self.control.dock_progress.load(Ordering::SeqCst) {
+                        self.clear_progress();
+                    }
+                }
+                Event::StartFile { playlist_entry_id } => {
+                    let path = self.handle.get::<String>("path").ok();
+                    self.loading = path.map(|p| (playlist_entry_id, p));
+                    self.chapters.clear();
+                    self.last_chapter = None;
+                    self.file_settings = None;
+                }
+                Event::EndFile {
+                    reason,
+                    playlist_entry_id,
+                } => {
+                    self.clear_progress();
+                    self.on_end_file(reason, playlist_entry_id);
+                }
+                Event::FileLoaded => {
+                    if let Some((_, path)) = self.loading.take() {
+                        self.emit("file-load-succeeded", FileLoadSucceeded { path });
+                    }
+                    self.chapters = read_chapters(&self.handle);
+                    self.control.playlist.record(&self.handle);
+                    self.restore_file_settings();
+                    self.offer_resume();
+                    self.start_history();
+                    self.prepare_frames();
+                }
+                // A seek invalidates the position the system is extrapolating from
+                #[cfg(target_os = "macos")]
+                Event::PlaybackRestart => self.update_now_playing(),
+                #[cfg(target_os = "linux")]
+                Event::PlaybackRestart => self.notify_mpris_seek(),
+                Event::Idle => {
+                    self.resume = None;
+                    #[cfg(target_os = "macos")]
+                    now_playing::clear(&self.app);
+                }
+                Event::LogMessage {
+                    prefix,
+                    level,
+                    text,
+                } => {
+                    let entry = LogEntry::new(prefix, level, &text);
+                    if let Some(dropped) = self.log_throttle.admit(&entry, Instant::now()) {
+                        let payload = LogMessage {
+                            entry: entry.clone(),
+                            dropped,
+                        };
+                        self.emit("mpv-log-message", payload);
+                    }
+                    self.control.log.push(entry);
+                }
+                Event::PropertyChange { id, name, value } => {
+                    self.on_property_change(id, &name, value);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Re-emit mpv's lifecycle events as mpv://<name> for the frontend to listen to
+    fn forward(&self, event: &Event) {
+        match event {
+            Event::StartFile { playlist_entry_id } => self.emit(
+                "mpv://start-file",
+                MpvStartFile {
+                    playlist_entry_id: *playlist_entry_id,
+                },
+            ),
+            Event::FileLoaded => self.emit(
+                "mpv://file-loaded",
+                MpvFileLoaded {
+                    path: self.handle.get::<String>("path").ok(),
+                    duration: self.handle.get::<f64>("duration").ok(),
+                },
+            ),
+            Event::EndFile {
+                reason,
+                playlist_entry_id,
+            } => {
+                let (name, error) = match reason {
+                    EndFileReason::Eof => ("eof", None),
+                    EndFileReason::Stop => ("stop", None),
+                    EndFileReason::Quit => ("quit", None),
+                    EndFileReason::Error(e) => ("error", Some(e.to_string())),
+                    EndFileReason::Redirect => ("redirect", None),
+                    EndFileReason::Other(_) => ("unknown", None),
+                };
+                self.emit(
+                    "mpv://end-file",
+                    MpvEndFile {
+                        reason: name,
+                        error,
+                        playlist_entry_id: *playlist_entry_id,
+                    },
+                );
+                if *reason == EndFileReason::Eof {
+                    self.emit(
+                        "mpv://eof",
+                        MpvEof {
+                            playlist_entry_id: *playlist_entry_id,
+                        },
+                    );
+                }
+            }
+            Event::Seek => self.emit("mpv://seek", ()),
+            Event::PlaybackRestart => self.emit(
+                "mpv://playback-restart",
+                MpvPlaybackRestart {
+                    position: self.handle.get::<f64>("time-pos").ok(),
+                },
+            ),
+            // Nothing left to play; the UI can show its finished screen
+            Event::Idle => self.emit("mpv://idle", ()),
+            Event::Shutdown => self.emit("mpv://shutdown", ()),
+            _ => {}
+        }
+    }
+
+    // Fire any due timers and return how long the loop may sleep (None = until woken)
+    fn run_timers(&mut self) -> Option<Duration> {
+        let deadlines = [
+            self.server.step_pause_fade(),
+            self.run_stats_timer(),
+            self.run_sleep_timer(),
+            self.run_session_timer(),
+            self.run_queue_timer(),
+            self.run_position_timer(),
+        ];
+        let next = deadlines.into_iter().flatten().min()?;
+        Some(next.saturating_duration_since(Instant::now()))
+    }
+
+    fn run_stats_timer(&mut self) -> Option<Instant> {
+        let interval = self.control.stats_interval_ms.load(Ordering::SeqCst);
+        if interval != self.stats_interval_ms {
+            // (Re)subscribing sends the first sample right away
+            self.stats_interval_ms = interval;
+            self.next_stats = None;
+        }
+        if interval == 0 {
+            return None;
+        }
+
+        let now = Instant::now();
+        let due = *self.next_stats.get_or_insert(now);
+        if now >= due {
+            let stats = read_playback_stats(&self.handle);
+            if let Err(e) = self
+                .app
+                .emit(&player::event_name(self.player_id, "playback-stats"), stats)
+            {
+                eprintln!("Failed to emit playback-stats: {}", e);
+            }
+            self.next_stats = Some(now + Duration::from_millis(interval));
+        }
+        self.next_stats
+    }
+
+    fn run_session_timer(&mut self) -> Option<Instant> {
+        let (due, _) = self.pending_session.as_ref()?;
+        if Instant::now() < *due {
+            return Some(*due);
+        }
+        self.save_session();
+        None
+    }
+
+    fn save_session(&mut self) {
+        let Some((_, session)) = self.pending_session.take() else {
+            return;
+        };
+        // The player may have been destroyed already
+        let Ok(instance) = self.app.state::<Players>().get(Some(self.player_id)) else {
+            return;
+        };
+        let mut player = instance.player.lock().unwrap();
+        if player.config.values.session != session {
+            player.config.values.session = session;
+            player.config.save_or_warn();
+        }
+    }
+
+    fn run_queue_timer(&mut self) -> Option<Instant> {
+        let due = self.queue_due?;
+        let now = Instant::now();
+        if now < due {
+            return Some(due);
+        }
+        self.save_queue();
+        let playing = self.handle.get::<bool>("idle-active") == Ok(false)
+            && self.handle.get::<bool>("pause") == Ok(false);
+        self.queue_due = playing.then(|| now + QUEUE_POSITION_INTERVAL);
+        self.queue_due
+    }
+
+    fn save_queue(&mut self) {
+        // Quitting empties the playlist, which isn't what should be restored
+        if !self.control.restore_queue.load(Ordering::SeqCst)
+            || self.control.quitting.load(Ordering::SeqCst)
+        {
+            return;
+        }
+        let queue = SavedQueue::read(&self.handle);
+        if self.saved_queue.as_ref() == Some(&queue) {
+            return;
+        }
+        match saved_queue::save(&self.app, &queue) {
+            Ok(()) => self.saved_queue = Some(queue),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    fn run_position_timer(&mut self) -> Option<Instant> {
+        let due = self.position_due?;
+        if Instant::now() < due {
+            return Some(due);
+        }
+        // The next time-pos change arms it again, so it's idle while paused
+        self.position_due = None;
+        self.save_position(false);
+        None
+    }
+
+    fn track_position(&mut self) {
+        let snapshot = self.server.snapshot();
+        // Streams without a duration have nothing to resume
+        let (Some(path), Some(position), Some(duration)) =
+            (snapshot.path, snapshot.position, snapshot.duration)
+        else {
+            return;
+        };
+        self.position = Some(SavedPosition::new(path, position, duration));
+        if !snapshot.paused && self.position_due.is_none() {
+            self.position_due = Some(Instant::now() + POSITION_SAVE_INTERVAL);
+        }
+    }
+
+    // For resuming the file and for its history entry. `finished` once the file played to
+    // its end, wherever the last update put it.
+    fn save_position(&mut self, finished: bool) {
+        let Some(mut current) = self.position.clone() else {
+            return;
+        };
+        if finished {
+            current.position = current.duration;
+        }
+        self.record_history(&current, false);
+        if !self.control.remember_position.load(Ordering::SeqCst) {
+            return;
+        }
+        let result = match current.progress() {
+            Progress::Started => Ok(()),
+            Progress::Resumable => resume_position::save(&self.app, &current),
+            Progress::Finished => resume_position::clear(&self.app, &current.path).map(|_| ()),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+        }
+    }
+
+    fn start_history(&mut self) {
+        self.playback = None;
+        let (Ok(path), Some(library)) = (
+            self.handle.get::<String>("path"),
+            self.app.try_state::<Library>(),
+        ) else {
+            return;
+        };
+        let title = self.handle.get::<String>("media-title").ok();
+        let duration = self.handle.get::<f64>("duration").ok();
+        match library.start_playback(&path, title, duration) {
+            Ok(id) => self.playback = Some((id, path)),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    // Seekbar previews and chapter thumbnails, for videos only; cover art looks the same
+    // all the way through. Anything else stops the work on the previous file.
+    fn prepare_frames(&self) {
+        let has_video = self.handle.get::<i64>("current-tracks/video/id").is_ok()
+            && self.handle.get::<bool>("current-tracks/video/albumart") != Ok(true);
+        let path = self.handle.get::<String>("path").ok().filter(|_| has_video);
+        if let Some(previews) = self.app.try_state::<SeekPreviews>() {
+            match (&path, self.handle.get::<f64>("duration")) {
+                (Some(path), Ok(duration)) => previews.prepare(&self.app, path.clone(), duration),
+                _ => previews.cancel(),
+            }
+        }
+        if let Some(thumbnails) = self.app.try_state::<ChapterThumbnails>() {
+            match path {
+                Some(path) => thumbnails.prepare(&self.app, path, &self.chapters),
+                None => thumbnails.cancel(),
+            }
+        }
+    }
+
+    fn record_history(&self, current: &SavedPosition, ended: bool) {
+        let (Some((id, path)), Some(library)) = (&self.playback, self.app.try_state::<Library>())
+        else {
+            return;
+        };
+        if *path != current.path {
+            return;
+        }
+        if let Err(e) =
+            library.update_playback(*id, current.position, Some(current.duration), ended)
+        {
+            eprintln!("Failed to record playback: {}", e);
+        }
+    }
+
+    // A load that gave no start (as LoadOptions::resume does) is told where the file was
+    // left, so the page can offer to go back there
+    fn offer_resume(&self) {
+        if !self.control.remember_position.load(Ordering::SeqCst)
+            || self
+                .handle
+                .get::<String>("start")
+                .is_ok_and(|start| start != "none")
+        {
+            return;
+        }
+        let Ok(path) = self.handle.get::<String>("path") else {
+            return;
+        };
+        if let Some(saved) = resume_position::load(&self.app, &path) {
+            if saved.progress() == Progress::Resumable {
+                self.emit("resume-available", saved);
+            }
+        }
+    }
+
+    fn run_sleep_timer(&mut self) -> Option<Instant> {
+        let mut timer = self.control.sleep_timer.lock().unwrap();
+        let deadline = timer.as_ref()?.deadline?;
+        if Instant::now() < deadline {
+            return Some(deadline);
+        }
+
+        let action = timer.take()?.action;
+        drop(timer);
+        self.fire_sleep_timer(action);
+        None
+    }
+
+    fn fire_sleep_timer(&mut self, action: SleepAction) {
+        let result = match action {
+            SleepAction::Pause => self.handle.set("pause", true),
+            SleepAction::Stop | SleepAction::EndOfFile => self.stop_keeping_queue(),
+            SleepAction::QuitApp => Ok(()),
+        };
+        if let Err(e) = result {
+            eprintln!("Sleep timer failed to {:?}: {}", action, e);
+        }
+
+        if let Err(e) = self.app.emit(
+            &player::event_name(self.player_id, "sleep-timer-fired"),
+            SleepTimerFired { action },
+        ) {
+            eprintln!("Failed to emit sleep-timer-fired: {}", e);
+        }
+        if action == SleepAction::QuitApp {
+            self.app.exit(0);
+        }
+    }
+
+    fn on_end_file(&mut self, reason: EndFileReason, playlist_entry_id: i64) {
+        self.save_position(reason == EndFileReason::Eof);
+        if let Some(mut current) = self.position.take() {
+            if reason == EndFileReason::Eof {
+                current.position = current.duration;
+            }
+            self.record_history(&current, true);
+        }
+        self.playback = None;
+        self.position_due = None;
+
+        // An error before FILE_LOADED means the file never opened (corrupt, unsupported
+        // codec, DRM, network failure...)
+        if let EndFileReason::Error(error) = &reason {
+            if let Some((entry_id, path)) = self.loading.take() {
+                if entry_id == playlist_entry_id {
+                    let reason = error.to_string();
+                    self.emit("file-load-failed", FileLoadFailed { path, reason });
+                }
+            }
+        }
+
+        // A stop armed for the end of the file happens at eof-reached instead
+        if reason == EndFileReason::Eof && !self.control.stop_armed() {
+            self.maybe_auto_advance(playlist_entry_id);
+        }
+    }
+
+    // The file is being held open at its end (see EventControl::update_end_hold), so
+    // the next entry hasn't started and the queue can be left as it is
+    fn stop_at_end(&mut self) {
+        let stop_after_current = self
+            .control
+            .stop_after_current
+            .swap(false, Ordering::SeqCst);
+        let sleep_timer = {
+            let mut timer = self.control.sleep_timer.lock().unwrap();
+            let at_end = timer
+                .as_ref()
+                .is_some_and(|t| t.action == SleepAction::EndOfFile);
+            at_end && timer.take().is_some()
+        };
+        if sleep_timer {
+            self.fire_sleep_timer(SleepAction::EndOfFile);
+        } else if stop_after_current {
+            if let Err(e) = self.stop_keeping_queue() {
+                eprintln!("Failed to stop after current file: {}", e);
+            }
+        } else {
+            return;
+        }
+
+        if stop_after_current {
+            if let Err(e) = self.app.emit(
+                &player::event_name(self.player_id, "stop-after-current-fired"),
+                (),
+            ) {
+                eprintln!("Failed to emit stop-after-current-fired: {}", e);
+            }
+        }
+        if let Err(e) = self.control.update_end_hold(&self.handle) {
+            eprintln!("Failed to restore keep-open: {}", e);
+        }
+    }
+
+    // A plain stop would clear the queue, and the saved queue along with it
+    fn stop_keeping_queue(&self) -> mpv::Result<()> {
+        self.handle.command(&["stop", "keep-playlist"])?;
+        // A file held open at its end was paused; whatever plays next shouldn't start paused
+        self.handle.set("pause", false)
+    }
+
+    // Only when the file that ended was the last in the queue and mpv isn't about to
+    // loop back to the first; streams have no folder to look in
+    fn maybe_auto_advance(&self, playlist_entry_id: i64) {
+        if !self.control.auto_advance.load(Ordering::SeqCst)
+            || self
+                .handle
+                .get::<String>("loop-playlist")
+                .is_ok_and(|v| v != "no")
+        {
+            return;
+        }
+        let Some((index, path)) = playlist::find_entry(&self.handle, playlist_entry_id) else {
+            return;
+        };
+        if index + 1 == playlist::count(&self.handle) && !path.contains("://") {
+            queue_next_in_directory(self.app.clone(), self.player_id, path);
+        }
+    }
+
+    fn update_sleep_inhibitor(&mut self) {
+        let playing = self.handle.get::<bool>("idle-active") == Ok(false)
+            && self.handle.get::<bool>("pause") == Ok(false);
+        let enabled = self.control.prevent_sleep.load(Ordering::SeqCst);
+        self.sleep_inhibitor.set(enabled && playing);
+    }
+
+    // Mirror percent-pos on the Dock icon / taskbar button. Tauri maps this to each
+    // platform's API and ignores it where there is none.
+    fn update_progress(&mut self, force: bool) {
+        if !self.control.dock_progress.load(Ordering::SeqCst) {
+            return;
+        }
+        if !force
+            && self
+                .progress_updated
+                .is_some_and(|t| t.elapsed() < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+
+        let percent = self.handle.get::<f64>("percent-pos").ok();
+        let paused = self.handle.get::<bool>("pause").unwrap_or(false);
+        let idle = self.handle.get::<bool>("idle-active").unwrap_or(true);
+        let Some(percent) = percent.filter(|_| !idle) else {
+            self.clear_progress();
+            return;
+        };
+
+        let status = if paused {
+            ProgressBarStatus::Paused
+        } else {
+            ProgressBarStatus::Normal
+        };
+        self.set_progress_bar(ProgressBarState {
+            status: Some(status),
+            progress: Some(percent.clamp(0.0, 100.0).round() as u64),
+        });
+        self.progress_updated = Some(Instant::now());
+    }
+
+    fn clear_progress(&mut self) {
+        if self.progress_updated.take().is_some() {
+            self.set_progress_bar(ProgressBarState {
+                status: Some(ProgressBarStatus::None),
+                progress: None,
+            });
+        }
+    }
+
+    fn set_progress_bar(&self, state: ProgressBarState) {
+        let Some(window) = self.app.get_webview_window("main") else {
+            return;
+        };
+        if let Err(e) = window.set_progress_bar(state) {
+            eprintln!("Failed to update progress indicator: {}", e);
+        }
+    }
+
+    fn emit<S: Serialize + Clone>(&self, event: &str, payload: S) {
+        if let Err(e) = self
+            .app
+            .emit(&player::event_name(self.player_id, event), payload)
+        {
+            eprintln!("Failed to emit {}: {}", event, e);
+        }
+    }
+
+    // Kept from the snapshot as playback goes, since nothing can be read back once mpv
+    // has stopped. Only a file ending without another to play clears it (on Idle); while
+    // shutting down mpv reports the file gone too.
+    fn update_resume_point(&mut self) {
+        let snapshot = self.server.snapshot();
+        let Some(path) = snapshot.path else {
+            return;
+        };
+        let position = snapshot.position.or_else(|| {
+            // Not known again yet while a file is opening or seeking
+            self.resume
+                .as_ref()
+                .filter(|resume| resume.path == path)
+                .and_then(|resume| resume.position)
+        });
+        self.resume = Some(ResumePoint {
+            path,
+            position,
+            paused: snapshot.paused,
+        });
+    }
+
+    fn on_property_change(&mut self, id: u64, name: &str, value: PropertyValue) {
+        if id >= FRONTEND_OBSERVER_BASE {
+            let payload = PropertyChanged {
+                id,
+                name: name.to_string(),
+                value,
+            };
+            self.emit("mpv://property-change", payload);
+            return;
+        }
+        if id == OBSERVE_FILE_SETTING {
+            self.record_file_settings();
+            return;
+        }
+        if id == OBSERVE_PLAYBACK_STATE {
+            if self.handle.get::<bool>("pause") == Ok(true) {
+                self.save_position(false);
+            }
+            self.update_sleep_inhibitor();
+            // Switch between the normal and paused indicator styles straight away
+            self.update_progress(true);
+            return;
+        }
+        if id == OBSERVE_SESSION {
+            // Snapshot now; the values may not be readable any more by the time we save
+            let session = read_session_settings(&self.handle);
+            let due = match &self.pending_session {
+                Some((due, _)) => *due,
+                None => Instant::now() + SESSION_SAVE_DELAY,
+            };
+            self.pending_session = Some((due, session));
+            return;
+        }
+        if id == OBSERVE_QUEUE {
+            // Soon, but not for every entry of a folder being queued
+            let due = Instant::now() + SESSION_SAVE_DELAY;
+            self.queue_due = Some(self.queue_due.map_or(due, |queued| queued.min(due)));
+            return;
+        }
+        if id == OBSERVE_SNAPSHOT {
+            self.server.on_property_change(name);
+            self.update_resume_point();
+            self.track_position();
+            return;
+        }
+        if id == OBSERVE_PROGRESS {
+            self.update_progress(false);
+            return;
+        }
+        if id == OBSERVE_VOLUME {
+            self.update_volume();
+            return;
+        }
+        if id == OBSERVE_SUBTITLE_TEXT {
+            self.update_subtitle_text();
+            return;
+        }
+        if id == OBSERVE_METADATA {
+            self.update_metadata();
+            return;
+        }
+        if id == OBSERVE_AUDIO_DEVICES {
+            self.update_audio_devices(name);
+            return;
+        }
+        #[cfg(target_os = "macos")]
+        if id == OBSERVE_NOW_PLAYING {
+            self.update_now_playing();
+            return;
+        }
+        #[cfg(target_os = "linux")]
+        if id == OBSERVE_MPRIS {
+            if let Some(mpris) = &self.mpris {
+                // Its getters read the snapshot, which may not have seen this change yet
+                self.server.on_property_change(name);
+                mpris.property_changed(name);
+            }
+            return;
+        }
+
+        match (id, value) {
+            (OBSERVE_CHAPTER, PropertyValue::Int64(index)) => self.on_chapter_change(index),
+            (OBSERVE_EOF_REACHED, PropertyValue::Flag(reached)) => {
+                if reached {
+                    // With keep-open the file doesn't end, but it's been watched
+                    self.save_position(true);
+                    let position = self.handle.get::<f64>("time-pos").ok();
+                    self.emit("mpv://eof-reached", MpvEofReached { position });
+                    self.stop_at_end();
+                }
+            }
+            // PropertyValue::None means the property is currently unavailable
+            (_, PropertyValue::None) => {}
+            _ => eprintln!("Unexpected property change: {:?}", name),
+        }
+    }
+
+    fn update_volume(&mut self) {
+        if self.server.pause_fade_active() {
+            return;
+        }
+        let volume = channel::Volume::read(&self.handle);
+        if volume.is_none() || volume == self.volume {
+            return;
+        }
+        self.emit("volume-changed", &volume);
+        self.volume = volume;
+    }
+
+    fn update_subtitle_text(&mut self) {
+        let text = SubtitleText::read(&self.handle, false);
+        if text == self.subtitle_text {
+            return;
+        }
+        self.emit("subtitle-text-changed", &text);
+        self.subtitle_text = text;
+    }
+
+    // Sent when a file loads and whenever a stream's tags change, e.g. its ICY title
+    fn update_metadata(&mut self) {
+        let metadata = Metadata::read(&self.handle);
+        if metadata == self.metadata {
+            return;
+        }
+        if let Some(metadata) = &metadata {
+            self.emit("metadata-update", metadata);
+        }
+        self.metadata = metadata;
+    }
+
+    // A device was plugged in or out, or another one chosen
+    fn update_audio_devices(&mut self, name: &str) {
+        let devices = audio_devices::read(&self.handle);
+        let current = self
+            .handle
+            .get::<String>(audio_devices::DEVICE_PROPERTY)
+            .unwrap_or_else(|_| audio_devices::AUTO.to_string());
+        if name == audio_devices::DEVICE_PROPERTY {
+            // Auto is a choice too, unless it's the fallback from a device that's gone
+            let fell_back = current == audio_devices::AUTO
+                && self
+                    .preferred_audio_device
+                    .as_ref()
+                    .is_some_and(|preferred| !devices.iter().any(|d| &d.name == preferred));
+            if !fell_back {
+                self.preferred_audio_device = Some(current).filter(|c| c != audio_devices::AUTO);
+            }
+        } else if let Some(device) =
+            audio_devices::follow(self.preferred_audio_device.as_deref(), &current, &devices)
+        {
+            if let Err(e) = self.handle.set(audio_devices::DEVICE_PROPERTY, device) {
+                eprintln!("Failed to switch audio to {}: {}", device, e);
+            }
+        }
+        self.emit("audio-devices-update", devices);
+    }
+
+    #[cfg(target_os = "macos")]
+    fn update_now_playing(&self) {
+        let handle = &self.handle;
+        let Ok(path) = handle.get::<String>("path") else {
+            return;
+        };
+        let paused = handle.get::<bool>("pause").unwrap_or(false);
+        let snapshot = self.server.snapshot();
+        let info = now_playing::NowPlayingInfo {
+            title: handle.get::<String>("media-title").unwrap_or(path.clone()),
+            duration: handle.get::<f64>("duration").ok(),
+            position: handle.get::<f64>("time-pos").unwrap_or(0.0),
+            rate: if paused {
+                0.0
+            } else {
+                handle.get::<f64>("speed").unwrap_or(1.0)
+            },
+            paused,
+            // Looked up once per file, as listing the folder can be slow
+            artwork_path: snapshot
+                .cover_art
+                .filter(|_| snapshot.path.as_deref() == Some(path.as_str())),
+        };
+        now_playing::update(&self.app, info);
+    }
+
+    #[cfg(target_os = "linux")]
+    fn notify_mpris_seek(&self) {
+        let Some(mpris) = &self.mpris else {
+            return;
+        };
+        if let Ok(position) = self.handle.get::<f64>("time-pos") {
+            mpris.seeked(position);
+        }
+    }
+
+    fn restore_file_settings(&mut self) {
+        let Ok(path) = self.handle.get::<String>("path") else {
+            return;
+        };
+
+        if self
+            .control
+            .restore_per_file_settings
+            .load(Ordering::SeqCst)
+        {
+            if let Some(saved) = file_settings::load(&self.app, &path) {
+                saved.apply(&self.handle);
+                if let Err(e) = self.app.emit(
+                    &player::event_name(self.player_id, "settings-restored"),
+                    &saved,
+                ) {
+                    eprintln!("Failed to emit settings-restored: {}", e);
+                }
+            }
+        }
+
+        // Only changes made after this point count as the user's choice for this file
+        self.file_settings = Some(FileSettings::read(&self.handle, &path));
+    }
+
+    fn record_file_settings(&mut self) {
+        let Some(known) = &self.file_settings else {
+            return;
+        };
+
+        let current = FileSettings::read(&self.handle, &known.path);
+        if &current == known {
+            return;
+        }
+        if let Err(e) = file_settings::save(&self.app, &current) {
+            eprintln!("{}", e);
+        }
+        self.file_settings = Some(current);
+    }
+
+    fn on_chapter_change(&mut self, index: i64) {
+        // mpv coalesces changes, so a seek across several chapters arrives as one update
+        if self.last_chapter == Some(index) {
+            return;
+        }
+        // The chapter-list may not be populated yet if the change raced file-loaded
+        if self.chapters.is_empty() {
+            self.chapters = read_chapters(&self.handle);
+        }
+        let Some(chapter) = usize::try_from(index)
+            .ok()
+            .and_then(|i| self.chapters.get(i))
+        else {
+            return;
+        };
+
+        self.last_chapter = Some(index);
+        let payload = ChapterChanged {
+            index,
+            title: chapter.title.clone(),
+            start: chapter.start,
+        };
+        if let Err(e) = self.app.emit(
+            &player::event_name(self.player_id, "chapter-changed"),
+            payload,
+        ) {
+            eprintln!("Failed to emit chapter-changed: {}", e);
+        }
+    }
+}
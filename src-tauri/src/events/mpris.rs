@@ -0,0 +1,383 @@
+// MPRIS D-Bus service so desktop media controls and playerctl can drive the player
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use tauri::{AppHandle, Manager, Runtime};
+use zbus::blocking::connection;
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{ObjectPath, OwnedValue, Str};
+
+use crate::error::PlayerError;
+use crate::player::channel::PlayerSnapshot;
+use crate::player::Players;
+use crate::rendering::video_window;
+
+const BUS_NAME: &str = "org.mpris.MediaPlayer2.media_player_tauri";
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+
+// Same limits as set_playback_speed
+const MIN_RATE: f64 = 0.25;
+const MAX_RATE: f64 = 4.0;
+
+// Properties the event thread forwards as PropertiesChanged signals; all are in the
+// player snapshot the getters read. A path appearing or going away starts or stops.
+pub const OBSERVED_PROPERTIES: &[&str] = &[
+    "pause",
+    "path",
+    "playlist-pos",
+    "speed",
+    "volume",
+    "media-title",
+    "duration",
+];
+
+const MICROS: f64 = 1_000_000.0;
+
+// The main player as the event thread last saw it; None until mpv is initialized.
+// Getters run while the event thread signals a change, so they can't wait on it.
+fn snapshot<R: Runtime>(app: &AppHandle<R>) -> Option<PlayerSnapshot> {
+    app.state::<Players>().main().channel.snapshot()
+}
+
+// The commands' errors, as D-Bus clients get them
+fn reply<T>(result: Result<T, PlayerError>) -> fdo::Result<()> {
+    result
+        .map(|_| ())
+        .map_err(|e| fdo::Error::Failed(e.to_string()))
+}
+
+// Object path identifying the current playlist entry
+fn track_id(snapshot: Option<&PlayerSnapshot>) -> ObjectPath<'static> {
+    let path = match snapshot.and_then(|s| s.playlist_pos) {
+        Some(pos) => format!("/org/media_player_tauri/track/{}", pos),
+        None => NO_TRACK.to_string(),
+    };
+    ObjectPath::try_from(path).unwrap_or_else(|_| ObjectPath::from_static_str_unchecked(NO_TRACK))
+}
+
+struct MediaPlayer2<R: Runtime> {
+    app: AppHandle<R>,
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2")]
+impl<R: Runtime> MediaPlayer2<R> {
+    fn raise(&self) {
+        if let Some(window) = self.app.get_webview_window(video_window::MAIN_WINDOW) {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+    }
+
+    fn quit(&self) {
+        self.app.exit(0);
+    }
+
+    #[zbus(property)]
+    fn can_quit(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_raise(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn has_track_list(&self) -> bool {
+        false
+    }
+
+    #[zbus(property)]
+    fn identity(&self) -> String {
+        self.app.package_info().name.clone()
+    }
+
+    #[zbus(property)]
+    fn supported_uri_schemes(&self) -> Vec<String> {
+        ["file", "http", "https"].map(String::from).to_vec()
+    }
+
+    #[zbus(property)]
+    fn supported_mime_types(&self) -> Vec<String> {
+        ["video/*", "audio/*"].map(String::from).to_vec()
+    }
+}
+
+struct Player<R: Runtime> {
+    app: AppHandle<R>,
+}
+
+#[zbus::interface(name = "org.mpris.MediaPlayer2.Player")]
+impl<R: Runtime> Player<R> {
+    async fn play(&self) -> fdo::Result<()> {
+        reply(crate::commands::playback::set_paused(false, None, self.app.state()).await)
+    }
+
+    async fn pause(&self) -> fdo::Result<()> {
+        reply(crate::commands::playback::set_paused(true, None, self.app.state()).await)
+    }
+
+    async fn play_pause(&self) -> fdo::Result<()> {
+        reply(crate::commands::playback::play_pause(None, self.app.state()).await)
+    }
+
+    async fn stop(&self) -> fdo::Result<()> {
+        reply(crate::commands::playback::stop_video(None, self.app.state()).await)
+    }
+
+    // At either end of the queue the spec has these do nothing
+    async fn next(&self) -> fdo::Result<()> {
+        match crate::commands::queue::playlist_next(None, self.app.state()).await {
+            Err(PlayerError::NotFound(_)) => Ok(()),
+            result => reply(result),
+        }
+    }
+
+    async fn previous(&self) -> fdo::Result<()> {
+        match crate::commands::queue::playlist_prev(None, self.app.state()).await {
+            Err(PlayerError::NotFound(_)) => Ok(()),
+            result => reply(result),
+        }
+    }
+
+    // Offset is in microseconds; seeking back past the start goes to the start
+    async fn seek(&self, offset: i64) -> fdo::Result<()> {
+        let Some(position) = snapshot(&self.app).and_then(|s| s.position) else {
+            return Ok(());
+        };
+        let target = (position + offset as f64 / MICROS).max(0.0);
+        reply(crate::commands::playback::seek(target, None, None, self.app.state()).await)
+    }
+
+    // The spec says to ignore requests for a stale track or an out-of-range position
+    async fn set_position(&self, track: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+        let snapshot = snapshot(&self.app);
+        if track != track_id(snapshot.as_ref()) || position < 0 {
+            return Ok(());
+        }
+        let seconds = position as f64 / MICROS;
+        if snapshot
+            .and_then(|s| s.duration)
+            .is_some_and(|d| seconds > d)
+        {
+            return Ok(());
+        }
+        reply(crate::commands::playback::seek(seconds, None, None, self.app.state()).await)
+    }
+
+    async fn open_uri(&self, uri: String) -> fdo::Result<()> {
+        reply(crate::commands::playback::load_video(uri, None, self.app.state()).await)
+    }
+
+    #[zbus(signal)]
+    async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn playback_status(&self) -> String {
+        let status = match snapshot(&self.app) {
+            Some(s) if s.path.is_some() && s.paused => "Paused",
+            Some(s) if s.path.is_some() => "Playing",
+            _ => "Stopped",
+        };
+        status.to_string()
+    }
+
+    #[zbus(property)]
+    fn metadata(&self) -> HashMap<String, OwnedValue> {
+        let mut metadata = HashMap::new();
+        let Some(snapshot) = snapshot(&self.app) else {
+            return metadata;
+        };
+        metadata.insert(
+            "mpris:trackid".to_string(),
+            track_id(Some(&snapshot)).into(),
+        );
+        if let Some(duration) = snapshot.duration {
+            let length = (duration * MICROS) as i64;
+            metadata.insert("mpris:length".to_string(), length.into());
+        }
+        if let Some(title) = snapshot.title {
+            metadata.insert("xesam:title".to_string(), Str::from(title).into());
+        }
+        let art = snapshot
+            .cover_art
+            .and_then(|art| tauri::Url::from_file_path(art).ok());
+        if let Some(art) = art {
+            metadata.insert(
+                "mpris:artUrl".to_string(),
+                Str::from(art.to_string()).into(),
+            );
+        }
+        metadata
+    }
+
+    // Microseconds; clients poll this rather than relying on change signals
+    #[zbus(property(emits_changed_signal = "false"))]
+    fn position(&self) -> i64 {
+        snapshot(&self.app)
+            .and_then(|s| s.position)
+            .map(|pos| (pos * MICROS) as i64)
+            .unwrap_or(0)
+    }
+
+    #[zbus(property)]
+    fn rate(&self) -> f64 {
+        snapshot(&self.app).map_or(1.0, |s| s.speed)
+    }
+
+    #[zbus(property)]
+    async fn set_rate(&self, rate: f64) {
+        let speed = crate::commands::playback::set_playback_speed(
+            rate.clamp(MIN_RATE, MAX_RATE),
+            None,
+            self.app.state(),
+        );
+        if let Err(e) = speed.await {
+            eprintln!("MPRIS rate change failed: {}", e);
+        }
+    }
+
+    #[zbus(property)]
+    fn minimum_rate(&self) -> f64 {
+        MIN_RATE
+    }
+
+    #[zbus(property)]
+    fn maximum_rate(&self) -> f64 {
+        MAX_RATE
+    }
+
+    // MPRIS uses 0.0-1.0 where mpv uses percent
+    #[zbus(property)]
+    fn volume(&self) -> f64 {
+        snapshot(&self.app)
+            .and_then(|s| s.volume)
+            .map(|v| v / 100.0)
+            .unwrap_or(1.0)
+    }
+
+    // Negative volumes count as 0; set_volume refuses anything past volume-max
+    #[zbus(property)]
+    async fn set_volume(&self, volume: f64) {
+        let percent = volume.max(0.0) * 100.0;
+        let result = crate::commands::playback::set_volume(percent, None, self.app.state());
+        if let Err(e) = result.await {
+            eprintln!("MPRIS volume change failed: {}", e);
+        }
+    }
+
+    #[zbus(property)]
+    fn can_go_next(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_go_previous(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_play(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_pause(&self) -> bool {
+        true
+    }
+
+    #[zbus(property)]
+    fn can_seek(&self) -> bool {
+        true
+    }
+
+    #[zbus(property(emits_changed_signal = "const"))]
+    fn can_control(&self) -> bool {
+        true
+    }
+}
+
+// The registered service; owned by the event thread and unregistered when it stops
+pub struct MprisServer<R: Runtime> {
+    connection: zbus::blocking::Connection,
+    runtime: PhantomData<R>,
+}
+
+impl<R: Runtime> MprisServer<R> {
+    // Claim the bus name and export both interfaces. Failure (e.g. no session bus) is
+    // logged and leaves the player running without MPRIS.
+    pub fn start(app: &AppHandle<R>) -> Option<Self> {
+        let connection = connection::Builder::session()
+            .and_then(|b| b.name(BUS_NAME))
+            .and_then(|b| b.serve_at(OBJECT_PATH, MediaPlayer2 { app: app.clone() }))
+            .and_then(|b| b.serve_at(OBJECT_PATH, Player { app: app.clone() }))
+            .and_then(|b| b.build());
+        match connection {
+            Ok(connection) => Some(Self {
+                connection,
+                runtime: PhantomData,
+            }),
+            Err(e) => {
+                eprintln!("MPRIS unavailable: {}", e);
+                None
+            }
+        }
+    }
+
+    // Forward an observed mpv property change to D-Bus clients
+    pub fn property_changed(&self, name: &str) {
+        let Ok(iface) = self
+            .connection
+            .object_server()
+            .interface::<_, Player<R>>(OBJECT_PATH)
+        else {
+            return;
+        };
+        let emitter = iface.signal_emitter();
+        let player = iface.get();
+        let result = zbus::block_on(async {
+            match name {
+                "pause" => player.playback_status_changed(emitter).await,
+                "path" => {
+                    player.playback_status_changed(emitter).await?;
+                    player.metadata_changed(emitter).await
+                }
+                "speed" => player.rate_changed(emitter).await,
+                "volume" => player.volume_changed(emitter).await,
+                "media-title" | "duration" | "playlist-pos" => {
+                    player.metadata_changed(emitter).await
+                }
+                _ => Ok(()),
+            }
+        });
+        if let Err(e) = result {
+            eprintln!("Failed to signal MPRIS {} change: {}", name, e);
+        }
+    }
+
+    // Position jumps aren't covered by PropertiesChanged, so tell clients explicitly
+    pub fn seeked(&self, position: f64) {
+        let Ok(iface) = self
+            .connection
+            .object_server()
+            .interface::<_, Player<R>>(OBJECT_PATH)
+        else {
+            return;
+        };
+        let micros = (position * MICROS) as i64;
+        if let Err(e) = zbus::block_on(Player::<R>::seeked(iface.signal_emitter(), micros)) {
+            eprintln!("Failed to signal MPRIS seek: {}", e);
+        }
+    }
+
+    pub fn stop(self) {
+        let server = self.connection.object_server();
+        let _ = server.remove::<Player<R>, _>(OBJECT_PATH);
+        let _ = server.remove::<MediaPlayer2<R>, _>(OBJECT_PATH);
+        if let Err(e) = self.connection.release_name(BUS_NAME) {
+            eprintln!("Failed to release MPRIS name: {}", e);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+// macOS Now Playing widget and media key integration via the MediaPlayer framework
+
+use objc::declare::ClassDecl;
+use objc::runtime::{Class, Object, Sel, YES};
+use objc::{class, msg_send, sel, sel_impl};
+use std::ffi::CString;
+use std::sync::OnceLock;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::commands::{playback, queue};
+use crate::player::Players;
+
+#[allow(non_camel_case_types)]
+type id = *mut Object;
+const NIL: id = std::ptr::null_mut();
+
+#[link(name = "MediaPlayer", kind = "framework")]
+extern "C" {
+    static MPMediaItemPropertyTitle: id;
+    static MPMediaItemPropertyPlaybackDuration: id;
+    static MPMediaItemPropertyArtwork: id;
+    static MPNowPlayingInfoPropertyElapsedPlaybackTime: id;
+    static MPNowPlayingInfoPropertyPlaybackRate: id;
+}
+
+// MPNowPlayingPlaybackState
+const PLAYBACK_STATE_PLAYING: usize = 1;
+const PLAYBACK_STATE_PAUSED: usize = 2;
+const PLAYBACK_STATE_STOPPED: usize = 3;
+
+// MPRemoteCommandHandlerStatus
+const HANDLER_SUCCESS: isize = 0;
+const HANDLER_NO_NOW_PLAYING_ITEM: isize = 110;
+
+// Remote command handlers are plain ObjC methods, so they reach the app through this
+static APP: OnceLock<AppHandle> = OnceLock::new();
+
+// What we publish to the system, gathered by the event thread
+#[derive(Debug, Clone, PartialEq)]
+pub struct NowPlayingInfo {
+    pub title: String,
+    pub duration: Option<f64>,
+    pub position: f64,
+    // 0.0 while paused so the system doesn't extrapolate the position
+    pub rate: f64,
+    pub paused: bool,
+    pub artwork_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RemoteCommand {
+    Play,
+    Pause,
+    TogglePlayPause,
+    Next,
+    Previous,
+    SeekTo(f64),
+}
+
+// Register the remote command handlers; call once from setup
+pub fn init(app: &AppHandle) {
+    if APP.set(app.clone()).is_err() {
+        return;
+    }
+
+    unsafe {
+        let target: id = msg_send![target_class(), new];
+        let center: id = msg_send![class!(MPRemoteCommandCenter), sharedCommandCenter];
+
+        let commands: [(id, Sel); 6] = [
+            (msg_send![center, playCommand], sel!(handlePlay:)),
+            (msg_send![center, pauseCommand], sel!(handlePause:)),
+            (
+                msg_send![center, togglePlayPauseCommand],
+                sel!(handleTogglePlayPause:),
+            ),
+            (msg_send![center, nextTrackCommand], sel!(handleNext:)),
+            (
+                msg_send![center, previousTrackCommand],
+                sel!(handlePrevious:),
+            ),
+            (
+                msg_send![center, changePlaybackPositionCommand],
+                sel!(handleChangePosition:),
+            ),
+        ];
+        for (command, action) in commands {
+            let _: () = msg_send![command, setEnabled: YES];
+            let _: () = msg_send![command, addTarget: target action: action];
+        }
+    }
+}
+
+// Publish the current item; the system extrapolates position from elapsed time and rate,
+// so this only needs calling when something changes (including seeks)
+pub fn update<R: Runtime>(app: &AppHandle<R>, info: NowPlayingInfo) {
+    let _ = app.run_on_main_thread(move || unsafe {
+        let dict: id = msg_send![class!(NSMutableDictionary), dictionary];
+        set_value(dict, MPMediaItemPropertyTitle, ns_string(&info.title));
+        if let Some(duration) = info.duration {
+            set_value(
+                dict,
+                MPMediaItemPropertyPlaybackDuration,
+                ns_number(duration),
+            );
+        }
+        set_value(
+            dict,
+            MPNowPlayingInfoPropertyElapsedPlaybackTime,
+            ns_number(info.position),
+        );
+        set_value(
+            dict,
+            MPNowPlayingInfoPropertyPlaybackRate,
+            ns_number(info.rate),
+        );
+        if let Some(artwork) = info.artwork_path.as_deref().and_then(|p| artwork(p)) {
+            set_value(dict, MPMediaItemPropertyArtwork, artwork);
+        }
+
+        let center: id = msg_send![class!(MPNowPlayingInfoCenter), defaultCenter];
+        let _: () = msg_send![center, setNowPlayingInfo: dict];
+        let state = if info.paused {
+            PLAYBACK_STATE_PAUSED
+        } else {
+            PLAYBACK_STATE_PLAYING
+        };
+        let _: () = msg_send![center, setPlaybackState: state];
+    });
+}
+
+// Remove the widget contents when playback stops
+pub fn clear<R: Runtime>(app: &AppHandle<R>) {
+    let _ = app.run_on_main_thread(|| unsafe {
+        let center: id = msg_send![class!(MPNowPlayingInfoCenter), defaultCenter];
+        let _: () = msg_send![center, setNowPlayingInfo: NIL];
+        let _: () = msg_send![center, setPlaybackState: PLAYBACK_STATE_STOPPED];
+    });
+}
+
+unsafe fn ns_string(value: &str) -> id {
+    // Interior NULs can't cross into an NSString; drop them rather than the whole title
+    let value = CString::new(value.replace('\0', "")).unwrap_or_default();
+    msg_send![class!(NSString), stringWithUTF8String: value.as_ptr()]
+}
+
+unsafe fn ns_number(value: f64) -> id {
+    msg_send![class!(NSNumber), numberWithDouble: value]
+}
+
+unsafe fn set_value(dict: id, key: id, value: id) {
+    let _: () = msg_send![dict, setObject: value forKey: key];
+}
+
+unsafe fn artwork(path: &str) -> Option<id> {
+    let image: id = msg_send![class!(NSImage), alloc];
+    let image: id = msg_send![image, initWithContentsOfFile: ns_string(path)];
+    if image == NIL {
+        return None;
+    }
+    // initWithImage: is deprecated but avoids needing an ObjC block for the size handler
+    let artwork: id = msg_send![class!(MPMediaItemArtwork), alloc];
+    let artwork: id = msg_send![artwork, initWithImage: image];
+    (artwork != NIL).then_some(artwork)
+}
+
+fn target_class() -> &'static Class {
+    static CLASS: OnceLock<usize> = OnceLock::new();
+    let class = *CLASS.get_or_init(|| {
+        let mut decl = ClassDecl::new("MediaPlayerRemoteCommandTarget", class!(NSObject))
+            .expect("remote command target class registered twice");
+        unsafe {
+            decl.add_method(
+                sel!(handlePlay:),
+                handle_play as extern "C" fn(&Object, Sel, id) -> isize,
+            );
+            decl.add_method(
+                sel!(handlePause:),
+                handle_pause as extern "C" fn(&Object, Sel, id) -> isize,
+            );
+            decl.add_method(
+                sel!(handleTogglePlayPause:),
+                handle_toggle as extern "C" fn(&Object, Sel, id) -> isize,
+            );
+            decl.add_method(
+                sel!(handleNext:),
+                handle_next as extern "C" fn(&Object, Sel, id) -> isize,
+            );
+            decl.add_method(
+                sel!(handlePrevious:),
+                handle_previous as extern "C" fn(&Object, Sel, id) -> isize,
+            );
+            decl.add_method(
+                sel!(handleChangePosition:),
+                handle_change_position as extern "C" fn(&Object, Sel, id) -> isize,
+            );
+        }
+        decl.register() as *const Class as usize
+    });
+    unsafe { &*(class as *const Class) }
+}
+
+extern "C" fn handle_play(_: &Object, _: Sel, _: id) -> isize {
+    dispatch(RemoteCommand::Play)
+}
+
+extern "C" fn handle_pause(_: &Object, _: Sel, _: id) -> isize {
+    dispatch(RemoteCommand::Pause)
+}
+
+extern "C" fn handle_toggle(_: &Object, _: Sel, _: id) -> isize {
+    dispatch(RemoteCommand::TogglePlayPause)
+}
+
+extern "C" fn handle_next(_: &Object, _: Sel, _: id) -> isize {
+    dispatch(RemoteCommand::Next)
+}
+
+extern "C" fn handle_previous(_: &Object, _: Sel, _: id) -> isize {
+    dispatch(RemoteCommand::Previous)
+}
+
+extern "C" fn handle_change_position(_: &Object, _: Sel, event: id) -> isize {
+    let position: f64 = unsafe { msg_send![event, positionTime] };
+    dispatch(RemoteCommand::SeekTo(position))
+}
+
+// Handlers run on the main thread, which mustn't wait on the event thread, so the
+// command is spawned and reported as handled once it's on its way
+fn dispatch(command: RemoteCommand) -> isize {
+    let Some(app) = APP.get() else {
+        return HANDLER_NO_NOW_PLAYING_ITEM;
+    };
+    if app.state::<Players>().main().channel.snapshot().is_none() {
+        return HANDLER_NO_NOW_PLAYING_ITEM;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        let players = app.state::<Players>();
+        let result = match command {
+            RemoteCommand::Play => playback::set_paused(false, None, players).await.map(drop),
+            RemoteCommand::Pause => playback::set_paused(true, None, players).await.map(drop),
+            RemoteCommand::TogglePlayPause => playback::play_pause(None, players).await.map(drop),
+            RemoteCommand::Next => queue::playlist_next(None, players).await.map(drop),
+            RemoteCommand::Previous => queue::playlist_prev(None, players).await.map(drop),
+            RemoteCommand::SeekTo(position) => playback::seek(position, None, None, players)
+                .await
+                .map(drop),
+        };
+        if let Err(e) = result {
+            eprintln!("Remote command {:?} failed: {}", command, e);
+        }
+    });
+    HANDLER_SUCCESS
+}
@@ -0,0 +1,147 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// Time between volume steps; short enough that the ramp sounds continuous
+const STEP: Duration = Duration::from_millis(15);
+
+// Volume values mpv reports back can differ slightly from what we set
+const VOLUME_TOLERANCE: f64 = 0.05;
+
+// A volume ramp in progress, stepped by the event thread
+struct Ramp {
+    from: f64,
+    to: f64,
+    total: Duration,
+    start: Instant,
+    // What the last step set, to tell the user's own changes apart
+    last_set: f64,
+}
+
+#[derive(Default)]
+struct FadeState {
+    // What the in-flight fade is heading towards; None when no fade is running
+    target_paused: Option<bool>,
+    // The user's volume before the fade sequence started
+    restore_volume: Option<f64>,
+    ramp: Option<Ramp>,
+}
+
+// Fades the volume around pause/resume. toggle and cancel are served on the event
+// thread, whose timers step the ramp (see step); a new fade replaces the ramp in place.
+#[derive(Default)]
+pub struct PauseFade {
+    // None disables fading
+    duration: Mutex<Option<Duration>>,
+    state: Mutex<FadeState>,
+}
+
+impl PauseFade {
+    pub fn set_duration(&self, duration: Option<Duration>) {
+        *self.duration.lock().unwrap() = duration;
+    }
+
+    pub fn duration(&self) -> Option<Duration> {
+        *self.duration.lock().unwrap()
+    }
+
+    // Whether a fade is currently ramping the volume
+    pub fn active(&self) -> bool {
+        self.state.lock().unwrap().target_paused.is_some()
+    }
+
+    // Where the fade in progress is heading, true for a pause; None when none is running
+    pub fn target(&self) -> Option<bool> {
+        self.state.lock().unwrap().target_paused
+    }
+
+    // Flip the pause state with a fade, returning whether playback ends up paused.
+    // Toggling mid-fade reverses the fade instead of starting from mpv's pause flag,
+    // which doesn't change until a fade-out completes.
+    pub fn toggle(&self, handle: &MpvHandle, duration: Duration) -> Result<bool, String> {
+        let mut state = self.state.lock().unwrap();
+        let currently_paused = match state.target_paused {
+            Some(target) => target,
+            None => handle
+                .get::<bool>("pause")
+                .map_err(|e| format!("Failed to get pause state: {}", e))?,
+        };
+        let target_paused = !currently_paused;
+
+        let volume = handle
+            .get::<f64>("volume")
+            .map_err(|e| format!("Failed to get volume: {}", e))?;
+        let restore = *state.restore_volume.get_or_insert(volume);
+        state.target_paused = Some(target_paused);
+
+        if !target_paused && handle.get::<bool>("pause") == Ok(true) {
+            // Resuming from a completed fade-out: start silent and ramp up
+            let _ = handle.set("volume", 0.0);
+            let _ = handle.set("pause", false);
+        }
+
+        let from = handle.get::<f64>("volume").unwrap_or(restore);
+        let to = if target_paused { 0.0 } else { restore };
+        // A reversed fade only covers the remaining distance, at the same rate
+        let fraction = if restore > 0.0 {
+            ((to - from).abs() / restore).min(1.0)
+        } else {
+            0.0
+        };
+        state.ramp = Some(Ramp {
+            from,
+            to,
+            total: duration.mul_f64(fraction),
+            start: Instant::now(),
+            last_set: from,
+        });
+        Ok(target_paused)
+    }
+
+    // Stop any fade in progress and put the volume back where the user had it
+    pub fn cancel(&self, handle: &MpvHandle) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(volume) = state.restore_volume.take() {
+            let _ = handle.set("volume", volume);
+        }
+        *state = FadeState::default();
+    }
+
+    // Move the ramp along; returns when the next step is due, or None once there is no
+    // fade left to run
+    pub fn step(&self, handle: &MpvHandle) -> Option<Instant> {
+        let mut state = self.state.lock().unwrap();
+        let FadeState {
+            target_paused: Some(target_paused),
+            restore_volume,
+            ramp: Some(ramp),
+        } = &mut *state
+        else {
+            return None;
+        };
+
+        let mut restore = restore_volume.unwrap_or(ramp.to);
+        let current = handle.get::<f64>("volume").unwrap_or(ramp.last_set);
+        if (current - ramp.last_set).abs() > VOLUME_TOLERANCE {
+            // The user changed the volume mid-fade; that becomes the volume to keep
+            restore = current;
+        } else {
+            let elapsed = ramp.start.elapsed();
+            if elapsed < ramp.total {
+                let t = elapsed.as_secs_f64() / ramp.total.as_secs_f64();
+                ramp.last_set = ramp.from + (ramp.to - ramp.from) * t;
+                let _ = handle.set("volume", ramp.last_set);
+                return Some(Instant::now() + STEP);
+            }
+        }
+
+        if *target_paused {
+            let _ = handle.set("pause", true);
+        }
+        // Leave the real volume in place while paused, so nothing reads back 0
+        let _ = handle.set("volume", restore);
+        *state = FadeState::default();
+        None
+    }
+}
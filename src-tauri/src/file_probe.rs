@@ -0,0 +1,208 @@
+// probe_file: whether a file can be played at all, checked before it goes to mpv so the
+// page can say "missing", "corrupt" or "not a video" instead of showing a bare loadfile
+// error. The cheap filesystem checks come first; then ffprobe reads the container, or,
+// without ffprobe, a headless mpv opens it the way a library scan does.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use crate::library::Prober;
+use crate::media_info;
+use crate::mpv::MpvError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileStatus {
+    Playable,
+    Missing,
+    NotAFile,
+    Unreadable,
+    Empty,
+    // Not a format that can be played, or one without audio or video in it
+    Unsupported,
+    // A known format, but damaged or cut short
+    Corrupt,
+    // URLs aren't checked; whether they play depends on the server
+    Remote,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FileReport {
+    pub path: String,
+    pub status: FileStatus,
+    // Why it can't be played, in the checking tool's words where there are some
+    pub message: Option<String>,
+    pub size: Option<u64>,
+    pub container: Option<String>,
+    pub duration: Option<f64>,
+    pub has_video: bool,
+    pub has_audio: bool,
+    // "ffprobe" or "mpv", once the container was looked at
+    pub checked_with: Option<&'static str>,
+}
+
+impl FileReport {
+    fn new(path: &str, status: FileStatus, message: Option<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            status,
+            message,
+            size: None,
+            container: None,
+            duration: None,
+            has_video: false,
+            has_audio: false,
+            checked_with: None,
+        }
+    }
+}
+
+pub fn probe_file(path: &str) -> FileReport {
+    if path.contains("://") && !path.starts_with("file://") {
+        return FileReport::new(path, FileStatus::Remote, None);
+    }
+    let local = path.strip_prefix("file://").unwrap_or(path);
+    let mut report = match check_local(local) {
+        Ok(size) => FileReport {
+            size: Some(size),
+            ..FileReport::new(path, FileStatus::Playable, None)
+        },
+        Err((status, message)) => return FileReport::new(path, status, Some(message)),
+    };
+
+    match media_info::ffprobe(local) {
+        Ok(json) => read_ffprobe(&mut report, &json),
+        // "Failed to run ffprobe": it isn't installed, so let mpv try opening it
+        Err(e) if e.starts_with("Failed to run") => read_with_mpv(&mut report, local),
+        Err(e) => {
+            // What ffprobe says for a file no demuxer recognizes
+            report.status = if e.contains("Invalid data found") {
+                FileStatus::Unsupported
+            } else {
+                FileStatus::Corrupt
+            };
+            report.message = Some(e);
+            report.checked_with = Some("ffprobe");
+        }
+    }
+    report
+}
+
+// The file's size, if it's there and can be read
+fn check_local(path: &str) -> Result<u64, (FileStatus, String)> {
+    let metadata = std::fs::metadata(path).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => (FileStatus::Missing, "The file doesn't exist".to_string()),
+        std::io::ErrorKind::PermissionDenied => (
+            FileStatus::Unreadable,
+            "No permission to read the file".to_string(),
+        ),
+        _ => (FileStatus::Unreadable, e.to_string()),
+    })?;
+    if metadata.is_dir() {
+        return Err((FileStatus::NotAFile, "That's a folder".to_string()));
+    }
+    if !metadata.is_file() {
+        return Err((
+            FileStatus::NotAFile,
+            "That's not a regular file".to_string(),
+        ));
+    }
+    if metadata.len() == 0 {
+        return Err((FileStatus::Empty, "The file is empty".to_string()));
+    }
+    // Readable by the metadata alone isn't certain (ACLs, offline cloud files)
+    File::open(path)
+        .and_then(|mut file| file.read(&mut [0; 1]))
+        .map_err(|e| (FileStatus::Unreadable, e.to_string()))?;
+    Ok(metadata.len())
+}
+
+fn read_ffprobe(report: &mut FileReport, json: &str) {
+    report.checked_with = Some("ffprobe");
+    let Ok(probed) = serde_json::from_str::<Value>(json) else {
+        report.status = FileStatus::Corrupt;
+        report.message = Some("ffprobe's output couldn't be read".to_string());
+        return;
+    };
+    let format = &probed["format"];
+    report.container = format["format_name"].as_str().map(str::to_string);
+    report.duration = format["duration"]
+        .as_str()
+        .and_then(|duration| duration.parse().ok());
+    let streams = probed["streams"].as_array().map_or(&[][..], Vec::as_slice);
+    let has = |kind: &str| streams.iter().any(|s| s["codec_type"] == kind);
+    report.has_video = has("video");
+    report.has_audio = has("audio");
+    if !report.has_video && !report.has_audio {
+        report.status = FileStatus::Unsupported;
+        report.message = Some("There's no audio or video in the file".to_string());
+    }
+}
+
+fn read_with_mpv(report: &mut FileReport, path: &str) {
+    report.checked_with = Some("mpv");
+    let probed = Prober::new().and_then(|prober| prober.probe(Path::new(path)));
+    match probed {
+        Ok(probed) => {
+            report.duration = probed.duration;
+            report.has_video = probed.video_codec.is_some();
+            report.has_audio = probed.audio_codec.is_some();
+            if !report.has_video && !report.has_audio {
+                report.status = FileStatus::Unsupported;
+                report.message = Some("There's no audio or video in the file".to_string());
+            }
+        }
+        Err(e) => {
+            report.status = if e.ends_with(&MpvError::UnknownFormat.to_string()) {
+                FileStatus::Unsupported
+            } else {
+                FileStatus::Corrupt
+            };
+            report.message = Some(e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filesystem_problems_are_caught_first() {
+        let dir = std::env::temp_dir().join(format!("file-probe-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let empty = dir.join("empty.mkv");
+        std::fs::write(&empty, "").unwrap();
+
+        let status = |path: &Path| probe_file(&path.to_string_lossy()).status;
+        assert_eq!(status(&dir.join("gone.mkv")), FileStatus::Missing);
+        assert_eq!(status(&dir), FileStatus::NotAFile);
+        assert_eq!(status(&empty), FileStatus::Empty);
+        assert_eq!(
+            probe_file("https://example.com/live.m3u8").status,
+            FileStatus::Remote
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn files_without_streams_are_unsupported() {
+        let mut report = FileReport::new("/videos/a.mkv", FileStatus::Playable, None);
+        read_ffprobe(
+            &mut report,
+            r#"{"format": {"format_name": "matroska,webm", "duration": "61.500000"},
+                "streams": [{"index": 0, "codec_type": "video"}]}"#,
+        );
+        assert_eq!(report.status, FileStatus::Playable);
+        assert_eq!(report.duration, Some(61.5));
+        assert!(report.has_video && !report.has_audio);
+
+        let mut report = FileReport::new("/videos/b.mkv", FileStatus::Playable, None);
+        read_ffprobe(&mut report, r#"{"format": {}, "streams": []}"#);
+        assert_eq!(report.status, FileStatus::Unsupported);
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+const SETTINGS_DIR: &str = "file-settings";
+
+// Playback settings remembered for one file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileSettings {
+    pub path: String,
+    pub speed: Option<f64>,
+    pub aid: Option<String>,
+    pub sid: Option<String>,
+    pub sub_delay: Option<f64>,
+    pub audio_delay: Option<f64>,
+}
+
+impl FileSettings {
+    // Snapshot the current values from mpv
+    pub fn read(handle: &MpvHandle, path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            speed: handle.get::<f64>("speed").ok(),
+            aid: handle.get::<String>("aid").ok(),
+            sid: handle.get::<String>("sid").ok(),
+            sub_delay: handle.get::<f64>("sub-delay").ok(),
+            audio_delay: handle.get::<f64>("audio-delay").ok(),
+        }
+    }
+
+    // Push the saved values to mpv; failures (e.g. a track that no longer exists) are
+    // logged and skipped so the rest still apply
+    pub fn apply(&self, handle: &MpvHandle) {
+        let speed = self.speed.map(|v| v.to_string());
+        let sub_delay = self.sub_delay.map(|v| v.to_string());
+        let audio_delay = self.audio_delay.map(|v| v.to_string());
+        let properties = [
+            ("speed", speed.as_ref()),
+            ("aid", self.aid.as_ref()),
+            ("sid", self.sid.as_ref()),
+            ("sub-delay", sub_delay.as_ref()),
+            ("audio-delay", audio_delay.as_ref()),
+        ];
+
+        for (name, value) in properties {
+            if let Some(value) = value {
+                if let Err(e) = handle.set(name, value) {
+                    eprintln!("Failed to restore {}={}: {}", name, value, e);
+                }
+            }
+        }
+    }
+}
+
+// A file name for what's stored about `path`, safe whatever characters the path has
+pub fn path_digest(path: &str) -> String {
+    let digest = Sha256::digest(path.as_bytes());
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn settings_file<R: Runtime>(app: &AppHandle<R>, path: &str) -> Option<PathBuf> {
+    let dir = app.path().app_data_dir().ok()?.join(SETTINGS_DIR);
+    Some(dir.join(format!("{}.json", path_digest(path))))
+}
+
+pub fn load<R: Runtime>(app: &AppHandle<R>, path: &str) -> Option<FileSettings> {
+    let contents = std::fs::read_to_string(settings_file(app, path)?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+pub fn save<R: Runtime>(app: &AppHandle<R>, settings: &FileSettings) -> Result<(), String> {
+    let file = settings_file(app, &settings.path).ok_or("No app data directory")?;
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create settings directory: {}", e))?;
+    }
+    let contents = serde_json::to_string_pretty(settings)
+        .map_err(|e| format!("Failed to serialize file settings: {}", e))?;
+    std::fs::write(file, contents).map_err(|e| format!("Failed to write file settings: {}", e))
+}
+
+// Returns whether anything was stored for `path`
+pub fn clear<R: Runtime>(app: &AppHandle<R>, path: &str) -> Result<bool, String> {
+    let file = settings_file(app, path).ok_or("No app data directory")?;
+    match std::fs::remove_file(file) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!("Failed to clear file settings: {}", e)),
+    }
+}
@@ -0,0 +1,76 @@
+// The current frame as PNG bytes, straight from mpv's screenshot-raw instead of a
+// temporary file, for the page's "frame captured" preview or pasting into a note. Fast
+// compression keeps a 4K frame to a few tens of milliseconds; the page only holds on to
+// it briefly.
+
+use crate::mpv::RawFrame;
+
+pub fn encode_png(frame: &RawFrame) -> Result<Vec<u8>, String> {
+    // Source byte of each output channel within a 4-byte pixel
+    let (channels, color): (&[usize], _) = match frame.format.as_str() {
+        "bgr0" => (&[2, 1, 0], png::ColorType::Rgb),
+        "rgb0" => (&[0, 1, 2], png::ColorType::Rgb),
+        "bgra" => (&[2, 1, 0, 3], png::ColorType::Rgba),
+        "rgba" => (&[0, 1, 2, 3], png::ColorType::Rgba),
+        other => return Err(format!("Unsupported frame format: {}", other)),
+    };
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    if width == 0 || height == 0 || frame.stride < width * 4 {
+        return Err(format!("Bad frame size: {}x{}", frame.width, frame.height));
+    }
+    if frame.pixels.len() < frame.stride * (height - 1) + width * 4 {
+        return Err("The captured frame is incomplete".to_string());
+    }
+
+    let mut image = Vec::with_capacity(width * height * channels.len());
+    for row in frame.pixels.chunks(frame.stride).take(height) {
+        for pixel in row[..width * 4].chunks_exact(4) {
+            image.extend(channels.iter().map(|&c| pixel[c]));
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, frame.width, frame.height);
+    encoder.set_color(color);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Fast);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&image))
+        .map_err(|e| format!("Failed to encode frame: {}", e))?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_are_encoded_as_rgb_png() {
+        // 2x2 bgr0 with padding after each row: red, green / blue, white
+        let mut pixels = Vec::new();
+        pixels.extend([0, 0, 255, 0, 0, 255, 0, 0, 9, 9, 9, 9]);
+        pixels.extend([255, 0, 0, 0, 255, 255, 255, 0]);
+        let frame = RawFrame {
+            width: 2,
+            height: 2,
+            stride: 12,
+            format: "bgr0".to_string(),
+            pixels,
+        };
+        let png = encode_png(&frame).unwrap();
+
+        let mut reader = png::Decoder::new(&png[..]).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (2, 2));
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        assert_eq!(decoded, [255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+
+        let short = RawFrame {
+            pixels: vec![0; 19],
+            ..frame
+        };
+        assert!(encode_png(&short).is_err());
+    }
+}
@@ -1,379 +1,311 @@
-use serde::{Deserialize, Serialize};
-use std::ffi::CString;
-use std::os::raw::c_void;
-use std::ptr;
-use std::sync::Mutex;
-use tauri::{Manager, Window};
+use tauri::{DragDropEvent, Manager, WindowEvent};
+
+use player::Players;
+
+mod audio_devices;
+mod bookmarks;
+mod chapter_thumbnails;
+mod clip;
+mod commands;
+mod config;
+mod contact_sheet;
+mod cover_art;
+mod downmix;
+mod equalizer;
+mod error;
+mod events;
+mod fade;
+mod file_probe;
+mod file_settings;
+mod frame_capture;
+mod library;
+mod loadfile;
+mod loop_mode;
+mod loudness;
+mod m3u;
+mod media;
+mod media_info;
+mod metadata;
+mod mpv;
+mod mpv_log;
+mod opensubtitles;
+mod pitch;
+mod player;
+mod playlist;
+mod pls;
+mod power;
+mod rendering;
+mod resume_position;
+mod saved_queue;
+mod seek_preview;
+mod spdif;
+mod subtitle_text;
+mod tracks;
+mod waveform;
 
-// Video area coordinates from frontend
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct VideoArea {
-    x: i32,
-    y: i32,
-    width: i32,
-    height: i32,
-}
-
-// MPV player state with native rendering support
-pub struct MpvPlayer {
-    handle: Option<*mut libmpv_sys::mpv_handle>,
-    video_area: Option<VideoArea>,
-    // We'll use this field when we implement more advanced native rendering
-    #[allow(dead_code)]
-    #[cfg(target_os = "macos")]
-    child_window: Option<*mut std::os::raw::c_void>,
-}
-
-unsafe impl Send for MpvPlayer {}
-unsafe impl Sync for MpvPlayer {}
-
-impl MpvPlayer {
-    fn new() -> Self {
-        Self {
-            handle: None,
-            video_area: None,
-            #[cfg(target_os = "macos")]
-            child_window: None,
-        }
-    }
-}
-
-// Native rendering setup for macOS
-#[cfg(target_os = "macos")]
-fn setup_macos_native_rendering(
-    window: &Window,
-    mpv_handle: *mut libmpv_sys::mpv_handle,
-    _video_area: &VideoArea,
-) -> Result<(), String> {
-    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
-
-    unsafe {
-        // Get the raw window handle from Tauri
-        let raw_handle = window
-            .window_handle()
-            .map_err(|e| format!("Failed to get window handle: {}", e))?
-            .as_raw();
-
-        match raw_handle {
-            RawWindowHandle::AppKit(appkit_handle) => {
-                // Get the native view pointer
-                let ns_view = appkit_handle.ns_view.as_ptr() as i64;
-
-                // Set the window ID for MPV to render into
-                let wid_prop = CString::new("wid").unwrap();
-                let ret = libmpv_sys::mpv_set_property(
-                    mpv_handle,
-                    wid_prop.as_ptr(),
-                    libmpv_sys::mpv_format_MPV_FORMAT_INT64,
-                    &ns_view as *const i64 as *mut c_void,
-                );
-
-                if ret != 0 {
-                    return Err(format!("Failed to set window ID: error code {}", ret));
-                }
-
-                // Force MPV to use the embedded view
-                let force_window_prop = CString::new("force-window").unwrap();
-                let force_window_value = CString::new("yes").unwrap();
-                libmpv_sys::mpv_set_property_string(
-                    mpv_handle,
-                    force_window_prop.as_ptr(),
-                    force_window_value.as_ptr(),
-                );
-
-                println!("MPV configured to render into view: {}", ns_view);
-                Ok(())
-            }
-            _ => Err("Unsupported window handle type".to_string()),
-        }
-    }
-}
-
-// Tauri commands
-#[tauri::command]
-fn init_mpv_player(state: tauri::State<Mutex<MpvPlayer>>) -> Result<String, String> {
-    let mut player = state.lock().unwrap();
-
-    unsafe {
-        let handle = libmpv_sys::mpv_create();
-        if handle.is_null() {
-            return Err("Failed to create MPV handle".to_string());
-        }
-
-        // CRITICAL: Set up MPV for embedding BEFORE initialization
-        let vo_prop = CString::new("vo").unwrap();
-        let vo_value = CString::new("libmpv").unwrap();
-        libmpv_sys::mpv_set_option_string(handle, vo_prop.as_ptr(), vo_value.as_ptr());
-
-        // Disable video output initially (no separate window)
-        let vid_prop = CString::new("vid").unwrap();
-        let vid_value = CString::new("no").unwrap();
-        libmpv_sys::mpv_set_option_string(handle, vid_prop.as_ptr(), vid_value.as_ptr());
-
-        // Initialize MPV
-        let ret = libmpv_sys::mpv_initialize(handle);
-        if ret != 0 {
-            libmpv_sys::mpv_destroy(handle);
-            return Err(format!("Failed to initialize MPV: {}", ret));
-        }
-
-        // Basic configuration
-        let hwdec_prop = CString::new("hwdec").unwrap();
-        let hwdec_value = CString::new("auto").unwrap();
-        libmpv_sys::mpv_set_option_string(handle, hwdec_prop.as_ptr(), hwdec_value.as_ptr());
-
-        player.handle = Some(handle);
-        Ok("MPV initialized for embedding - no popup windows".to_string())
-    }
-}
-
-#[tauri::command]
-fn setup_video_rendering(
-    window: Window,
-    video_area: VideoArea,
-    state: tauri::State<Mutex<MpvPlayer>>,
-) -> Result<String, String> {
-    let mut player = state.lock().unwrap();
-
-    if let Some(handle) = player.handle {
-        player.video_area = Some(video_area.clone());
-
-        unsafe {
-            // Enable video output now that we have a target
-            let vid_prop = CString::new("vid").unwrap();
-            let vid_value = CString::new("auto").unwrap();
-            libmpv_sys::mpv_set_property_string(handle, vid_prop.as_ptr(), vid_value.as_ptr());
-
-            #[cfg(target_os = "macos")]
-            {
-                match setup_macos_native_rendering(&window, handle, &video_area) {
-                    Ok(_) => Ok(format!(
-                        "✅ Video will render in app (no popup) at {}x{}",
-                        video_area.width, video_area.height
-                    )),
-                    Err(e) => Err(format!("Failed to setup embedding: {}", e)),
-                }
-            }
-
-            #[cfg(not(target_os = "macos"))]
-            {
-                Ok("Video rendering not yet implemented for this platform".to_string())
-            }
-        }
-    } else {
-        Err("MPV not initialized - call init_mpv_player first".to_string())
-    }
-}
-
-#[tauri::command]
-fn load_video(file_path: String, state: tauri::State<Mutex<MpvPlayer>>) -> Result<String, String> {
-    let player = state.lock().unwrap();
-
-    if let Some(handle) = player.handle {
-        unsafe {
-            let cmd = CString::new("loadfile").unwrap();
-            let path = CString::new(file_path.clone()).unwrap();
-            let mut args = [cmd.as_ptr(), path.as_ptr(), ptr::null()];
-
-            let ret = libmpv_sys::mpv_command(handle, args.as_mut_ptr());
-            if ret != 0 {
-                return Err(format!("Failed to load file: {}", ret));
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    tauri::Builder::default()
+        .plugin(tauri_plugin_clipboard_manager::init())
+        .manage(Players::default())
+        // The path names the player, e.g. mpvframe://localhost/2; anything else is the
+        // main one
+        .register_uri_scheme_protocol(rendering::frame_stream::SCHEME, |ctx, request| {
+            let id = request.uri().path().trim_start_matches('/').parse().ok();
+            let players = ctx.app_handle().state::<Players>();
+            let instance = players.get(id).unwrap_or_else(|_| players.main());
+            rendering::frame_stream::respond(&instance.frames, &request)
+        })
+        .invoke_handler(tauri::generate_handler![
+            commands::lifecycle::init_mpv_player,
+            commands::lifecycle::destroy_player,
+            commands::lifecycle::create_player,
+            commands::lifecycle::list_players,
+            commands::video::detach_video_window,
+            commands::video::dock_video_window,
+            commands::video::setup_video_rendering,
+            commands::video::update_video_area,
+            commands::video::set_video_layer_visible,
+            commands::playback::load_video,
+            commands::playback::probe_file,
+            commands::playback::load_video_with_options,
+            commands::playback::play_pause,
+            commands::playback::set_paused,
+            commands::playback::frame_step,
+            commands::playback::frame_back_step,
+            commands::playback::set_pause_fade,
+            commands::playback::stop_video,
+            commands::playback::seek,
+            commands::playback::seek_percent,
+            commands::playback::get_position,
+            commands::playback::get_duration,
+            commands::playback::get_progress,
+            commands::playback::get_preview_thumbnail,
+            commands::playback::get_waveform,
+            commands::navigation::get_chapters,
+            commands::navigation::get_chapter_thumbnails,
+            commands::tracks::get_tracks,
+            commands::tracks::get_media_info,
+            commands::tracks::get_metadata,
+            commands::tracks::get_cover_art,
+            commands::tracks::set_audio_track,
+            commands::tracks::cycle_audio_track,
+            commands::tracks::set_subtitle_track,
+            commands::tracks::toggle_subtitles,
+            commands::tracks::cycle_subtitle_track,
+            commands::tracks::load_subtitle,
+            commands::tracks::set_sub_codepage,
+            commands::navigation::set_chapter,
+            commands::navigation::next_chapter,
+            commands::navigation::prev_chapter,
+            commands::playback::set_playback_speed,
+            commands::playback::get_playback_speed,
+            commands::playback::adjust_playback_speed,
+            commands::playback::reset_playback_speed,
+            commands::playback::speed_preset,
+            commands::playback::set_pitch_correction,
+            commands::playback::get_pitch_correction,
+            commands::playback::set_volume,
+            commands::playback::get_volume,
+            commands::playback::toggle_mute,
+            commands::playback::set_audio_delay,
+            commands::playback::get_audio_delay,
+            commands::playback::list_audio_devices,
+            commands::playback::set_audio_device,
+            commands::audio::get_equalizer,
+            commands::audio::set_equalizer_enabled,
+            commands::audio::set_equalizer_preset,
+            commands::audio::set_equalizer_band,
+            commands::audio::reset_equalizer_preset,
+            commands::audio::set_loudness_normalization,
+            commands::audio::get_loudness_normalization,
+            commands::audio::set_audio_channels,
+            commands::audio::set_downmix,
+            commands::audio::get_audio_channels,
+            commands::audio::set_audio_passthrough,
+            commands::audio::get_audio_passthrough,
+            commands::queue::save_playlist,
+            commands::queue::load_playlist,
+            commands::queue::load_directory,
+            commands::queue::get_playlist,
+            commands::queue::restore_session,
+            commands::queue::playlist_add,
+            commands::queue::playlist_remove,
+            commands::queue::playlist_move,
+            commands::queue::playlist_clear,
+            commands::queue::playlist_next,
+            commands::queue::playlist_prev,
+            commands::queue::playlist_play_index,
+            commands::video::export_clip,
+            commands::video::export_contact_sheet,
+            commands::video::copy_frame_to_clipboard,
+            commands::video::capture_frame,
+            commands::playback::get_playback_stats,
+            commands::playback::subscribe_playback_stats,
+            commands::playback::unsubscribe_playback_stats,
+            commands::video::set_video_enhancements,
+            commands::video::get_video_enhancements,
+            commands::video::reset_video_enhancements,
+            commands::video::set_smooth_motion,
+            commands::video::get_smooth_motion,
+            commands::playback::set_precise_seeking,
+            commands::playback::get_precise_seeking,
+            commands::video::set_video_backend,
+            commands::video::get_video_backend,
+            commands::video::set_tone_mapping,
+            commands::subtitles::set_subtitle_style,
+            commands::subtitles::get_subtitle_style,
+            commands::subtitles::set_opensubtitles_api_key,
+            commands::subtitles::search_subtitles,
+            commands::subtitles::download_subtitle,
+            commands::video::get_hdr_info,
+            commands::settings::get_file_settings,
+            commands::settings::clear_file_settings,
+            commands::settings::get_saved_position,
+            commands::settings::clear_saved_position,
+            commands::settings::set_restore_per_file_settings,
+            commands::settings::set_auto_advance,
+            commands::settings::set_restore_queue,
+            commands::settings::set_remember_position,
+            commands::settings::get_app_settings,
+            commands::settings::set_app_settings,
+            commands::settings::set_prevent_sleep,
+            commands::settings::set_dock_progress_enabled,
+            commands::navigation::add_bookmark,
+            commands::navigation::get_bookmarks,
+            commands::navigation::remove_bookmark,
+            commands::navigation::jump_to_bookmark,
+            commands::queue::set_sleep_timer,
+            commands::queue::cancel_sleep_timer,
+            commands::queue::get_sleep_timer,
+            commands::queue::set_end_behavior,
+            commands::queue::get_end_behavior,
+            commands::queue::set_loop_mode,
+            commands::queue::get_loop_mode,
+            commands::queue::set_shuffle,
+            commands::queue::set_repeat,
+            commands::queue::get_queue_modes,
+            commands::queue::stop_after_current,
+            commands::queue::get_stop_after_current,
+            commands::library::library_add_folder,
+            commands::library::library_remove_folder,
+            commands::library::library_folders,
+            commands::library::library_list,
+            commands::library::library_search,
+            commands::library::library_get,
+            commands::library::library_rescan,
+            commands::library::get_recent,
+            commands::library::get_history,
+            commands::library::clear_history,
+            commands::passthrough::set_mpv_property,
+            commands::passthrough::get_mpv_property_string,
+            commands::passthrough::set_mpv_option,
+            commands::passthrough::send_mpv_command,
+            commands::passthrough::mpv_set_option,
+            commands::passthrough::mpv_run_command,
+            commands::passthrough::get_mpv_allowlist,
+            commands::passthrough::load_script,
+            commands::passthrough::get_loaded_scripts,
+            commands::passthrough::send_script_message,
+            commands::passthrough::get_player_logs,
+            commands::passthrough::clear_player_logs,
+            commands::passthrough::observe_property,
+            commands::passthrough::unobserve_property,
+            commands::subtitles::toggle_subtitle_visibility,
+            commands::subtitles::set_subtitle_visibility,
+            commands::subtitles::set_native_subtitle_rendering,
+            commands::subtitles::get_current_subtitle,
+            commands::playback::get_player_state,
+            commands::window::move_to_monitor
+        ])
+        .on_window_event(|window, event| match event {
+            WindowEvent::DragDrop(DragDropEvent::Drop { paths, .. }) => {
+                commands::queue::handle_file_drop(window, paths);
             }
-            Ok(format!("🎬 Loading video: {}", file_path))
-        }
-    } else {
-        Err("MPV not initialized".to_string())
-    }
-}
-
-#[tauri::command]
-fn play_pause(state: tauri::State<Mutex<MpvPlayer>>) -> Result<String, String> {
-    let player = state.lock().unwrap();
-
-    if let Some(handle) = player.handle {
-        unsafe {
-            let prop = CString::new("pause").unwrap();
-            let mut paused: i64 = 0;
-            let ret = libmpv_sys::mpv_get_property(
-                handle,
-                prop.as_ptr(),
-                libmpv_sys::mpv_format_MPV_FORMAT_FLAG,
-                &mut paused as *mut _ as *mut c_void,
-            );
-
-            if ret == 0 {
-                let new_pause = if paused != 0 { 0i64 } else { 1i64 };
-                let ret = libmpv_sys::mpv_set_property(
-                    handle,
-                    prop.as_ptr(),
-                    libmpv_sys::mpv_format_MPV_FORMAT_FLAG,
-                    &new_pause as *const _ as *mut c_void,
-                );
-
-                if ret == 0 {
-                    Ok(if paused != 0 {
-                        "▶️ Playing"
-                    } else {
-                        "⏸️ Paused"
+            // Bring a popped-out video back before its view goes with the window. Closing
+            // the main window stops every player while the views mpv draws into still
+            // exist, rather than leaving it to app exit.
+            WindowEvent::CloseRequested { api, .. } => {
+                if window.label() == rendering::video_window::MAIN_WINDOW {
+                    player::teardown_all(&window.state::<Players>());
+                } else if let Some(id) = rendering::video_window::player_for(window.label()) {
+                    api.prevent_close();
+                    match window.state::<Players>().get(Some(id)) {
+                        Ok(instance) => {
+                            let app = window.app_handle().clone();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) =
+                                    rendering::video_window::close(&app, &instance).await
+                                {
+                                    eprintln!("Failed to dock the video: {}", e);
+                                }
+                            });
+                        }
+                        Err(_) => {
+                            let _ = window.destroy();
+                        }
                     }
-                    .to_string())
-                } else {
-                    Err(format!("Failed to toggle pause: {}", ret))
                 }
-            } else {
-                Err(format!("Failed to get pause state: {}", ret))
-            }
-        }
-    } else {
-        Err("MPV not initialized".to_string())
-    }
-}
-
-#[tauri::command]
-fn stop_video(state: tauri::State<Mutex<MpvPlayer>>) -> Result<String, String> {
-    let player = state.lock().unwrap();
-
-    if let Some(handle) = player.handle {
-        unsafe {
-            let cmd = CString::new("stop").unwrap();
-            let mut args = [cmd.as_ptr(), ptr::null()];
-
-            let ret = libmpv_sys::mpv_command(handle, args.as_mut_ptr());
-            if ret != 0 {
-                return Err(format!("Failed to stop: {}", ret));
             }
-            Ok("⏹️ Stopped".to_string())
-        }
-    } else {
-        Err("MPV not initialized".to_string())
-    }
-}
-
-#[tauri::command]
-fn set_playback_speed(speed: f64, state: tauri::State<Mutex<MpvPlayer>>) -> Result<String, String> {
-    let player = state.lock().unwrap();
-
-    if let Some(handle) = player.handle {
-        unsafe {
-            // Validate speed range
-            if !(0.25..=4.0).contains(&speed) {
-                return Err("Speed must be between 0.25x and 4.0x".to_string());
+            // Moves are followed too: relayout is debounced and cheap, and a move onto
+            // another monitor can change the geometry before any resize is reported
+            WindowEvent::Resized(_)
+            | WindowEvent::Moved(_)
+            | WindowEvent::ScaleFactorChanged { .. } => {
+                rendering::layout::schedule_relayout(window);
             }
-
-            let prop = CString::new("speed").unwrap();
-            let ret = libmpv_sys::mpv_set_property(
-                handle,
-                prop.as_ptr(),
-                libmpv_sys::mpv_format_MPV_FORMAT_DOUBLE,
-                &speed as *const f64 as *mut c_void,
-            );
-
-            if ret == 0 {
-                Ok(format!("🏃 Speed set to {:.2}x", speed))
-            } else {
-                Err(format!("Failed to set speed: {}", ret))
-            }
-        }
-    } else {
-        Err("MPV not initialized".to_string())
-    }
-}
-
-#[tauri::command]
-fn get_playback_speed(state: tauri::State<Mutex<MpvPlayer>>) -> Result<f64, String> {
-    let player = state.lock().unwrap();
-
-    if let Some(handle) = player.handle {
-        unsafe {
-            let prop = CString::new("speed").unwrap();
-            let mut speed: f64 = 1.0;
-            let ret = libmpv_sys::mpv_get_property(
-                handle,
-                prop.as_ptr(),
-                libmpv_sys::mpv_format_MPV_FORMAT_DOUBLE,
-                &mut speed as *mut f64 as *mut c_void,
+            _ => {}
+        })
+        .setup(|app| {
+            let config = config::ConfigStore::load(app.handle());
+            app.state::<Players>().main().player.lock().unwrap().config = config;
+
+            let library = app
+                .path()
+                .app_data_dir()
+                .map_err(|e| e.to_string())
+                .and_then(|dir| library::Library::open(&dir.join(library::DATABASE_FILE)))
+                .or_else(|e| {
+                    eprintln!("{}; the library won't be kept after quitting", e);
+                    library::Library::in_memory()
+                })?;
+            // Videos a previous session didn't get to are picked up again
+            let thumbnails = app.path().app_cache_dir().ok();
+            let thumbnailer = library::Thumbnailer::start(
+                app.handle().clone(),
+                thumbnails.map(|dir| dir.join(library::THUMBNAILS_DIR)),
             );
-
-            if ret == 0 {
-                Ok(speed)
-            } else {
-                Err(format!("Failed to get speed: {}", ret))
+            if let Err(e) = thumbnailer.queue_missing(&library) {
+                eprintln!("Failed to queue library thumbnails: {}", e);
             }
-        }
-    } else {
-        Err("MPV not initialized".to_string())
-    }
-}
-
-#[tauri::command]
-fn speed_preset(speed: f64, state: tauri::State<Mutex<MpvPlayer>>) -> Result<String, String> {
-    set_playback_speed(speed, state)
-}
-
-// Window positioning commands (from your existing code)
-#[tauri::command]
-fn move_to_monitor(window: tauri::Window, monitor_index: u32) -> Result<String, String> {
-    use tauri::{LogicalPosition, Position};
-
-    let (x, y) = match monitor_index {
-        0 => (0, 100),
-        1 => (1920, 100),
-        2 => (3840, 100),
-        _ => return Err("Invalid monitor index. Use 0, 1, or 2.".to_string()),
-    };
-
-    window
-        .set_position(Position::Logical(LogicalPosition {
-            x: x as f64,
-            y: y as f64,
-        }))
-        .map_err(|e| format!("Failed to move window: {}", e))?;
-
-    Ok(format!("Moved window to monitor {}", monitor_index))
-}
-
-fn setup_window_position(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
-    let window = app.get_webview_window("main").unwrap();
-    let preferred_monitor = 2;
+            app.manage(library);
+            app.manage(thumbnailer);
+            app.manage(seek_preview::SeekPreviews::new(
+                app.path()
+                    .app_cache_dir()
+                    .ok()
+                    .map(|dir| dir.join(seek_preview::PREVIEWS_DIR)),
+            ));
+            app.manage(chapter_thumbnails::ChapterThumbnails::new(
+                app.path()
+                    .app_cache_dir()
+                    .ok()
+                    .map(|dir| dir.join(chapter_thumbnails::CHAPTERS_DIR)),
+            ));
 
-    let (x, y) = match preferred_monitor {
-        0 => (0, 100),
-        1 => (1920, 100),
-        2 => (3840, 100),
-        _ => (0, 100),
-    };
-
-    use tauri::{LogicalPosition, Position};
-    window.set_position(Position::Logical(LogicalPosition {
-        x: x as f64,
-        y: y as f64,
-    }))?;
-
-    println!("Window moved to monitor {}", preferred_monitor);
-    Ok(())
-}
+            #[cfg(target_os = "macos")]
+            events::now_playing::init(app.handle());
 
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    tauri::Builder::default()
-        .manage(Mutex::new(MpvPlayer::new()))
-        .invoke_handler(tauri::generate_handler![
-            init_mpv_player,
-            setup_video_rendering,
-            load_video,
-            play_pause,
-            stop_video,
-            set_playback_speed,
-            get_playback_speed,
-            speed_preset,
-            move_to_monitor
-        ])
-        .setup(|app| {
-            if let Err(e) = setup_window_position(app) {
+            if let Err(e) = commands::window::setup_window_position(app) {
                 eprintln!("Failed to position window: {}", e);
             }
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                player::teardown_all(&app.state::<Players>());
+            }
+        });
 }
@@ -0,0 +1,180 @@
+// Every playback, from the file loading to it ending, for the "Continue watching" and
+// "Recent" lists. The event thread records it; the history commands read it back.
+
+use rusqlite::{params, Row};
+use serde::{Deserialize, Serialize};
+
+use super::{now, query_all, Library};
+
+const ENTRY_COLUMNS: &str = "h.id, h.path, h.title, h.started_at, h.ended_at, h.position, \
+                             h.duration, h.completion, m.id";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub path: String,
+    pub title: Option<String>,
+    // Seconds since the Unix epoch; ended_at stays None while it's playing, or if the
+    // app didn't get to see it end
+    pub started_at: i64,
+    pub ended_at: Option<i64>,
+    // How far it got, in seconds, and as a percentage of the duration
+    pub position: Option<f64>,
+    pub duration: Option<f64>,
+    pub completion: Option<f64>,
+    // The library's entry for the file, if it's in a watched folder
+    pub media_id: Option<i64>,
+}
+
+impl HistoryEntry {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            path: row.get(1)?,
+            title: row.get(2)?,
+            started_at: row.get(3)?,
+            ended_at: row.get(4)?,
+            position: row.get(5)?,
+            duration: row.get(6)?,
+            completion: row.get(7)?,
+            media_id: row.get(8)?,
+        })
+    }
+}
+
+// Playbacks that started within [from, to), newest first; all fields are optional
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct HistoryRange {
+    pub from: Option<i64>,
+    pub to: Option<i64>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+impl Library {
+    // Returns the entry's id, for update_playback
+    pub fn start_playback(
+        &self,
+        path: &str,
+        title: Option<String>,
+        duration: Option<f64>,
+    ) -> Result<i64, String> {
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT INTO history (path, title, started_at, duration) VALUES (?, ?, ?, ?)",
+            params![path, title, now(), duration],
+        )
+        .map_err(|e| format!("Failed to record playback: {}", e))?;
+        Ok(db.last_insert_rowid())
+    }
+
+    pub fn update_playback(
+        &self,
+        id: i64,
+        position: f64,
+        duration: Option<f64>,
+        ended: bool,
+    ) -> Result<(), String> {
+        // The duration given, else the one known when it started; none for a stream
+        let ended_at = ended.then(now);
+        self.execute(
+            "UPDATE history SET position = ?1, duration = COALESCE(?2, duration),
+                completion = MIN(100.0, MAX(0.0,
+                    ?1 * 100.0 / NULLIF(COALESCE(?2, duration), 0))),
+                ended_at = COALESCE(?3, ended_at)
+            WHERE id = ?4",
+            params![position, duration, ended_at, id],
+        )
+        .map(|_| ())
+    }
+
+    // The latest playback of each file, most recent first
+    pub fn recent(&self, limit: u32) -> Result<Vec<HistoryEntry>, String> {
+        let sql = format!(
+            "SELECT {} FROM history h LEFT JOIN media m ON m.path = h.path
+            WHERE h.id IN (SELECT MAX(id) FROM history GROUP BY path)
+            ORDER BY h.started_at DESC, h.id DESC LIMIT ?",
+            ENTRY_COLUMNS
+        );
+        query_all(
+            &self.db.lock().unwrap(),
+            &sql,
+            [limit],
+            HistoryEntry::from_row,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn history(&self, range: &HistoryRange) -> Result<Vec<HistoryEntry>, String> {
+        let sql = format!(
+            "SELECT {} FROM history h LEFT JOIN media m ON m.path = h.path
+            WHERE h.started_at >= COALESCE(?, h.started_at)
+                AND h.started_at < COALESCE(?, h.started_at + 1)
+            ORDER BY h.started_at DESC, h.id DESC LIMIT ? OFFSET ?",
+            ENTRY_COLUMNS
+        );
+        let params = params![
+            range.from,
+            range.to,
+            // -1 is SQLite for no limit
+            range.limit.map_or(-1, i64::from),
+            range.offset.unwrap_or(0),
+        ];
+        query_all(
+            &self.db.lock().unwrap(),
+            &sql,
+            params,
+            HistoryEntry::from_row,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    // Returns how many entries were removed
+    pub fn clear_history(&self) -> Result<usize, String> {
+        self.execute("DELETE FROM history", [])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recent_lists_each_file_once() {
+        let library = Library::in_memory().unwrap();
+        let first = library
+            .start_playback("/videos/a.mkv", Some("A".to_string()), Some(200.0))
+            .unwrap();
+        library.update_playback(first, 50.0, None, true).unwrap();
+        let other = library.start_playback("/videos/b.mkv", None, None).unwrap();
+        library.update_playback(other, 12.0, None, false).unwrap();
+        let again = library
+            .start_playback("/videos/a.mkv", Some("A".to_string()), Some(200.0))
+            .unwrap();
+        library
+            .update_playback(again, 150.0, Some(200.0), false)
+            .unwrap();
+
+        let recent = library.recent(10).unwrap();
+        let ids: Vec<_> = recent.iter().map(|e| e.id).collect();
+        assert_eq!(ids, [again, other]);
+        assert_eq!(recent[0].completion, Some(75.0));
+        assert_eq!(recent[0].ended_at, None);
+        // Streams have no duration to measure against
+        assert_eq!(recent[1].completion, None);
+
+        let all = library.history(&HistoryRange::default()).unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[2].completion, Some(25.0));
+        assert!(all[2].ended_at.is_some());
+        let before = HistoryRange {
+            to: Some(all[0].started_at),
+            ..HistoryRange::default()
+        };
+        assert!(library.history(&before).unwrap().is_empty());
+
+        assert_eq!(library.clear_history().unwrap(), 3);
+        assert!(library.recent(10).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,588 @@
+// The media library: folders the user asked us to watch, and what a scan of them found.
+// Each file's duration, resolution and codecs are read once with a headless mpv
+// (probe.rs) and kept in SQLite, so the page can list thousands of files without
+// opening any. A rescan only probes files whose size or modification time changed, and
+// then queues poster frames for the new videos (thumbnails.rs).
+
+mod history;
+mod probe;
+mod search;
+mod thumbnails;
+
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, Params, Row};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::media;
+pub use history::{HistoryEntry, HistoryRange};
+pub use probe::{Probed, Prober};
+pub use search::{LibraryFilters, SearchPage};
+pub use thumbnails::{grab_frame, Thumbnailer, THUMBNAILS_DIR};
+
+pub const DATABASE_FILE: &str = "library.sqlite3";
+
+// Each entry upgrades the schema by one version, tracked in PRAGMA user_version. Append
+// to this; never edit a migration that has shipped.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE folders (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        added_at INTEGER NOT NULL
+    );
+    CREATE TABLE media (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL UNIQUE,
+        folder_id INTEGER NOT NULL REFERENCES folders(id) ON DELETE CASCADE,
+        title TEXT,
+        size INTEGER NOT NULL,
+        mtime INTEGER NOT NULL,
+        duration REAL,
+        width INTEGER,
+        height INTEGER,
+        video_codec TEXT,
+        audio_codec TEXT,
+        scanned_at INTEGER NOT NULL
+    );
+    CREATE INDEX media_folder ON media(folder_id);",
+    "CREATE TABLE history (
+        id INTEGER PRIMARY KEY,
+        path TEXT NOT NULL,
+        title TEXT,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER,
+        position REAL,
+        duration REAL,
+        completion REAL
+    );
+    CREATE INDEX history_path ON history(path);
+    CREATE INDEX history_started ON history(started_at);",
+    "ALTER TABLE media ADD COLUMN thumbnail TEXT;",
+];
+
+const MEDIA_COLUMNS: &str = "id, folder_id, path, title, size, mtime, duration, width, \
+                             height, video_codec, audio_codec, scanned_at, thumbnail";
+
+// Times are seconds since the Unix epoch
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LibraryFolder {
+    pub id: i64,
+    pub path: String,
+    pub added_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LibraryItem {
+    pub id: i64,
+    pub folder_id: i64,
+    pub path: String,
+    // The file's tags, else its name
+    pub title: Option<String>,
+    pub size: i64,
+    pub mtime: i64,
+    pub duration: Option<f64>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    pub scanned_at: i64,
+    // A JPEG poster frame in the app's cache, once one has been made; never for audio
+    pub thumbnail: Option<String>,
+}
+
+impl LibraryItem {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get(0)?,
+            folder_id: row.get(1)?,
+            path: row.get(2)?,
+            title: row.get(3)?,
+            size: row.get(4)?,
+            mtime: row.get(5)?,
+            duration: row.get(6)?,
+            width: row.get(7)?,
+            height: row.get(8)?,
+            video_codec: row.get(9)?,
+            audio_codec: row.get(10)?,
+            scanned_at: row.get(11)?,
+            thumbnail: row.get(12)?,
+        })
+    }
+}
+
+// Filters for library_list; all are optional
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LibraryQuery {
+    pub folder_id: Option<i64>,
+    // Matched case-insensitively against the title and the path
+    pub search: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ScanSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    // Files that couldn't be opened; they're tried again on the next rescan
+    pub failed: usize,
+}
+
+pub struct Library {
+    db: Mutex<Connection>,
+    scanning: AtomicBool,
+}
+
+impl Library {
+    pub fn open(path: &Path) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create data directory: {}", e))?;
+        }
+        let db = Connection::open(path)
+            .map_err(|e| format!("Failed to open the library database: {}", e))?;
+        Self::with_connection(db)
+    }
+
+    // Used when the database file can't be opened, so the library still works until quit
+    pub fn in_memory() -> Result<Self, String> {
+        let db = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open the library database: {}", e))?;
+        Self::with_connection(db)
+    }
+
+    fn with_connection(mut db: Connection) -> Result<Self, String> {
+        migrate(&mut db).map_err(|e| format!("Failed to set up the library database: {}", e))?;
+        Ok(Self {
+            db: Mutex::new(db),
+            scanning: AtomicBool::new(false),
+        })
+    }
+
+    pub fn add_folder(&self, path: &Path) -> Result<LibraryFolder, String> {
+        if !path.is_dir() {
+            return Err(format!("{} is not a folder", path.display()));
+        }
+        let path = path.to_string_lossy().into_owned();
+        let db = self.db.lock().unwrap();
+        db.execute(
+            "INSERT INTO folders (path, added_at) VALUES (?, ?) ON CONFLICT(path) DO NOTHING",
+            params![path, now()],
+        )
+        .map_err(|e| format!("Failed to add {}: {}", path, e))?;
+        query_all(
+            &db,
+            "SELECT id, path, added_at FROM folders WHERE path = ?",
+            [&path],
+            folder_from_row,
+        )
+        .map_err(|e| e.to_string())?
+        .pop()
+        .ok_or_else(|| "Folder vanished while being added".to_string())
+    }
+
+    // Forgets the folder and everything found in it. Returns false if it wasn't watched.
+    pub fn remove_folder(&self, id: i64) -> Result<bool, String> {
+        self.delete_thumbnails("folder_id = ?", id)?;
+        let removed = self
+            .db
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM folders WHERE id = ?", [id])
+            .map_err(|e| format!("Failed to remove the folder: {}", e))?;
+        Ok(removed > 0)
+    }
+
+    pub fn folders(&self) -> Result<Vec<LibraryFolder>, String> {
+        query_all(
+            &self.db.lock().unwrap(),
+            "SELECT id, path, added_at FROM folders ORDER BY path",
+            [],
+            folder_from_row,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn list(&self, query: &LibraryQuery) -> Result<Vec<LibraryItem>, String> {
+        let mut sql = format!("SELECT {} FROM media WHERE 1", MEDIA_COLUMNS);
+        let mut params: Vec<Value> = Vec::new();
+        if let Some(folder_id) = query.folder_id {
+            sql.push_str(" AND folder_id = ?");
+            params.push(folder_id.into());
+        }
+        if let Some(search) = query.search.as_deref().map(str::trim) {
+            if !search.is_empty() {
+                sql.push_str(" AND (title LIKE ? ESCAPE '\\' OR path LIKE ? ESCAPE '\\')");
+                let pattern = format!("%{}%", escape_like(search));
+                params.push(pattern.clone().into());
+                params.push(pattern.into());
+            }
+        }
+        // LIMIT -1 is SQLite for no limit, which OFFSET needs
+        sql.push_str(" ORDER BY title COLLATE NOCASE, path LIMIT ? OFFSET ?");
+        params.push(query.limit.map_or(-1, i64::from).into());
+        params.push(i64::from(query.offset.unwrap_or(0)).into());
+
+        query_all(
+            &self.db.lock().unwrap(),
+            &sql,
+            params_from_iter(&params),
+            LibraryItem::from_row,
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<LibraryItem>, String> {
+        let sql = format!("SELECT {} FROM media WHERE id = ?", MEDIA_COLUMNS);
+        Ok(
+            query_all(&self.db.lock().unwrap(), &sql, [id], LibraryItem::from_row)
+                .map_err(|e| e.to_string())?
+                .pop(),
+        )
+    }
+
+    // Walk every watched folder and bring the database up to date with what's on disk.
+    // Only one scan runs at a time; None if one already is.
+    pub fn rescan(&self) -> Option<Result<ScanSummary, String>> {
+        let mut prober = None;
+        self.rescan_with(|path| {
+            if prober.is_none() {
+                prober = Some(Prober::new()?);
+            }
+            prober.as_ref().unwrap().probe(path)
+        })
+    }
+
+    fn rescan_with(
+        &self,
+        probe: impl FnMut(&Path) -> Result<Probed, String>,
+    ) -> Option<Result<ScanSummary, String>> {
+        if self.scanning.swap(true, Ordering::SeqCst) {
+            return None;
+        }
+        let result = self.scan(probe);
+        self.scanning.store(false, Ordering::SeqCst);
+        Some(result)
+    }
+
+    fn scan(
+        &self,
+        mut probe: impl FnMut(&Path) -> Result<Probed, String>,
+    ) -> Result<ScanSummary, String> {
+        // Walking the folders and probing files is slow, so the database is only locked
+        // for each statement, and the page can keep querying meanwhile
+        let folders = self.folders()?;
+        let known: HashMap<String, (i64, i64, i64, i64)> = query_all(
+            &self.db.lock().unwrap(),
+            "SELECT path, id, folder_id, size, mtime FROM media",
+            [],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    (row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?),
+                ))
+            },
+        )
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+
+        let mut summary = ScanSummary::default();
+        let mut seen = HashSet::new();
+        for folder in &folders {
+            // An unplugged drive or a share that's offline would otherwise look empty, and
+            // lose its files; they're kept as they were until it can be read again
+            if Path::new(&folder.path).read_dir().is_err() {
+                seen.extend(
+                    known
+                        .iter()
+                        .filter(|(_, &(_, folder_id, ..))| folder_id == folder.id)
+                        .map(|(path, _)| path.clone()),
+                );
+                continue;
+            }
+            for file in media::scan_directory(Path::new(&folder.path), true) {
+                // A file under two watched folders (one inside the other) belongs to the
+                // first one listed
+                if !seen.insert(file.path.clone()) {
+                    continue;
+                }
+                let Some((size, mtime)) = file_stamp(Path::new(&file.path)) else {
+                    continue;
+                };
+                let previous = known.get(&file.path);
+                match previous {
+                    Some(&(id, folder_id, old_size, old_mtime))
+                        if old_size == size && old_mtime == mtime =>
+                    {
+                        if folder_id != folder.id {
+                            self.execute(
+                                "UPDATE media SET folder_id = ? WHERE id = ?",
+                                [folder.id, id],
+                            )?;
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+
+                if let Some(&(id, ..)) = previous {
+                    self.delete_thumbnails("id = ?", id)?;
+                }
+                let probed = match probe(Path::new(&file.path)) {
+                    Ok(probed) => probed,
+                    Err(e) => {
+                        eprintln!("Library scan skipped {}: {}", file.path, e);
+                        summary.failed += 1;
+                        continue;
+                    }
+                };
+                self.execute(
+                    "INSERT INTO media (path, folder_id, title, size, mtime, duration, width,
+                        height, video_codec, audio_codec, scanned_at)
+                    VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                    ON CONFLICT(path) DO UPDATE SET folder_id = excluded.folder_id,
+                        title = excluded.title, size = excluded.size, mtime = excluded.mtime,
+                        duration = excluded.duration, width = excluded.width,
+                        height = excluded.height, video_codec = excluded.video_codec,
+                        audio_codec = excluded.audio_codec, scanned_at = excluded.scanned_at,
+                        thumbnail = NULL",
+                    params![
+                        file.path,
+                        folder.id,
+                        probed.title,
+                        size,
+                        mtime,
+                        probed.duration,
+                        probed.width,
+                        probed.height,
+                        probed.video_codec,
+                        probed.audio_codec,
+                        now(),
+                    ],
+                )?;
+                if previous.is_some() {
+                    summary.updated += 1;
+                } else {
+                    summary.added += 1;
+                }
+            }
+        }
+
+        for (path, &(id, ..)) in &known {
+            if !seen.contains(path) {
+                self.delete_thumbnails("id = ?", id)?;
+                self.execute("DELETE FROM media WHERE id = ?", [id])?;
+                summary.removed += 1;
+            }
+        }
+        Ok(summary)
+    }
+
+    fn execute(&self, sql: &str, params: impl Params) -> Result<usize, String> {
+        self.db
+            .lock()
+            .unwrap()
+            .execute(sql, params)
+            .map_err(|e| e.to_string())
+    }
+}
+
+fn migrate(db: &mut Connection) -> rusqlite::Result<()> {
+    db.pragma_update(None, "foreign_keys", true)?;
+    // Replies with the mode it ended up in, which in memory stays "memory"
+    db.pragma_update_and_check(None, "journal_mode", "WAL", |_| Ok(()))?;
+    let version: i64 = db.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        // Rolled back if dropped before the commit
+        let tx = db.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i as i64 + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+// Every row `sql` returns, mapped
+fn query_all<T>(
+    db: &Connection,
+    sql: &str,
+    params: impl Params,
+    map: impl FnMut(&Row) -> rusqlite::Result<T>,
+) -> rusqlite::Result<Vec<T>> {
+    let mut statement = db.prepare(sql)?;
+    let rows = statement.query_map(params, map)?.collect();
+    rows
+}
+
+fn folder_from_row(row: &Row) -> rusqlite::Result<LibraryFolder> {
+    Ok(LibraryFolder {
+        id: row.get(0)?,
+        path: row.get(1)?,
+        added_at: row.get(2)?,
+    })
+}
+
+// Size in bytes and modification time, which together say whether a file changed
+fn file_stamp(path: &Path) -> Option<(i64, i64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let mtime = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64);
+    Some((metadata.len() as i64, mtime))
+}
+
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    fn probed(title: &str) -> Result<Probed, String> {
+        Ok(Probed {
+            title: Some(title.to_string()),
+            duration: Some(60.0),
+            width: Some(1920),
+            height: Some(1080),
+            video_codec: Some("h264".to_string()),
+            audio_codec: Some("aac".to_string()),
+        })
+    }
+
+    #[test]
+    fn rescan_probes_only_what_changed() {
+        let dir = std::env::temp_dir().join(format!("library-scan-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("season 1")).unwrap();
+        for name in ["a.mkv", "season 1/b.mkv", "broken.mp4", "notes.txt"] {
+            std::fs::write(dir.join(name), "x").unwrap();
+        }
+
+        let library = Library::in_memory().unwrap();
+        let folder = library.add_folder(&dir).unwrap();
+        assert_eq!(library.add_folder(&dir).unwrap(), folder);
+
+        let probed_paths = RefCell::new(Vec::new());
+        let mut probe = |path: &Path| {
+            probed_paths
+                .borrow_mut()
+                .push(path.file_name().unwrap().to_string_lossy().into_owned());
+            if path.ends_with("broken.mp4") {
+                return Err("unreadable".to_string());
+            }
+            probed(&path.file_stem().unwrap().to_string_lossy())
+        };
+        let summary = library.rescan_with(&mut probe).unwrap().unwrap();
+        assert_eq!(
+            summary,
+            ScanSummary {
+                added: 2,
+                failed: 1,
+                ..ScanSummary::default()
+            }
+        );
+
+        let items = library.list(&LibraryQuery::default()).unwrap();
+        let titles: Vec<_> = items.iter().map(|i| i.title.as_deref().unwrap()).collect();
+        assert_eq!(titles, ["a", "b"]);
+        assert_eq!(items[0].width, Some(1920));
+        assert_eq!(library.get(items[1].id).unwrap(), Some(items[1].clone()));
+
+        std::fs::write(dir.join("a.mkv"), "longer").unwrap();
+        std::fs::remove_file(dir.join("season 1/b.mkv")).unwrap();
+        probed_paths.borrow_mut().clear();
+        let summary = library.rescan_with(&mut probe).unwrap().unwrap();
+        assert_eq!(*probed_paths.borrow(), ["a.mkv", "broken.mp4"]);
+        assert_eq!(summary.updated, 1);
+        assert_eq!(summary.removed, 1);
+
+        assert!(library.remove_folder(folder.id).unwrap());
+        assert!(library.list(&LibraryQuery::default()).unwrap().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn missing_folders_keep_their_files() {
+        let dir = std::env::temp_dir().join(format!("library-missing-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.mkv"), "x").unwrap();
+
+        let library = Library::in_memory().unwrap();
+        library.add_folder(&dir).unwrap();
+        let scan = || {
+            library
+                .rescan_with(|path| probed(&path.file_stem().unwrap().to_string_lossy()))
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(scan().added, 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(scan(), ScanSummary::default());
+        assert_eq!(library.list(&LibraryQuery::default()).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn list_filters_by_search_text() {
+        let dir = std::env::temp_dir().join(format!("library-search-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["Holiday_2019.mp4", "Holiday 2020.mp4", "concert.mkv"] {
+            std::fs::write(dir.join(name), "x").unwrap();
+        }
+        let library = Library::in_memory().unwrap();
+        library.add_folder(&dir).unwrap();
+        library
+            .rescan_with(|path| probed(&path.file_stem().unwrap().to_string_lossy()))
+            .unwrap()
+            .unwrap();
+
+        let search = |text: &str| {
+            let query = LibraryQuery {
+                search: Some(text.to_string()),
+                ..LibraryQuery::default()
+            };
+            library
+                .list(&query)
+                .unwrap()
+                .into_iter()
+                .map(|i| i.title.unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(search("holiday"), ["Holiday 2020", "Holiday_2019"]);
+        // _ is a wildcard in LIKE, but not in a search
+        assert_eq!(search("y_"), ["Holiday_2019"]);
+        assert_eq!(search("  "), search(""));
+
+        let page = LibraryQuery {
+            limit: Some(1),
+            offset: Some(1),
+            ..LibraryQuery::default()
+        };
+        assert_eq!(
+            library.list(&page).unwrap()[0].title.as_deref(),
+            Some("Holiday 2020")
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,96 @@
+// Reads a file's duration, size and codecs by opening it in a headless mpv instance of
+// its own, so a rescan never disturbs what the players are doing. Nothing is decoded
+// beyond what opening the file takes.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::mpv::{EndFileReason, Event, MpvHandle, PlayerBackend};
+
+// Long enough for a slow network share; a file that takes longer is reported as failed
+const OPEN_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Probed {
+    pub title: Option<String>,
+    pub duration: Option<f64>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+}
+
+pub struct Prober {
+    handle: MpvHandle,
+}
+
+impl Prober {
+    pub fn new() -> Result<Self, String> {
+        let handle = MpvHandle::create().map_err(|e| format!("Failed to create mpv: {}", e))?;
+        for (name, value) in [
+            ("vo", "null"),
+            ("ao", "null"),
+            ("pause", "yes"),
+            ("idle", "yes"),
+            // Keep the user's mpv.conf and scripts out of it
+            ("config", "no"),
+            ("load-scripts", "no"),
+            ("ytdl", "no"),
+            ("terminal", "no"),
+        ] {
+            handle
+                .set_option_string(name, value)
+                .map_err(|e| format!("Failed to set {}: {}", name, e))?;
+        }
+        handle
+            .initialize()
+            .map_err(|e| format!("Failed to initialize mpv: {}", e))?;
+        Ok(Self { handle })
+    }
+
+    pub fn probe(&self, path: &Path) -> Result<Probed, String> {
+        let path = path.to_string_lossy();
+        self.handle
+            .command(&["loadfile", &path, "replace"])
+            .map_err(|e| format!("Failed to open {}: {}", path, e))?;
+
+        // Events for whatever was loaded before can still be queued; only the StartFile
+        // for this loadfile says which entry id the rest will carry
+        let deadline = Instant::now() + OPEN_TIMEOUT;
+        let mut entry = None;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(format!("Timed out opening {}", path));
+            }
+            match self.handle.wait_event(remaining.as_secs_f64()) {
+                Event::StartFile { playlist_entry_id } => entry = Some(playlist_entry_id),
+                Event::FileLoaded if entry.is_some() => break,
+                Event::EndFile {
+                    reason,
+                    playlist_entry_id,
+                } if entry == Some(playlist_entry_id) => {
+                    return Err(match reason {
+                        EndFileReason::Error(e) => format!("Failed to open {}: {}", path, e),
+                        _ => format!("Failed to open {}", path),
+                    });
+                }
+                Event::Shutdown => return Err("mpv shut down".to_string()),
+                _ => {}
+            }
+        }
+
+        let string = |name: &str| self.handle.get::<String>(name).ok();
+        let probed = Probed {
+            title: string("media-title"),
+            duration: self.handle.get::<f64>("duration").ok(),
+            width: self.handle.get::<i64>("width").ok(),
+            height: self.handle.get::<i64>("height").ok(),
+            video_codec: string("video-format"),
+            audio_codec: string("audio-codec-name"),
+        };
+        // Release the file, so it can be moved or deleted while the scan goes on
+        let _ = self.handle.command(&["stop"]);
+        Ok(probed)
+    }
+}
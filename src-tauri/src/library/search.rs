@@ -0,0 +1,174 @@
+// library_search: the filters narrow the rows in SQL, then the query is matched against
+// what's left here, since LIKE can't rank or forgive a missing letter. Each word of the
+// query has to match the title (or file name) somewhere; whole-word and substring
+// matches rank above fuzzy ones, where the letters only appear in order.
+
+use rusqlite::params_from_iter;
+use rusqlite::types::Value;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use super::{query_all, Library, LibraryItem, MEDIA_COLUMNS};
+use crate::resume_position::WATCHED_FRACTION;
+
+// How many results a page has when the filters don't say
+const DEFAULT_PAGE_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct LibraryFilters {
+    pub folder_id: Option<i64>,
+    // Seconds
+    pub min_duration: Option<f64>,
+    pub max_duration: Option<f64>,
+    // Video height in pixels, e.g. 720 for at least HD; files without video never match
+    pub min_height: Option<i64>,
+    pub max_height: Option<i64>,
+    // Never watched to the end
+    pub unwatched: bool,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SearchPage {
+    pub items: Vec<LibraryItem>,
+    // Matches across all pages
+    pub total: usize,
+}
+
+impl Library {
+    pub fn search(&self, query: &str, filters: &LibraryFilters) -> Result<SearchPage, String> {
+        let mut sql = format!("SELECT {} FROM media WHERE 1", MEDIA_COLUMNS);
+        let mut params: Vec<Value> = Vec::new();
+        let mut filter = |clause: &str, value: Value| {
+            sql.push_str(clause);
+            params.push(value);
+        };
+        if let Some(folder_id) = filters.folder_id {
+            filter(" AND folder_id = ?", folder_id.into());
+        }
+        if let Some(min) = filters.min_duration {
+            filter(" AND duration >= ?", min.into());
+        }
+        if let Some(max) = filters.max_duration {
+            filter(" AND duration <= ?", max.into());
+        }
+        if let Some(min) = filters.min_height {
+            filter(" AND height >= ?", min.into());
+        }
+        if let Some(max) = filters.max_height {
+            filter(" AND height <= ?", max.into());
+        }
+        if filters.unwatched {
+            filter(
+                " AND NOT EXISTS (SELECT 1 FROM history h
+                    WHERE h.path = media.path AND h.completion >= ?)",
+                (WATCHED_FRACTION * 100.0).into(),
+            );
+        }
+        sql.push_str(" ORDER BY title COLLATE NOCASE, path");
+
+        let rows = query_all(
+            &self.db.lock().unwrap(),
+            &sql,
+            params_from_iter(&params),
+            LibraryItem::from_row,
+        )
+        .map_err(|e| e.to_string())?;
+
+        let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+        let mut matches: Vec<(u32, LibraryItem)> = rows
+            .into_iter()
+            .filter_map(|item| Some((score(&words, &searchable(&item))?, item)))
+            .collect();
+        // Stable, so equal scores keep the title order
+        matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+
+        let total = matches.len();
+        let items = matches
+            .into_iter()
+            .skip(filters.offset.unwrap_or(0))
+            .take(filters.limit.unwrap_or(DEFAULT_PAGE_SIZE))
+            .map(|(_, item)| item)
+            .collect();
+        Ok(SearchPage { items, total })
+    }
+}
+
+fn searchable(item: &LibraryItem) -> String {
+    let name = Path::new(&item.path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    match &item.title {
+        Some(title) if *title != name => format!("{} {}", title, name),
+        _ => name,
+    }
+    .to_lowercase()
+}
+
+// Higher is better; None if any word doesn't match at all. No words match everything.
+fn score(words: &[String], text: &str) -> Option<u32> {
+    words.iter().map(|word| word_score(word, text)).sum()
+}
+
+fn word_score(word: &str, text: &str) -> Option<u32> {
+    if let Some(index) = text.find(word) {
+        let at_word_start = !text[..index]
+            .chars()
+            .next_back()
+            .is_some_and(char::is_alphanumeric);
+        return Some(if at_word_start { 300 } else { 200 });
+    }
+    fuzzy_score(word, text)
+}
+
+// The word's letters in order, anywhere in the text; the fewer letters skipped between
+// them, the better
+fn fuzzy_score(word: &str, text: &str) -> Option<u32> {
+    let mut chars = text.chars();
+    let mut skipped = 0u32;
+    let mut started = false;
+    for wanted in word.chars() {
+        loop {
+            let c = chars.next()?;
+            if c == wanted {
+                started = true;
+                break;
+            }
+            if started {
+                skipped += 1;
+            }
+        }
+    }
+    Some(100u32.saturating_sub(skipped).max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(query: &str) -> Vec<String> {
+        query.split_whitespace().map(str::to_lowercase).collect()
+    }
+
+    #[test]
+    fn every_word_must_match() {
+        let text = "the grand budapest hotel";
+        assert_eq!(score(&words("Grand Hotel"), text), Some(600));
+        assert_eq!(score(&words("rand"), text), Some(200));
+        assert_eq!(score(&words("grand museum"), text), None);
+        assert_eq!(score(&words(""), text), Some(0));
+    }
+
+    #[test]
+    fn fuzzy_matches_rank_below_substrings() {
+        let text = "the grand budapest hotel";
+        let fuzzy = score(&words("grnd"), text).unwrap();
+        assert!(fuzzy < 200 && fuzzy > 0);
+        // Letters further apart match worse
+        assert!(score(&words("gbh"), text).unwrap() < fuzzy);
+        assert_eq!(score(&words("hotle"), text), None);
+    }
+}
@@ -0,0 +1,278 @@
+// Poster frames for the library's videos, made in the background after a scan. Each is
+// a small JPEG grabbed by ffmpeg a fifth of the way in (past most intros), kept in the
+// app's cache dir under a name derived from the file's path, size and modification
+// time, and recorded in the media table. Only a couple of ffmpeg processes run at once,
+// however many files a scan found, so playback and the page stay responsive.
+
+use rusqlite::params;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::{query_all, Library};
+use crate::clip::ffmpeg_available;
+use crate::file_settings::path_digest;
+
+pub const THUMBNAILS_DIR: &str = "thumbnails";
+
+// Where in the file the poster frame is taken, as a share of its duration
+const POSTER_AT: f64 = 0.2;
+
+const WIDTH: u32 = 320;
+
+// Decoding is the expensive part; more workers than this only slow everything else down
+const MAX_WORKERS: usize = 2;
+
+// A video without a thumbnail, as it was when it was scanned
+#[derive(Debug, Clone, PartialEq)]
+struct Job {
+    id: i64,
+    path: String,
+    duration: Option<f64>,
+    size: i64,
+    mtime: i64,
+}
+
+// Payload of library://thumbnail
+#[derive(Debug, Clone, Serialize)]
+struct ThumbnailReady {
+    id: i64,
+    thumbnail: String,
+}
+
+pub struct Thumbnailer {
+    // None without ffmpeg or a cache dir; nothing gets a thumbnail then
+    jobs: Option<Sender<Job>>,
+    // Ids queued or being worked on, so repeated scans don't queue them twice
+    pending: Arc<Mutex<HashSet<i64>>>,
+}
+
+impl Thumbnailer {
+    pub fn start(app: AppHandle, dir: Option<PathBuf>) -> Self {
+        let pending = Arc::new(Mutex::new(HashSet::new()));
+        let Some(dir) = dir else {
+            eprintln!("No cache directory; library items won't get thumbnails");
+            return Self {
+                jobs: None,
+                pending,
+            };
+        };
+        if !ffmpeg_available() {
+            eprintln!("Library thumbnails need ffmpeg on PATH");
+            return Self {
+                jobs: None,
+                pending,
+            };
+        }
+
+        let (jobs, receiver) = mpsc::channel();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = std::thread::available_parallelism()
+            .map_or(1, |n| n.get() / 4)
+            .clamp(1, MAX_WORKERS);
+        for _ in 0..workers {
+            let app = app.clone();
+            let dir = dir.clone();
+            let receiver = receiver.clone();
+            let pending = pending.clone();
+            std::thread::spawn(move || work(&app, &dir, &receiver, &pending));
+        }
+        Self {
+            jobs: Some(jobs),
+            pending,
+        }
+    }
+
+    // Queue every video in the library that doesn't have a thumbnail yet; returns how
+    // many were newly queued
+    pub fn queue_missing(&self, library: &Library) -> Result<usize, String> {
+        let Some(jobs) = &self.jobs else {
+            return Ok(0);
+        };
+        let mut pending = self.pending.lock().unwrap();
+        let mut queued = 0;
+        for job in library.missing_thumbnails()? {
+            if pending.insert(job.id) && jobs.send(job).is_ok() {
+                queued += 1;
+            }
+        }
+        Ok(queued)
+    }
+}
+
+fn work(app: &AppHandle, dir: &Path, jobs: &Mutex<Receiver<Job>>, pending: &Mutex<HashSet<i64>>) {
+    loop {
+        // Held only while waiting, so the other workers can take the next job meanwhile
+        let Ok(job) = jobs.lock().unwrap().recv() else {
+            return;
+        };
+        match generate(dir, &job) {
+            Ok(file) => {
+                let thumbnail = file.to_string_lossy().into_owned();
+                match app.state::<Library>().set_thumbnail(&job, &thumbnail) {
+                    Ok(true) => {
+                        let ready = ThumbnailReady {
+                            id: job.id,
+                            thumbnail,
+                        };
+                        if let Err(e) = app.emit("library://thumbnail", ready) {
+                            eprintln!("Failed to emit library://thumbnail: {}", e);
+                        }
+                    }
+                    // Changed or removed since; the next scan takes care of it
+                    Ok(false) => {}
+                    Err(e) => eprintln!("Failed to record the thumbnail of {}: {}", job.path, e),
+                }
+            }
+            Err(e) => eprintln!("No thumbnail for {}: {}", job.path, e),
+        }
+        pending.lock().unwrap().remove(&job.id);
+    }
+}
+
+// A changed file gets a new name, so a stale frame is never shown for it
+fn thumbnail_file(dir: &Path, job: &Job) -> PathBuf {
+    let key = format!("{}\n{}\n{}", job.path, job.size, job.mtime);
+    dir.join(format!("{}.jpg", path_digest(&key)))
+}
+
+fn generate(dir: &Path, job: &Job) -> Result<PathBuf, String> {
+    let file = thumbnail_file(dir, job);
+    if file.exists() {
+        return Ok(file);
+    }
+    std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create the thumbnails directory: {}", e))?;
+    let at = job.duration.map_or(0.0, |duration| duration * POSTER_AT);
+    grab_frame(&job.path, at, WIDTH, &file)?;
+    Ok(file)
+}
+
+// One frame of `input` at `at` seconds, scaled to `width`, as a JPEG at `output`. Also
+// used for chapter thumbnails.
+pub fn grab_frame(input: &str, at: f64, width: u32, output: &Path) -> Result<(), String> {
+    // Written under another name first, so a crash never leaves a truncated image behind
+    let partial = output.with_extension("part.jpg");
+    let result = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostdin", "-loglevel", "error", "-y"])
+        // Before -i, so ffmpeg seeks by keyframe instead of decoding its way there
+        .args(["-ss", &format!("{:.3}", at)])
+        .args(["-i", input])
+        .args(["-frames:v", "1", "-an", "-sn"])
+        .args(["-vf", &format!("scale={}:-2", width), "-q:v", "4"])
+        .arg(&partial)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+    if !result.status.success() || !partial.exists() {
+        let _ = std::fs::remove_file(&partial);
+        let stderr = String::from_utf8_lossy(&result.stderr);
+        let reason = stderr.lines().last().unwrap_or("no frame decoded");
+        return Err(format!("ffmpeg failed: {}", reason));
+    }
+    std::fs::rename(&partial, output).map_err(|e| format!("Failed to save thumbnail: {}", e))
+}
+
+impl Library {
+    // Videos only: audio files have nothing to show but their cover, if that
+    fn missing_thumbnails(&self) -> Result<Vec<Job>, String> {
+        query_all(
+            &self.db.lock().unwrap(),
+            "SELECT id, path, duration, size, mtime FROM media
+            WHERE thumbnail IS NULL AND height IS NOT NULL ORDER BY id",
+            [],
+            |row| {
+                Ok(Job {
+                    id: row.get(0)?,
+                    path: row.get(1)?,
+                    duration: row.get(2)?,
+                    size: row.get(3)?,
+                    mtime: row.get(4)?,
+                })
+            },
+        )
+        .map_err(|e| e.to_string())
+    }
+
+    // False if the file changed since the job was queued, so the frame is already stale
+    fn set_thumbnail(&self, job: &Job, thumbnail: &str) -> Result<bool, String> {
+        let updated = self.execute(
+            "UPDATE media SET thumbnail = ? WHERE id = ? AND size = ? AND mtime = ?",
+            params![thumbnail, job.id, job.size, job.mtime],
+        )?;
+        if updated == 0 {
+            let _ = std::fs::remove_file(thumbnail);
+        }
+        Ok(updated > 0)
+    }
+
+    // Remove the cached frames of the media rows `condition` (on one parameter) selects,
+    // before the rows themselves are changed or deleted
+    pub(super) fn delete_thumbnails(&self, condition: &str, param: i64) -> Result<(), String> {
+        let sql = format!(
+            "SELECT thumbnail FROM media WHERE thumbnail IS NOT NULL AND {}",
+            condition
+        );
+        let files: Vec<String> =
+            query_all(&self.db.lock().unwrap(), &sql, [param], |row| row.get(0))
+                .map_err(|e| e.to_string())?;
+        for file in files {
+            let _ = std::fs::remove_file(file);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{LibraryQuery, Probed};
+    use super::*;
+
+    #[test]
+    fn thumbnails_follow_the_file() {
+        let dir = std::env::temp_dir().join(format!("library-thumbnails-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["film.mkv", "song.mp3"] {
+            std::fs::write(dir.join(name), "x").unwrap();
+        }
+        let library = Library::in_memory().unwrap();
+        library.add_folder(&dir).unwrap();
+        let probe = |path: &Path| {
+            let video = path.extension().is_some_and(|ext| ext == "mkv");
+            Ok(Probed {
+                duration: Some(100.0),
+                height: video.then_some(1080),
+                ..Probed::default()
+            })
+        };
+        library.rescan_with(probe).unwrap().unwrap();
+
+        let missing = library.missing_thumbnails().unwrap();
+        assert_eq!(missing.len(), 1);
+        assert!(missing[0].path.ends_with("film.mkv"));
+
+        let frame = thumbnail_file(&dir, &missing[0]);
+        std::fs::write(&frame, "jpeg").unwrap();
+        let thumbnail = frame.to_string_lossy();
+        assert!(library.set_thumbnail(&missing[0], &thumbnail).unwrap());
+        assert!(library.missing_thumbnails().unwrap().is_empty());
+        let items = library.list(&LibraryQuery::default()).unwrap();
+        assert_eq!(items[0].thumbnail.as_deref(), Some(&*thumbnail));
+
+        // A changed file loses its old frame, and needs a new one
+        std::fs::write(dir.join("film.mkv"), "longer").unwrap();
+        library.rescan_with(probe).unwrap().unwrap();
+        assert!(!frame.exists());
+        let missing_again = library.missing_thumbnails().unwrap();
+        assert_eq!(missing_again.len(), 1);
+        assert_ne!(thumbnail_file(&dir, &missing_again[0]), frame);
+        // A frame made from the old version isn't recorded
+        assert!(!library.set_thumbnail(&missing[0], &thumbnail).unwrap());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::commands::passthrough::check_unmanaged;
+use crate::error::PlayerError;
+
+// Per-file options for loadfile; they apply to that file only and reset afterwards
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct LoadOptions {
+    // Seconds; negative values count back from the end, as in mpv
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+    // Show the first frame but don't start playing
+    pub pause_on_load: bool,
+    // Start where the file was left last time (see resume_position), unless `start` is
+    // given. Not an mpv option, so encode leaves it out.
+    pub resume: bool,
+    // Any other mpv option, e.g. {"sub-delay": "0.5"}
+    pub extra: HashMap<String, String>,
+}
+
+// Quote a value for a `key=value,...` mpv option list using the `%len%` form,
+// so commas, quotes and equals signs inside paths need no further escaping
+pub fn quote_option_value(value: &str) -> String {
+    // mpv counts bytes, not characters
+    format!("%{}%{}", value.len(), value)
+}
+
+fn format_time(name: &str, value: f64) -> Result<String, PlayerError> {
+    if !value.is_finite() {
+        return Err(PlayerError::InvalidArgument(format!(
+            "{} must be a finite number of seconds",
+            name
+        )));
+    }
+    Ok(format!("{}={}", name, value))
+}
+
+impl LoadOptions {
+    // Encode as loadfile's options argument; an empty string means no options
+    pub fn encode(&self) -> Result<String, PlayerError> {
+        if let (Some(start), Some(end)) = (self.start, self.end) {
+            if start >= 0.0 && end >= 0.0 && end <= start {
+                return Err(PlayerError::InvalidArgument(format!(
+                    "End ({}) must be after start ({})",
+                    end, start
+                )));
+            }
+        }
+
+        let mut options = Vec::new();
+        if let Some(start) = self.start {
+            options.push(format_time("start", start)?);
+        }
+        if let Some(end) = self.end {
+            options.push(format_time("end", end)?);
+        }
+        if self.pause_on_load {
+            options.push("pause=yes".to_string());
+        }
+
+        // Sorted so the same options always produce the same command
+        let mut extra: Vec<_> = self.extra.iter().collect();
+        extra.sort();
+        for (key, value) in extra {
+            if key.is_empty() || key.contains([',', '=', '%', ' ']) || key.contains('"') {
+                return Err(PlayerError::InvalidArgument(format!(
+                    "Invalid option name: {:?}",
+                    key
+                )));
+            }
+            check_unmanaged(key)?;
+            options.push(format!("{}={}", key, quote_option_value(value)));
+        }
+        Ok(options.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extra(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn encodes_nothing_by_default() {
+        assert_eq!(LoadOptions::default().encode().unwrap(), "");
+    }
+
+    #[test]
+    fn encodes_start_end_and_pause() {
+        let options = LoadOptions {
+            start: Some(12.5),
+            end: Some(90.0),
+            pause_on_load: true,
+            ..Default::default()
+        };
+        assert_eq!(options.encode().unwrap(), "start=12.5,end=90,pause=yes");
+    }
+
+    #[test]
+    fn quotes_extra_values_with_commas_and_quotes() {
+        let options = LoadOptions {
+            extra: extra(&[
+                ("sub-files-append", "/subs/a,b \"final\".srt"),
+                ("audio-delay", "0.2"),
+            ]),
+            ..Default::default()
+        };
+        assert_eq!(
+            options.encode().unwrap(),
+            "audio-delay=%3%0.2,sub-files-append=%21%/subs/a,b \"final\".srt"
+        );
+    }
+
+    #[test]
+    fn quote_length_counts_bytes() {
+        assert_eq!(quote_option_value("café"), "%5%café");
+        assert_eq!(quote_option_value(""), "%0%");
+    }
+
+    #[test]
+    fn rejects_bad_ranges_and_names() {
+        let backwards = LoadOptions {
+            start: Some(30.0),
+            end: Some(10.0),
+            ..Default::default()
+        };
+        assert!(backwards.encode().is_err());
+
+        let not_finite = LoadOptions {
+            start: Some(f64::NAN),
+            ..Default::default()
+        };
+        assert!(not_finite.encode().is_err());
+
+        for key in ["", "a,b", "a=b", "a b"] {
+            let options = LoadOptions {
+                extra: extra(&[(key, "1")]),
+                ..Default::default()
+            };
+            assert!(options.encode().is_err(), "accepted {:?}", key);
+        }
+    }
+
+    #[test]
+    fn rejects_managed_options() {
+        let options = LoadOptions {
+            extra: extra(&[("wid", "0")]),
+            ..Default::default()
+        };
+        assert!(options.encode().is_err());
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// How often to play something again after it first finishes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Repeat {
+    Off,
+    Once,
+    Inf,
+    // Extra plays after the first; 0 is Off and 1 is Once
+    Times(u32),
+}
+
+// What the repeat button shows and sets: the current file and the whole playlist loop
+// independently, with the file loop taking priority
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LoopMode {
+    pub file: Repeat,
+    pub playlist: Repeat,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct LoopModeUpdate {
+    pub file: Option<Repeat>,
+    pub playlist: Option<Repeat>,
+}
+
+// The single off / one / all control most queue UIs have
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepeatMode {
+    Off,
+    // The current file, over and over
+    One,
+    // The whole playlist
+    All,
+}
+
+impl RepeatMode {
+    // A counted loop (e.g. from set_loop_mode) reads as the kind it is
+    pub fn of(mode: LoopMode) -> Self {
+        if mode.file != Repeat::Off {
+            Self::One
+        } else if mode.playlist != Repeat::Off {
+            Self::All
+        } else {
+            Self::Off
+        }
+    }
+
+    pub fn update(self) -> LoopModeUpdate {
+        let (file, playlist) = match self {
+            Self::Off => (Repeat::Off, Repeat::Off),
+            Self::One => (Repeat::Inf, Repeat::Off),
+            Self::All => (Repeat::Off, Repeat::Inf),
+        };
+        LoopModeUpdate {
+            file: Some(file),
+            playlist: Some(playlist),
+        }
+    }
+}
+
+impl Repeat {
+    fn extra_plays(self) -> Option<u32> {
+        match self {
+            Self::Off => Some(0),
+            Self::Once => Some(1),
+            Self::Inf => None,
+            Self::Times(n) => Some(n),
+        }
+    }
+
+    fn from_extra_plays(n: u32) -> Self {
+        match n {
+            0 => Self::Off,
+            1 => Self::Once,
+            n => Self::Times(n),
+        }
+    }
+
+    // loop-file=N plays the file N more times
+    pub fn to_loop_file(self) -> String {
+        match self.extra_plays() {
+            None => "inf".to_string(),
+            Some(0) => "no".to_string(),
+            Some(n) => n.to_string(),
+        }
+    }
+
+    // loop-playlist=N plays the playlist N times in total, so 1 is no repeat
+    pub fn to_loop_playlist(self) -> String {
+        match self.extra_plays() {
+            None => "inf".to_string(),
+            Some(0) => "no".to_string(),
+            Some(n) => n.saturating_add(1).to_string(),
+        }
+    }
+
+    // "yes" is mpv's alias for inf, and force loops even playlists that fail to play
+    pub fn from_loop_file(value: &str) -> Option<Self> {
+        match value {
+            "no" => Some(Self::Off),
+            "inf" | "yes" => Some(Self::Inf),
+            n => n.parse().ok().map(Self::from_extra_plays),
+        }
+    }
+
+    pub fn from_loop_playlist(value: &str) -> Option<Self> {
+        match value {
+            "no" => Some(Self::Off),
+            "inf" | "yes" | "force" => Some(Self::Inf),
+            n => n
+                .parse::<u32>()
+                .ok()
+                .map(|total| Self::from_extra_plays(total.saturating_sub(1))),
+        }
+    }
+}
+
+impl LoopMode {
+    // mpv counts a finite loop-playlist down as it repeats, so this is what's left
+    pub fn read(handle: &MpvHandle) -> Result<Self, String> {
+        let read = |name: &str| {
+            handle
+                .get::<String>(name)
+                .map_err(|e| format!("Failed to get {}: {}", name, e))
+        };
+        let file = read("loop-file")?;
+        let playlist = read("loop-playlist")?;
+        Ok(Self {
+            file: Repeat::from_loop_file(&file)
+                .ok_or_else(|| format!("Unexpected loop-file value: {}", file))?,
+            playlist: Repeat::from_loop_playlist(&playlist)
+                .ok_or_else(|| format!("Unexpected loop-playlist value: {}", playlist))?,
+        })
+    }
+
+    pub fn apply(update: &LoopModeUpdate, handle: &MpvHandle) -> Result<(), String> {
+        let properties = [
+            ("loop-file", update.file.map(Repeat::to_loop_file)),
+            (
+                "loop-playlist",
+                update.playlist.map(Repeat::to_loop_playlist),
+            ),
+        ];
+        for (name, value) in properties {
+            if let Some(value) = value {
+                handle
+                    .set(name, &value)
+                    .map_err(|e| format!("Failed to set {}: {}", name, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Repeat; 5] = [
+        Repeat::Off,
+        Repeat::Once,
+        Repeat::Inf,
+        Repeat::Times(2),
+        Repeat::Times(10),
+    ];
+
+    #[test]
+    fn file_and_playlist_counts_differ_by_one() {
+        assert_eq!(Repeat::Once.to_loop_file(), "1");
+        assert_eq!(Repeat::Once.to_loop_playlist(), "2");
+        assert_eq!(Repeat::Times(3).to_loop_file(), "3");
+        assert_eq!(Repeat::Times(3).to_loop_playlist(), "4");
+        assert_eq!(Repeat::Off.to_loop_playlist(), "no");
+        assert_eq!(Repeat::Inf.to_loop_file(), "inf");
+    }
+
+    #[test]
+    fn values_round_trip() {
+        for repeat in ALL {
+            assert_eq!(Repeat::from_loop_file(&repeat.to_loop_file()), Some(repeat));
+            assert_eq!(
+                Repeat::from_loop_playlist(&repeat.to_loop_playlist()),
+                Some(repeat)
+            );
+        }
+    }
+
+    #[test]
+    fn mpv_aliases_are_understood() {
+        assert_eq!(Repeat::from_loop_file("yes"), Some(Repeat::Inf));
+        assert_eq!(Repeat::from_loop_playlist("force"), Some(Repeat::Inf));
+        // The playlist default, playing it once
+        assert_eq!(Repeat::from_loop_playlist("1"), Some(Repeat::Off));
+        assert_eq!(Repeat::from_loop_file("0"), Some(Repeat::Off));
+        assert_eq!(Repeat::from_loop_file("sometimes"), None);
+    }
+
+    #[test]
+    fn repeat_modes_read_back_as_set() {
+        for repeat in [RepeatMode::Off, RepeatMode::One, RepeatMode::All] {
+            let update = repeat.update();
+            let mode = LoopMode {
+                file: update.file.unwrap(),
+                playlist: update.playlist.unwrap(),
+            };
+            assert_eq!(RepeatMode::of(mode), repeat);
+        }
+        let counted = LoopMode {
+            file: Repeat::Off,
+            playlist: Repeat::Times(2),
+        };
+        assert_eq!(RepeatMode::of(counted), RepeatMode::All);
+    }
+
+    #[test]
+    fn times_zero_and_one_normalize() {
+        assert_eq!(Repeat::Times(0).to_loop_file(), "no");
+        assert_eq!(
+            Repeat::from_loop_file(&Repeat::Times(1).to_loop_file()),
+            Some(Repeat::Once)
+        );
+    }
+}
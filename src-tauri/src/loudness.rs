@@ -0,0 +1,53 @@
+// Loudness normalization with lavfi's loudnorm, so a queue mixing quiet films, loud
+// adverts and old recordings plays at about the same level throughout. It runs in
+// loudnorm's single-pass (dynamic) mode, the only one possible while playing; it looks a
+// little ahead, adding a fraction of a second of latency to the audio.
+
+use crate::config::LoudnessNormalization;
+use crate::error::{Context, PlayerError};
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// What loudnorm accepts for its integrated loudness target
+pub const MIN_TARGET_LUFS: f64 = -70.0;
+pub const MAX_TARGET_LUFS: f64 = -5.0;
+
+const LABEL: &str = "loudnorm";
+
+// True peak ceiling, below full scale so the lookahead limiter has room
+const TRUE_PEAK: f64 = -1.5;
+
+fn filter(target_lufs: f64) -> String {
+    format!(
+        "@{}:lavfi=[loudnorm=I={:.1}:TP={:.1}:LRA=11]",
+        LABEL, target_lufs, TRUE_PEAK
+    )
+}
+
+// Put the filter in, replacing one with an older target, or take it out
+pub fn apply(handle: &MpvHandle, settings: &LoudnessNormalization) -> Result<(), PlayerError> {
+    if !settings.enabled {
+        // Fails if it was never added, which is as good as removed
+        let _ = handle.command(&["af", "remove", &format!("@{}", LABEL)]);
+        return Ok(());
+    }
+    handle
+        .command(&["af", "add", &filter(settings.target_lufs)])
+        .context("Failed to set up loudness normalization")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_filter_aims_for_the_target() {
+        assert_eq!(
+            filter(-16.0),
+            "@loudnorm:lavfi=[loudnorm=I=-16.0:TP=-1.5:LRA=11]"
+        );
+        assert_eq!(
+            filter(-23.0),
+            "@loudnorm:lavfi=[loudnorm=I=-23.0:TP=-1.5:LRA=11]"
+        );
+    }
+}
@@ -0,0 +1,259 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// A single playlist entry as read from or written to an M3U (or PLS) file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PlaylistEntry {
+    pub path: String,
+    pub title: Option<String>,
+    pub duration: Option<f64>,
+}
+
+// Result of parsing a playlist file: the usable entries plus one warning per skipped line
+#[derive(Debug, Default, Serialize)]
+pub struct ParsedPlaylist {
+    pub entries: Vec<PlaylistEntry>,
+    pub warnings: Vec<String>,
+}
+
+// Parse an M3U/M3U8 playlist. Relative entries are resolved against `base_dir`
+// (normally the directory containing the playlist file).
+pub fn parse(contents: &str, base_dir: &Path) -> ParsedPlaylist {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    let mut parsed = ParsedPlaylist::default();
+    let mut pending: Option<(usize, Option<f64>, Option<String>)> = None;
+
+    for (index, raw_line) in contents.lines().enumerate() {
+        let line_no = index + 1;
+        let line = raw_line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            if let Some((previous, _, _)) = pending.take() {
+                parsed.warnings.push(format!(
+                    "Line {}: #EXTINF has no entry after it, skipped",
+                    previous
+                ));
+            }
+            match parse_extinf(info) {
+                Some((duration, title)) => pending = Some((line_no, duration, title)),
+                None => parsed
+                    .warnings
+                    .push(format!("Line {}: malformed #EXTINF, skipped", line_no)),
+            }
+            continue;
+        }
+
+        // #EXTM3U, #EXTVLCOPT, #PLAYLIST and plain comments carry nothing we use
+        if line.starts_with('#') {
+            continue;
+        }
+
+        if line.chars().any(|c| c.is_control()) {
+            parsed.warnings.push(format!(
+                "Line {}: entry contains control characters, skipped",
+                line_no
+            ));
+            pending = None;
+            continue;
+        }
+
+        let (duration, title) = match pending.take() {
+            Some((_, duration, title)) => (duration, title),
+            None => (None, None),
+        };
+
+        parsed.entries.push(PlaylistEntry {
+            path: resolve_entry(line, base_dir),
+            title,
+            duration,
+        });
+    }
+
+    if let Some((line_no, _, _)) = pending {
+        parsed.warnings.push(format!(
+            "Line {}: #EXTINF has no entry after it, skipped",
+            line_no
+        ));
+    }
+
+    parsed
+}
+
+// Render entries as an extended M3U playlist
+pub fn write(entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from("#EXTM3U\n");
+
+    for entry in entries {
+        if entry.title.is_some() || entry.duration.is_some() {
+            let duration = entry.duration.map(|d| d.round() as i64).unwrap_or(-1);
+            // A newline in the title would start a bogus entry line
+            let title = entry
+                .title
+                .as_deref()
+                .unwrap_or("")
+                .replace(['\r', '\n'], " ");
+            out.push_str(&format!("#EXTINF:{},{}\n", duration, title));
+        }
+        out.push_str(&entry.path);
+        out.push('\n');
+    }
+
+    out
+}
+
+// `#EXTINF:<duration> [key="value" ...],<title>` - returns None when malformed
+fn parse_extinf(info: &str) -> Option<(Option<f64>, Option<String>)> {
+    let (head, title) = info.split_once(',')?;
+    // Attributes such as tvg-id="..." may follow the duration
+    let duration_str = head.split_whitespace().next()?;
+    let duration: f64 = duration_str.parse().ok()?;
+
+    let duration = if duration < 0.0 { None } else { Some(duration) };
+    let title = title.trim();
+    let title = if title.is_empty() {
+        None
+    } else {
+        Some(title.to_string())
+    };
+
+    Some((duration, title))
+}
+
+pub fn resolve_entry(entry: &str, base_dir: &Path) -> String {
+    // URLs (http://, file://, ...) are handed to mpv untouched
+    if entry.contains("://") {
+        return entry.to_string();
+    }
+
+    // Playlists written on Windows use backslash separators
+    let entry = if cfg!(windows) {
+        entry.to_string()
+    } else {
+        entry.replace('\\', "/")
+    };
+
+    let path = Path::new(&entry);
+    if path.is_absolute() {
+        entry
+    } else {
+        base_dir.join(path).to_string_lossy().into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> &'static Path {
+        Path::new("/media/playlists")
+    }
+
+    #[test]
+    fn parses_extended_m3u_with_bom_and_crlf() {
+        let input = "\u{feff}#EXTM3U\r\n#EXTINF:123,Artist - Song\r\nsong.mp3\r\n\r\n#EXTINF:-1,Radio\r\nhttp://stream.example.com/live\r\n";
+        let parsed = parse(input, base());
+
+        assert!(parsed.warnings.is_empty());
+        assert_eq!(
+            parsed.entries,
+            vec![
+                PlaylistEntry {
+                    path: "/media/playlists/song.mp3".to_string(),
+                    title: Some("Artist - Song".to_string()),
+                    duration: Some(123.0),
+                },
+                PlaylistEntry {
+                    path: "http://stream.example.com/live".to_string(),
+                    title: Some("Radio".to_string()),
+                    duration: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn handles_iptv_attributes_and_titles_with_commas() {
+        let input = "#EXTM3U\n#EXTINF:-1 tvg-id=\"news.uk\" group-title=\"News\",BBC News, Live\nhttps://example.com/bbc.m3u8\n";
+        let parsed = parse(input, base());
+
+        assert_eq!(parsed.entries.len(), 1);
+        assert_eq!(parsed.entries[0].title.as_deref(), Some("BBC News, Live"));
+        assert_eq!(parsed.entries[0].duration, None);
+    }
+
+    #[test]
+    fn plain_m3u_with_comments_and_absolute_paths() {
+        let input = "# My mix\n/music/a.flac\n\n   ../other/b.ogg  \n#EXTVLCOPT:network-caching=1000\nc.mkv\n";
+        let parsed = parse(input, base());
+
+        let paths: Vec<_> = parsed.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/music/a.flac",
+                "/media/playlists/../other/b.ogg",
+                "/media/playlists/c.mkv"
+            ]
+        );
+        assert!(parsed.entries.iter().all(|e| e.title.is_none()));
+    }
+
+    #[test]
+    fn skips_malformed_lines_with_warnings() {
+        let input = "#EXTM3U\n#EXTINF:abc,Broken duration\nfirst.mp4\n#EXTINF:10 no comma\n#EXTINF:20,Dangling\n#EXTINF:30,Real\nsecond.mp4\nbad\u{7}entry.mp4\n#EXTINF:40,At the end\n";
+        let parsed = parse(input, base());
+
+        let paths: Vec<_> = parsed.entries.iter().map(|e| e.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec!["/media/playlists/first.mp4", "/media/playlists/second.mp4"]
+        );
+        // The malformed #EXTINF does not taint the entry after it
+        assert_eq!(parsed.entries[0].title, None);
+        assert_eq!(parsed.entries[1].title.as_deref(), Some("Real"));
+        assert_eq!(parsed.warnings.len(), 5);
+        assert!(parsed.warnings[0].starts_with("Line 2:"));
+        assert!(parsed.warnings[4].starts_with("Line 9:"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn converts_windows_separators_in_relative_paths() {
+        let parsed = parse("Season 1\\Episode 01.mkv\n", base());
+        assert_eq!(
+            parsed.entries[0].path,
+            "/media/playlists/Season 1/Episode 01.mkv"
+        );
+    }
+
+    #[test]
+    fn written_playlist_parses_back() {
+        let entries = vec![
+            PlaylistEntry {
+                path: "/videos/one.mkv".to_string(),
+                title: Some("One\nTwo".to_string()),
+                duration: Some(61.6),
+            },
+            PlaylistEntry {
+                path: "/videos/two.mkv".to_string(),
+                title: None,
+                duration: None,
+            },
+        ];
+
+        let written = write(&entries);
+        assert_eq!(
+            written,
+            "#EXTM3U\n#EXTINF:62,One Two\n/videos/one.mkv\n/videos/two.mkv\n"
+        );
+
+        let parsed = parse(&written, base());
+        assert!(parsed.warnings.is_empty());
+        assert_eq!(parsed.entries[0].title.as_deref(), Some("One Two"));
+        assert_eq!(parsed.entries[1].path, "/videos/two.mkv");
+    }
+}
@@ -0,0 +1,293 @@
+use serde::Serialize;
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+// File extensions we hand to mpv, compared case-insensitively
+pub const MEDIA_EXTENSIONS: &[&str] = &[
+    // Video
+    "3gp", "avi", "flv", "m2ts", "m4v", "mkv", "mov", "mp4", "mpeg", "mpg", "mts", "ogv", "ts",
+    "vob", "webm", "wmv", // Audio
+    "aac", "aiff", "alac", "ape", "flac", "m4a", "mka", "mp3", "ogg", "opus", "wav", "wma",
+];
+
+// External subtitle formats picked up next to a video
+pub const SUBTITLE_EXTENSIONS: &[&str] = &["ass", "srt", "ssa", "vtt"];
+
+// Image files commonly saved next to music as album art
+const COVER_ART_NAMES: &[&str] = &[
+    "cover.jpg",
+    "cover.png",
+    "folder.jpg",
+    "folder.png",
+    "front.jpg",
+    "front.png",
+];
+
+// How deep a recursive directory scan may descend below the starting directory
+const MAX_SCAN_DEPTH: usize = 8;
+
+// A media file plus the sidecar subtitles that should load with it
+#[derive(Debug, Clone, Serialize)]
+pub struct MediaFile {
+    pub path: String,
+    pub subtitles: Vec<String>,
+}
+
+impl MediaFile {
+    pub fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_string_lossy().into_owned(),
+            subtitles: sidecar_subtitles(path),
+        }
+    }
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_ascii_lowercase().as_str()))
+}
+
+pub fn is_media_file(path: &Path) -> bool {
+    has_extension(path, MEDIA_EXTENSIONS)
+}
+
+pub fn is_subtitle_file(path: &Path) -> bool {
+    has_extension(path, SUBTITLE_EXTENSIONS)
+}
+
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with('.'))
+}
+
+// Subtitles named after the video, e.g. `ep1.srt` or `ep1.en.srt` for `ep1.mkv`
+pub fn sidecar_subtitles(video: &Path) -> Vec<String> {
+    let Some(dir) = video.parent() else {
+        return Vec::new();
+    };
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let siblings: Vec<PathBuf> = read_dir
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+    subtitles_among(video, &siblings)
+}
+
+// The sidecar subtitles of `video` among `siblings`, the paths listed in its folder
+fn subtitles_among(video: &Path, siblings: &[PathBuf]) -> Vec<String> {
+    let Some(stem) = video.file_stem().and_then(|s| s.to_str()) else {
+        return Vec::new();
+    };
+    let mut subtitles: Vec<String> = siblings
+        .iter()
+        .filter(|path| has_extension(path, SUBTITLE_EXTENSIONS) && !is_hidden(path))
+        .filter(|path| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .is_some_and(|sub_stem| {
+                    sub_stem == stem
+                        || sub_stem
+                            .strip_prefix(stem)
+                            .is_some_and(|rest| rest.starts_with('.'))
+                })
+        })
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    subtitles.sort_by(|a, b| natural_cmp(a, b));
+    subtitles
+}
+
+// Album art stored alongside `path`, matching names case-insensitively
+pub fn cover_art(path: &Path) -> Option<String> {
+    let read_dir = std::fs::read_dir(path.parent()?).ok()?;
+    read_dir
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .find(|candidate| {
+            candidate
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| COVER_ART_NAMES.contains(&name.to_ascii_lowercase().as_str()))
+        })
+        .map(|p| p.to_string_lossy().into_owned())
+}
+
+// The media file after `path` in its folder, in the order load_directory queues them
+pub fn next_in_directory(path: &Path) -> Option<MediaFile> {
+    let files = scan_directory(path.parent()?, false);
+    let index = files
+        .iter()
+        .position(|file| Path::new(&file.path) == path)?;
+    files.into_iter().nth(index + 1)
+}
+
+// Media files in `dir` in natural order, optionally including subdirectories
+pub fn scan_directory(dir: &Path, recursive: bool) -> Vec<MediaFile> {
+    let mut files = Vec::new();
+    let mut visited = HashSet::new();
+    collect_media(dir, recursive, 0, &mut visited, &mut files);
+
+    files.sort_by(|a, b| {
+        let (a, b) = (Path::new(&a.path), Path::new(&b.path));
+        let a = a.strip_prefix(dir).unwrap_or(a).to_string_lossy();
+        let b = b.strip_prefix(dir).unwrap_or(b).to_string_lossy();
+        natural_cmp(&a, &b)
+    });
+    files
+}
+
+// Each folder is listed once; its media files' subtitles are found in the same listing
+fn collect_media(
+    dir: &Path,
+    recursive: bool,
+    depth: usize,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<MediaFile>,
+) {
+    // Canonical paths let us notice symlinks that loop back to a directory we've seen
+    let Ok(canonical) = dir.canonicalize() else {
+        return;
+    };
+    if !visited.insert(canonical) {
+        return;
+    }
+    let Ok(read_dir) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let entries: Vec<PathBuf> = read_dir
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .collect();
+
+    for path in &entries {
+        if is_hidden(path) {
+            continue;
+        }
+        if path.is_dir() {
+            if recursive && depth < MAX_SCAN_DEPTH {
+                collect_media(path, recursive, depth + 1, visited, files);
+            }
+        } else if is_media_file(path) {
+            files.push(MediaFile {
+                path: path.to_string_lossy().into_owned(),
+                subtitles: subtitles_among(path, &entries),
+            });
+        }
+    }
+}
+
+// Compare strings so that embedded numbers order by value: "ep2" < "ep10"
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return a.cmp(b),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_num = take_number(&mut a_chars);
+                let y_num = take_number(&mut b_chars);
+                let x_digits = x_num.trim_start_matches('0');
+                let y_digits = y_num.trim_start_matches('0');
+                let ordering = x_digits
+                    .len()
+                    .cmp(&y_digits.len())
+                    .then_with(|| x_digits.cmp(y_digits));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x.to_lowercase().cmp(y.to_lowercase());
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.peek().copied().filter(|c| c.is_ascii_digit()) {
+        number.push(c);
+        chars.next();
+    }
+    number
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn natural_order_sorts_numbers_by_value() {
+        let mut names = vec![
+            "Show ep10.mkv",
+            "show ep2.mkv",
+            "Show ep1.mkv",
+            "Show ep02b.mkv",
+            "Show.mkv",
+        ];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(
+            names,
+            vec![
+                "Show ep1.mkv",
+                "show ep2.mkv",
+                "Show ep02b.mkv",
+                "Show ep10.mkv",
+                "Show.mkv"
+            ]
+        );
+    }
+
+    #[test]
+    fn next_in_directory_skips_other_files() {
+        let dir = std::env::temp_dir().join(format!("media-next-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["ep1.mkv", "ep2.srt", "ep10.mkv", "ep2.mkv", "notes.txt"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let next = |name: &str| next_in_directory(&dir.join(name)).map(|f| f.path);
+        assert_eq!(
+            next("ep1.mkv"),
+            Some(dir.join("ep2.mkv").to_string_lossy().into_owned())
+        );
+        assert_eq!(
+            next("ep2.mkv"),
+            Some(dir.join("ep10.mkv").to_string_lossy().into_owned())
+        );
+        assert_eq!(next("ep10.mkv"), None);
+        assert_eq!(next("gone.mkv"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scanned_files_carry_their_sidecar_subtitles() {
+        let dir = std::env::temp_dir().join(format!("media-sidecars-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["ep1.mkv", "ep1.en.srt", "ep1.srt", "ep10.srt", "ep2.mkv"] {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+
+        let files = scan_directory(&dir, false);
+        let path = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].path, path("ep1.mkv"));
+        assert_eq!(files[0].subtitles, [path("ep1.en.srt"), path("ep1.srt")]);
+        assert_eq!(
+            files[0].subtitles,
+            sidecar_subtitles(Path::new(&files[0].path))
+        );
+        assert!(files[1].subtitles.is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
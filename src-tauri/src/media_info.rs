@@ -0,0 +1,349 @@
+// What get_media_info reports about the loaded file: the container and every stream in
+// it. mpv knows most of it from the demuxer, but only the playing video track's bit
+// depth, and some demuxers leave out bitrates or audio details; for local files those
+// gaps are filled from ffprobe when it's installed.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct MediaInfo {
+    pub path: String,
+    // mpv's name for the format, e.g. "mkv" or "mp4"
+    pub container: Option<String>,
+    // Bytes
+    pub size: Option<i64>,
+    pub duration: Option<f64>,
+    // Bits per second over the whole file
+    pub bitrate: Option<f64>,
+    pub video: Vec<VideoStream>,
+    pub audio: Vec<AudioStream>,
+    pub subtitles: Vec<SubtitleStream>,
+}
+
+// Ids are the track ids vid, aid and sid take, as in get_tracks
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct VideoStream {
+    pub id: i64,
+    pub codec: Option<String>,
+    pub width: Option<i64>,
+    pub height: Option<i64>,
+    pub fps: Option<f64>,
+    pub bit_depth: Option<u32>,
+    // e.g. "yuv420p10"
+    pub pixel_format: Option<String>,
+    pub bitrate: Option<f64>,
+    pub selected: bool,
+    // The stream's index in the file, which is how ffprobe names it
+    #[serde(skip)]
+    ff_index: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct AudioStream {
+    pub id: i64,
+    pub codec: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub channels: Option<i64>,
+    // e.g. "5.1(side)"
+    pub channel_layout: Option<String>,
+    pub sample_rate: Option<i64>,
+    pub bitrate: Option<f64>,
+    pub selected: bool,
+    #[serde(skip)]
+    ff_index: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SubtitleStream {
+    pub id: i64,
+    pub codec: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+    pub external: bool,
+    pub selected: bool,
+}
+
+impl MediaInfo {
+    // None when nothing is loaded
+    pub fn read(handle: &MpvHandle) -> Option<Self> {
+        let path = handle.get::<String>("path").ok()?;
+        let size = handle.get::<i64>("file-size").ok();
+        let duration = handle.get::<f64>("duration").ok();
+        let mut info = Self {
+            path,
+            container: handle.get::<String>("file-format").ok(),
+            size,
+            duration,
+            bitrate: overall_bitrate(size, duration),
+            ..Self::default()
+        };
+
+        let count = handle.get::<i64>("track-list/count").unwrap_or(0);
+        for i in 0..count.max(0) {
+            let field = |name: &str| format!("track-list/{}/{}", i, name);
+            let string = |name: &str| handle.get::<String>(&field(name)).ok();
+            let int = |name: &str| handle.get::<i64>(&field(name)).ok();
+            let float = |name: &str| handle.get::<f64>(&field(name)).ok();
+            let flag = |name: &str| handle.get::<bool>(&field(name)).unwrap_or(false);
+            let Some(id) = int("id") else {
+                continue;
+            };
+            match string("type").as_deref() {
+                Some("video") => {
+                    let selected = flag("selected");
+                    // Only known for the track being decoded
+                    let pixel_format = selected
+                        .then(|| handle.get::<String>("video-params/pixelformat").ok())
+                        .flatten();
+                    info.video.push(VideoStream {
+                        id,
+                        codec: string("codec"),
+                        width: int("demux-w"),
+                        height: int("demux-h"),
+                        fps: float("demux-fps"),
+                        bit_depth: pixel_format.as_deref().and_then(bit_depth),
+                        pixel_format,
+                        bitrate: float("demux-bitrate"),
+                        selected,
+                        ff_index: int("ff-index"),
+                    });
+                }
+                Some("audio") => info.audio.push(AudioStream {
+                    id,
+                    codec: string("codec"),
+                    language: string("lang"),
+                    title: string("title"),
+                    channels: int("demux-channel-count"),
+                    channel_layout: string("demux-channels"),
+                    sample_rate: int("demux-samplerate"),
+                    bitrate: float("demux-bitrate"),
+                    selected: flag("selected"),
+                    ff_index: int("ff-index"),
+                }),
+                Some("sub") => info.subtitles.push(SubtitleStream {
+                    id,
+                    codec: string("codec"),
+                    language: string("lang"),
+                    title: string("title"),
+                    default: flag("default"),
+                    forced: flag("forced"),
+                    external: flag("external"),
+                    selected: flag("selected"),
+                }),
+                _ => {}
+            }
+        }
+        Some(info)
+    }
+
+    // Whether ffprobe could tell us more. Streams and URLs aren't worth probing twice.
+    pub fn is_incomplete(&self) -> bool {
+        if self.path.contains("://") || !Path::new(&self.path).is_file() {
+            return false;
+        }
+        self.container.is_none()
+            || self.bitrate.is_none()
+            || self
+                .video
+                .iter()
+                .any(|v| v.bit_depth.is_none() || v.bitrate.is_none())
+            || self
+                .audio
+                .iter()
+                .any(|a| a.channels.is_none() || a.sample_rate.is_none() || a.bitrate.is_none())
+    }
+
+    // Fill what mpv didn't report from `ffprobe -show_format -show_streams` JSON; what
+    // mpv did report is kept
+    pub fn fill_from_ffprobe(&mut self, json: &str) {
+        let Ok(probed) = serde_json::from_str::<Value>(json) else {
+            return;
+        };
+        let format = &probed["format"];
+        if self.container.is_none() {
+            self.container = format["format_name"].as_str().map(str::to_string);
+        }
+        self.size = self
+            .size
+            .or_else(|| number(&format["size"]).map(|s| s as i64));
+        self.duration = self.duration.or_else(|| number(&format["duration"]));
+        self.bitrate = self
+            .bitrate
+            .or_else(|| number(&format["bit_rate"]))
+            .or_else(|| overall_bitrate(self.size, self.duration));
+
+        let streams = probed["streams"].as_array().map_or(&[][..], Vec::as_slice);
+        let stream = |ff_index: Option<i64>| {
+            streams
+                .iter()
+                .find(|s| ff_index.is_some() && s["index"].as_i64() == ff_index)
+        };
+        for video in &mut self.video {
+            let Some(s) = stream(video.ff_index) else {
+                continue;
+            };
+            video.width = video.width.or(s["width"].as_i64());
+            video.height = video.height.or(s["height"].as_i64());
+            video.fps = video.fps.or_else(|| frame_rate(&s["avg_frame_rate"]));
+            if video.pixel_format.is_none() {
+                video.pixel_format = s["pix_fmt"].as_str().map(str::to_string);
+            }
+            video.bit_depth = video
+                .bit_depth
+                .or_else(|| number(&s["bits_per_raw_sample"]).map(|b| b as u32))
+                .or_else(|| video.pixel_format.as_deref().and_then(bit_depth));
+            video.bitrate = video.bitrate.or_else(|| number(&s["bit_rate"]));
+        }
+        for audio in &mut self.audio {
+            let Some(s) = stream(audio.ff_index) else {
+                continue;
+            };
+            audio.channels = audio.channels.or(s["channels"].as_i64());
+            if audio.channel_layout.is_none() {
+                audio.channel_layout = s["channel_layout"].as_str().map(str::to_string);
+            }
+            audio.sample_rate = audio
+                .sample_rate
+                .or_else(|| number(&s["sample_rate"]).map(|r| r as i64));
+            audio.bitrate = audio.bitrate.or_else(|| number(&s["bit_rate"]));
+        }
+    }
+}
+
+// ffprobe's JSON output for `path`
+pub fn ffprobe(path: &str) -> Result<String, String> {
+    let output = Command::new("ffprobe")
+        .args([
+            "-v",
+            "error",
+            "-print_format",
+            "json",
+            "-show_format",
+            "-show_streams",
+        ])
+        .arg(path)
+        .output()
+        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
+    if !output.status.success() {
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn overall_bitrate(size: Option<i64>, duration: Option<f64>) -> Option<f64> {
+    let (size, duration) = (size?, duration?);
+    (duration > 0.0).then(|| size as f64 * 8.0 / duration)
+}
+
+// ffprobe writes most numbers as strings, e.g. "bit_rate": "4500000"
+fn number(value: &Value) -> Option<f64> {
+    value
+        .as_f64()
+        .or_else(|| value.as_str()?.parse().ok())
+        .filter(|n: &f64| n.is_finite() && *n > 0.0)
+}
+
+// "24000/1001" -> 23.976...
+fn frame_rate(value: &Value) -> Option<f64> {
+    let (num, den) = value.as_str()?.split_once('/')?;
+    let (num, den): (f64, f64) = (num.parse().ok()?, den.parse().ok()?);
+    (num > 0.0 && den > 0.0).then(|| num / den)
+}
+
+// Bits per component from an FFmpeg pixel format name: "yuv420p10le" -> 10, "p010" -> 10,
+// "nv12" -> 8. None for names that don't say.
+pub fn bit_depth(pixel_format: &str) -> Option<u32> {
+    let name = pixel_format
+        .strip_suffix("le")
+        .or_else(|| pixel_format.strip_suffix("be"))
+        .unwrap_or(pixel_format);
+    // Semi-planar high depth formats: p010, p016, p210...
+    if let Some(digits) = name.strip_prefix('p').filter(|d| d.len() == 3) {
+        return match digits.get(1..)?.parse::<u32>().ok()? {
+            depth @ (10 | 12 | 16) => Some(depth),
+            _ => None,
+        };
+    }
+    let (rest, digits) = name.split_at(name.trim_end_matches(|c: char| c.is_ascii_digit()).len());
+    match (rest, digits) {
+        (_, "") if rest.ends_with('p') => Some(8),
+        // "yuv420p10", "gray12"
+        (_, _) if rest.ends_with('p') || rest == "gray" => digits.parse().ok(),
+        ("nv" | "yuyv" | "uyvy" | "rgb" | "bgr", _) => Some(8),
+        ("rgba" | "bgra" | "rgb0" | "bgr0" | "gray", "") => Some(8),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_bit_depth_from_pixel_formats() {
+        assert_eq!(bit_depth("yuv420p"), Some(8));
+        assert_eq!(bit_depth("yuvj420p"), Some(8));
+        assert_eq!(bit_depth("yuv420p10"), Some(10));
+        assert_eq!(bit_depth("yuv444p12le"), Some(12));
+        assert_eq!(bit_depth("p010"), Some(10));
+        assert_eq!(bit_depth("nv12"), Some(8));
+        assert_eq!(bit_depth("gray10le"), Some(10));
+        assert_eq!(bit_depth("vaapi"), None);
+    }
+
+    #[test]
+    fn ffprobe_fills_only_the_gaps() {
+        let mut info = MediaInfo {
+            path: "/videos/a.mkv".to_string(),
+            container: Some("mkv".to_string()),
+            video: vec![VideoStream {
+                id: 1,
+                codec: Some("hevc".to_string()),
+                width: Some(3840),
+                ff_index: Some(0),
+                ..VideoStream::default()
+            }],
+            audio: vec![AudioStream {
+                id: 1,
+                channels: Some(6),
+                ff_index: Some(1),
+                ..AudioStream::default()
+            }],
+            ..MediaInfo::default()
+        };
+        let json = r#"{
+            "streams": [
+                {"index": 0, "codec_type": "video", "width": 1920, "height": 2160,
+                 "pix_fmt": "yuv420p10le", "avg_frame_rate": "24000/1001"},
+                {"index": 1, "codec_type": "audio", "channels": 2, "sample_rate": "48000",
+                 "channel_layout": "5.1(side)", "bit_rate": "640000"}
+            ],
+            "format": {"format_name": "matroska,webm", "size": "1000000",
+                       "duration": "8.000000"}
+        }"#;
+        info.fill_from_ffprobe(json);
+
+        assert_eq!(info.container.as_deref(), Some("mkv"));
+        assert_eq!(info.size, Some(1_000_000));
+        assert_eq!(info.bitrate, Some(1_000_000.0));
+        let video = &info.video[0];
+        assert_eq!((video.width, video.height), (Some(3840), Some(2160)));
+        assert_eq!(video.bit_depth, Some(10));
+        assert!((video.fps.unwrap() - 23.976).abs() < 0.001);
+        let audio = &info.audio[0];
+        assert_eq!(audio.channels, Some(6));
+        assert_eq!(audio.sample_rate, Some(48000));
+        assert_eq!(audio.bitrate, Some(640000.0));
+    }
+}
@@ -0,0 +1,107 @@
+// The tags of the loaded file, from mpv's metadata property: what get_metadata returns
+// and metadata-update carries. Internet radio streams update theirs as songs change
+// (the ICY title), so the event thread re-reads them whenever mpv says they changed.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// Observed by the event thread
+pub const OBSERVED_PROPERTY: &str = "metadata";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Metadata {
+    pub path: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    // As written in the file: a year, a full date, or anything else
+    pub date: Option<String>,
+    pub encoder: Option<String>,
+    // The song a stream is playing right now
+    pub icy_title: Option<String>,
+    // Every tag, with keys as the file has them (Matroska's are upper case, MP4's not)
+    pub tags: BTreeMap<String, String>,
+}
+
+impl Metadata {
+    // None when no file is loaded
+    pub fn read(handle: &MpvHandle) -> Option<Self> {
+        let path = handle.get::<String>("path").ok()?;
+        let count = handle.get::<i64>("metadata/list/count").unwrap_or(0);
+        let tags = (0..count.max(0))
+            .filter_map(|i| {
+                let key = handle.get::<String>(&format!("metadata/list/{}/key", i));
+                let value = handle.get::<String>(&format!("metadata/list/{}/value", i));
+                Some((key.ok()?, value.ok()?))
+            })
+            .collect();
+        Some(Self::from_tags(path, tags))
+    }
+
+    fn from_tags(path: String, tags: BTreeMap<String, String>) -> Self {
+        Self {
+            path,
+            title: tag(&tags, &["title"]),
+            artist: tag(&tags, &["artist", "album_artist"]),
+            album: tag(&tags, &["album"]),
+            date: tag(&tags, &["date", "year", "date_released"]),
+            encoder: tag(&tags, &["encoder", "encoded_by"]),
+            icy_title: tag(&tags, &["icy-title"]),
+            tags,
+        }
+    }
+}
+
+// The first of `keys` the file has a non-empty value for, whatever its case
+fn tag(tags: &BTreeMap<String, String>, keys: &[&str]) -> Option<String> {
+    keys.iter().find_map(|key| {
+        tags.iter()
+            .find(|(name, value)| name.eq_ignore_ascii_case(key) && !value.trim().is_empty())
+            .map(|(_, value)| value.trim().to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn tags_are_found_whatever_their_case() {
+        let metadata = Metadata::from_tags(
+            "/music/a.mka".to_string(),
+            tags(&[
+                ("TITLE", "Blue in Green"),
+                ("ARTIST", " "),
+                ("ALBUM_ARTIST", "Miles Davis"),
+                ("DATE_RELEASED", "1959"),
+                ("ENCODER", "libebml v1.4.2"),
+            ]),
+        );
+        assert_eq!(metadata.title.as_deref(), Some("Blue in Green"));
+        // A blank tag doesn't hide the one behind it
+        assert_eq!(metadata.artist.as_deref(), Some("Miles Davis"));
+        assert_eq!(metadata.album, None);
+        assert_eq!(metadata.date.as_deref(), Some("1959"));
+        assert_eq!(metadata.encoder.as_deref(), Some("libebml v1.4.2"));
+        assert_eq!(metadata.tags.len(), 5);
+    }
+
+    #[test]
+    fn streams_report_the_song_playing() {
+        let metadata = Metadata::from_tags(
+            "https://radio.example/stream".to_string(),
+            tags(&[("icy-name", "Example FM"), ("icy-title", "Artist - Song")]),
+        );
+        assert_eq!(metadata.icy_title.as_deref(), Some("Artist - Song"));
+        assert_eq!(metadata.title, None);
+    }
+}
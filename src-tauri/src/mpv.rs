@@ -0,0 +1,502 @@
+// Safe wrapper over the libmpv client API. All raw libmpv calls live in mpv/libmpv.rs;
+// without the `real-mpv` feature, the in-memory player in mpv/stub.rs takes its place.
+// Both implement PlayerBackend.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[cfg(feature = "real-mpv")]
+mod libmpv;
+#[cfg(not(feature = "real-mpv"))]
+pub mod stub;
+
+#[cfg(feature = "real-mpv")]
+use libmpv as backend;
+#[cfg(not(feature = "real-mpv"))]
+use stub as backend;
+
+pub use backend::{MpvClient, MpvHandle, RenderContext, SW_PIXEL_SIZE};
+
+// Looks up an OpenGL function by name for mpv's renderer, e.g. eglGetProcAddress
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+pub type GetProcAddress =
+    unsafe extern "C" fn(name: *const std::os::raw::c_char) -> *mut std::os::raw::c_void;
+
+// The window system connection the app's GL context lives on, which mpv shares for
+// hardware decoding interop. Only the real backend reads it.
+#[cfg_attr(not(all(target_os = "linux", feature = "real-mpv")), allow(dead_code))]
+#[derive(Debug, Clone, Copy)]
+pub enum GlDisplay {
+    // An Xlib Display*
+    X11(*mut std::os::raw::c_void),
+    // A wl_display*
+    Wayland(*mut std::os::raw::c_void),
+}
+
+// Status codes from mpv's client.h, which are part of its stable ABI
+const ERROR_EVENT_QUEUE_FULL: i32 = -1;
+const ERROR_INVALID_PARAMETER: i32 = -4;
+const ERROR_OPTION_NOT_FOUND: i32 = -5;
+const ERROR_OPTION_FORMAT: i32 = -6;
+const ERROR_OPTION_ERROR: i32 = -7;
+const ERROR_PROPERTY_NOT_FOUND: i32 = -8;
+const ERROR_PROPERTY_FORMAT: i32 = -9;
+const ERROR_PROPERTY_UNAVAILABLE: i32 = -10;
+const ERROR_PROPERTY_ERROR: i32 = -11;
+const ERROR_COMMAND: i32 = -12;
+const ERROR_LOADING_FAILED: i32 = -13;
+const ERROR_UNKNOWN_FORMAT: i32 = -17;
+const ERROR_UNSUPPORTED: i32 = -18;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MpvError {
+    // A name or value contained a NUL byte, which can't cross the C API
+    NulByte(String),
+    // mpv_create returned NULL (out of memory or a broken libmpv)
+    CreateFailed,
+    EventQueueFull,
+    InvalidParameter,
+    OptionNotFound,
+    OptionFormat,
+    OptionError,
+    PropertyNotFound,
+    PropertyFormat,
+    PropertyUnavailable,
+    PropertyError,
+    Command,
+    LoadingFailed,
+    UnknownFormat,
+    Unsupported,
+    // Any other negative status code
+    Other(i32),
+}
+
+pub type Result<T> = std::result::Result<T, MpvError>;
+
+impl MpvError {
+    // Map a libmpv status code; None for success (codes >= 0)
+    pub fn from_code(code: i32) -> Option<Self> {
+        if code >= 0 {
+            return None;
+        }
+        Some(match code {
+            ERROR_EVENT_QUEUE_FULL => Self::EventQueueFull,
+            ERROR_INVALID_PARAMETER => Self::InvalidParameter,
+            ERROR_OPTION_NOT_FOUND => Self::OptionNotFound,
+            ERROR_OPTION_FORMAT => Self::OptionFormat,
+            ERROR_OPTION_ERROR => Self::OptionError,
+            ERROR_PROPERTY_NOT_FOUND => Self::PropertyNotFound,
+            ERROR_PROPERTY_FORMAT => Self::PropertyFormat,
+            ERROR_PROPERTY_UNAVAILABLE => Self::PropertyUnavailable,
+            ERROR_PROPERTY_ERROR => Self::PropertyError,
+            ERROR_COMMAND => Self::Command,
+            ERROR_LOADING_FAILED => Self::LoadingFailed,
+            ERROR_UNKNOWN_FORMAT => Self::UnknownFormat,
+            ERROR_UNSUPPORTED => Self::Unsupported,
+            other => Self::Other(other),
+        })
+    }
+
+    // The libmpv status code, for the variants that came from one
+    pub fn code(&self) -> Option<i32> {
+        Some(match self {
+            Self::NulByte(_) | Self::CreateFailed => return None,
+            Self::EventQueueFull => ERROR_EVENT_QUEUE_FULL,
+            Self::InvalidParameter => ERROR_INVALID_PARAMETER,
+            Self::OptionNotFound => ERROR_OPTION_NOT_FOUND,
+            Self::OptionFormat => ERROR_OPTION_FORMAT,
+            Self::OptionError => ERROR_OPTION_ERROR,
+            Self::PropertyNotFound => ERROR_PROPERTY_NOT_FOUND,
+            Self::PropertyFormat => ERROR_PROPERTY_FORMAT,
+            Self::PropertyUnavailable => ERROR_PROPERTY_UNAVAILABLE,
+            Self::PropertyError => ERROR_PROPERTY_ERROR,
+            Self::Command => ERROR_COMMAND,
+            Self::LoadingFailed => ERROR_LOADING_FAILED,
+            Self::UnknownFormat => ERROR_UNKNOWN_FORMAT,
+            Self::Unsupported => ERROR_UNSUPPORTED,
+            Self::Other(code) => *code,
+        })
+    }
+
+    // A stable identifier for the frontend to match on, e.g. "property_unavailable"
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::NulByte(_) => "nul_byte",
+            Self::CreateFailed => "create_failed",
+            Self::EventQueueFull => "event_queue_full",
+            Self::InvalidParameter => "invalid_parameter",
+            Self::OptionNotFound => "option_not_found",
+            Self::OptionFormat => "option_format",
+            Self::OptionError => "option_error",
+            Self::PropertyNotFound => "property_not_found",
+            Self::PropertyFormat => "property_format",
+            Self::PropertyUnavailable => "property_unavailable",
+            Self::PropertyError => "property_error",
+            Self::Command => "command",
+            Self::LoadingFailed => "loading_failed",
+            Self::UnknownFormat => "unknown_format",
+            Self::Unsupported => "unsupported",
+            Self::Other(_) => "other",
+        }
+    }
+}
+
+impl fmt::Display for MpvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NulByte(value) => write!(f, "{:?} contains a NUL byte", value),
+            Self::CreateFailed => write!(f, "failed to create mpv instance"),
+            // mpv's own wording, e.g. "property not found"
+            other => write!(
+                f,
+                "{}",
+                backend::error_string(other.code().unwrap_or_default())
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MpvError {}
+
+// Lets helpers that report errors as strings use `?` directly
+impl From<MpvError> for String {
+    fn from(e: MpvError) -> Self {
+        e.to_string()
+    }
+}
+
+// What the player needs from a playback engine: loading and seeking, properties, and the
+// event queue. Names and events are mpv's; a GStreamer or AVFoundation backend would map
+// them onto its own pipeline. Creating and initializing a handle, rendering and logging
+// stay specific to each backend. A backend is driven from the thread that created it.
+pub trait PlayerBackend {
+    fn command(&self, args: &[&str]) -> Result<()>;
+
+    fn get_property_string(&self, name: &str) -> Result<String>;
+    fn get_property_flag(&self, name: &str) -> Result<bool>;
+    fn get_property_i64(&self, name: &str) -> Result<i64>;
+    fn get_property_f64(&self, name: &str) -> Result<f64>;
+    fn set_property_string(&self, name: &str, value: &str) -> Result<()>;
+    fn set_property_flag(&self, name: &str, value: bool) -> Result<()>;
+    fn set_property_i64(&self, name: &str, value: i64) -> Result<()>;
+    fn set_property_f64(&self, name: &str, value: f64) -> Result<()>;
+
+    fn observe(&self, id: u64, name: &str, format: Format) -> Result<()>;
+    fn unobserve(&self, id: u64) -> Result<bool>;
+    fn wakeup(&self);
+    fn wait_event(&self, timeout: f64) -> Event;
+
+    // Replace what's playing. `options` is loadfile's encoded options argument (see
+    // loadfile::LoadOptions).
+    fn load(&self, path: &str, options: Option<&str>) -> Result<()> {
+        match options {
+            Some(options) => self.loadfile(path, "replace", options),
+            None => self.command(&["loadfile", path]),
+        }
+    }
+
+    // Add `path` to the playlist the way `mode` says, e.g. "append", with per-file
+    // `options`. mpv 0.38 added an insertion index before the options; older versions
+    // read them in its place.
+    fn loadfile(&self, path: &str, mode: &str, options: &str) -> Result<()> {
+        if options.is_empty() {
+            return self.command(&["loadfile", path, mode]);
+        }
+        let version = self.get_property_string("mpv-version").ok();
+        if takes_loadfile_index(version.as_deref()) {
+            // -1 keeps the default position
+            self.command(&["loadfile", path, mode, "-1", options])
+        } else {
+            self.command(&["loadfile", path, mode, options])
+        }
+    }
+
+    // `flags` are mpv's, e.g. "absolute+exact"
+    fn seek(&self, target: f64, flags: &str) -> Result<()> {
+        self.command(&["seek", &target.to_string(), flags])
+    }
+
+    // Read a property as whichever type the caller wants, e.g. `get::<f64>("time-pos")`
+    fn get<T: GetProperty>(&self, name: &str) -> Result<T>
+    where
+        Self: Sized,
+    {
+        T::get_from(self, name)
+    }
+
+    // Set a property in the format its value has: strings as strings, bools as flags
+    fn set<T: SetProperty>(&self, name: &str, value: T) -> Result<()>
+    where
+        Self: Sized,
+    {
+        value.set_on(self, name)
+    }
+}
+
+// Whether the mpv `version` names (its mpv-version property, e.g. "mpv 0.37.0" or
+// "mpv v0.38.0-12-gabcdef") takes loadfile's insertion index. Builds that don't say
+// which release they are, e.g. from git, are taken to be recent.
+fn takes_loadfile_index(version: Option<&str>) -> bool {
+    let Some(number) = version.and_then(|v| v.strip_prefix("mpv ")) else {
+        return true;
+    };
+    let mut parts = number
+        .trim_start_matches('v')
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|part| part.parse::<u32>().ok());
+    match (parts.next().flatten(), parts.next().flatten()) {
+        (Some(major), Some(minor)) => (major, minor) >= (0, 38),
+        _ => true,
+    }
+}
+
+// Rust types a property can be read as, for get. Each maps onto one of the typed getters
+// backends provide.
+pub trait GetProperty: Sized {
+    fn get_from(backend: &impl PlayerBackend, name: &str) -> Result<Self>;
+}
+
+// Rust types a property can be set from, for set
+pub trait SetProperty {
+    fn set_on(self, backend: &impl PlayerBackend, name: &str) -> Result<()>;
+}
+
+macro_rules! property_type {
+    ($type:ty, $get:ident, $set:ident) => {
+        impl GetProperty for $type {
+            fn get_from(backend: &impl PlayerBackend, name: &str) -> Result<Self> {
+                backend.$get(name)
+            }
+        }
+
+        impl SetProperty for $type {
+            fn set_on(self, backend: &impl PlayerBackend, name: &str) -> Result<()> {
+                backend.$set(name, self)
+            }
+        }
+    };
+}
+
+property_type!(bool, get_property_flag, set_property_flag);
+property_type!(i64, get_property_i64, set_property_i64);
+property_type!(f64, get_property_f64, set_property_f64);
+
+impl GetProperty for String {
+    fn get_from(backend: &impl PlayerBackend, name: &str) -> Result<Self> {
+        backend.get_property_string(name)
+    }
+}
+
+impl SetProperty for &str {
+    fn set_on(self, backend: &impl PlayerBackend, name: &str) -> Result<()> {
+        backend.set_property_string(name, self)
+    }
+}
+
+impl SetProperty for &String {
+    fn set_on(self, backend: &impl PlayerBackend, name: &str) -> Result<()> {
+        backend.set_property_string(name, self)
+    }
+}
+
+// Property formats we observe or read; the frontend names them in lowercase
+#[cfg_attr(not(feature = "real-mpv"), allow(dead_code))]
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    // Change notifications only; read the value separately
+    None,
+    String,
+    Flag,
+    Int64,
+    Double,
+}
+
+// The value carried by a property-change event; serialized as the bare value
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum PropertyValue {
+    // Observed with Format::None, or the property is currently unavailable
+    None,
+    String(String),
+    Flag(bool),
+    Int64(i64),
+    Double(f64),
+}
+
+// A video frame from screenshot-raw, copied out of mpv's memory
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawFrame {
+    pub width: u32,
+    pub height: u32,
+    // Bytes from the start of one row to the next
+    pub stride: usize,
+    // mpv's name for the 4-byte pixel layout, "bgr0" unless asked for another
+    pub format: String,
+    pub pixels: Vec<u8>,
+}
+
+// Some reasons only ever come from libmpv, never from the stub
+#[cfg_attr(not(feature = "real-mpv"), allow(dead_code))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum EndFileReason {
+    Eof,
+    Stop,
+    Quit,
+    // The file couldn't be opened or played
+    Error(MpvError),
+    Redirect,
+    Other(i32),
+}
+
+// An event from mpv_wait_event, with its payload copied out of mpv's memory
+#[cfg_attr(not(feature = "real-mpv"), allow(dead_code))]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    // The wait timed out, or mpv_wakeup was called
+    None,
+    Shutdown,
+    StartFile {
+        playlist_entry_id: i64,
+    },
+    EndFile {
+        reason: EndFileReason,
+        playlist_entry_id: i64,
+    },
+    FileLoaded,
+    // A seek started; PlaybackRestart follows once playback resumes from the target
+    Seek,
+    PlaybackRestart,
+    Idle,
+    LogMessage {
+        prefix: String,
+        level: String,
+        text: String,
+    },
+    PropertyChange {
+        id: u64,
+        name: String,
+        value: PropertyValue,
+    },
+    Other,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nul_byte_error_names_the_value() {
+        let message = MpvError::NulByte("a\0b".to_string()).to_string();
+        assert_eq!(message, "\"a\\0b\" contains a NUL byte");
+    }
+
+    #[test]
+    fn success_codes_are_not_errors() {
+        assert_eq!(MpvError::from_code(0), None);
+        assert_eq!(MpvError::from_code(3), None);
+    }
+
+    #[test]
+    fn error_codes_round_trip() {
+        for code in -20..0 {
+            let error = MpvError::from_code(code).unwrap();
+            assert_eq!(error.code(), Some(code), "{:?}", error);
+        }
+        assert_eq!(
+            MpvError::from_code(ERROR_PROPERTY_NOT_FOUND),
+            Some(MpvError::PropertyNotFound)
+        );
+        assert_eq!(MpvError::from_code(-999), Some(MpvError::Other(-999)));
+        assert_eq!(MpvError::NulByte(String::new()).code(), None);
+    }
+
+    // Only records commands, standing in for an engine that isn't mpv
+    #[derive(Default)]
+    struct Recorder(std::sync::Mutex<Vec<String>>);
+
+    impl PlayerBackend for Recorder {
+        fn command(&self, args: &[&str]) -> Result<()> {
+            self.0.lock().unwrap().push(args.join(" "));
+            Ok(())
+        }
+        fn get_property_string(&self, _: &str) -> Result<String> {
+            Err(MpvError::PropertyUnavailable)
+        }
+        fn get_property_flag(&self, _: &str) -> Result<bool> {
+            Err(MpvError::PropertyUnavailable)
+        }
+        fn get_property_i64(&self, _: &str) -> Result<i64> {
+            Err(MpvError::PropertyUnavailable)
+        }
+        fn get_property_f64(&self, _: &str) -> Result<f64> {
+            Err(MpvError::PropertyUnavailable)
+        }
+        fn set_property_string(&self, _: &str, _: &str) -> Result<()> {
+            Err(MpvError::PropertyUnavailable)
+        }
+        fn set_property_flag(&self, _: &str, _: bool) -> Result<()> {
+            Err(MpvError::PropertyUnavailable)
+        }
+        fn set_property_i64(&self, _: &str, _: i64) -> Result<()> {
+            Err(MpvError::PropertyUnavailable)
+        }
+        fn set_property_f64(&self, _: &str, _: f64) -> Result<()> {
+            Err(MpvError::PropertyUnavailable)
+        }
+        fn observe(&self, _: u64, _: &str, _: Format) -> Result<()> {
+            Ok(())
+        }
+        fn unobserve(&self, _: u64) -> Result<bool> {
+            Ok(false)
+        }
+        fn wakeup(&self) {}
+        fn wait_event(&self, _: f64) -> Event {
+            Event::None
+        }
+    }
+
+    #[test]
+    fn load_and_seek_are_mpv_commands_by_default() {
+        let backend = Recorder::default();
+        backend.load("/videos/a.mkv", None).unwrap();
+        backend.load("/videos/b.mkv", Some("start=10")).unwrap();
+        backend.seek(42.5, "absolute+exact").unwrap();
+        assert_eq!(
+            *backend.0.lock().unwrap(),
+            [
+                "loadfile /videos/a.mkv",
+                "loadfile /videos/b.mkv replace -1 start=10",
+                "seek 42.5 absolute+exact",
+            ]
+        );
+        assert_eq!(
+            backend.get::<f64>("time-pos"),
+            Err(MpvError::PropertyUnavailable)
+        );
+    }
+
+    #[test]
+    fn loadfile_only_gets_an_index_where_mpv_takes_one() {
+        assert!(!takes_loadfile_index(Some("mpv 0.34.1")));
+        assert!(!takes_loadfile_index(Some("mpv v0.37.0-dirty")));
+        assert!(takes_loadfile_index(Some("mpv 0.38.0")));
+        assert!(takes_loadfile_index(Some("mpv v0.39.0-5-gabcdef")));
+        assert!(takes_loadfile_index(Some("mpv 1.0.0")));
+        assert!(takes_loadfile_index(Some("mpv git-2024-01-01")));
+        assert!(takes_loadfile_index(None));
+    }
+
+    #[cfg(not(feature = "real-mpv"))]
+    #[test]
+    fn properties_are_read_and_set_by_type() {
+        let handle = MpvHandle::create().unwrap();
+        handle.initialize().unwrap();
+        handle.set("volume", 40.0).unwrap();
+        assert_eq!(handle.get::<f64>("volume").unwrap(), 40.0);
+        handle.set("pause", true).unwrap();
+        assert!(handle.get::<bool>("pause").unwrap());
+        handle.set("sub-codepage", "cp1256").unwrap();
+        assert_eq!(handle.get::<String>("sub-codepage").unwrap(), "cp1256");
+    }
+}
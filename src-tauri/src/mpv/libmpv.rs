@@ -0,0 +1,626 @@
+// The real backend: MpvHandle over libmpv's client API
+
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr::{self, NonNull};
+
+use libmpv_sys as sys;
+
+use super::{
+    EndFileReason, Event, Format, GetProcAddress, GlDisplay, MpvError, PlayerBackend,
+    PropertyValue, RawFrame, Result,
+};
+
+// mpv's description of a status code, e.g. "property not found"
+pub(super) fn error_string(code: i32) -> String {
+    unsafe { CStr::from_ptr(sys::mpv_error_string(code)) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+// Ok for success codes, the matching MpvError otherwise
+fn check(code: c_int) -> Result<()> {
+    match MpvError::from_code(code) {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+fn c_string(value: &str) -> Result<CString> {
+    CString::new(value).map_err(|_| MpvError::NulByte(value.to_string()))
+}
+
+// Copy a C string owned by mpv; NULL becomes an empty string
+fn from_c_str(value: *const c_char) -> String {
+    if value.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr(value) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+impl Format {
+    fn raw(self) -> sys::mpv_format {
+        match self {
+            Self::None => sys::mpv_format_MPV_FORMAT_NONE,
+            Self::String => sys::mpv_format_MPV_FORMAT_STRING,
+            Self::Flag => sys::mpv_format_MPV_FORMAT_FLAG,
+            Self::Int64 => sys::mpv_format_MPV_FORMAT_INT64,
+            Self::Double => sys::mpv_format_MPV_FORMAT_DOUBLE,
+        }
+    }
+}
+
+// An mpv instance, driven by the thread that created it; dropping it shuts the instance
+// down. Other threads get an MpvClient of their own instead.
+pub struct MpvHandle {
+    handle: NonNull<sys::mpv_handle>,
+    // Boxed twice so the pointer handed to mpv stays put
+    wakeup: Option<Box<Callback>>,
+}
+
+impl MpvHandle {
+    // A new, not yet initialized instance; set pre-init options, then call initialize
+    pub fn create() -> Result<Self> {
+        let handle = NonNull::new(unsafe { sys::mpv_create() }).ok_or(MpvError::CreateFailed)?;
+        Ok(Self {
+            handle,
+            wakeup: None,
+        })
+    }
+
+    pub fn initialize(&self) -> Result<()> {
+        check(unsafe { sys::mpv_initialize(self.as_ptr()) })
+    }
+
+    fn as_ptr(&self) -> *mut sys::mpv_handle {
+        self.handle.as_ptr()
+    }
+
+    // A second client of this instance, for a render context on another thread
+    pub fn create_client(&self) -> Result<MpvClient> {
+        NonNull::new(unsafe { sys::mpv_create_client(self.as_ptr(), ptr::null()) })
+            .map(MpvClient)
+            .ok_or(MpvError::CreateFailed)
+    }
+
+    // Called from mpv's own threads whenever events are waiting, and by wakeup; like a
+    // render update callback it must not call back into mpv
+    pub fn set_wakeup_callback(&mut self, callback: impl Fn() + Send + Sync + 'static) {
+        let mut callback: Box<Callback> = Box::new(Box::new(callback));
+        unsafe {
+            sys::mpv_set_wakeup_callback(
+                self.as_ptr(),
+                Some(run_callback),
+                &mut *callback as *mut Callback as *mut c_void,
+            )
+        };
+        // Only dropped once mpv has switched to the new one
+        self.wakeup = Some(callback);
+    }
+
+    pub fn set_option_string(&self, name: &str, value: &str) -> Result<()> {
+        let (name, value) = (c_string(name)?, c_string(value)?);
+        check(unsafe { sys::mpv_set_option_string(self.as_ptr(), name.as_ptr(), value.as_ptr()) })
+    }
+
+    fn set_property(&self, name: &str, format: Format, data: *mut c_void) -> Result<()> {
+        let name = c_string(name)?;
+        check(unsafe { sys::mpv_set_property(self.as_ptr(), name.as_ptr(), format.raw(), data) })
+    }
+
+    fn get_property(&self, name: &str, format: Format, data: *mut c_void) -> Result<()> {
+        let name = c_string(name)?;
+        check(unsafe { sys::mpv_get_property(self.as_ptr(), name.as_ptr(), format.raw(), data) })
+    }
+
+    // Deliver log messages at `level` and above as Event::LogMessage
+    pub fn request_log_messages(&self, level: &str) -> Result<()> {
+        let level = c_string(level)?;
+        check(unsafe { sys::mpv_request_log_messages(self.as_ptr(), level.as_ptr()) })
+    }
+
+    // The current frame, in memory; `flags` as for screenshot ("video", "subtitles", ...).
+    // Fails when nothing with video is playing.
+    pub fn screenshot_raw(&self, flags: &str) -> Result<RawFrame> {
+        let (command, flags) = (c_string("screenshot-raw")?, c_string(flags)?);
+        let mut args = [string_node(&command), string_node(&flags)];
+        let mut list = sys::mpv_node_list {
+            num: args.len() as c_int,
+            values: args.as_mut_ptr(),
+            keys: ptr::null_mut(),
+        };
+        let mut command = sys::mpv_node {
+            u: sys::mpv_node__bindgen_ty_1 { list: &mut list },
+            format: sys::mpv_format_MPV_FORMAT_NODE_ARRAY,
+        };
+        let mut result: sys::mpv_node = unsafe { std::mem::zeroed() };
+        check(unsafe { sys::mpv_command_node(self.as_ptr(), &mut command, &mut result) })?;
+        let frame = unsafe { raw_frame(&result) };
+        unsafe { sys::mpv_free_node_contents(&mut result) };
+        frame
+    }
+}
+
+// A node borrowing `value`, as a command argument
+fn string_node(value: &CStr) -> sys::mpv_node {
+    sys::mpv_node {
+        u: sys::mpv_node__bindgen_ty_1 {
+            string: value.as_ptr() as *mut c_char,
+        },
+        format: sys::mpv_format_MPV_FORMAT_STRING,
+    }
+}
+
+// Safety: `node` must be a node mpv returned, still unfreed
+unsafe fn raw_frame(node: &sys::mpv_node) -> Result<RawFrame> {
+    if node.format != sys::mpv_format_MPV_FORMAT_NODE_MAP || node.u.list.is_null() {
+        return Err(MpvError::PropertyFormat);
+    }
+    let list = &*node.u.list;
+    let (mut width, mut height, mut stride) = (0, 0, 0);
+    let mut format = String::new();
+    let mut pixels = Vec::new();
+    for i in 0..list.num.max(0) as usize {
+        let value = &*list.values.add(i);
+        match (from_c_str(*list.keys.add(i)).as_str(), value.format) {
+            ("w", sys::mpv_format_MPV_FORMAT_INT64) => width = value.u.int64,
+            ("h", sys::mpv_format_MPV_FORMAT_INT64) => height = value.u.int64,
+            ("stride", sys::mpv_format_MPV_FORMAT_INT64) => stride = value.u.int64,
+            ("format", sys::mpv_format_MPV_FORMAT_STRING) => format = from_c_str(value.u.string),
+            ("data", sys::mpv_format_MPV_FORMAT_BYTE_ARRAY) if !value.u.ba.is_null() => {
+                let data = &*value.u.ba;
+                if !data.data.is_null() {
+                    pixels = std::slice::from_raw_parts(data.data as *const u8, data.size as usize)
+                        .to_vec();
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(RawFrame {
+        width: u32::try_from(width).map_err(|_| MpvError::PropertyFormat)?,
+        height: u32::try_from(height).map_err(|_| MpvError::PropertyFormat)?,
+        stride: usize::try_from(stride).map_err(|_| MpvError::PropertyFormat)?,
+        format,
+        pixels,
+    })
+}
+
+impl PlayerBackend for MpvHandle {
+    fn set_property_string(&self, name: &str, value: &str) -> Result<()> {
+        let (name, value) = (c_string(name)?, c_string(value)?);
+        check(unsafe { sys::mpv_set_property_string(self.as_ptr(), name.as_ptr(), value.as_ptr()) })
+    }
+
+    fn set_property_flag(&self, name: &str, value: bool) -> Result<()> {
+        let mut value = c_int::from(value);
+        self.set_property(name, Format::Flag, &mut value as *mut c_int as *mut c_void)
+    }
+
+    fn set_property_i64(&self, name: &str, value: i64) -> Result<()> {
+        let mut value = value;
+        self.set_property(name, Format::Int64, &mut value as *mut i64 as *mut c_void)
+    }
+
+    fn set_property_f64(&self, name: &str, value: f64) -> Result<()> {
+        let mut value = value;
+        self.set_property(name, Format::Double, &mut value as *mut f64 as *mut c_void)
+    }
+
+    fn get_property_string(&self, name: &str) -> Result<String> {
+        let mut value = ptr::null_mut::<c_char>();
+        self.get_property(
+            name,
+            Format::String,
+            &mut value as *mut *mut c_char as *mut c_void,
+        )?;
+        let result = from_c_str(value);
+        unsafe { sys::mpv_free(value as *mut c_void) };
+        Ok(result)
+    }
+
+    fn get_property_flag(&self, name: &str) -> Result<bool> {
+        let mut value: c_int = 0;
+        self.get_property(name, Format::Flag, &mut value as *mut c_int as *mut c_void)?;
+        Ok(value != 0)
+    }
+
+    fn get_property_i64(&self, name: &str) -> Result<i64> {
+        let mut value: i64 = 0;
+        self.get_property(name, Format::Int64, &mut value as *mut i64 as *mut c_void)?;
+        Ok(value)
+    }
+
+    fn get_property_f64(&self, name: &str) -> Result<f64> {
+        let mut value: f64 = 0.0;
+        self.get_property(name, Format::Double, &mut value as *mut f64 as *mut c_void)?;
+        Ok(value)
+    }
+
+    // Run a command such as ["seek", "10", "relative"]
+    fn command(&self, args: &[&str]) -> Result<()> {
+        let args = args
+            .iter()
+            .map(|a| c_string(a))
+            .collect::<Result<Vec<_>>>()?;
+        let mut ptrs: Vec<*const c_char> = args.iter().map(|a| a.as_ptr()).collect();
+        ptrs.push(ptr::null());
+        check(unsafe { sys::mpv_command(self.as_ptr(), ptrs.as_mut_ptr()) })
+    }
+
+    // Report changes to `name` as Event::PropertyChange tagged with `id`
+    fn observe(&self, id: u64, name: &str, format: Format) -> Result<()> {
+        let name = c_string(name)?;
+        check(unsafe { sys::mpv_observe_property(self.as_ptr(), id, name.as_ptr(), format.raw()) })
+    }
+
+    // Stop every observer registered with `id`; false if there were none
+    fn unobserve(&self, id: u64) -> Result<bool> {
+        let removed = unsafe { sys::mpv_unobserve_property(self.as_ptr(), id) };
+        check(removed)?;
+        Ok(removed > 0)
+    }
+
+    // Make a blocked wait_event return Event::None
+    fn wakeup(&self) {
+        unsafe { sys::mpv_wakeup(self.as_ptr()) };
+    }
+
+    // Wait up to `timeout` seconds (negative = forever) for the next event. Only one
+    // thread may wait on a handle at a time.
+    fn wait_event(&self, timeout: f64) -> Event {
+        let event = unsafe { &*sys::mpv_wait_event(self.as_ptr(), timeout) };
+        unsafe { convert_event(event) }
+    }
+}
+
+impl Drop for MpvHandle {
+    // Waits for every MpvClient to go first. No wakeup callbacks run once this returns,
+    // so `wakeup` can go after it.
+    fn drop(&mut self) {
+        unsafe { sys::mpv_terminate_destroy(self.as_ptr()) };
+    }
+}
+
+// A client of an instance made by MpvHandle::create_client, which a render context holds
+// so mpv outlives it
+pub struct MpvClient(NonNull<sys::mpv_handle>);
+
+// The client API is thread-safe, and a client is only ever used by whoever holds it; it
+// isn't Sync, so it can't be shared
+unsafe impl Send for MpvClient {}
+
+impl Drop for MpvClient {
+    fn drop(&mut self) {
+        unsafe { sys::mpv_destroy(self.0.as_ptr()) };
+    }
+}
+
+type Callback = Box<dyn Fn() + Send + Sync>;
+
+// Software rendering from render.h, which is newer than these bindings
+const RENDER_API_TYPE_SW: &CStr = c"sw";
+const RENDER_PARAM_SW_SIZE: sys::mpv_render_param_type = 17;
+const RENDER_PARAM_SW_FORMAT: sys::mpv_render_param_type = 18;
+const RENDER_PARAM_SW_STRIDE: sys::mpv_render_param_type = 19;
+const RENDER_PARAM_SW_POINTER: sys::mpv_render_param_type = 20;
+// Bytes per pixel of the "rgb0" format software frames use
+pub const SW_PIXEL_SIZE: usize = 4;
+
+// Renders the video of an instance started with vo=libmpv into a target the app owns,
+// instead of mpv opening a window. Every call, including the drop, must happen on the
+// thread whose GL context was current at creation; a software renderer only has to stay
+// on the thread that created it.
+pub struct RenderContext {
+    context: NonNull<sys::mpv_render_context>,
+    // Boxed twice so the pointer handed to mpv stays put
+    update: Option<Box<Callback>>,
+    // mpv must outlive its render context; dropped after it
+    _client: MpvClient,
+}
+
+impl RenderContext {
+    // An OpenGL renderer; the context it'll draw with must be current. `display` lets
+    // hardware decoding share the app's window system connection.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn opengl(
+        client: MpvClient,
+        get_proc_address: GetProcAddress,
+        display: Option<GlDisplay>,
+    ) -> Result<Self> {
+        let mut init = sys::mpv_opengl_init_params {
+            get_proc_address: Some(proc_address),
+            get_proc_address_ctx: get_proc_address as *mut c_void,
+            extra_exts: ptr::null(),
+        };
+        let mut params = vec![
+            render_param(
+                sys::mpv_render_param_type_MPV_RENDER_PARAM_API_TYPE,
+                sys::MPV_RENDER_API_TYPE_OPENGL.as_ptr() as *mut c_void,
+            ),
+            render_param(
+                sys::mpv_render_param_type_MPV_RENDER_PARAM_OPENGL_INIT_PARAMS,
+                &mut init as *mut _ as *mut c_void,
+            ),
+        ];
+        match display {
+            Some(GlDisplay::X11(display)) => params.push(render_param(
+                sys::mpv_render_param_type_MPV_RENDER_PARAM_X11_DISPLAY,
+                display,
+            )),
+            Some(GlDisplay::Wayland(display)) => params.push(render_param(
+                sys::mpv_render_param_type_MPV_RENDER_PARAM_WL_DISPLAY,
+                display,
+            )),
+            None => {}
+        }
+        Self::create(client, params)
+    }
+
+    // A renderer that draws into memory on the CPU, for when there is no GPU surface
+    pub fn software(client: MpvClient) -> Result<Self> {
+        Self::create(
+            client,
+            vec![render_param(
+                sys::mpv_render_param_type_MPV_RENDER_PARAM_API_TYPE,
+                RENDER_API_TYPE_SW.as_ptr() as *mut c_void,
+            )],
+        )
+    }
+
+    fn create(client: MpvClient, mut params: Vec<sys::mpv_render_param>) -> Result<Self> {
+        params.push(render_param(
+            sys::mpv_render_param_type_MPV_RENDER_PARAM_INVALID,
+            ptr::null_mut(),
+        ));
+        let mut context = ptr::null_mut();
+        check(unsafe {
+            sys::mpv_render_context_create(&mut context, client.0.as_ptr(), params.as_mut_ptr())
+        })?;
+        Ok(Self {
+            context: NonNull::new(context).ok_or(MpvError::Unsupported)?,
+            update: None,
+            _client: client,
+        })
+    }
+
+    // Called from mpv's own threads whenever there is something new to render; it must
+    // not call back into mpv, only schedule a render on the right thread
+    pub fn set_update_callback(&mut self, callback: impl Fn() + Send + Sync + 'static) {
+        let mut callback: Box<Callback> = Box::new(Box::new(callback));
+        unsafe {
+            sys::mpv_render_context_set_update_callback(
+                self.context.as_ptr(),
+                Some(run_callback),
+                &mut *callback as *mut Callback as *mut c_void,
+            )
+        };
+        // Only dropped once mpv has switched to the new one
+        self.update = Some(callback);
+    }
+
+    // True when a new video frame is waiting to be rendered
+    pub fn update(&self) -> bool {
+        let flags = unsafe { sys::mpv_render_context_update(self.context.as_ptr()) };
+        flags & sys::mpv_render_update_flag_MPV_RENDER_UPDATE_FRAME as u64 != 0
+    }
+
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    // Draw the current frame into framebuffer `fbo` (0 for the default one). GL has
+    // y pointing up, so window-backed targets need `flip_y`.
+    pub fn render_opengl(&self, fbo: i32, width: i32, height: i32, flip_y: bool) -> Result<()> {
+        let mut target = sys::mpv_opengl_fbo {
+            fbo,
+            w: width,
+            h: height,
+            internal_format: 0,
+        };
+        let mut flip: c_int = flip_y.into();
+        self.render(vec![
+            render_param(
+                sys::mpv_render_param_type_MPV_RENDER_PARAM_OPENGL_FBO,
+                &mut target as *mut _ as *mut c_void,
+            ),
+            render_param(
+                sys::mpv_render_param_type_MPV_RENDER_PARAM_FLIP_Y,
+                &mut flip as *mut c_int as *mut c_void,
+            ),
+        ])
+    }
+
+    // Draw the current frame into `pixels` as RGBX rows `stride` bytes apart; the X byte
+    // is left unspecified
+    pub fn render_software(
+        &self,
+        width: i32,
+        height: i32,
+        stride: usize,
+        pixels: &mut [u8],
+    ) -> Result<()> {
+        check_software_target(width, height, stride, pixels.len())?;
+        let mut size: [c_int; 2] = [width, height];
+        let mut stride = stride;
+        self.render(vec![
+            render_param(RENDER_PARAM_SW_SIZE, size.as_mut_ptr() as *mut c_void),
+            render_param(RENDER_PARAM_SW_FORMAT, c"rgb0".as_ptr() as *mut c_void),
+            render_param(
+                RENDER_PARAM_SW_STRIDE,
+                &mut stride as *mut usize as *mut c_void,
+            ),
+            render_param(RENDER_PARAM_SW_POINTER, pixels.as_mut_ptr() as *mut c_void),
+        ])
+    }
+
+    fn render(&self, mut params: Vec<sys::mpv_render_param>) -> Result<()> {
+        params.push(render_param(
+            sys::mpv_render_param_type_MPV_RENDER_PARAM_INVALID,
+            ptr::null_mut(),
+        ));
+        check(unsafe { sys::mpv_render_context_render(self.context.as_ptr(), params.as_mut_ptr()) })
+    }
+}
+
+impl Drop for RenderContext {
+    fn drop(&mut self) {
+        // No update callbacks run once this returns, so `update` can go after it
+        unsafe { sys::mpv_render_context_free(self.context.as_ptr()) };
+    }
+}
+
+fn render_param(kind: sys::mpv_render_param_type, data: *mut c_void) -> sys::mpv_render_param {
+    sys::mpv_render_param { type_: kind, data }
+}
+
+// mpv writes `height` rows of `stride` bytes without checking the buffer
+fn check_software_target(width: i32, height: i32, stride: usize, len: usize) -> Result<()> {
+    if width <= 0
+        || height <= 0
+        || stride < width as usize * SW_PIXEL_SIZE
+        || len < stride * height as usize
+    {
+        return Err(MpvError::InvalidParameter);
+    }
+    Ok(())
+}
+
+// mpv's get_proc_address callback; `ctx` is the app's lookup function
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+unsafe extern "C" fn proc_address(ctx: *mut c_void, name: *const c_char) -> *mut c_void {
+    let lookup: GetProcAddress = std::mem::transmute(ctx);
+    lookup(name)
+}
+
+// Runs a wakeup or render update callback; `ctx` is the boxed Callback
+unsafe extern "C" fn run_callback(ctx: *mut c_void) {
+    let callback = &*(ctx as *const Callback);
+    callback();
+}
+
+// Safety: `event.data` must point to the struct matching `event.event_id`, as it does for
+// events returned by mpv_wait_event
+unsafe fn convert_event(event: &sys::mpv_event) -> Event {
+    match event.event_id {
+        sys::mpv_event_id_MPV_EVENT_NONE => Event::None,
+        sys::mpv_event_id_MPV_EVENT_SHUTDOWN => Event::Shutdown,
+        sys::mpv_event_id_MPV_EVENT_START_FILE => {
+            let data = &*(event.data as *const sys::mpv_event_start_file);
+            Event::StartFile {
+                playlist_entry_id: data.playlist_entry_id,
+            }
+        }
+        sys::mpv_event_id_MPV_EVENT_END_FILE => {
+            let data = &*(event.data as *const sys::mpv_event_end_file);
+            Event::EndFile {
+                reason: end_file_reason(data.reason as u32, data.error),
+                playlist_entry_id: data.playlist_entry_id,
+            }
+        }
+        sys::mpv_event_id_MPV_EVENT_FILE_LOADED => Event::FileLoaded,
+        sys::mpv_event_id_MPV_EVENT_SEEK => Event::Seek,
+        sys::mpv_event_id_MPV_EVENT_PLAYBACK_RESTART => Event::PlaybackRestart,
+        sys::mpv_event_id_MPV_EVENT_IDLE => Event::Idle,
+        sys::mpv_event_id_MPV_EVENT_LOG_MESSAGE => {
+            let data = &*(event.data as *const sys::mpv_event_log_message);
+            Event::LogMessage {
+                prefix: from_c_str(data.prefix),
+                level: from_c_str(data.level),
+                text: from_c_str(data.text),
+            }
+        }
+        sys::mpv_event_id_MPV_EVENT_PROPERTY_CHANGE => {
+            let data = &*(event.data as *const sys::mpv_event_property);
+            Event::PropertyChange {
+                id: event.reply_userdata,
+                name: from_c_str(data.name),
+                value: property_value(data),
+            }
+        }
+        _ => Event::Other,
+    }
+}
+
+fn end_file_reason(reason: u32, error: c_int) -> EndFileReason {
+    match reason {
+        sys::mpv_end_file_reason_MPV_END_FILE_REASON_EOF => EndFileReason::Eof,
+        sys::mpv_end_file_reason_MPV_END_FILE_REASON_STOP => EndFileReason::Stop,
+        sys::mpv_end_file_reason_MPV_END_FILE_REASON_QUIT => EndFileReason::Quit,
+        sys::mpv_end_file_reason_MPV_END_FILE_REASON_ERROR => {
+            EndFileReason::Error(MpvError::from_code(error).unwrap_or(MpvError::Other(error)))
+        }
+        sys::mpv_end_file_reason_MPV_END_FILE_REASON_REDIRECT => EndFileReason::Redirect,
+        other => EndFileReason::Other(other as i32),
+    }
+}
+
+unsafe fn property_value(data: &sys::mpv_event_property) -> PropertyValue {
+    if data.data.is_null() {
+        return PropertyValue::None;
+    }
+    match data.format {
+        sys::mpv_format_MPV_FORMAT_STRING => {
+            PropertyValue::String(from_c_str(*(data.data as *const *const c_char)))
+        }
+        sys::mpv_format_MPV_FORMAT_FLAG => PropertyValue::Flag(*(data.data as *const c_int) != 0),
+        sys::mpv_format_MPV_FORMAT_INT64 => PropertyValue::Int64(*(data.data as *const i64)),
+        sys::mpv_format_MPV_FORMAT_DOUBLE => PropertyValue::Double(*(data.data as *const f64)),
+        _ => PropertyValue::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn c_string_rejects_interior_nul() {
+        assert_eq!(c_string("volume").unwrap().as_bytes(), b"volume");
+        assert_eq!(
+            c_string("vol\0ume"),
+            Err(MpvError::NulByte("vol\0ume".to_string()))
+        );
+    }
+
+    #[test]
+    fn success_codes_are_not_errors() {
+        assert_eq!(check(0), Ok(()));
+        assert_eq!(check(3), Ok(()));
+    }
+
+    #[test]
+    fn software_targets_must_hold_the_frame() {
+        assert_eq!(check_software_target(2, 2, 8, 16), Ok(()));
+        assert_eq!(check_software_target(2, 2, 64, 128), Ok(()));
+        assert!(check_software_target(2, 2, 4, 16).is_err());
+        assert!(check_software_target(2, 2, 8, 15).is_err());
+        assert!(check_software_target(0, 2, 8, 16).is_err());
+    }
+
+    #[test]
+    fn error_codes_match_libmpv() {
+        assert_eq!(
+            MpvError::from_code(sys::mpv_error_MPV_ERROR_PROPERTY_NOT_FOUND),
+            Some(MpvError::PropertyNotFound)
+        );
+        assert_eq!(
+            MpvError::Unsupported.code(),
+            Some(sys::mpv_error_MPV_ERROR_UNSUPPORTED)
+        );
+    }
+
+    #[test]
+    fn end_file_errors_keep_the_code() {
+        assert_eq!(
+            end_file_reason(
+                sys::mpv_end_file_reason_MPV_END_FILE_REASON_ERROR,
+                sys::mpv_error_MPV_ERROR_LOADING_FAILED
+            ),
+            EndFileReason::Error(MpvError::LoadingFailed)
+        );
+        assert_eq!(
+            end_file_reason(sys::mpv_end_file_reason_MPV_END_FILE_REASON_EOF, 0),
+            EndFileReason::Eof
+        );
+    }
+}
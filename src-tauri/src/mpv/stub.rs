@@ -0,0 +1,1518 @@
+// In-memory stand-in for libmpv, used when the `real-mpv` feature is off so the app, CI
+// and tests run without libmpv installed. Properties live in a map, time-pos advances
+// with the clock while a file "plays", and commands queue the events mpv would send.
+
+use std::collections::{HashMap, VecDeque};
+use std::marker::PhantomData;
+use std::path::Path;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use super::{
+    EndFileReason, Event, Format, GetProcAddress, GlDisplay, MpvError, PlayerBackend,
+    PropertyValue, RawFrame, Result,
+};
+
+// Every file pretends to be this long, in seconds
+pub const FAKE_DURATION: f64 = 600.0;
+
+// Width and height of the frames screenshot_raw makes up
+const FAKE_FRAME_SIZE: (u32, u32) = (16, 9);
+
+// The tracks every file pretends to have, as in track-list
+struct FakeTrack {
+    kind: &'static str,
+    id: i64,
+    lang: &'static str,
+    title: Option<&'static str>,
+    codec: &'static str,
+    default: bool,
+    forced: bool,
+}
+
+const FAKE_TRACKS: &[FakeTrack] = &[
+    FakeTrack {
+        kind: "video",
+        id: 1,
+        lang: "und",
+        title: None,
+        codec: "h264",
+        default: true,
+        forced: false,
+    },
+    FakeTrack {
+        kind: "audio",
+        id: 1,
+        lang: "eng",
+        title: Some("Stereo"),
+        codec: "aac",
+        default: true,
+        forced: false,
+    },
+    FakeTrack {
+        kind: "audio",
+        id: 2,
+        lang: "jpn",
+        title: None,
+        codec: "opus",
+        default: false,
+        forced: false,
+    },
+    FakeTrack {
+        kind: "sub",
+        id: 1,
+        lang: "eng",
+        title: None,
+        codec: "subrip",
+        default: false,
+        forced: false,
+    },
+    FakeTrack {
+        kind: "sub",
+        id: 2,
+        lang: "eng",
+        title: Some("Signs"),
+        codec: "ass",
+        default: false,
+        forced: true,
+    },
+];
+
+// A subtitle file attached with sub-add; gone when the next file starts
+struct ExternalSub {
+    id: i64,
+    path: String,
+    title: Option<String>,
+    lang: Option<String>,
+}
+
+// The property selecting tracks of `kind`
+fn track_property_for(kind: &str) -> &'static str {
+    match kind {
+        "video" => "vid",
+        "audio" => "aid",
+        _ => "sid",
+    }
+}
+
+// How often observed time-pos/percent-pos are reported while playing
+const TICK: Duration = Duration::from_millis(250);
+
+// Properties computed from the playback state rather than stored
+const PLAYBACK_PROPERTIES: &[&str] = &[
+    "path",
+    "filename",
+    "media-title",
+    "duration",
+    "time-pos",
+    "percent-pos",
+    "time-remaining",
+    "playlist-pos",
+    "playlist-count",
+    "idle-active",
+    "eof-reached",
+];
+
+pub(super) fn error_string(code: i32) -> String {
+    match MpvError::from_code(code) {
+        Some(MpvError::EventQueueFull) => "event queue full",
+        Some(MpvError::InvalidParameter) => "invalid parameter",
+        Some(MpvError::OptionNotFound) => "option not found",
+        Some(MpvError::OptionFormat) => "unsupported format for accessing option",
+        Some(MpvError::OptionError) => "error setting option",
+        Some(MpvError::PropertyNotFound) => "property not found",
+        Some(MpvError::PropertyFormat) => "unsupported format for accessing property",
+        Some(MpvError::PropertyUnavailable) => "property unavailable",
+        Some(MpvError::PropertyError) => "error accessing property",
+        Some(MpvError::Command) => "error running command",
+        Some(MpvError::LoadingFailed) => "loading failed",
+        Some(MpvError::UnknownFormat) => "unrecognized file format",
+        Some(MpvError::Unsupported) => "not implemented",
+        None => "success",
+        _ => "unknown error",
+    }
+    .to_string()
+}
+
+fn check_nul(value: &str) -> Result<()> {
+    if value.contains('\0') {
+        return Err(MpvError::NulByte(value.to_string()));
+    }
+    Ok(())
+}
+
+fn as_string(value: &PropertyValue) -> Result<String> {
+    Ok(match value {
+        PropertyValue::None => return Err(MpvError::PropertyUnavailable),
+        PropertyValue::String(s) => s.clone(),
+        PropertyValue::Flag(b) => if *b { "yes" } else { "no" }.to_string(),
+        PropertyValue::Int64(i) => i.to_string(),
+        // mpv prints doubles with six decimals
+        PropertyValue::Double(d) => format!("{:.6}", d),
+    })
+}
+
+fn as_flag(value: &PropertyValue) -> Result<bool> {
+    match value {
+        PropertyValue::Flag(b) => Ok(*b),
+        PropertyValue::String(s) if s == "yes" => Ok(true),
+        PropertyValue::String(s) if s == "no" => Ok(false),
+        PropertyValue::None => Err(MpvError::PropertyUnavailable),
+        _ => Err(MpvError::PropertyFormat),
+    }
+}
+
+fn as_i64(value: &PropertyValue) -> Result<i64> {
+    match value {
+        PropertyValue::Int64(i) => Ok(*i),
+        PropertyValue::String(s) => s.parse().map_err(|_| MpvError::PropertyFormat),
+        PropertyValue::None => Err(MpvError::PropertyUnavailable),
+        _ => Err(MpvError::PropertyFormat),
+    }
+}
+
+fn as_f64(value: &PropertyValue) -> Result<f64> {
+    match value {
+        PropertyValue::Double(d) => Ok(*d),
+        PropertyValue::Int64(i) => Ok(*i as f64),
+        PropertyValue::String(s) => s.parse().map_err(|_| MpvError::PropertyFormat),
+        PropertyValue::None => Err(MpvError::PropertyUnavailable),
+        _ => Err(MpvError::PropertyFormat),
+    }
+}
+
+// Convert to `format`, as mpv does for observed properties
+fn convert(value: &PropertyValue, format: Format) -> PropertyValue {
+    let converted = match format {
+        Format::None => return PropertyValue::None,
+        Format::String => as_string(value).map(PropertyValue::String),
+        Format::Flag => as_flag(value).map(PropertyValue::Flag),
+        Format::Int64 => as_i64(value).map(PropertyValue::Int64),
+        Format::Double => as_f64(value).map(PropertyValue::Double),
+    };
+    converted.unwrap_or(PropertyValue::None)
+}
+
+// Give `new` the type of the value it replaces, so e.g. "speed" stays a double when set
+// from a string; properties we haven't seen take whatever they are given
+fn coerce(existing: Option<&PropertyValue>, new: PropertyValue) -> Result<PropertyValue> {
+    match existing {
+        Some(PropertyValue::Flag(_)) => as_flag(&new).map(PropertyValue::Flag),
+        Some(PropertyValue::Int64(_)) => as_i64(&new).map(PropertyValue::Int64),
+        Some(PropertyValue::Double(_)) => as_f64(&new).map(PropertyValue::Double),
+        _ => Ok(new),
+    }
+}
+
+fn default_properties() -> HashMap<String, PropertyValue> {
+    let defaults = [
+        ("pause", PropertyValue::Flag(false)),
+        ("speed", PropertyValue::Double(1.0)),
+        ("volume", PropertyValue::Double(100.0)),
+        ("volume-max", PropertyValue::Double(130.0)),
+        ("mute", PropertyValue::Flag(false)),
+        ("audio-pitch-correction", PropertyValue::Flag(true)),
+        ("sub-visibility", PropertyValue::Flag(true)),
+        ("sub-delay", PropertyValue::Double(0.0)),
+        ("audio-delay", PropertyValue::Double(0.0)),
+        ("audio-device", PropertyValue::String("auto".to_string())),
+        ("audio-device-list/count", PropertyValue::Int64(2)),
+        (
+            "audio-device-list/0/name",
+            PropertyValue::String("auto".to_string()),
+        ),
+        (
+            "audio-device-list/0/description",
+            PropertyValue::String("Autoselect device".to_string()),
+        ),
+        (
+            "audio-device-list/1/name",
+            PropertyValue::String("null".to_string()),
+        ),
+        (
+            "audio-device-list/1/description",
+            PropertyValue::String("Null audio output".to_string()),
+        ),
+        ("vid", PropertyValue::String("auto".to_string())),
+        ("aid", PropertyValue::String("auto".to_string())),
+        ("sid", PropertyValue::String("no".to_string())),
+        ("chapters", PropertyValue::Int64(0)),
+        ("chapter-list/count", PropertyValue::Int64(0)),
+        ("loop-file", PropertyValue::String("no".to_string())),
+        ("loop-playlist", PropertyValue::String("no".to_string())),
+        ("keep-open", PropertyValue::String("no".to_string())),
+        ("hr-seek", PropertyValue::String("default".to_string())),
+        ("hr-seek-framedrop", PropertyValue::Flag(true)),
+    ];
+    defaults
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect()
+}
+
+struct Entry {
+    id: i64,
+    path: String,
+}
+
+struct State {
+    properties: HashMap<String, PropertyValue>,
+    observers: Vec<(u64, String, Format)>,
+    events: VecDeque<Event>,
+    playlist: Vec<Entry>,
+    next_entry_id: i64,
+    // Entry ids in the order playlist-shuffle found them, for playlist-unshuffle
+    unshuffled: Vec<i64>,
+    external_subs: Vec<ExternalSub>,
+    // Index into playlist of the loaded file, if any
+    current: Option<usize>,
+    // time-pos as of `since`; it advances at `speed` from there unless paused
+    position: f64,
+    since: Instant,
+    // Per-file `end` from loadfile options
+    end: Option<f64>,
+    eof_reached: bool,
+    last_tick: Instant,
+    woken: bool,
+    shutdown: bool,
+}
+
+impl State {
+    fn flag(&self, name: &str) -> bool {
+        self.properties
+            .get(name)
+            .and_then(|v| as_flag(v).ok())
+            .unwrap_or(false)
+    }
+
+    fn string(&self, name: &str) -> String {
+        self.properties
+            .get(name)
+            .and_then(|v| as_string(v).ok())
+            .unwrap_or_default()
+    }
+
+    fn speed(&self) -> f64 {
+        self.properties
+            .get("speed")
+            .and_then(|v| as_f64(v).ok())
+            .unwrap_or(1.0)
+    }
+
+    fn playing(&self) -> bool {
+        self.current.is_some() && !self.flag("pause") && !self.eof_reached
+    }
+
+    fn end_time(&self) -> f64 {
+        self.end.unwrap_or(FAKE_DURATION).clamp(0.0, FAKE_DURATION)
+    }
+
+    fn time_pos(&self) -> f64 {
+        if !self.playing() {
+            return self.position;
+        }
+        let elapsed = self.since.elapsed().as_secs_f64() * self.speed();
+        (self.position + elapsed).min(self.end_time())
+    }
+
+    // Fold the elapsed time into `position`, e.g. before pause or speed change
+    fn rebase(&mut self) {
+        self.position = self.time_pos();
+        self.since = Instant::now();
+    }
+
+    // The id (or "no") of the track of `kind` that's playing; "auto" picks the default
+    fn selected_track(&self, kind: &str) -> String {
+        let selection = self.string(track_property_for(kind));
+        if selection != "auto" {
+            return selection;
+        }
+        FAKE_TRACKS
+            .iter()
+            .find(|t| t.kind == kind && t.default)
+            .map_or("no".to_string(), |t| t.id.to_string())
+    }
+
+    // "track-list/count", "track-list/2/lang" and so on
+    fn track_list_property(&self, name: &str) -> Option<PropertyValue> {
+        let rest = name.strip_prefix("track-list/")?;
+        if rest == "count" {
+            let count = FAKE_TRACKS.len() + self.external_subs.len();
+            return Some(PropertyValue::Int64(count as i64));
+        }
+        let (index, field) = rest.split_once('/')?;
+        let index = index.parse::<usize>().ok()?;
+        let string = |value: &str| PropertyValue::String(value.to_string());
+        let Some(track) = FAKE_TRACKS.get(index) else {
+            let sub = self.external_subs.get(index - FAKE_TRACKS.len())?;
+            return Some(match field {
+                "id" => PropertyValue::Int64(sub.id),
+                "type" => string("sub"),
+                "lang" => sub.lang.as_deref().map_or(PropertyValue::None, string),
+                "title" => sub.title.as_deref().map_or(PropertyValue::None, string),
+                "codec" => string(match Path::new(&sub.path).extension() {
+                    Some(ext) if ext == "srt" => "subrip",
+                    Some(ext) if ext == "vtt" => "webvtt",
+                    _ => "ass",
+                }),
+                "default" | "forced" => PropertyValue::Flag(false),
+                "external" => PropertyValue::Flag(true),
+                "external-filename" => string(&sub.path),
+                "selected" => PropertyValue::Flag(self.selected_track("sub") == sub.id.to_string()),
+                _ => return None,
+            });
+        };
+        Some(match field {
+            "id" => PropertyValue::Int64(track.id),
+            "type" => string(track.kind),
+            "lang" => string(track.lang),
+            "title" => track.title.map_or(PropertyValue::None, string),
+            "codec" => string(track.codec),
+            "default" => PropertyValue::Flag(track.default),
+            "forced" => PropertyValue::Flag(track.forced),
+            "external" => PropertyValue::Flag(false),
+            "selected" => {
+                PropertyValue::Flag(self.selected_track(track.kind) == track.id.to_string())
+            }
+            _ => return None,
+        })
+    }
+
+    // Step through the tracks of `kind` in id order, then "no", as mpv's cycle does
+    fn cycle_track(&mut self, kind: &str, down: bool) -> Result<()> {
+        let mut choices: Vec<String> = FAKE_TRACKS
+            .iter()
+            .filter(|t| t.kind == kind)
+            .map(|t| t.id.to_string())
+            .collect();
+        if kind == "sub" {
+            choices.extend(self.external_subs.iter().map(|s| s.id.to_string()));
+        }
+        choices.push("no".to_string());
+        let current = self.selected_track(kind);
+        let index = choices.iter().position(|c| *c == current).unwrap_or(0);
+        let next = if down {
+            (index + choices.len() - 1) % choices.len()
+        } else {
+            (index + 1) % choices.len()
+        };
+        let value = PropertyValue::String(choices[next].clone());
+        self.set(track_property_for(kind), value)
+    }
+
+    // sub-add: "select" (the default) plays the new track, "auto" only adds it, and
+    // "cached" selects an already attached copy of the same file instead of adding it again
+    fn add_subtitle(
+        &mut self,
+        path: &str,
+        flag: &str,
+        title: Option<&str>,
+        lang: Option<&str>,
+    ) -> Result<()> {
+        if self.current.is_none() {
+            return Err(MpvError::Command);
+        }
+        if !matches!(flag, "select" | "auto" | "cached") {
+            return Err(MpvError::InvalidParameter);
+        }
+        let existing = self.external_subs.iter().find(|s| s.path == path);
+        let id = match existing {
+            Some(sub) if flag == "cached" => sub.id,
+            _ => {
+                let last = FAKE_TRACKS
+                    .iter()
+                    .filter(|t| t.kind == "sub")
+                    .map(|t| t.id)
+                    .chain(self.external_subs.iter().map(|s| s.id))
+                    .max()
+                    .unwrap_or(0);
+                self.external_subs.push(ExternalSub {
+                    id: last + 1,
+                    path: path.to_string(),
+                    title: Some(title.map_or_else(|| file_name(path), str::to_string)),
+                    lang: lang.map(str::to_string),
+                });
+                self.notify("track-list");
+                last + 1
+            }
+        };
+        if flag == "auto" {
+            return Ok(());
+        }
+        self.set("sid", PropertyValue::String(id.to_string()))
+    }
+
+    fn current_entry(&self) -> Option<&Entry> {
+        self.current.map(|i| &self.playlist[i])
+    }
+
+    fn get(&self, name: &str) -> Result<PropertyValue> {
+        let entry = self.current_entry();
+        let loaded = |value: PropertyValue| {
+            if entry.is_some() {
+                Ok(value)
+            } else {
+                Err(MpvError::PropertyUnavailable)
+            }
+        };
+        match name {
+            "path" => loaded(PropertyValue::String(
+                entry.map(|e| e.path.clone()).unwrap_or_default(),
+            )),
+            "filename" | "media-title" => loaded(PropertyValue::String(
+                entry.map(|e| file_name(&e.path)).unwrap_or_default(),
+            )),
+            "duration" => loaded(PropertyValue::Double(FAKE_DURATION)),
+            "time-pos" | "playback-time" => loaded(PropertyValue::Double(self.time_pos())),
+            "percent-pos" => loaded(PropertyValue::Double(
+                self.time_pos() / FAKE_DURATION * 100.0,
+            )),
+            "time-remaining" => loaded(PropertyValue::Double(FAKE_DURATION - self.time_pos())),
+            "playlist-pos" => Ok(PropertyValue::Int64(self.current.map_or(-1, |i| i as i64))),
+            "playlist-count" => Ok(PropertyValue::Int64(self.playlist.len() as i64)),
+            "idle-active" => Ok(PropertyValue::Flag(self.current.is_none())),
+            "eof-reached" => loaded(PropertyValue::Flag(self.eof_reached)),
+            _ if name.starts_with("track-list/") => self
+                .track_list_property(name)
+                .map_or(Err(MpvError::PropertyUnavailable), loaded),
+            _ => {
+                if let Some((index, property)) = playlist_property(name) {
+                    let entry = self
+                        .playlist
+                        .get(index)
+                        .ok_or(MpvError::PropertyUnavailable)?;
+                    return match property {
+                        "filename" => Ok(PropertyValue::String(entry.path.clone())),
+                        "id" => Ok(PropertyValue::Int64(entry.id)),
+                        "current" => Ok(PropertyValue::Flag(self.current == Some(index))),
+                        _ => Err(MpvError::PropertyUnavailable),
+                    };
+                }
+                self.properties
+                    .get(name)
+                    .cloned()
+                    .ok_or(MpvError::PropertyNotFound)
+            }
+        }
+    }
+
+    fn set(&mut self, name: &str, value: PropertyValue) -> Result<()> {
+        match name {
+            "time-pos" | "playback-time" => return self.seek(as_f64(&value)?),
+            "playlist-pos" => {
+                let index = as_i64(&value)?;
+                return match usize::try_from(index) {
+                    Ok(index) if index < self.playlist.len() => {
+                        self.play(index, EndFileReason::Stop);
+                        Ok(())
+                    }
+                    _ if index == -1 => {
+                        self.stop();
+                        Ok(())
+                    }
+                    _ => Err(MpvError::PropertyError),
+                };
+            }
+            _ if PLAYBACK_PROPERTIES.contains(&name) || playlist_property(name).is_some() => {
+                return Err(MpvError::PropertyError);
+            }
+            _ => {}
+        }
+
+        let value = coerce(self.properties.get(name), value)?;
+        if name == "pause" || name == "speed" {
+            self.rebase();
+        }
+        self.properties.insert(name.to_string(), value);
+        self.notify(name);
+        Ok(())
+    }
+
+    // Queue a change event for everyone observing `name`
+    fn notify(&mut self, name: &str) {
+        let value = self.get(name).unwrap_or(PropertyValue::None);
+        let changes: Vec<_> = self
+            .observers
+            .iter()
+            .filter(|(_, observed, _)| observed == name)
+            .map(|(id, _, format)| Event::PropertyChange {
+                id: *id,
+                name: name.to_string(),
+                value: convert(&value, *format),
+            })
+            .collect();
+        self.events.extend(changes);
+    }
+
+    // Rearrange the playlist, keeping `current` on the entry that's playing
+    fn reorder(&mut self, change: impl FnOnce(&mut Vec<Entry>)) {
+        let current = self.current.map(|i| self.playlist[i].id);
+        change(&mut self.playlist);
+        self.current = current.and_then(|id| self.playlist.iter().position(|e| e.id == id));
+        self.notify_playback();
+    }
+
+    fn notify_playback(&mut self) {
+        for name in PLAYBACK_PROPERTIES {
+            self.notify(name);
+        }
+    }
+
+    fn end_current(&mut self, reason: EndFileReason) {
+        if let Some(entry) = self.current_entry() {
+            let playlist_entry_id = entry.id;
+            self.events.push_back(Event::EndFile {
+                reason,
+                playlist_entry_id,
+            });
+        }
+    }
+
+    // Switch to playlist[index], ending whatever was playing for `reason`
+    fn play(&mut self, index: usize, reason: EndFileReason) {
+        self.end_current(reason);
+        self.current = Some(index);
+        self.external_subs.clear();
+        self.position = 0.0;
+        self.since = Instant::now();
+        self.eof_reached = false;
+        self.events.push_back(Event::StartFile {
+            playlist_entry_id: self.playlist[index].id,
+        });
+        self.notify_playback();
+        self.events.push_back(Event::FileLoaded);
+        self.events.push_back(Event::PlaybackRestart);
+
+        // Encoding (clip export) runs as fast as it can, so finish straight away
+        if !self.string("o").is_empty() {
+            self.position = self.end_time();
+            self.finish_file();
+        }
+    }
+
+    fn stop(&mut self) {
+        self.end_current(EndFileReason::Stop);
+        self.playlist.clear();
+        self.go_idle();
+    }
+
+    fn go_idle(&mut self) {
+        self.current = None;
+        self.external_subs.clear();
+        self.position = 0.0;
+        self.end = None;
+        self.eof_reached = false;
+        self.notify_playback();
+        self.events.push_back(Event::Idle);
+    }
+
+    fn seek(&mut self, target: f64) -> Result<()> {
+        if self.current.is_none() || !target.is_finite() {
+            return Err(MpvError::Command);
+        }
+        self.position = target.clamp(0.0, self.end_time());
+        self.since = Instant::now();
+        self.eof_reached = false;
+        self.notify("time-pos");
+        self.notify("percent-pos");
+        self.notify("eof-reached");
+        self.events.push_back(Event::Seek);
+        self.events.push_back(Event::PlaybackRestart);
+        Ok(())
+    }
+
+    // The loaded file reached its end: loop, hold the last frame, or move on
+    fn finish_file(&mut self) {
+        let Some(index) = self.current else {
+            return;
+        };
+        if !matches!(self.string("loop-file").as_str(), "no" | "") {
+            self.position = 0.0;
+            self.since = Instant::now();
+            self.events.push_back(Event::PlaybackRestart);
+            return;
+        }
+        let keep_open = self.string("keep-open");
+        let last = index + 1 == self.playlist.len();
+        if keep_open == "always" || (keep_open == "yes" && last) {
+            self.rebase();
+            self.eof_reached = true;
+            // As with mpv's default keep-open-pause=yes
+            self.properties
+                .insert("pause".to_string(), PropertyValue::Flag(true));
+            self.notify("pause");
+            self.notify("eof-reached");
+            return;
+        }
+
+        self.end = None;
+        if !last {
+            self.play(index + 1, EndFileReason::Eof);
+        } else if !matches!(self.string("loop-playlist").as_str(), "no" | "") {
+            self.play(0, EndFileReason::Eof);
+        } else {
+            self.end_current(EndFileReason::Eof);
+            self.go_idle();
+        }
+    }
+
+    // Catch up with the clock: report the position and handle reaching the end
+    fn advance(&mut self) {
+        if !self.playing() {
+            return;
+        }
+        if self.time_pos() >= self.end_time() {
+            self.rebase();
+            self.finish_file();
+        } else if self.last_tick.elapsed() >= TICK {
+            self.last_tick = Instant::now();
+            self.notify("time-pos");
+            self.notify("percent-pos");
+        }
+    }
+
+    fn loadfile(&mut self, path: &str, mode: &str, options: &str) -> Result<()> {
+        let entry = Entry {
+            id: self.next_entry_id,
+            path: path.to_string(),
+        };
+        self.next_entry_id += 1;
+
+        let index = match mode {
+            "replace" => {
+                self.end_current(EndFileReason::Stop);
+                self.playlist.clear();
+                self.current = None;
+                0
+            }
+            "append" | "append-play" => self.playlist.len(),
+            "insert-next" | "insert-next-play" => self.current.map_or(0, |i| i + 1),
+            _ => return Err(MpvError::InvalidParameter),
+        };
+        // The entry we were playing moves down if something is inserted before it
+        if let Some(current) = self.current.as_mut() {
+            if index <= *current {
+                *current += 1;
+            }
+        }
+        self.playlist.insert(index, entry);
+        self.notify("playlist-count");
+
+        let starts = mode == "replace" || (mode.ends_with("-play") && self.current.is_none());
+        if starts {
+            // Only the options the stub can act on; quoted values never come first
+            let mut start = None;
+            for option in options.split(',') {
+                match option.split_once('=') {
+                    Some(("start", value)) => start = value.parse::<f64>().ok(),
+                    Some(("end", value)) => self.end = value.parse().ok(),
+                    Some(("pause", "yes")) => {
+                        self.properties
+                            .insert("pause".to_string(), PropertyValue::Flag(true));
+                    }
+                    _ => {}
+                }
+            }
+            self.play(index, EndFileReason::Stop);
+            if let Some(start) = start {
+                let start = if start < 0.0 {
+                    FAKE_DURATION + start
+                } else {
+                    start
+                };
+                self.position = start.clamp(0.0, self.end_time());
+            }
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, args: &[&str]) -> Result<()> {
+        let Some((&name, args)) = args.split_first() else {
+            return Err(MpvError::InvalidParameter);
+        };
+        let arg = |i: usize| args.get(i).copied();
+        match name {
+            "loadfile" => {
+                let path = arg(0).ok_or(MpvError::InvalidParameter)?;
+                self.loadfile(path, arg(1).unwrap_or("replace"), arg(3).unwrap_or(""))
+            }
+            "stop" if arg(0) == Some("keep-playlist") => {
+                self.end_current(EndFileReason::Stop);
+                self.go_idle();
+                Ok(())
+            }
+            "stop" => {
+                self.stop();
+                Ok(())
+            }
+            "quit" => {
+                self.end_current(EndFileReason::Quit);
+                self.shutdown = true;
+                self.events.push_back(Event::Shutdown);
+                Ok(())
+            }
+            "seek" => {
+                let amount: f64 = arg(0)
+                    .and_then(|a| a.parse().ok())
+                    .ok_or(MpvError::InvalidParameter)?;
+                let flags = arg(1).unwrap_or("relative");
+                let target = if flags.contains("absolute-percent") {
+                    amount / 100.0 * FAKE_DURATION
+                } else if flags.contains("relative-percent") {
+                    self.time_pos() + amount / 100.0 * FAKE_DURATION
+                } else if flags.contains("absolute") {
+                    amount
+                } else {
+                    self.time_pos() + amount
+                };
+                self.seek(target)
+            }
+            "playlist-next" | "playlist-prev" => {
+                let Some(current) = self.current else {
+                    return Err(MpvError::Command);
+                };
+                let next = if name == "playlist-next" {
+                    current + 1
+                } else {
+                    current.checked_sub(1).ok_or(MpvError::Command)?
+                };
+                if next >= self.playlist.len() {
+                    return Err(MpvError::Command);
+                }
+                self.play(next, EndFileReason::Stop);
+                Ok(())
+            }
+            "playlist-play-index" => {
+                let index: usize = arg(0)
+                    .and_then(|a| a.parse().ok())
+                    .filter(|&i| i < self.playlist.len())
+                    .ok_or(MpvError::InvalidParameter)?;
+                self.play(index, EndFileReason::Stop);
+                Ok(())
+            }
+            "playlist-clear" => {
+                // Everything but the current file, as in mpv
+                match self.current {
+                    Some(current) => {
+                        let entry = self.playlist.swap_remove(current);
+                        self.playlist = vec![entry];
+                        self.current = Some(0);
+                    }
+                    None => self.playlist.clear(),
+                }
+                self.notify_playback();
+                Ok(())
+            }
+            "playlist-remove" => {
+                let index = match arg(0).unwrap_or("current") {
+                    "current" => self.current.ok_or(MpvError::Command)?,
+                    index => index.parse().map_err(|_| MpvError::InvalidParameter)?,
+                };
+                if index >= self.playlist.len() {
+                    return Err(MpvError::Command);
+                }
+                if self.current == Some(index) {
+                    self.end_current(EndFileReason::Stop);
+                    self.playlist.remove(index);
+                    if index < self.playlist.len() {
+                        self.current = None;
+                        self.play(index, EndFileReason::Stop);
+                    } else {
+                        self.go_idle();
+                    }
+                } else {
+                    self.playlist.remove(index);
+                    if let Some(current) = self.current.as_mut() {
+                        if index < *current {
+                            *current -= 1;
+                        }
+                    }
+                    self.notify_playback();
+                }
+                Ok(())
+            }
+            // Reverses the playlist rather than shuffling it, so tests know the order
+            "playlist-shuffle" => {
+                self.unshuffled = self.playlist.iter().map(|e| e.id).collect();
+                self.reorder(|playlist| playlist.reverse());
+                Ok(())
+            }
+            // Entries added since the shuffle go last, in the order they're in
+            "playlist-unshuffle" => {
+                let unshuffled = std::mem::take(&mut self.unshuffled);
+                self.reorder(|playlist| {
+                    playlist.sort_by_key(|e| {
+                        unshuffled
+                            .iter()
+                            .position(|&id| id == e.id)
+                            .unwrap_or(usize::MAX)
+                    })
+                });
+                Ok(())
+            }
+            // Puts the entry before the one at the second index, which may be one past
+            // the end
+            "playlist-move" => {
+                let index = |i: usize| arg(i).and_then(|a| a.parse::<usize>().ok());
+                let (from, to) = index(0).zip(index(1)).ok_or(MpvError::InvalidParameter)?;
+                if from >= self.playlist.len() || to > self.playlist.len() {
+                    return Err(MpvError::Command);
+                }
+                let at = if to > from { to - 1 } else { to };
+                self.reorder(|playlist| {
+                    let entry = playlist.remove(from);
+                    playlist.insert(at, entry);
+                });
+                Ok(())
+            }
+            "set" => {
+                let (name, value) = arg(0).zip(arg(1)).ok_or(MpvError::InvalidParameter)?;
+                self.set(name, PropertyValue::String(value.to_string()))
+            }
+            "add" => {
+                let name = arg(0).ok_or(MpvError::InvalidParameter)?;
+                let delta: f64 = arg(1).map_or(Ok(1.0), |a| {
+                    a.parse().map_err(|_| MpvError::InvalidParameter)
+                })?;
+                let current = as_f64(&self.get(name)?)?;
+                self.set(name, PropertyValue::Double(current + delta))
+            }
+            "cycle" => {
+                let name = arg(0).ok_or(MpvError::InvalidParameter)?;
+                let kind = match name {
+                    "vid" => Some("video"),
+                    "aid" => Some("audio"),
+                    "sid" => Some("sub"),
+                    _ => None,
+                };
+                if let Some(kind) = kind {
+                    if self.current.is_none() {
+                        return Err(MpvError::PropertyUnavailable);
+                    }
+                    return self.cycle_track(kind, arg(1) == Some("down"));
+                }
+                let current = as_flag(&self.get(name)?)?;
+                self.set(name, PropertyValue::Flag(!current))
+            }
+            "sub-add" => {
+                let path = arg(0).ok_or(MpvError::InvalidParameter)?;
+                self.add_subtitle(path, arg(1).unwrap_or("select"), arg(2), arg(3))
+            }
+            "frame-step" | "frame-back-step" => self.set("pause", PropertyValue::Flag(true)),
+            // Everything else (scripts, filters, OSD, screenshots...) has nothing to fake
+            _ => Ok(()),
+        }
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.to_string())
+}
+
+// "playlist/3/filename" -> Some((3, "filename"))
+fn playlist_property(name: &str) -> Option<(usize, &str)> {
+    let (index, property) = name.strip_prefix("playlist/")?.split_once('/')?;
+    Some((index.parse().ok()?, property))
+}
+
+// What a handle shares with its clients and render contexts
+struct Core {
+    state: Mutex<State>,
+    events_ready: Condvar,
+    wakeup: Mutex<Option<Callback>>,
+}
+
+type Callback = Arc<dyn Fn() + Send + Sync>;
+
+impl Core {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    // Wake wait_event, and whoever set a wakeup callback
+    fn wake(&self) {
+        self.events_ready.notify_all();
+        let callback = self.wakeup.lock().unwrap().clone();
+        if let Some(callback) = callback {
+            callback();
+        }
+    }
+}
+
+// Same API as the libmpv-backed handle; see mpv/libmpv.rs for what each call does
+pub struct MpvHandle {
+    core: Arc<Core>,
+    // Like the real handle, it stays on the thread that created it
+    _thread_bound: PhantomData<*const ()>,
+}
+
+impl MpvHandle {
+    pub fn create() -> Result<Self> {
+        let now = Instant::now();
+        let state = State {
+            properties: default_properties(),
+            observers: Vec::new(),
+            events: VecDeque::new(),
+            playlist: Vec::new(),
+            next_entry_id: 1,
+            unshuffled: Vec::new(),
+            current: None,
+            position: 0.0,
+            since: now,
+            end: None,
+            eof_reached: false,
+            external_subs: Vec::new(),
+            last_tick: now,
+            woken: false,
+            shutdown: false,
+        };
+        Ok(Self {
+            core: Arc::new(Core {
+                state: Mutex::new(state),
+                events_ready: Condvar::new(),
+                wakeup: Mutex::new(None),
+            }),
+            _thread_bound: PhantomData,
+        })
+    }
+
+    pub fn initialize(&self) -> Result<()> {
+        Ok(())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.core.lock()
+    }
+
+    // Run `f` on the state, waking the event thread if it queued anything
+    fn with_state<T>(&self, f: impl FnOnce(&mut State) -> T) -> T {
+        let mut state = self.lock();
+        let queued = state.events.len();
+        let result = f(&mut state);
+        let added = state.events.len() != queued;
+        drop(state);
+        if added {
+            self.core.wake();
+        }
+        result
+    }
+
+    pub fn create_client(&self) -> Result<MpvClient> {
+        Ok(MpvClient {
+            core: self.core.clone(),
+        })
+    }
+
+    // mpv's threads call it as events come in; here a ticker stands in for them while
+    // a file plays, so observed positions keep moving without anyone polling
+    pub fn set_wakeup_callback(&mut self, callback: impl Fn() + Send + Sync + 'static) {
+        let first = self
+            .core
+            .wakeup
+            .lock()
+            .unwrap()
+            .replace(Arc::new(callback))
+            .is_none();
+        if !first {
+            return;
+        }
+        let core = Arc::downgrade(&self.core);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(TICK);
+            let Some(core) = core.upgrade() else {
+                return;
+            };
+            let (playing, shutdown) = {
+                let state = core.lock();
+                (state.playing(), state.shutdown)
+            };
+            if shutdown {
+                return;
+            }
+            if playing {
+                core.wake();
+            }
+        });
+    }
+
+    pub fn set_option_string(&self, name: &str, value: &str) -> Result<()> {
+        check_nul(name)?;
+        check_nul(value)?;
+        let mut state = self.lock();
+        let value = coerce(
+            state.properties.get(name),
+            PropertyValue::String(value.to_string()),
+        )
+        .map_err(|_| MpvError::OptionFormat)?;
+        state.properties.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    fn set_property(&self, name: &str, value: PropertyValue) -> Result<()> {
+        check_nul(name)?;
+        self.with_state(|state| state.set(name, value))
+    }
+
+    fn get_property(&self, name: &str) -> Result<PropertyValue> {
+        check_nul(name)?;
+        self.lock().get(name)
+    }
+
+    // The stub doesn't log anything
+    pub fn request_log_messages(&self, level: &str) -> Result<()> {
+        check_nul(level)
+    }
+
+    // A mid-grey frame while a file is loaded; mpv's command fails without one
+    pub fn screenshot_raw(&self, flags: &str) -> Result<RawFrame> {
+        check_nul(flags)?;
+        if self.lock().current_entry().is_none() {
+            return Err(MpvError::Command);
+        }
+        let (width, height) = FAKE_FRAME_SIZE;
+        let stride = width as usize * SW_PIXEL_SIZE;
+        Ok(RawFrame {
+            width,
+            height,
+            stride,
+            format: "bgr0".to_string(),
+            pixels: vec![0x80; stride * height as usize],
+        })
+    }
+
+    // Pretend `duration` of playback went by, without sleeping
+    #[cfg(test)]
+    pub fn elapse(&self, duration: Duration) {
+        let mut state = self.lock();
+        if state.playing() {
+            state.rebase();
+            state.position += duration.as_secs_f64() * state.speed();
+        }
+        state.last_tick = state
+            .last_tick
+            .checked_sub(duration)
+            .unwrap_or(state.last_tick);
+    }
+
+    // Everything queued so far, without waiting
+    #[cfg(test)]
+    pub fn drain_events(&self) -> Vec<Event> {
+        let mut state = self.lock();
+        state.advance();
+        state.events.drain(..).collect()
+    }
+}
+
+impl PlayerBackend for MpvHandle {
+    fn set_property_string(&self, name: &str, value: &str) -> Result<()> {
+        check_nul(value)?;
+        self.set_property(name, PropertyValue::String(value.to_string()))
+    }
+
+    fn set_property_flag(&self, name: &str, value: bool) -> Result<()> {
+        self.set_property(name, PropertyValue::Flag(value))
+    }
+
+    fn set_property_i64(&self, name: &str, value: i64) -> Result<()> {
+        self.set_property(name, PropertyValue::Int64(value))
+    }
+
+    fn set_property_f64(&self, name: &str, value: f64) -> Result<()> {
+        self.set_property(name, PropertyValue::Double(value))
+    }
+
+    fn get_property_string(&self, name: &str) -> Result<String> {
+        as_string(&self.get_property(name)?)
+    }
+
+    fn get_property_flag(&self, name: &str) -> Result<bool> {
+        as_flag(&self.get_property(name)?)
+    }
+
+    fn get_property_i64(&self, name: &str) -> Result<i64> {
+        as_i64(&self.get_property(name)?)
+    }
+
+    fn get_property_f64(&self, name: &str) -> Result<f64> {
+        as_f64(&self.get_property(name)?)
+    }
+
+    fn command(&self, args: &[&str]) -> Result<()> {
+        for arg in args {
+            check_nul(arg)?;
+        }
+        self.with_state(|state| state.command(args))
+    }
+
+    fn observe(&self, id: u64, name: &str, format: Format) -> Result<()> {
+        check_nul(name)?;
+        self.with_state(|state| {
+            state.observers.push((id, name.to_string(), format));
+            // mpv reports the current value straight away
+            let value = state.get(name).unwrap_or(PropertyValue::None);
+            state.events.push_back(Event::PropertyChange {
+                id,
+                name: name.to_string(),
+                value: convert(&value, format),
+            });
+        });
+        Ok(())
+    }
+
+    fn unobserve(&self, id: u64) -> Result<bool> {
+        self.with_state(|state| {
+            let before = state.observers.len();
+            state.observers.retain(|(observer, _, _)| *observer != id);
+            Ok(state.observers.len() < before)
+        })
+    }
+
+    fn wakeup(&self) {
+        self.lock().woken = true;
+        self.core.wake();
+    }
+
+    fn wait_event(&self, timeout: f64) -> Event {
+        let deadline = (timeout >= 0.0).then(|| Instant::now() + Duration::from_secs_f64(timeout));
+        let mut state = self.lock();
+        loop {
+            state.advance();
+            if let Some(event) = state.events.pop_front() {
+                return event;
+            }
+            if state.shutdown {
+                return Event::Shutdown;
+            }
+            if std::mem::take(&mut state.woken) {
+                return Event::None;
+            }
+
+            let now = Instant::now();
+            let mut wait = match deadline {
+                Some(deadline) if deadline <= now => return Event::None,
+                Some(deadline) => Some(deadline - now),
+                None => None,
+            };
+            // Wake up for the next tick so observed positions keep moving
+            if state.playing() {
+                wait = Some(wait.map_or(TICK, |w| w.min(TICK)));
+            }
+            state = match wait {
+                Some(wait) => self.core.events_ready.wait_timeout(state, wait).unwrap().0,
+                None => self.core.events_ready.wait(state).unwrap(),
+            };
+        }
+    }
+}
+
+// A second client of an instance, for a render context
+pub struct MpvClient {
+    core: Arc<Core>,
+}
+
+// There's no video to draw, so rendering succeeds without touching the target. A file
+// being loaded stands in for a frame being ready.
+pub struct RenderContext {
+    client: MpvClient,
+    _update: Option<Box<dyn Fn() + Send + Sync>>,
+}
+
+pub const SW_PIXEL_SIZE: usize = 4;
+
+impl RenderContext {
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn opengl(
+        client: MpvClient,
+        _get_proc_address: GetProcAddress,
+        _display: Option<GlDisplay>,
+    ) -> Result<Self> {
+        Ok(Self {
+            client,
+            _update: None,
+        })
+    }
+
+    pub fn software(client: MpvClient) -> Result<Self> {
+        Ok(Self {
+            client,
+            _update: None,
+        })
+    }
+
+    // Like mpv, report an update straight away
+    pub fn set_update_callback(&mut self, callback: impl Fn() + Send + Sync + 'static) {
+        callback();
+        self._update = Some(Box::new(callback));
+    }
+
+    pub fn update(&self) -> bool {
+        self.client.core.lock().current.is_some()
+    }
+
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn render_opengl(&self, _fbo: i32, width: i32, height: i32, _flip_y: bool) -> Result<()> {
+        if width <= 0 || height <= 0 {
+            return Err(MpvError::InvalidParameter);
+        }
+        Ok(())
+    }
+
+    pub fn render_software(
+        &self,
+        width: i32,
+        height: i32,
+        stride: usize,
+        pixels: &mut [u8],
+    ) -> Result<()> {
+        if width <= 0
+            || height <= 0
+            || stride < width as usize * SW_PIXEL_SIZE
+            || pixels.len() < stride * height as usize
+        {
+            return Err(MpvError::InvalidParameter);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loaded(path: &str) -> MpvHandle {
+        let handle = MpvHandle::create().unwrap();
+        handle.initialize().unwrap();
+        handle.command(&["loadfile", path]).unwrap();
+        handle
+    }
+
+    #[test]
+    fn properties_keep_their_type() {
+        let handle = MpvHandle::create().unwrap();
+        handle.set_property_string("pause", "yes").unwrap();
+        assert!(handle.get_property_flag("pause").unwrap());
+        handle.set_property_string("speed", "1.5").unwrap();
+        assert_eq!(handle.get_property_f64("speed").unwrap(), 1.5);
+        assert_eq!(handle.get_property_string("speed").unwrap(), "1.500000");
+        assert_eq!(
+            handle.set_property_string("speed", "fast"),
+            Err(MpvError::PropertyFormat)
+        );
+        assert_eq!(
+            handle.get_property_f64("no-such-property"),
+            Err(MpvError::PropertyNotFound)
+        );
+        assert!(matches!(
+            handle.set_property_string("vol\0ume", "1"),
+            Err(MpvError::NulByte(_))
+        ));
+    }
+
+    #[test]
+    fn position_follows_the_clock_until_paused() {
+        let handle = loaded("/videos/a.mkv");
+        handle.set_property_f64("speed", 2.0).unwrap();
+        handle.elapse(Duration::from_secs(10));
+        let position = handle.get_property_f64("time-pos").unwrap();
+        assert!((20.0..21.0).contains(&position), "{}", position);
+
+        handle.set_property_flag("pause", true).unwrap();
+        handle.elapse(Duration::from_secs(10));
+        let paused = handle.get_property_f64("time-pos").unwrap();
+        assert!(
+            (paused - position).abs() < 1.0,
+            "{} vs {}",
+            paused,
+            position
+        );
+    }
+
+    #[test]
+    fn loadfile_emits_file_events_and_observed_values() {
+        let handle = MpvHandle::create().unwrap();
+        handle.observe(9, "path", Format::String).unwrap();
+        handle.command(&["loadfile", "/videos/a.mkv"]).unwrap();
+
+        let events = handle.drain_events();
+        assert_eq!(
+            events[0],
+            Event::PropertyChange {
+                id: 9,
+                name: "path".to_string(),
+                value: PropertyValue::None,
+            }
+        );
+        assert!(events.contains(&Event::StartFile {
+            playlist_entry_id: 1
+        }));
+        assert!(events.contains(&Event::PropertyChange {
+            id: 9,
+            name: "path".to_string(),
+            value: PropertyValue::String("/videos/a.mkv".to_string()),
+        }));
+        assert!(events.contains(&Event::FileLoaded));
+    }
+
+    #[test]
+    fn tracks_are_listed_and_cycle_through_off() {
+        let handle = MpvHandle::create().unwrap();
+        assert_eq!(
+            handle.get_property_i64("track-list/count"),
+            Err(MpvError::PropertyUnavailable)
+        );
+
+        handle.command(&["loadfile", "/videos/a.mkv"]).unwrap();
+        assert_eq!(handle.get_property_i64("track-list/count").unwrap(), 5);
+        assert_eq!(
+            handle.get_property_string("track-list/2/lang").unwrap(),
+            "jpn"
+        );
+        assert_eq!(
+            handle.get_property_string("track-list/2/title"),
+            Err(MpvError::PropertyUnavailable)
+        );
+        // aid=auto plays the default track
+        assert!(handle.get_property_flag("track-list/1/selected").unwrap());
+
+        handle.command(&["cycle", "aid"]).unwrap();
+        assert_eq!(handle.get_property_string("aid").unwrap(), "2");
+        handle.command(&["cycle", "aid"]).unwrap();
+        assert_eq!(handle.get_property_string("aid").unwrap(), "no");
+        handle.command(&["cycle", "aid", "down"]).unwrap();
+        assert_eq!(handle.get_property_string("aid").unwrap(), "2");
+    }
+
+    #[test]
+    fn added_subtitles_join_the_track_list_until_the_next_file() {
+        let handle = MpvHandle::create().unwrap();
+        assert_eq!(
+            handle.command(&["sub-add", "/subs/a.srt"]),
+            Err(MpvError::Command)
+        );
+
+        handle.command(&["loadfile", "/videos/a.mkv"]).unwrap();
+        handle.command(&["sub-add", "/subs/a.srt"]).unwrap();
+        assert_eq!(handle.get_property_string("sid").unwrap(), "3");
+        assert_eq!(
+            handle.get_property_string("track-list/5/title").unwrap(),
+            "a.srt"
+        );
+        assert!(handle.get_property_flag("track-list/5/external").unwrap());
+
+        // cached reuses the track; auto adds one without selecting it
+        handle
+            .command(&["sub-add", "/subs/a.srt", "cached"])
+            .unwrap();
+        handle
+            .command(&["sub-add", "/subs/b.ass", "auto", "Signs", "eng"])
+            .unwrap();
+        assert_eq!(handle.get_property_i64("track-list/count").unwrap(), 7);
+        assert_eq!(handle.get_property_string("sid").unwrap(), "3");
+
+        handle.command(&["loadfile", "/videos/b.mkv"]).unwrap();
+        assert_eq!(handle.get_property_i64("track-list/count").unwrap(), 5);
+    }
+
+    #[test]
+    fn seeking_clamps_and_needs_a_file() {
+        let handle = MpvHandle::create().unwrap();
+        assert_eq!(
+            handle.command(&["seek", "10", "absolute"]),
+            Err(MpvError::Command)
+        );
+
+        handle.command(&["loadfile", "/videos/a.mkv"]).unwrap();
+        handle.set_property_flag("pause", true).unwrap();
+        handle.drain_events();
+        handle.command(&["seek", "50", "absolute-percent"]).unwrap();
+        assert_eq!(
+            handle.get_property_f64("time-pos").unwrap(),
+            FAKE_DURATION / 2.0
+        );
+        let events = handle.drain_events();
+        assert_eq!(
+            events[events.len() - 2..],
+            [Event::Seek, Event::PlaybackRestart]
+        );
+        handle.command(&["seek", "-1000"]).unwrap();
+        assert_eq!(handle.get_property_f64("time-pos").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn reaching_the_end_moves_to_the_next_entry() {
+        let handle = loaded("/videos/a.mkv");
+        handle
+            .command(&["loadfile", "/videos/b.mkv", "append"])
+            .unwrap();
+        handle.drain_events();
+
+        handle.elapse(Duration::from_secs_f64(FAKE_DURATION));
+        let events = handle.drain_events();
+        assert!(events.contains(&Event::EndFile {
+            reason: EndFileReason::Eof,
+            playlist_entry_id: 1,
+        }));
+        assert!(events.contains(&Event::StartFile {
+            playlist_entry_id: 2
+        }));
+        assert_eq!(handle.get_property_string("filename").unwrap(), "b.mkv");
+
+        handle.elapse(Duration::from_secs_f64(FAKE_DURATION));
+        assert!(handle.drain_events().contains(&Event::Idle));
+        assert!(handle.get_property_flag("idle-active").unwrap());
+    }
+
+    #[test]
+    fn keep_open_holds_the_last_frame() {
+        let handle = loaded("/videos/a.mkv");
+        handle.set_property_string("keep-open", "yes").unwrap();
+        handle.elapse(Duration::from_secs_f64(FAKE_DURATION + 5.0));
+        handle.drain_events();
+        assert!(handle.get_property_flag("eof-reached").unwrap());
+        assert!(handle.get_property_flag("pause").unwrap());
+        assert_eq!(handle.get_property_f64("time-pos").unwrap(), FAKE_DURATION);
+    }
+
+    #[test]
+    fn stop_can_keep_the_playlist() {
+        let handle = loaded("/videos/a.mkv");
+        handle
+            .command(&["loadfile", "/videos/b.mkv", "append"])
+            .unwrap();
+        handle.command(&["stop", "keep-playlist"]).unwrap();
+        assert!(handle.get_property_flag("idle-active").unwrap());
+        assert_eq!(handle.get_property_i64("playlist-count").unwrap(), 2);
+
+        handle.command(&["stop"]).unwrap();
+        assert_eq!(handle.get_property_i64("playlist-count").unwrap(), 0);
+    }
+
+    #[test]
+    fn loadfile_options_set_start_and_pause() {
+        let handle = MpvHandle::create().unwrap();
+        handle
+            .command(&[
+                "loadfile",
+                "/videos/a.mkv",
+                "replace",
+                "-1",
+                "start=30,pause=yes",
+            ])
+            .unwrap();
+        assert_eq!(handle.get_property_f64("time-pos").unwrap(), 30.0);
+        assert!(handle.get_property_flag("pause").unwrap());
+    }
+
+    #[test]
+    fn quit_ends_with_shutdown() {
+        let handle = loaded("/videos/a.mkv");
+        handle.command(&["quit"]).unwrap();
+        let events = handle.drain_events();
+        assert_eq!(events.last(), Some(&Event::Shutdown));
+        assert_eq!(handle.wait_event(-1.0), Event::Shutdown);
+        assert_eq!(handle.wait_event(-1.0), Event::Shutdown);
+    }
+}
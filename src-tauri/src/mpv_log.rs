@@ -0,0 +1,148 @@
+// mpv's own log lines, kept so a "why won't this file play" report can carry the actual
+// demuxer or decoder errors. The event thread records them; get_player_logs reads them
+// back, and the more severe ones are also sent to the page as they come in.
+
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Lowest level requested from mpv, and so kept in the buffer
+pub const BUFFER_LEVEL: &str = "info";
+
+// Lines this severe or worse are also emitted as mpv-log-message events
+const FORWARD_LEVELS: &[&str] = &["fatal", "error", "warn"];
+
+// Enough to cover opening a file and the first few seconds of playing it
+const CAPACITY: usize = 500;
+
+// A broken file can log a warning per frame; at most this many events go out per window
+const FORWARD_LIMIT: u32 = 20;
+const FORWARD_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LogEntry {
+    // Milliseconds since the Unix epoch
+    pub time: u64,
+    // The module that logged it, e.g. "cplayer", "ffmpeg/demuxer" or a script name
+    pub prefix: String,
+    pub level: String,
+    pub text: String,
+}
+
+impl LogEntry {
+    pub fn new(prefix: String, level: String, text: &str) -> Self {
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64);
+        Self {
+            time,
+            prefix,
+            level,
+            // mpv terminates each message with a newline
+            text: text.trim_end().to_string(),
+        }
+    }
+
+    fn forwarded(&self) -> bool {
+        FORWARD_LEVELS.contains(&self.level.as_str())
+    }
+}
+
+// The most recent lines, oldest first. Lives as long as the player, so what mpv said
+// before it stopped is still there after a restart.
+#[derive(Default)]
+pub struct MpvLog {
+    entries: Mutex<VecDeque<LogEntry>>,
+}
+
+impl MpvLog {
+    pub fn push(&self, entry: LogEntry) {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() == CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    // The last `limit` lines, or all of them
+    pub fn recent(&self, limit: Option<usize>) -> Vec<LogEntry> {
+        let entries = self.entries.lock().unwrap();
+        let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+        entries.iter().skip(skip).cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
+
+// Decides which lines the event thread emits. Lines over the limit are only dropped from
+// the events; the next one sent says how many were skipped.
+#[derive(Default)]
+pub struct LogThrottle {
+    window_start: Option<Instant>,
+    sent: u32,
+    dropped: u64,
+}
+
+impl LogThrottle {
+    // Some(lines skipped since the last one sent) if `entry` should be emitted
+    pub fn admit(&mut self, entry: &LogEntry, now: Instant) -> Option<u64> {
+        if !entry.forwarded() {
+            return None;
+        }
+        match self.window_start {
+            Some(start) if now.duration_since(start) < FORWARD_WINDOW => {}
+            _ => {
+                self.window_start = Some(now);
+                self.sent = 0;
+            }
+        }
+        if self.sent == FORWARD_LIMIT {
+            self.dropped += 1;
+            return None;
+        }
+        self.sent += 1;
+        Some(std::mem::take(&mut self.dropped))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: &str, text: &str) -> LogEntry {
+        LogEntry::new("cplayer".to_string(), level.to_string(), text)
+    }
+
+    #[test]
+    fn keeps_the_most_recent_lines() {
+        let log = MpvLog::default();
+        for i in 0..CAPACITY + 5 {
+            log.push(entry("info", &format!("line {}\n", i)));
+        }
+        let entries = log.recent(None);
+        assert_eq!(entries.len(), CAPACITY);
+        assert_eq!(entries[0].text, "line 5");
+        let last = log.recent(Some(2));
+        assert_eq!(last[0].text, format!("line {}", CAPACITY + 3));
+        assert_eq!(last[1].text, format!("line {}", CAPACITY + 4));
+    }
+
+    #[test]
+    fn throttle_counts_what_it_holds_back() {
+        let mut throttle = LogThrottle::default();
+        let start = Instant::now();
+        let warning = entry("warn", "Invalid video timestamp");
+        assert_eq!(throttle.admit(&entry("info", "Playing"), start), None);
+        for _ in 0..FORWARD_LIMIT {
+            assert_eq!(throttle.admit(&warning, start), Some(0));
+        }
+        assert_eq!(throttle.admit(&warning, start), None);
+        assert_eq!(throttle.admit(&warning, start), None);
+        let later = start + FORWARD_WINDOW;
+        assert_eq!(throttle.admit(&warning, later), Some(2));
+        assert_eq!(throttle.admit(&warning, later), Some(0));
+    }
+}
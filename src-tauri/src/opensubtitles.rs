@@ -0,0 +1,323 @@
+// Subtitle search and download through the OpenSubtitles REST API. Requests go through
+// the system curl, as clip export goes through ffmpeg, so the app doesn't carry its own
+// HTTP and TLS stack.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const API_URL: &str = "https://api.opensubtitles.com/api/v1";
+// The API rejects requests that don't name the app
+const USER_AGENT: &str = concat!("media-player-tauri v", env!("CARGO_PKG_VERSION"));
+// The movie hash covers this much of each end of the file
+const HASH_CHUNK: u64 = 64 * 1024;
+
+// One downloadable subtitle file from a search
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubtitleCandidate {
+    // What download takes
+    pub file_id: i64,
+    pub file_name: String,
+    pub language: Option<String>,
+    // The release the subtitles were made for, e.g. "Movie.2019.1080p.BluRay"
+    pub release: Option<String>,
+    pub download_count: i64,
+    pub hearing_impaired: bool,
+    pub machine_translated: bool,
+    // Found by the file's hash, so timed for this exact release
+    pub hash_match: bool,
+}
+
+// OpenSubtitles' movie hash: the file size plus every little-endian u64 in the first
+// and last 64 KiB, wrapping on overflow
+pub fn movie_hash(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    Ok(format!("{:016x}", hash_reader(&mut file, size)?))
+}
+
+fn hash_reader<R: Read + Seek>(reader: &mut R, size: u64) -> io::Result<u64> {
+    if size < HASH_CHUNK {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "file is too small to hash",
+        ));
+    }
+    let mut hash = size;
+    let mut chunk = vec![0; HASH_CHUNK as usize];
+    for offset in [0, size - HASH_CHUNK] {
+        reader.seek(SeekFrom::Start(offset))?;
+        reader.read_exact(&mut chunk)?;
+        hash = chunk.chunks_exact(8).fold(hash, |hash, word| {
+            hash.wrapping_add(u64::from_le_bytes(word.try_into().unwrap()))
+        });
+    }
+    Ok(hash)
+}
+
+// Search by hash when there is one and by name either way, since many uploads were
+// never hashed. `languages` is a comma-separated list of codes such as "en,fr".
+pub fn search(
+    api_key: &str,
+    hash: Option<&str>,
+    query: &str,
+    languages: &str,
+) -> Result<Vec<SubtitleCandidate>, String> {
+    // The API wants its parameters in alphabetical order
+    let mut params = vec![format!("languages={}", languages)];
+    if let Some(hash) = hash {
+        params.push(format!("moviehash={}", hash));
+    }
+    params.push(format!("query={}", query));
+
+    let mut args = vec!["--get"];
+    for param in &params {
+        args.extend(["--data-urlencode", param.as_str()]);
+    }
+    let url = format!("{}/subtitles", API_URL);
+    args.push(&url);
+    parse_search(&api_request(api_key, &args)?)
+}
+
+// Download `file_id` into `dir`, or reuse the copy from an earlier download; each one
+// counts against the account's daily quota
+pub fn download(api_key: &str, file_id: i64, dir: &Path) -> Result<PathBuf, String> {
+    let prefix = format!("{}-", file_id);
+    if let Some(cached) = std::fs::read_dir(dir).ok().and_then(|entries| {
+        entries
+            .filter_map(Result::ok)
+            .find(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+    }) {
+        return Ok(cached.path());
+    }
+
+    let body = serde_json::json!({ "file_id": file_id }).to_string();
+    let url = format!("{}/download", API_URL);
+    let response = api_request(
+        api_key,
+        &[
+            "-H",
+            "Content-Type: application/json",
+            "--data",
+            &body,
+            &url,
+        ],
+    )?;
+    let (link, file_name) = parse_download(&response)?;
+
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create subtitle cache: {}", e))?;
+    let path = dir.join(format!("{}{}", prefix, file_name));
+    // The link is a one-off URL on another host, so it doesn't get the API key
+    curl(&["--output", &path.to_string_lossy(), &link], None).inspect_err(|_| {
+        let _ = std::fs::remove_file(&path);
+    })?;
+    Ok(path)
+}
+
+fn api_request(api_key: &str, args: &[&str]) -> Result<String, String> {
+    // API calls don't follow redirects, which would hand the key to whatever host they point at
+    curl(args, Some(&api_headers(api_key)))
+}
+
+// A curl config read from stdin, so the key never shows up in the process list
+fn api_headers(api_key: &str) -> String {
+    [
+        format!("Api-Key: {}", api_key),
+        format!("User-Agent: {}", USER_AGENT),
+        "Accept: application/json".to_string(),
+    ]
+    .iter()
+    .map(|header| format!("header = {}\n", config_quote(header)))
+    .collect()
+}
+
+// Control characters are dropped rather than escaped, so a header can't smuggle in another
+fn config_quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars().filter(|c| !c.is_control()) {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    quoted
+}
+
+// `config` is passed on stdin; requests without one may follow redirects
+fn curl(args: &[&str], config: Option<&str>) -> Result<String, String> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--silent", "--show-error", "--fail-with-body"])
+        .args(["--max-time", "30"]);
+    match config {
+        Some(_) => command.args(["--config", "-"]).stdin(Stdio::piped()),
+        None => command.arg("--location").stdin(Stdio::null()),
+    };
+    let mut child = command
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    if let (Some(config), Some(mut stdin)) = (config, child.stdin.take()) {
+        // curl reads all of its config before it starts, so this can't block on the output pipes
+        stdin
+            .write_all(config.as_bytes())
+            .map_err(|e| format!("Failed to run curl: {}", e))?;
+    }
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("Failed to run curl: {}", e))?;
+    let body = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() {
+        // HTTP errors come with a JSON body explaining them; network errors only on stderr
+        let reason = api_message(&body)
+            .unwrap_or_else(|| String::from_utf8_lossy(&output.stderr).trim().to_string());
+        return Err(format!("OpenSubtitles request failed: {}", reason));
+    }
+    Ok(body)
+}
+
+fn api_message(body: &str) -> Option<String> {
+    let value: Value = serde_json::from_str(body).ok()?;
+    value["message"]
+        .as_str()
+        .or_else(|| value["errors"][0].as_str())
+        .map(str::to_string)
+}
+
+// Each result can hold several files (e.g. one per CD); hash matches come first, then
+// the most downloaded
+fn parse_search(body: &str) -> Result<Vec<SubtitleCandidate>, String> {
+    let value: Value = serde_json::from_str(body)
+        .map_err(|e| format!("Unreadable OpenSubtitles response: {}", e))?;
+    let results = value["data"]
+        .as_array()
+        .ok_or("Unexpected OpenSubtitles response")?;
+
+    let mut candidates: Vec<SubtitleCandidate> = results
+        .iter()
+        .flat_map(|result| {
+            let attributes = &result["attributes"];
+            let string = |field: &str| attributes[field].as_str().map(str::to_string);
+            let flag = |field: &str| attributes[field].as_bool().unwrap_or(false);
+            attributes["files"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(move |file| {
+                    Some(SubtitleCandidate {
+                        file_id: file["file_id"].as_i64()?,
+                        file_name: file["file_name"].as_str().unwrap_or_default().to_string(),
+                        language: string("language"),
+                        release: string("release"),
+                        download_count: attributes["download_count"].as_i64().unwrap_or(0),
+                        hearing_impaired: flag("hearing_impaired"),
+                        machine_translated: flag("machine_translated") || flag("ai_translated"),
+                        hash_match: flag("moviehash_match"),
+                    })
+                })
+        })
+        .collect();
+    candidates.sort_by_key(|c| (!c.hash_match, Reverse(c.download_count)));
+    Ok(candidates)
+}
+
+// The temporary link and a file name safe to save under
+fn parse_download(body: &str) -> Result<(String, String), String> {
+    let value: Value = serde_json::from_str(body)
+        .map_err(|e| format!("Unreadable OpenSubtitles response: {}", e))?;
+    let link = value["link"]
+        .as_str()
+        .ok_or("OpenSubtitles didn't return a download link")?;
+    let file_name = value["file_name"]
+        .as_str()
+        .and_then(|name| Path::new(name).file_name())
+        .map_or("subtitles.srt".into(), |name| name.to_string_lossy());
+    Ok((link.to_string(), file_name.into_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn hash_adds_size_and_both_ends() {
+        let size = 3 * HASH_CHUNK;
+        let mut data = vec![0u8; size as usize];
+        data[0] = 1;
+        // The last word of the file, and a word in the middle that isn't hashed
+        data[size as usize - 8] = 2;
+        data[HASH_CHUNK as usize + 8] = 100;
+        let hash = hash_reader(&mut Cursor::new(data), size).unwrap();
+        assert_eq!(hash, size + 3);
+    }
+
+    #[test]
+    fn hash_wraps_and_needs_a_full_chunk() {
+        let data = vec![0xff; HASH_CHUNK as usize];
+        let hash = hash_reader(&mut Cursor::new(data), HASH_CHUNK).unwrap();
+        // Both "ends" are the whole file: 2 * 8192 words of u64::MAX, plus the size
+        assert_eq!(hash, HASH_CHUNK.wrapping_sub(2 * HASH_CHUNK / 8));
+
+        let small = vec![0; 100];
+        assert!(hash_reader(&mut Cursor::new(small), 100).is_err());
+    }
+
+    #[test]
+    fn search_results_flatten_files_and_rank_hash_matches() {
+        let body = r#"{"total_count": 2, "data": [
+            {"attributes": {"language": "en", "release": "Popular", "download_count": 900,
+                "hearing_impaired": true, "files": [{"file_id": 1, "file_name": "a.srt"}]}},
+            {"attributes": {"language": "en", "release": "Exact", "download_count": 10,
+                "moviehash_match": true, "ai_translated": true,
+                "files": [{"file_id": 2, "file_name": "cd1.srt"}, {"file_id": 3}]}}
+        ]}"#;
+        let candidates = parse_search(body).unwrap();
+        let ids: Vec<_> = candidates.iter().map(|c| c.file_id).collect();
+        assert_eq!(ids, [2, 3, 1]);
+        assert!(candidates[0].hash_match && candidates[0].machine_translated);
+        assert!(candidates[2].hearing_impaired);
+        assert_eq!(candidates[2].release.as_deref(), Some("Popular"));
+
+        assert!(parse_search(r#"{"message": "nope"}"#).is_err());
+    }
+
+    #[test]
+    fn download_file_names_stay_in_the_cache() {
+        let (link, name) =
+            parse_download(r#"{"link": "https://dl/x", "file_name": "../../evil.srt"}"#).unwrap();
+        assert_eq!((link.as_str(), name.as_str()), ("https://dl/x", "evil.srt"));
+        let (_, name) = parse_download(r#"{"link": "https://dl/x"}"#).unwrap();
+        assert_eq!(name, "subtitles.srt");
+    }
+
+    #[test]
+    fn api_errors_carry_the_message() {
+        assert_eq!(
+            api_message(r#"{"message": "You have downloaded your allowed 5 subtitles"}"#),
+            Some("You have downloaded your allowed 5 subtitles".to_string())
+        );
+        assert_eq!(
+            api_message(r#"{"errors": ["Invalid file_id"], "status": 400}"#),
+            Some("Invalid file_id".to_string())
+        );
+        assert_eq!(api_message("<html>"), None);
+    }
+
+    #[test]
+    fn api_key_is_quoted_for_the_curl_config() {
+        let config = api_headers("ab\"c\\d\nheader = \"X: y");
+        assert_eq!(
+            config.lines().next(),
+            Some(r#"header = "Api-Key: ab\"c\\dheader = \"X: y""#)
+        );
+        assert_eq!(config.lines().count(), 3);
+    }
+}
@@ -0,0 +1,49 @@
+// Speed changes that keep the pitch, so a podcast at 1.8x sounds like faster speech
+// rather than chipmunks. mpv only does that with audio-pitch-correction on, and then
+// with whichever time stretcher its version defaults to (plain scaletempo before 0.34).
+// While it's on, the app puts scaletempo2 in the filter chain itself, which mpv hands
+// the speed to instead of inserting its own; with it off, speed changes the pitch too.
+
+use crate::error::{Context, PlayerError};
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+pub const PROPERTY: &str = "audio-pitch-correction";
+
+const LABEL: &str = "pitch";
+
+// Whether mpv's `af` list already has our stretcher
+fn has_filter(af: &str) -> bool {
+    af.contains(&format!("@{}:", LABEL))
+}
+
+// Add or remove the stretcher to match audio-pitch-correction. Called when the setting
+// changes and before each speed change, in case something cleared the filter chain.
+pub fn sync(handle: &MpvHandle) -> Result<(), PlayerError> {
+    let enabled = handle.get::<bool>(PROPERTY).unwrap_or(true);
+    let present = handle.get::<String>("af").is_ok_and(|af| has_filter(&af));
+    if enabled && !present {
+        handle
+            .command(&["af", "add", &format!("@{}:scaletempo2", LABEL)])
+            .context("Failed to add pitch correction")?;
+    } else if !enabled && present {
+        handle
+            .command(&["af", "remove", &format!("@{}", LABEL)])
+            .context("Failed to remove pitch correction")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_stretcher_is_found_by_its_label() {
+        assert!(has_filter("@pitch:scaletempo2"));
+        assert!(has_filter(
+            "@equalizer:lavfi=[firequalizer],@pitch:scaletempo2"
+        ));
+        assert!(!has_filter("scaletempo2"));
+        assert!(!has_filter(""));
+    }
+}
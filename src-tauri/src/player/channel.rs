@@ -0,0 +1,567 @@
+// Work served by the event thread, which owns the mpv event loop and is the only
+// thread that drives mpv once it's running. Tauri commands send a PlayerCommand over
+// the channel and await the reply on a oneshot instead of calling mpv themselves, so
+// mpv calls are serialized in one place and don't contend with the event loop.
+
+use serde::Serialize;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+
+use crate::error::{Context, PlayerError};
+use crate::fade::PauseFade;
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::{media, pitch};
+
+pub type Reply<T> = oneshot::Sender<Result<T, PlayerError>>;
+
+// A job for PlayerChannel::run; it sends its own reply, through Server::finish
+pub type Job = Box<dyn FnOnce(&Server) + Send>;
+
+pub enum PlayerCommand {
+    // `options` is loadfile's encoded options argument (see loadfile::LoadOptions)
+    Load {
+        path: String,
+        options: Option<String>,
+        reply: Reply<()>,
+    },
+    // Replies whether playback ends up paused
+    TogglePause {
+        reply: Reply<bool>,
+    },
+    // Pause or resume, through a pause fade when one is set; replies with the new state
+    SetPause {
+        paused: bool,
+        reply: Reply<bool>,
+    },
+    Stop {
+        reply: Reply<()>,
+    },
+    // `exact` overrides the precise seeking setting for this seek
+    Seek {
+        target: SeekTarget,
+        exact: Option<bool>,
+        reply: Reply<SeekResult>,
+    },
+    SetSpeed {
+        speed: f64,
+        reply: Reply<f64>,
+    },
+    // Step from the current speed; replies with the new one
+    AdjustSpeed {
+        delta: f64,
+        reply: Reply<f64>,
+    },
+    // One frame forwards or backwards, leaving playback paused
+    FrameStep {
+        backward: bool,
+        reply: Reply<()>,
+    },
+    // Replies with the volume actually set
+    SetVolume {
+        volume: f64,
+        reply: Reply<f64>,
+    },
+    // Replies whether audio ends up muted
+    ToggleMute {
+        reply: Reply<bool>,
+    },
+    // None toggles; replies with the new visibility
+    SetSubtitleVisibility {
+        visible: Option<bool>,
+        reply: Reply<bool>,
+    },
+    // Anything else a command needs done with the handle
+    Run(Job),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SeekTarget {
+    Seconds(f64),
+    Percent(f64),
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeekResult {
+    // time-pos right after the seek
+    pub position: f64,
+    // Whether this was a frame-exact seek rather than one to the nearest keyframe
+    pub precise: bool,
+}
+
+// The playback state get_player_state reports, kept current by the event thread
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerSnapshot {
+    pub path: Option<String>,
+    // A cover image next to the file, looked up once each time the path changes
+    pub cover_art: Option<String>,
+    pub title: Option<String>,
+    // None while idle
+    pub playlist_pos: Option<i64>,
+    pub paused: bool,
+    pub position: Option<f64>,
+    pub duration: Option<f64>,
+    pub speed: f64,
+    pub volume: Option<f64>,
+    pub muted: bool,
+    // "no" when no subtitle track is selected
+    pub subtitle_track: Option<String>,
+    pub subtitle_visible: bool,
+}
+
+impl Default for PlayerSnapshot {
+    fn default() -> Self {
+        Self {
+            path: None,
+            cover_art: None,
+            title: None,
+            playlist_pos: None,
+            paused: true,
+            position: None,
+            duration: None,
+            speed: 1.0,
+            volume: None,
+            muted: false,
+            subtitle_track: None,
+            subtitle_visible: true,
+        }
+    }
+}
+
+// Payload of volume-changed, and what get_volume returns
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Volume {
+    pub volume: f64,
+    pub muted: bool,
+}
+
+impl Volume {
+    pub fn read(handle: &MpvHandle) -> Option<Self> {
+        Some(Self {
+            volume: handle.get::<f64>("volume").ok()?,
+            muted: handle.get::<bool>("mute").ok()?,
+        })
+    }
+}
+
+// mpv's default volume-max; louder than 100 is software amplification
+pub const DEFAULT_MAX_VOLUME: f64 = 130.0;
+
+// Observed by the event thread; each change re-reads that field of the snapshot
+pub const SNAPSHOT_PROPERTIES: &[&str] = &[
+    "path",
+    "media-title",
+    "playlist-pos",
+    "pause",
+    "time-pos",
+    "duration",
+    "speed",
+    "volume",
+    "mute",
+    "sid",
+    "sub-visibility",
+];
+
+impl PlayerSnapshot {
+    pub fn read(handle: &MpvHandle) -> Self {
+        let mut snapshot = Self::default();
+        for name in SNAPSHOT_PROPERTIES {
+            snapshot.update(handle, name);
+        }
+        snapshot
+    }
+
+    pub fn update(&mut self, handle: &MpvHandle, name: &str) {
+        match name {
+            "path" => {
+                let path = handle.get::<String>("path").ok();
+                if path != self.path {
+                    self.cover_art = path
+                        .as_deref()
+                        .and_then(|path| media::cover_art(Path::new(path)));
+                    self.path = path;
+                }
+            }
+            "media-title" => self.title = handle.get::<String>("media-title").ok(),
+            "playlist-pos" => {
+                self.playlist_pos = handle.get::<i64>("playlist-pos").ok().filter(|&p| p >= 0)
+            }
+            "pause" => self.paused = handle.get::<bool>("pause").unwrap_or(true),
+            "time-pos" => self.position = handle.get::<f64>("time-pos").ok(),
+            "duration" => self.duration = handle.get::<f64>("duration").ok(),
+            "speed" => self.speed = handle.get::<f64>("speed").unwrap_or(1.0),
+            "volume" => self.volume = handle.get::<f64>("volume").ok(),
+            "mute" => self.muted = handle.get::<bool>("mute").unwrap_or(false),
+            "sid" => self.subtitle_track = handle.get::<String>("sid").ok(),
+            "sub-visibility" => {
+                self.subtitle_visible = handle.get::<bool>("sub-visibility").unwrap_or(true)
+            }
+            _ => {}
+        }
+    }
+}
+
+// Wakes the event thread: rung by mpv's wakeup callback when it has events, and when a
+// command is queued
+#[derive(Default)]
+pub struct Wakeup {
+    rung: Mutex<bool>,
+    ready: Condvar,
+}
+
+impl Wakeup {
+    pub fn ring(&self) {
+        *self.rung.lock().unwrap() = true;
+        self.ready.notify_one();
+    }
+
+    // Sleep until rung, or until `timeout` passes if there is one. A ring from before
+    // the call returns straight away.
+    pub fn wait(&self, timeout: Option<Duration>) {
+        let rung = self.rung.lock().unwrap();
+        let mut rung = match timeout {
+            Some(timeout) => {
+                self.ready
+                    .wait_timeout_while(rung, timeout, |rung| !*rung)
+                    .unwrap()
+                    .0
+            }
+            None => self.ready.wait_while(rung, |rung| !*rung).unwrap(),
+        };
+        *rung = false;
+    }
+}
+
+// The event thread's end of the channel
+pub struct Inbox {
+    pub commands: Receiver<PlayerCommand>,
+    pub wakeup: Arc<Wakeup>,
+}
+
+// Managed by Tauri; connected while the event thread is running
+#[derive(Default)]
+pub struct PlayerChannel {
+    connection: Mutex<Option<(Sender<PlayerCommand>, Arc<Wakeup>)>>,
+    snapshot: Arc<RwLock<PlayerSnapshot>>,
+}
+
+impl PlayerChannel {
+    // Route commands into the returned inbox, which the event thread serves
+    pub fn connect(&self) -> Inbox {
+        let (sender, commands) = mpsc::channel();
+        let wakeup = Arc::new(Wakeup::default());
+        *self.connection.lock().unwrap() = Some((sender, wakeup.clone()));
+        Inbox { commands, wakeup }
+    }
+
+    // Requests after this fail; ones already queued get an error once the receiver drops
+    pub fn disconnect(&self) {
+        *self.connection.lock().unwrap() = None;
+        *self.snapshot.write().unwrap() = PlayerSnapshot::default();
+    }
+
+    pub fn shared_snapshot(&self) -> Arc<RwLock<PlayerSnapshot>> {
+        self.snapshot.clone()
+    }
+
+    // None until mpv is initialized
+    pub fn snapshot(&self) -> Option<PlayerSnapshot> {
+        self.connection.lock().unwrap().as_ref()?;
+        Some(self.snapshot.read().unwrap().clone())
+    }
+
+    pub async fn request<T>(
+        &self,
+        command: impl FnOnce(Reply<T>) -> PlayerCommand,
+    ) -> Result<T, PlayerError> {
+        let (reply, response) = oneshot::channel();
+        self.send(command(reply))?;
+        response.await.map_err(|_| PlayerError::ShutDown)?
+    }
+
+    fn send(&self, command: PlayerCommand) -> Result<(), PlayerError> {
+        let connection = self.connection.lock().unwrap();
+        let Some((sender, wakeup)) = connection.as_ref() else {
+            return Err(PlayerError::NotInitialized);
+        };
+        sender.send(command).map_err(|_| PlayerError::ShutDown)?;
+        // The event thread is usually asleep until mpv or a timer needs it
+        wakeup.ring();
+        Ok(())
+    }
+
+    // Run `work` on the event thread and await what it returns
+    pub async fn run<T: Send + 'static>(
+        &self,
+        work: impl FnOnce(&MpvHandle) -> Result<T, PlayerError> + Send + 'static,
+    ) -> Result<T, PlayerError> {
+        self.request(|reply| {
+            PlayerCommand::Run(Box::new(move |server| {
+                server.finish(reply, work(&server.handle))
+            }))
+        })
+        .await
+    }
+
+    // Queue `work` for the event thread without waiting for it to run, for callers that
+    // mustn't block on mpv, like the main thread
+    pub fn post(&self, work: impl FnOnce(&MpvHandle) + Send + 'static) -> Result<(), PlayerError> {
+        self.send(PlayerCommand::Run(Box::new(move |server| {
+            work(&server.handle)
+        })))
+    }
+}
+
+// Runs PlayerCommands on the event thread, sharing its handle
+pub struct Server {
+    handle: Rc<MpvHandle>,
+    pause_fade: Arc<PauseFade>,
+    snapshot: Arc<RwLock<PlayerSnapshot>>,
+}
+
+impl Server {
+    // Fills in the snapshot from `handle`
+    pub fn new(
+        handle: Rc<MpvHandle>,
+        pause_fade: Arc<PauseFade>,
+        snapshot: Arc<RwLock<PlayerSnapshot>>,
+    ) -> Self {
+        *snapshot.write().unwrap() = PlayerSnapshot::read(&handle);
+        Self {
+            handle,
+            pause_fade,
+            snapshot,
+        }
+    }
+
+    pub fn serve(&self, command: PlayerCommand) {
+        match command {
+            PlayerCommand::Load {
+                path,
+                options,
+                reply,
+            } => self.finish(reply, self.load(&path, options.as_deref())),
+            PlayerCommand::TogglePause { reply } => self.finish(reply, self.toggle_pause()),
+            PlayerCommand::SetPause { paused, reply } => self.finish(reply, self.set_pause(paused)),
+            PlayerCommand::Stop { reply } => {
+                let result = self.handle.command(&["stop"]).context("Failed to stop");
+                self.finish(reply, result)
+            }
+            PlayerCommand::Seek {
+                target,
+                exact,
+                reply,
+            } => self.finish(reply, self.seek(target, exact)),
+            PlayerCommand::SetSpeed { speed, reply } => self.finish(reply, self.set_speed(speed)),
+            PlayerCommand::AdjustSpeed { delta, reply } => {
+                self.finish(reply, self.adjust_speed(delta))
+            }
+            PlayerCommand::FrameStep { backward, reply } => {
+                self.finish(reply, self.frame_step(backward))
+            }
+            PlayerCommand::SetVolume { volume, reply } => {
+                self.finish(reply, self.set_volume(volume))
+            }
+            PlayerCommand::ToggleMute { reply } => self.finish(reply, self.toggle_mute()),
+            PlayerCommand::SetSubtitleVisibility { visible, reply } => {
+                self.finish(reply, self.set_subtitle_visibility(visible))
+            }
+            PlayerCommand::Run(job) => job(self),
+        }
+    }
+
+    // Volume changes from a pause fade are ramp steps, not a new volume
+    pub fn pause_fade_active(&self) -> bool {
+        self.pause_fade.active()
+    }
+
+    // For the event thread's timers: when the pause fade's next step is due
+    pub fn step_pause_fade(&self) -> Option<Instant> {
+        self.pause_fade.step(&self.handle)
+    }
+
+    pub fn snapshot(&self) -> PlayerSnapshot {
+        self.snapshot.read().unwrap().clone()
+    }
+
+    // A property the snapshot mirrors changed
+    pub fn on_property_change(&self, name: &str) {
+        self.snapshot.write().unwrap().update(&self.handle, name);
+    }
+
+    // Refresh the snapshot before replying, so a get_player_state right after the reply
+    // sees the change even though mpv's property-change event hasn't arrived yet
+    fn finish<T>(&self, reply: Reply<T>, result: Result<T, PlayerError>) {
+        self.refresh_snapshot();
+        // The caller may have given up waiting
+        let _ = reply.send(result);
+    }
+
+    // In place, so what's only worked out when a property changes is kept
+    fn refresh_snapshot(&self) {
+        let mut snapshot = self.snapshot.write().unwrap();
+        for name in SNAPSHOT_PROPERTIES {
+            snapshot.update(&self.handle, name);
+        }
+    }
+
+    fn load(&self, path: &str, options: Option<&str>) -> Result<(), PlayerError> {
+        self.handle
+            .load(path, options)
+            .context("Failed to load file")
+    }
+
+    fn toggle_pause(&self) -> Result<bool, PlayerError> {
+        if let Some(duration) = self.pause_fade.duration() {
+            return Ok(self.pause_fade.toggle(&self.handle, duration)?);
+        }
+        self.pause_fade.cancel(&self.handle);
+
+        let paused = self
+            .handle
+            .get::<bool>("pause")
+            .context("Failed to get pause state")?;
+        self.handle
+            .set("pause", !paused)
+            .context("Failed to toggle pause")?;
+        Ok(!paused)
+    }
+
+    // A fade already heading that way is left to finish rather than restarted
+    fn set_pause(&self, paused: bool) -> Result<bool, PlayerError> {
+        let current = match self.pause_fade.target() {
+            Some(target) => target,
+            None => self
+                .handle
+                .get::<bool>("pause")
+                .context("Failed to get pause state")?,
+        };
+        if current == paused {
+            return Ok(paused);
+        }
+        self.toggle_pause()
+    }
+
+    // An exact seek goes to the precise frame; otherwise mpv snaps to the nearest
+    // keyframe. Without an override, precise seeking mode (hr-seek=yes) decides.
+    fn seek(&self, target: SeekTarget, exact: Option<bool>) -> Result<SeekResult, PlayerError> {
+        let precise =
+            exact.unwrap_or_else(|| self.handle.get::<String>("hr-seek").as_deref() == Ok("yes"));
+        let (amount, mode) = match target {
+            SeekTarget::Seconds(seconds) => (seconds, "absolute"),
+            SeekTarget::Percent(percent) => (percent, "absolute-percent"),
+        };
+        let flags = format!("{}+{}", mode, if precise { "exact" } else { "keyframes" });
+        self.handle.seek(amount, &flags).context("Failed to seek")?;
+        // mpv reports the seek target right away, before playback restarts
+        let position = self
+            .handle
+            .get::<f64>("time-pos")
+            .context("Failed to get position after seek")?;
+        Ok(SeekResult { position, precise })
+    }
+
+    fn set_speed(&self, speed: f64) -> Result<f64, PlayerError> {
+        // The speed still changes if this fails, only with mpv's own pitch handling
+        if let Err(e) = pitch::sync(&self.handle) {
+            eprintln!("{}", e);
+        }
+        self.handle
+            .set("speed", speed)
+            .context("Failed to set speed")?;
+        Ok(speed)
+    }
+
+    fn adjust_speed(&self, delta: f64) -> Result<f64, PlayerError> {
+        let current = self
+            .handle
+            .get::<f64>("speed")
+            .context("Failed to get speed")?;
+        // Rounded so repeated steps of 0.05 or 0.1 don't accumulate float error
+        let speed = (((current + delta) * 100.0).round() / 100.0).clamp(0.25, 4.0);
+        self.set_speed(speed)
+    }
+
+    // mpv pauses after the step itself; a fade still running would resume or pause
+    // playback behind it, so that stops first
+    fn frame_step(&self, backward: bool) -> Result<(), PlayerError> {
+        self.pause_fade.cancel(&self.handle);
+        let command = if backward {
+            "frame-back-step"
+        } else {
+            "frame-step"
+        };
+        self.handle
+            .command(&[command])
+            .context("Failed to step frame")
+    }
+
+    // The upper limit is mpv's volume-max, which users may raise
+    fn set_volume(&self, volume: f64) -> Result<f64, PlayerError> {
+        let max = self
+            .handle
+            .get::<f64>("volume-max")
+            .unwrap_or(DEFAULT_MAX_VOLUME);
+        if !(0.0..=max).contains(&volume) {
+            return Err(PlayerError::InvalidArgument(format!(
+                "Volume must be between 0 and {}",
+                max
+            )));
+        }
+        self.handle
+            .set("volume", volume)
+            .context("Failed to set volume")?;
+        Ok(volume)
+    }
+
+    fn toggle_mute(&self) -> Result<bool, PlayerError> {
+        let muted = self
+            .handle
+            .get::<bool>("mute")
+            .context("Failed to get mute state")?;
+        self.handle
+            .set("mute", !muted)
+            .context("Failed to toggle mute")?;
+        Ok(!muted)
+    }
+
+    // Hiding subtitles leaves sid alone, so showing them again brings back the same track
+    fn set_subtitle_visibility(&self, visible: Option<bool>) -> Result<bool, PlayerError> {
+        let (visible, action) = match visible {
+            Some(visible) => (visible, "set"),
+            None => {
+                let visible = self
+                    .handle
+                    .get::<bool>("sub-visibility")
+                    .context("Failed to get subtitle visibility")?;
+                (!visible, "toggle")
+            }
+        };
+        self.handle
+            .set("sub-visibility", visible)
+            .context(format!("Failed to {} subtitle visibility", action))?;
+        Ok(visible)
+    }
+}
+
+// Serve `channel` from a plain thread with an mpv instance of its own, standing in for
+// the event loop in tests
+#[cfg(all(test, not(feature = "real-mpv")))]
+pub fn spawn_test_server(
+    channel: &PlayerChannel,
+    pause_fade: Arc<PauseFade>,
+) -> std::thread::JoinHandle<()> {
+    let inbox = channel.connect();
+    let snapshot = channel.shared_snapshot();
+    std::thread::spawn(move || {
+        let handle = MpvHandle::create().unwrap();
+        handle.initialize().unwrap();
+        let server = Server::new(Rc::new(handle), pause_fade, snapshot);
+        for command in inbox.commands {
+            server.serve(command);
+        }
+    })
+}
@@ -0,0 +1,244 @@
+// Per-player state, and the registry and command channel that go with it
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::commands::{off_thread, on_main_thread};
+use crate::error::PlayerError;
+use crate::mpv::{MpvHandle, PlayerBackend};
+#[cfg(target_os = "linux")]
+use crate::rendering::gl_view;
+use crate::rendering::{frame_stream, layout, VideoArea};
+use crate::{config, events, fade, media};
+
+pub mod channel;
+pub mod registry;
+
+pub use registry::{event_name, PlayerId, PlayerInstance, Players, MAIN_PLAYER};
+
+// MPV player state with native rendering support
+pub struct MpvPlayer {
+    pub(crate) video_area: Option<VideoArea>,
+    // How video_area follows window resizes; set once rendering is set up
+    pub(crate) video_layout: Option<layout::VideoLayout>,
+    // False while the frontend shows a screen the video shouldn't cover
+    pub(crate) video_layer_visible: bool,
+    // The vid to restore when showing again, if hiding had to fall back to vid=no
+    pub(crate) hidden_vid: Option<String>,
+    // Files dropped onto the window before mpv was initialized
+    pub(crate) pending_files: Vec<media::MediaFile>,
+    pub(crate) pause_fade: Arc<fade::PauseFade>,
+    // Set while a clip export thread is running
+    pub(crate) clip_export_running: Arc<AtomicBool>,
+    // Settings shared with the event thread
+    pub(crate) event_control: Arc<events::EventControl>,
+    pub(crate) config: config::ConfigStore,
+    // User-supplied options applied before mpv_initialize, in the order they were set
+    pub(crate) pre_init_options: Vec<(String, String)>,
+    // Paths passed to load-script, including those auto-loaded from the scripts dir
+    pub(crate) loaded_scripts: Vec<String>,
+    // Owns the mpv instance, which it destroys as it exits; set while mpv is running
+    pub(crate) event_thread: Option<std::thread::JoinHandle<()>>,
+    // Set when frames go to the page instead, e.g. because embedding failed
+    pub(crate) frame_stream: Option<frame_stream::FrameStream>,
+    // What setup_video_rendering ended up using
+    pub(crate) video_backend: Option<config::VideoBackend>,
+    // The label of the window the video was popped out into, if it was
+    pub(crate) video_window: Option<String>,
+    // Where the main page wants the video, for when it's docked again
+    pub(crate) docked_layout: Option<layout::VideoLayout>,
+    // When mpv was restarted after stopping on its own, most recent last
+    pub(crate) restarts: Vec<Instant>,
+    // Whether set_shuffle last shuffled the playlist; mpv has no property saying so
+    pub(crate) shuffled: bool,
+}
+
+impl MpvPlayer {
+    pub(crate) fn new() -> Self {
+        Self {
+            video_area: None,
+            video_layout: None,
+            video_layer_visible: true,
+            hidden_vid: None,
+            pending_files: Vec::new(),
+            pause_fade: Arc::new(fade::PauseFade::default()),
+            clip_export_running: Arc::new(AtomicBool::new(false)),
+            event_control: Arc::new(events::EventControl::default()),
+            config: config::ConfigStore::default(),
+            pre_init_options: Vec::new(),
+            loaded_scripts: Vec::new(),
+            event_thread: None,
+            frame_stream: None,
+            video_backend: None,
+            video_window: None,
+            docked_layout: None,
+            restarts: Vec::new(),
+            shuffled: false,
+        }
+    }
+
+    // Whether there's an mpv instance for commands to go to
+    pub(crate) fn running(&self) -> bool {
+        self.event_thread.is_some()
+    }
+}
+
+// How long teardown waits for mpv to wind down, e.g. while a network stream it was
+// buffering gives up, before leaving it to the process exit
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Poll `done` until it holds or `deadline` passes; returns whether it held
+fn wait_until(deadline: Instant, done: impl Fn() -> bool) -> bool {
+    while !done() {
+        if Instant::now() >= deadline {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+// On the main thread as the app exits, so the views go while their windows still exist.
+// Every player is told to quit before any is waited on, so they wind down together
+// within one timeout.
+pub(crate) fn teardown_all(players: &Players) {
+    let stopping: Vec<_> = players
+        .all()
+        .iter()
+        .filter_map(|i| Teardown::start(i))
+        .collect();
+    #[cfg(target_os = "linux")]
+    for teardown in &stopping {
+        teardown.detach_gl_view();
+    }
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    for teardown in stopping {
+        teardown.wait(deadline).release_views();
+    }
+}
+
+// Stop mpv and release whatever it rendered into, leaving the player ready for another
+// init_mpv_player. Runs for destroy_player and before a restart; false if there was no
+// player to stop. The waiting happens on the blocking pool, and only the views go
+// through the main thread.
+pub(crate) async fn teardown_player<R: tauri::Runtime>(
+    app: &tauri::AppHandle<R>,
+    instance: &PlayerInstance,
+) -> Result<bool, PlayerError> {
+    let Some(teardown) = Teardown::start(instance) else {
+        return Ok(false);
+    };
+    let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+    #[cfg(target_os = "linux")]
+    let teardown = on_main_thread(app, move || {
+        teardown.detach_gl_view();
+        Ok(teardown)
+    })
+    .await?;
+    let teardown = off_thread(move || Ok(teardown.wait(deadline))).await?;
+    on_main_thread(app, move || {
+        teardown.release_views();
+        Ok(())
+    })
+    .await?;
+    Ok(true)
+}
+
+// A player that has been told to quit, holding what has to be released once it has
+struct Teardown {
+    id: PlayerId,
+    event_thread: Option<std::thread::JoinHandle<()>>,
+    frame_stream: Option<frame_stream::FrameStream>,
+}
+
+impl Teardown {
+    // Detach mpv from the player and ask it to quit, without waiting for it to
+    fn start(instance: &PlayerInstance) -> Option<Self> {
+        let mut player = instance.player.lock().unwrap();
+        let event_thread = player.event_thread.take()?;
+        // So the event thread doesn't take this shutdown for a crash
+        player.event_control.quitting.store(true, Ordering::SeqCst);
+        let pause_fade = player.pause_fade.clone();
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        let embedded = player.video_backend == Some(config::VideoBackend::Native);
+        let frame_stream = player.frame_stream.take();
+        // Everything tied to this instance goes with it
+        player.video_area = None;
+        player.video_layout = None;
+        player.video_backend = None;
+        player.video_window = None;
+        player.docked_layout = None;
+        player.hidden_vid = None;
+        player.loaded_scripts.clear();
+        player.shuffled = false;
+        // The event thread may need the player lock (e.g. MPRIS getters) before it exits
+        drop(player);
+
+        // The event thread quits mpv like anything else it's asked to do; queued before
+        // the disconnect, so it still gets served
+        let quit = instance.channel.post(move |handle: &MpvHandle| {
+            pause_fade.cancel(handle);
+
+            // mpv lets go of the view once there's no video to show, and without a `wid`
+            // it won't take it back before quitting
+            #[cfg(any(target_os = "macos", target_os = "windows"))]
+            if embedded {
+                for (name, value) in [("vid", "no"), ("force-window", "no"), ("wid", "-1")] {
+                    if let Err(e) = handle.set(name, value) {
+                        eprintln!("Failed to set {} before teardown: {}", name, e);
+                    }
+                }
+            }
+
+            // Silence it straight away; quitting can take a moment, e.g. while a stream
+            // buffers
+            let _ = handle.set("pause", true);
+            // Every client sees MPV_EVENT_SHUTDOWN, which ends the event loop
+            if let Err(e) = handle.command(&["quit"]) {
+                eprintln!("Failed to quit mpv: {}", e);
+            }
+        });
+        if let Err(e) = quit {
+            eprintln!("Failed to quit mpv: {}", e);
+        }
+        instance.channel.disconnect();
+
+        Some(Self {
+            id: instance.id,
+            event_thread: Some(event_thread),
+            frame_stream,
+        })
+    }
+
+    // On the main thread. The view's render context is an mpv client, which mpv waits
+    // for as it's destroyed.
+    #[cfg(target_os = "linux")]
+    fn detach_gl_view(&self) {
+        gl_view::detach(self.id);
+    }
+
+    // Wait, until `deadline` at most, for the event thread to destroy mpv and exit
+    fn wait(mut self, deadline: Instant) -> Self {
+        // A render context too, so it goes first
+        self.frame_stream = None;
+        let Some(thread) = self.event_thread.take() else {
+            return self;
+        };
+        if wait_until(deadline, || thread.is_finished()) {
+            let _ = thread.join();
+        } else {
+            eprintln!(
+                "Player {}'s event thread didn't stop in time; leaving it to exit",
+                self.id
+            );
+        }
+        self
+    }
+
+    // On the main thread, once mpv has stopped drawing into them
+    fn release_views(self) {
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        crate::rendering::destroy_native_views(self.id);
+    }
+}
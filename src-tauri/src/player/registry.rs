@@ -0,0 +1,153 @@
+// Every player the app runs. The main one always exists and is what commands address
+// when they're given no player id; others come and go, e.g. to compare two takes side
+// by side or for a picture-in-picture preview.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use super::channel::PlayerChannel;
+use super::MpvPlayer;
+use crate::commands::off_thread;
+use crate::error::PlayerError;
+use crate::mpv::MpvHandle;
+use crate::rendering::frame_stream::Frames;
+
+pub type PlayerId = u32;
+
+pub const MAIN_PLAYER: PlayerId = 0;
+
+// One player's state, the channel to its transport server and where its streamed
+// frames wait for the page
+pub struct PlayerInstance {
+    pub id: PlayerId,
+    pub player: Mutex<MpvPlayer>,
+    pub channel: PlayerChannel,
+    pub frames: Frames,
+}
+
+impl PlayerInstance {
+    // Run `work` on the event thread with this player's state; the lock is taken there,
+    // so the event loop never waits on a command holding it
+    pub async fn run<T: Send + 'static>(
+        self: &Arc<Self>,
+        work: impl FnOnce(&mut MpvPlayer, &MpvHandle) -> Result<T, PlayerError> + Send + 'static,
+    ) -> Result<T, PlayerError> {
+        let instance = self.clone();
+        self.channel
+            .run(move |handle| work(&mut instance.player.lock().unwrap(), handle))
+            .await
+    }
+
+    // `run` without waiting for the result; failures are for `work` to report
+    pub fn post(
+        self: &Arc<Self>,
+        work: impl FnOnce(&mut MpvPlayer, &MpvHandle) + Send + 'static,
+    ) -> Result<(), PlayerError> {
+        let instance = self.clone();
+        self.channel
+            .post(move |handle| work(&mut instance.player.lock().unwrap(), handle))
+    }
+
+    // For settings that can also change before init_mpv_player: `run` while mpv is up,
+    // and otherwise `work` with no handle, on the blocking pool. The check is made under
+    // the lock start_player holds, so the two can't cross.
+    pub async fn configure<T, F>(self: &Arc<Self>, work: F) -> Result<T, PlayerError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut MpvPlayer, Option<&MpvHandle>) -> Result<T, PlayerError> + Send + 'static,
+    {
+        let instance = self.clone();
+        let stored = off_thread(move || {
+            let mut player = instance.player.lock().unwrap();
+            if player.running() {
+                return Ok(Err(work));
+            }
+            Ok(Ok(work(&mut player, None)))
+        })
+        .await?;
+        match stored {
+            Ok(result) => result,
+            Err(work) => {
+                self.run(move |player, handle| work(player, Some(handle)))
+                    .await
+            }
+        }
+    }
+
+    fn new(id: PlayerId, player: MpvPlayer) -> Self {
+        Self {
+            id,
+            player: Mutex::new(player),
+            channel: PlayerChannel::default(),
+            frames: Frames::default(),
+        }
+    }
+}
+
+pub struct Players {
+    instances: Mutex<BTreeMap<PlayerId, Arc<PlayerInstance>>>,
+    next_id: AtomicU32,
+}
+
+impl Default for Players {
+    fn default() -> Self {
+        Self::with_main(MpvPlayer::new())
+    }
+}
+
+impl Players {
+    pub fn with_main(player: MpvPlayer) -> Self {
+        let main = Arc::new(PlayerInstance::new(MAIN_PLAYER, player));
+        Self {
+            instances: Mutex::new(BTreeMap::from([(MAIN_PLAYER, main)])),
+            next_id: AtomicU32::new(MAIN_PLAYER + 1),
+        }
+    }
+
+    // The player `id` names, or the main one for None
+    pub fn get(&self, id: Option<PlayerId>) -> Result<Arc<PlayerInstance>, PlayerError> {
+        let id = id.unwrap_or(MAIN_PLAYER);
+        self.instances
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or(PlayerError::NoPlayer(id))
+    }
+
+    pub fn main(&self) -> Arc<PlayerInstance> {
+        self.instances.lock().unwrap()[&MAIN_PLAYER].clone()
+    }
+
+    // Ids aren't reused, so a stale one can't reach a newer player
+    pub fn add(&self, player: MpvPlayer) -> PlayerId {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let instance = Arc::new(PlayerInstance::new(id, player));
+        self.instances.lock().unwrap().insert(id, instance);
+        id
+    }
+
+    // The main player stays, only ever being torn down
+    pub fn remove(&self, id: PlayerId) -> Option<Arc<PlayerInstance>> {
+        if id == MAIN_PLAYER {
+            return None;
+        }
+        self.instances.lock().unwrap().remove(&id)
+    }
+
+    pub fn all(&self) -> Vec<Arc<PlayerInstance>> {
+        self.instances.lock().unwrap().values().cloned().collect()
+    }
+}
+
+// What a player's events are called: the main player keeps the plain names, and the
+// others prefix theirs, e.g. "player-2/mpv://seek"
+pub fn event_name(id: PlayerId, event: &str) -> Cow<'_, str> {
+    if id == MAIN_PLAYER {
+        Cow::Borrowed(event)
+    } else {
+        Cow::Owned(format!("player-{}/{}", id, event))
+    }
+}
@@ -0,0 +1,117 @@
+// The queue as the page shows it: mpv's playlist, plus the titles and durations of the
+// files in it. mpv only knows those for the file it has open, so the event thread
+// remembers them as each file loads.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::m3u;
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlaylistItem {
+    pub index: usize,
+    // mpv's playlist entry id, which unlike the index stays with the entry as it moves
+    pub id: Option<i64>,
+    pub path: String,
+    // From the playlist file if it had one (#EXTINF), else the file's own tags
+    pub title: Option<String>,
+    // Known once the file has been loaded
+    pub duration: Option<f64>,
+    pub current: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+struct Metadata {
+    title: Option<String>,
+    duration: Option<f64>,
+}
+
+// What was learned about each file while it was open, by path
+#[derive(Default)]
+pub struct PlaylistMetadata {
+    by_path: Mutex<HashMap<String, Metadata>>,
+}
+
+impl PlaylistMetadata {
+    // What a playlist file said about an entry it queued
+    pub fn remember(&self, entry: &m3u::PlaylistEntry) {
+        let metadata = Metadata {
+            title: entry.title.clone(),
+            duration: entry.duration,
+        };
+        self.by_path
+            .lock()
+            .unwrap()
+            .insert(entry.path.clone(), metadata);
+    }
+
+    // Remember the open file's title and duration, forgetting files no longer queued. A
+    // title from the playlist file wins over the file's tags, which are often missing
+    // and leave mpv showing the file name.
+    pub fn record(&self, handle: &MpvHandle) {
+        let Ok(path) = handle.get::<String>("path") else {
+            return;
+        };
+        let queued: Vec<String> = (0..count(handle))
+            .filter_map(|i| entry_property(handle, i, "filename"))
+            .collect();
+        let mut by_path = self.by_path.lock().unwrap();
+        by_path.retain(|known, _| queued.contains(known));
+        let known = by_path.entry(path).or_default();
+        if known.title.is_none() {
+            known.title = handle.get::<String>("media-title").ok();
+        }
+        known.duration = handle.get::<f64>("duration").ok().or(known.duration);
+    }
+
+    pub fn read(&self, handle: &MpvHandle) -> Vec<PlaylistItem> {
+        let by_path = self.by_path.lock().unwrap();
+        (0..count(handle))
+            .filter_map(|index| {
+                let path = entry_property(handle, index, "filename")?;
+                let known = by_path.get(&path).cloned().unwrap_or_default();
+                Some(PlaylistItem {
+                    index,
+                    id: handle.get::<i64>(&format!("playlist/{}/id", index)).ok(),
+                    title: entry_property(handle, index, "title").or(known.title),
+                    duration: known.duration,
+                    current: handle
+                        .get::<bool>(&format!("playlist/{}/current", index))
+                        .unwrap_or(false),
+                    path,
+                })
+            })
+            .collect()
+    }
+}
+
+// The index and path of the entry mpv gave `id`, if it's still in the playlist
+pub fn find_entry(handle: &MpvHandle, id: i64) -> Option<(usize, String)> {
+    (0..count(handle))
+        .find(|&i| handle.get::<i64>(&format!("playlist/{}/id", i)) == Ok(id))
+        .and_then(|i| Some((i, entry_property(handle, i, "filename")?)))
+}
+
+pub fn count(handle: &MpvHandle) -> usize {
+    handle
+        .get::<i64>("playlist-count")
+        .map_or(0, |count| count.max(0) as usize)
+}
+
+fn entry_property(handle: &MpvHandle, index: usize, name: &str) -> Option<String> {
+    handle
+        .get::<String>(&format!("playlist/{}/{}", index, name))
+        .ok()
+}
+
+// playlist-move puts an entry before the one at its second index; this is that index
+// for the entry to end up at `to`
+pub fn move_target(from: usize, to: usize) -> usize {
+    if to > from {
+        to + 1
+    } else {
+        to
+    }
+}
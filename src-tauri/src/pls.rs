@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::m3u::{resolve_entry, ParsedPlaylist, PlaylistEntry};
+
+// Whether `path` names a PLS playlist rather than an M3U one
+pub fn is_pls(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pls"))
+}
+
+// Parse a PLS playlist: an INI-style [playlist] section of FileN/TitleN/LengthN keys.
+// Entries are ordered by N, which needn't be contiguous; relative entries are resolved
+// against `base_dir` as for M3U.
+pub fn parse(contents: &str, base_dir: &Path) -> ParsedPlaylist {
+    let contents = contents.strip_prefix('\u{feff}').unwrap_or(contents);
+    let mut parsed = ParsedPlaylist::default();
+    let mut entries: BTreeMap<u32, (Option<String>, PlaylistEntry)> = BTreeMap::new();
+
+    for (index, raw_line) in contents.lines().enumerate() {
+        let line_no = index + 1;
+        let line = raw_line.trim();
+
+        // The section header, NumberOfEntries and Version carry nothing we use
+        if line.is_empty() || line.starts_with('[') || line.starts_with(';') {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            parsed
+                .warnings
+                .push(format!("Line {}: not a key=value pair, skipped", line_no));
+            continue;
+        };
+        let key = key.trim().to_ascii_lowercase();
+        let value = value.trim();
+
+        let (field, number) = match ["file", "title", "length"]
+            .into_iter()
+            .find_map(|field| Some((field, key.strip_prefix(field)?.parse::<u32>().ok()?)))
+        {
+            Some(found) => found,
+            None => continue,
+        };
+        let (file, entry) = entries.entry(number).or_insert_with(|| {
+            (
+                None,
+                PlaylistEntry {
+                    path: String::new(),
+                    title: None,
+                    duration: None,
+                },
+            )
+        });
+        match field {
+            "file" if value.chars().any(|c| c.is_control()) => parsed.warnings.push(format!(
+                "Line {}: entry contains control characters, skipped",
+                line_no
+            )),
+            "file" => *file = Some(value.to_string()),
+            "title" if !value.is_empty() => entry.title = Some(value.to_string()),
+            "length" => match value.parse::<f64>() {
+                Ok(length) if length >= 0.0 => entry.duration = Some(length),
+                Ok(_) => {}
+                Err(_) => parsed
+                    .warnings
+                    .push(format!("Line {}: malformed Length{}", line_no, number)),
+            },
+            _ => {}
+        }
+    }
+
+    for (number, (file, mut entry)) in entries {
+        match file {
+            Some(file) => {
+                entry.path = resolve_entry(&file, base_dir);
+                parsed.entries.push(entry);
+            }
+            None => parsed
+                .warnings
+                .push(format!("Entry {} has no File{}, skipped", number, number)),
+        }
+    }
+
+    parsed
+}
+
+pub fn write(entries: &[PlaylistEntry]) -> String {
+    let mut out = String::from("[playlist]\n");
+
+    for (i, entry) in entries.iter().enumerate() {
+        let n = i + 1;
+        out.push_str(&format!("File{}={}\n", n, entry.path));
+        if let Some(title) = &entry.title {
+            out.push_str(&format!(
+                "Title{}={}\n",
+                n,
+                title.replace(['\r', '\n'], " ")
+            ));
+        }
+        let length = entry.duration.map(|d| d.round() as i64).unwrap_or(-1);
+        out.push_str(&format!("Length{}={}\n", n, length));
+    }
+
+    out.push_str(&format!("NumberOfEntries={}\nVersion=2\n", entries.len()));
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> &'static Path {
+        Path::new("/media/playlists")
+    }
+
+    #[test]
+    fn parses_entries_in_number_order() {
+        let input = "[playlist]\r\nFile2=http://stream.example.com/live\r\nTitle2=Radio\r\nLength2=-1\r\nFile1=song.mp3\r\nTitle1=Artist - Song\r\nLength1=123\r\nNumberOfEntries=2\r\nVersion=2\r\n";
+        let parsed = parse(input, base());
+
+        assert!(parsed.warnings.is_empty());
+        assert_eq!(
+            parsed.entries,
+            vec![
+                PlaylistEntry {
+                    path: "/media/playlists/song.mp3".to_string(),
+                    title: Some("Artist - Song".to_string()),
+                    duration: Some(123.0),
+                },
+                PlaylistEntry {
+                    path: "http://stream.example.com/live".to_string(),
+                    title: Some("Radio".to_string()),
+                    duration: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn skips_entries_without_a_file() {
+        let input = "[playlist]\nTitle1=Nothing here\nfile3=/videos/c.mkv\nLength3=abc\ngarbage\n";
+        let parsed = parse(input, base());
+
+        assert_eq!(parsed.entries.len(), 1);
+        assert_eq!(parsed.entries[0].path, "/videos/c.mkv");
+        assert_eq!(parsed.entries[0].duration, None);
+        assert_eq!(parsed.warnings.len(), 3);
+    }
+
+    #[test]
+    fn written_playlist_parses_back() {
+        let entries = vec![
+            PlaylistEntry {
+                path: "/videos/one.mkv".to_string(),
+                title: Some("One".to_string()),
+                duration: Some(61.6),
+            },
+            PlaylistEntry {
+                path: "/videos/two.mkv".to_string(),
+                title: None,
+                duration: None,
+            },
+        ];
+
+        let written = write(&entries);
+        assert_eq!(
+            written,
+            "[playlist]\nFile1=/videos/one.mkv\nTitle1=One\nLength1=62\nFile2=/videos/two.mkv\nLength2=-1\nNumberOfEntries=2\nVersion=2\n"
+        );
+        let parsed = parse(&written, base());
+        assert!(parsed.warnings.is_empty());
+        assert_eq!(parsed.entries[0].duration, Some(62.0));
+        assert_eq!(parsed.entries[1].path, "/videos/two.mkv");
+    }
+}
@@ -0,0 +1,179 @@
+// Keeps the display awake while a file is playing
+
+// Owned by the event thread; the platform assertion is released when this is dropped
+#[derive(Default)]
+pub struct SleepInhibitor {
+    assertion: Option<platform::Assertion>,
+}
+
+impl SleepInhibitor {
+    // Acquire or release to match `inhibit`; repeated calls with the same value are no-ops
+    pub fn set(&mut self, inhibit: bool) {
+        match (inhibit, self.assertion.is_some()) {
+            (true, false) => {
+                self.assertion = platform::Assertion::acquire()
+                    .map_err(|e| eprintln!("Failed to prevent sleep: {}", e))
+                    .ok();
+            }
+            (false, true) => self.assertion = None,
+            _ => {}
+        }
+    }
+}
+
+// Shown by tools that list who is keeping the machine awake
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+const REASON: &str = "Playing video";
+
+#[cfg(target_os = "macos")]
+mod platform {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_void};
+
+    type CFStringRef = *const c_void;
+
+    const CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+    const IOPM_ASSERTION_LEVEL_ON: u32 = 255;
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        fn CFStringCreateWithCString(
+            alloc: *const c_void,
+            c_str: *const c_char,
+            encoding: u32,
+        ) -> CFStringRef;
+        fn CFRelease(cf: *const c_void);
+    }
+
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPMAssertionCreateWithName(
+            assertion_type: CFStringRef,
+            level: u32,
+            name: CFStringRef,
+            assertion_id: *mut u32,
+        ) -> i32;
+        fn IOPMAssertionRelease(assertion_id: u32) -> i32;
+    }
+
+    unsafe fn cf_string(value: &str) -> CFStringRef {
+        let value = CString::new(value).unwrap();
+        CFStringCreateWithCString(std::ptr::null(), value.as_ptr(), CF_STRING_ENCODING_UTF8)
+    }
+
+    pub struct Assertion(u32);
+
+    impl Assertion {
+        pub fn acquire() -> Result<Self, String> {
+            unsafe {
+                // kIOPMAssertionTypePreventUserIdleDisplaySleep, as video players use
+                let kind = cf_string("PreventUserIdleDisplaySleep");
+                let name = cf_string(super::REASON);
+                let mut id = 0;
+                let ret = IOPMAssertionCreateWithName(kind, IOPM_ASSERTION_LEVEL_ON, name, &mut id);
+                CFRelease(kind);
+                CFRelease(name);
+                if ret == 0 {
+                    Ok(Self(id))
+                } else {
+                    Err(format!("IOPMAssertionCreateWithName returned {}", ret))
+                }
+            }
+        }
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            unsafe { IOPMAssertionRelease(self.0) };
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod platform {
+    const ES_CONTINUOUS: u32 = 0x8000_0000;
+    const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+    const ES_DISPLAY_REQUIRED: u32 = 0x0000_0002;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetThreadExecutionState(flags: u32) -> u32;
+    }
+
+    // The execution state belongs to the calling thread, which is why the event
+    // thread (not a command) owns the inhibitor
+    pub struct Assertion;
+
+    impl Assertion {
+        pub fn acquire() -> Result<Self, String> {
+            let flags = ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED;
+            if unsafe { SetThreadExecutionState(flags) } == 0 {
+                return Err("SetThreadExecutionState failed".to_string());
+            }
+            Ok(Self)
+        }
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            unsafe { SetThreadExecutionState(ES_CONTINUOUS) };
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod platform {
+    use zbus::blocking::Connection;
+
+    const SCREENSAVER: &str = "org.freedesktop.ScreenSaver";
+    const SCREENSAVER_PATH: &str = "/org/freedesktop/ScreenSaver";
+
+    // The inhibition lasts until UnInhibit or until our bus connection closes
+    pub struct Assertion {
+        connection: Connection,
+        cookie: u32,
+    }
+
+    impl Assertion {
+        pub fn acquire() -> Result<Self, String> {
+            let connection = Connection::session().map_err(|e| e.to_string())?;
+            let reply = connection
+                .call_method(
+                    Some(SCREENSAVER),
+                    SCREENSAVER_PATH,
+                    Some(SCREENSAVER),
+                    "Inhibit",
+                    &("media-player-tauri", super::REASON),
+                )
+                .map_err(|e| e.to_string())?;
+            let cookie: u32 = reply.body().deserialize().map_err(|e| e.to_string())?;
+            Ok(Self { connection, cookie })
+        }
+    }
+
+    impl Drop for Assertion {
+        fn drop(&mut self) {
+            let result = self.connection.call_method(
+                Some(SCREENSAVER),
+                SCREENSAVER_PATH,
+                Some(SCREENSAVER),
+                "UnInhibit",
+                &(self.cookie,),
+            );
+            if let Err(e) = result {
+                eprintln!("Failed to release sleep inhibitor: {}", e);
+            }
+        }
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "windows", target_os = "linux")))]
+mod platform {
+    pub struct Assertion;
+
+    impl Assertion {
+        pub fn acquire() -> Result<Self, String> {
+            Err("not supported on this platform".to_string())
+        }
+    }
+}
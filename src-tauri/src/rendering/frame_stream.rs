@@ -0,0 +1,315 @@
+// Video for when mpv can't draw into the window at all: mpv's software renderer draws
+// each frame on the CPU, downscaled and at a limited rate, and the page fetches frames
+// over the mpvframe:// protocol and paints them into a canvas. It costs a copy per
+// frame, but works wherever mpv itself does.
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+use tauri::http::{Request, Response, StatusCode};
+
+use crate::mpv::{MpvClient, RenderContext, SW_PIXEL_SIZE};
+
+pub const SCHEME: &str = "mpvframe";
+// Frames are scaled down to fit in this, as they cross the IPC boundary uncompressed
+const MAX_SIZE: (i32, i32) = (1280, 720);
+const FRAME_INTERVAL: Duration = Duration::from_millis(1000 / 30);
+// How often the render thread checks for a stop without being woken
+const IDLE_WAKEUP: Duration = Duration::from_millis(250);
+// Width and height as u32, then the sequence number as u64, all little-endian
+const HEADER_SIZE: usize = 16;
+
+// The newest frame, shared between the render thread and the protocol handler
+#[derive(Debug, Clone, Default)]
+pub struct Frames {
+    latest: Arc<Mutex<Option<Frame>>>,
+}
+
+#[derive(Debug)]
+struct Frame {
+    sequence: u64,
+    // Header then RGBA pixels, ready to send
+    encoded: Vec<u8>,
+}
+
+impl Frames {
+    fn publish(&self, width: i32, height: i32, rgba: &[u8]) {
+        let mut latest = self.latest.lock().unwrap();
+        let sequence = latest.as_ref().map_or(1, |frame| frame.sequence + 1);
+        *latest = Some(Frame {
+            sequence,
+            encoded: encode(sequence, width, height, rgba),
+        });
+    }
+
+    fn clear(&self) {
+        self.latest.lock().unwrap().take();
+    }
+}
+
+fn encode(sequence: u64, width: i32, height: i32, rgba: &[u8]) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(HEADER_SIZE + rgba.len());
+    encoded.extend((width as u32).to_le_bytes());
+    encoded.extend((height as u32).to_le_bytes());
+    encoded.extend(sequence.to_le_bytes());
+    encoded.extend(rgba);
+    encoded
+}
+
+// Answer a page's fetch. `?since=N` asks only for a frame newer than N, and gets
+// 204 No Content when there isn't one yet.
+pub fn respond(frames: &Frames, request: &Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+    let since = request
+        .uri()
+        .query()
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|pair| pair.strip_prefix("since="))
+        .and_then(|value| value.parse::<u64>().ok())
+        .unwrap_or(0);
+
+    let latest = frames.latest.lock().unwrap();
+    let (status, body) = match latest.as_ref() {
+        Some(frame) if frame.sequence > since => (StatusCode::OK, frame.encoded.clone()),
+        _ => (StatusCode::NO_CONTENT, Vec::new()),
+    };
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/octet-stream")
+        // The page's own origin isn't mpvframe://
+        .header("Access-Control-Allow-Origin", "*")
+        .body(Cow::Owned(body))
+        .unwrap()
+}
+
+// Scale `width` x `height` down to fit MAX_SIZE, keeping the aspect ratio
+fn fit(width: i32, height: i32) -> (i32, i32) {
+    let scale = (MAX_SIZE.0 as f64 / width as f64)
+        .min(MAX_SIZE.1 as f64 / height as f64)
+        .min(1.0);
+    (
+        ((width as f64 * scale).round() as i32).max(1),
+        ((height as f64 * scale).round() as i32).max(1),
+    )
+}
+
+// mpv renders faster into rows aligned to 64 bytes
+fn stride_for(width: i32) -> usize {
+    (width as usize * SW_PIXEL_SIZE).next_multiple_of(64)
+}
+
+// Drop the row padding and make every pixel opaque, as rgb0 leaves the fourth byte
+// undefined and a canvas would read it as alpha
+fn to_rgba(pixels: &[u8], width: i32, height: i32, stride: usize) -> Vec<u8> {
+    let row = width as usize * SW_PIXEL_SIZE;
+    let mut rgba = Vec::with_capacity(row * height as usize);
+    for line in pixels.chunks(stride).take(height as usize) {
+        rgba.extend_from_slice(&line[..row]);
+    }
+    for pixel in rgba.chunks_exact_mut(SW_PIXEL_SIZE) {
+        pixel[3] = 0xff;
+    }
+    rgba
+}
+
+struct Control {
+    size: Mutex<(i32, i32)>,
+    // Set by set_size so the next frame is drawn even while paused
+    resized: AtomicBool,
+    stop: AtomicBool,
+}
+
+// The render thread; dropping it stops the thread and frees the render context, which
+// has to happen before the mpv instance is destroyed
+pub struct FrameStream {
+    control: Arc<Control>,
+    wake: SyncSender<()>,
+    frames: Frames,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl FrameStream {
+    pub fn start(client: MpvClient, frames: Frames) -> Result<Self, String> {
+        let control = Arc::new(Control {
+            size: Mutex::new(MAX_SIZE),
+            resized: AtomicBool::new(true),
+            stop: AtomicBool::new(false),
+        });
+        // One pending wakeup is enough; the thread renders whatever is newest
+        let (wake, woken) = mpsc::sync_channel(1);
+        let (started, result) = mpsc::channel();
+
+        let thread = {
+            let control = control.clone();
+            let wake = wake.clone();
+            let frames = frames.clone();
+            std::thread::spawn(move || {
+                // The render context stays on the thread that created it
+                let mut renderer = match RenderContext::software(client) {
+                    Ok(renderer) => renderer,
+                    Err(e) => {
+                        let _ = started.send(Err(e));
+                        return;
+                    }
+                };
+                renderer.set_update_callback(move || {
+                    let _ = wake.try_send(());
+                });
+                let _ = started.send(Ok(()));
+                run(&renderer, &control, &woken, &frames);
+            })
+        };
+
+        match result.recv() {
+            Ok(Ok(())) => Ok(Self {
+                control,
+                wake,
+                frames,
+                thread: Some(thread),
+            }),
+            Ok(Err(e)) => {
+                let _ = thread.join();
+                Err(format!("Failed to create mpv software renderer: {}", e))
+            }
+            Err(_) => Err("The video render thread exited".to_string()),
+        }
+    }
+
+    // Render for an area of `width` x `height`, within MAX_SIZE
+    pub fn set_size(&self, width: i32, height: i32) {
+        if width <= 0 || height <= 0 {
+            return;
+        }
+        *self.control.size.lock().unwrap() = fit(width, height);
+        self.control.resized.store(true, Ordering::SeqCst);
+        let _ = self.wake.try_send(());
+    }
+}
+
+impl Drop for FrameStream {
+    fn drop(&mut self) {
+        self.control.stop.store(true, Ordering::SeqCst);
+        let _ = self.wake.try_send(());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+        self.frames.clear();
+    }
+}
+
+fn run(renderer: &RenderContext, control: &Control, woken: &Receiver<()>, frames: &Frames) {
+    let mut last_frame: Option<Instant> = None;
+    let mut pixels = Vec::new();
+    while !control.stop.load(Ordering::SeqCst) {
+        if woken.recv_timeout(IDLE_WAKEUP).is_err() {
+            continue;
+        }
+        // Updates arriving while this waits are folded into the frame drawn after it
+        if let Some(elapsed) = last_frame.map(|last| last.elapsed()) {
+            std::thread::sleep(FRAME_INTERVAL.saturating_sub(elapsed));
+        }
+        let resized = control.resized.swap(false, Ordering::SeqCst);
+        // Always ask, as mpv expects an update() after every callback
+        if !renderer.update() && !resized {
+            continue;
+        }
+
+        let (width, height) = *control.size.lock().unwrap();
+        let stride = stride_for(width);
+        pixels.resize(stride * height as usize, 0);
+        match renderer.render_software(width, height, stride, &mut pixels) {
+            Ok(()) => frames.publish(width, height, &to_rgba(&pixels, width, height, stride)),
+            Err(e) => eprintln!("Failed to render video frame: {}", e),
+        }
+        last_frame = Some(Instant::now());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fetch(frames: &Frames, uri: &str) -> Response<Cow<'static, [u8]>> {
+        respond(
+            frames,
+            &Request::builder().uri(uri).body(Vec::new()).unwrap(),
+        )
+    }
+
+    #[test]
+    fn frames_are_scaled_to_fit() {
+        assert_eq!(fit(640, 360), (640, 360));
+        assert_eq!(fit(2560, 1440), (1280, 720));
+        assert_eq!(fit(1000, 1000), (720, 720));
+        assert_eq!(stride_for(3), 64);
+        assert_eq!(stride_for(16), 64);
+        assert_eq!(stride_for(17), 128);
+    }
+
+    #[test]
+    fn padding_is_dropped_and_pixels_made_opaque() {
+        // Two rows of one pixel, each padded to 8 bytes
+        let pixels = [1, 2, 3, 0, 9, 9, 9, 9, 4, 5, 6, 0, 9, 9, 9, 9];
+        assert_eq!(to_rgba(&pixels, 1, 2, 8), [1, 2, 3, 0xff, 4, 5, 6, 0xff]);
+    }
+
+    #[test]
+    fn pages_only_get_frames_they_havent_seen() {
+        let frames = Frames::default();
+        assert_eq!(fetch(&frames, "mpvframe://localhost/").status(), 204);
+
+        frames.publish(1, 1, &[1, 2, 3, 0xff]);
+        let response = fetch(&frames, "mpvframe://localhost/?since=0");
+        assert_eq!(response.status(), 200);
+        let body = response.body();
+        assert_eq!(&body[..4], 1u32.to_le_bytes());
+        assert_eq!(&body[8..16], 1u64.to_le_bytes());
+        assert_eq!(&body[HEADER_SIZE..], [1, 2, 3, 0xff]);
+
+        assert_eq!(
+            fetch(&frames, "mpvframe://localhost/?since=1").status(),
+            204
+        );
+        frames.publish(1, 1, &[0; 4]);
+        assert_eq!(
+            fetch(&frames, "mpvframe://localhost/?since=1").status(),
+            200
+        );
+        frames.clear();
+        assert_eq!(fetch(&frames, "mpvframe://localhost/").status(), 204);
+    }
+
+    #[cfg(not(feature = "real-mpv"))]
+    #[test]
+    fn the_stream_renders_at_the_requested_size() {
+        let handle = crate::mpv::MpvHandle::create().unwrap();
+        handle.initialize().unwrap();
+        let frames = Frames::default();
+        let stream = FrameStream::start(handle.create_client().unwrap(), frames.clone()).unwrap();
+        stream.set_size(320, 180);
+
+        // A frame at the default size may come first
+        let expected = HEADER_SIZE + 320 * 180 * 4;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while frames
+            .latest
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|f| f.encoded.len())
+            != Some(expected)
+        {
+            assert!(
+                Instant::now() < deadline,
+                "no frame was rendered at 320x180"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        drop(stream);
+        assert!(frames.latest.lock().unwrap().is_none());
+    }
+}
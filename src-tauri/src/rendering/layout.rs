@@ -0,0 +1,282 @@
+// Keeps the embedded video inside its container as the window resizes
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{Manager, Window};
+
+use super::frame_stream::FrameStream;
+use super::video_window;
+use super::{AreaUnits, VideoArea};
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::player::{MpvPlayer, PlayerInstance, Players};
+
+// Resize events arrive far faster than we can usefully redraw; coalesce to one per frame
+const FRAME: Duration = Duration::from_millis(16);
+
+// Labels of the windows with a relayout on its way, so each window is coalesced on its own
+static RELAYOUT_PENDING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+// How the video rectangle follows the window when it resizes
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LayoutRule {
+    // Scale the last area the frontend sent by the window's growth
+    #[default]
+    Proportional,
+    // Fill the window except for a control bar of fixed height along the bottom
+    ControlBar {
+        height: f64,
+    },
+}
+
+// The frontend's last requested area and the window size it was computed for
+#[derive(Debug, Clone)]
+pub struct VideoLayout {
+    reference: VideoArea,
+    reference_size: (f64, f64),
+    rule: LayoutRule,
+}
+
+impl VideoLayout {
+    pub fn new(reference: VideoArea, reference_size: (f64, f64), rule: LayoutRule) -> Self {
+        Self {
+            reference,
+            reference_size,
+            rule,
+        }
+    }
+
+    pub fn rule(&self) -> LayoutRule {
+        self.rule
+    }
+
+    // The area to use once the window has become `size`
+    pub fn area_for(&self, (width, height): (f64, f64)) -> VideoArea {
+        match self.rule {
+            LayoutRule::Proportional => {
+                let (ref_width, ref_height) = self.reference_size;
+                if ref_width <= 0.0 || ref_height <= 0.0 {
+                    return self.reference.clone();
+                }
+                let sx = width / ref_width;
+                let sy = height / ref_height;
+                VideoArea {
+                    x: (self.reference.x as f64 * sx).round() as i32,
+                    y: (self.reference.y as f64 * sy).round() as i32,
+                    width: (self.reference.width as f64 * sx).round() as i32,
+                    height: (self.reference.height as f64 * sy).round() as i32,
+                    units: AreaUnits::Logical,
+                }
+            }
+            LayoutRule::ControlBar { height: bar } => VideoArea {
+                x: 0,
+                y: 0,
+                width: width.round() as i32,
+                height: (height - bar).max(0.0).round() as i32,
+                units: AreaUnits::Logical,
+            },
+        }
+    }
+}
+
+// The window's content size in logical (CSS) pixels, the unit stored layouts use. Working
+// in logical pixels keeps the layout stable when the window moves to a monitor with a
+// different scale factor; the ScaleFactorChanged relayout then only re-applies it.
+pub fn window_size(window: &Window) -> Result<(f64, f64), String> {
+    let size = window
+        .inner_size()
+        .map_err(|e| format!("Failed to get window size: {}", e))?;
+    let scale = window
+        .scale_factor()
+        .map_err(|e| format!("Failed to get scale factor: {}", e))?;
+    let size = size.to_logical::<f64>(scale);
+    Ok((size.width, size.height))
+}
+
+// Reject empty areas and trim the rest to the window's content area
+pub fn clamp_to_window(area: &VideoArea, (width, height): (f64, f64)) -> Result<VideoArea, String> {
+    if area.width <= 0 || area.height <= 0 {
+        return Err(format!(
+            "Video area must have a positive size, got {}x{}",
+            area.width, area.height
+        ));
+    }
+
+    let (max_x, max_y) = (width.floor() as i32, height.floor() as i32);
+    let x = area.x.clamp(0, max_x);
+    let y = area.y.clamp(0, max_y);
+    let clamped = VideoArea {
+        x,
+        y,
+        // The frontend can send anything, so the far edges mustn't overflow
+        width: area.x.saturating_add(area.width).min(max_x) - x,
+        height: area.y.saturating_add(area.height).min(max_y) - y,
+        units: area.units,
+    };
+    if clamped.width <= 0 || clamped.height <= 0 {
+        return Err(format!(
+            "Video area {:?} lies outside the {}x{} window",
+            area, max_x, max_y
+        ));
+    }
+
+    if clamped.x != area.x
+        || clamped.y != area.y
+        || clamped.width != area.width
+        || clamped.height != area.height
+    {
+        eprintln!(
+            "Warning: video area {:?} clamped to the {}x{} window: {:?}",
+            area, max_x, max_y, clamped
+        );
+    }
+    Ok(clamped)
+}
+
+// Move the video to `area`: the child view itself where there is one, otherwise shrink
+// the picture within the view mpv renders into. Streamed frames are rendered at the
+// area's size in device pixels, so they stay sharp at `scale`, and the page places its
+// canvas. Margins are set on the event thread, and only while mpv is running. Views are
+// kept on the main thread, so this runs there where there are any.
+pub fn apply_area(
+    instance: &Arc<PlayerInstance>,
+    player: &MpvPlayer,
+    area: &VideoArea,
+    size: (f64, f64),
+    scale: f64,
+) {
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    if let Some(view) = super::native_view(instance.id) {
+        unsafe { super::native_view::set_frame(view.0, area) };
+        return;
+    }
+    if let Some(stream) = &player.frame_stream {
+        size_stream(stream, area, scale);
+        return;
+    }
+    let area = area.clone();
+    let _ = instance.channel.post(move |handle| {
+        if let Err(e) = apply_margins(handle, &area, size) {
+            eprintln!("Failed to move the video: {}", e);
+        }
+    });
+}
+
+// Render streamed frames at `area`'s size in device pixels
+pub fn size_stream(stream: &FrameStream, area: &VideoArea, scale: f64) {
+    let physical = |value: i32| (value as f64 * scale).round() as i32;
+    stream.set_size(physical(area.width), physical(area.height));
+}
+
+// mpv renders into the whole view; margins shrink the picture to `area` within it
+fn apply_margins(
+    handle: &MpvHandle,
+    area: &VideoArea,
+    (width, height): (f64, f64),
+) -> Result<(), String> {
+    if width <= 0.0 || height <= 0.0 {
+        return Ok(());
+    }
+    let left = area.x as f64 / width;
+    let top = area.y as f64 / height;
+    let right = (width - area.x as f64 - area.width as f64) / width;
+    let bottom = (height - area.y as f64 - area.height as f64) / height;
+
+    let margins = [
+        ("video-margin-ratio-left", left),
+        ("video-margin-ratio-top", top),
+        ("video-margin-ratio-right", right),
+        ("video-margin-ratio-bottom", bottom),
+    ];
+    for (name, ratio) in margins {
+        handle
+            .set(name, ratio.clamp(0.0, 1.0))
+            .map_err(|e| format!("Failed to set {}: {}", name, e))?;
+    }
+    Ok(())
+}
+
+// Called for every resize/scale event; the relayout itself runs at most once per frame
+pub fn schedule_relayout(window: &Window) {
+    let label = window.label().to_string();
+    if !RELAYOUT_PENDING.lock().unwrap().insert(label.clone()) {
+        return;
+    }
+    let window = window.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(FRAME);
+        RELAYOUT_PENDING.lock().unwrap().remove(&label);
+        // AppKit views may only be touched from the main thread
+        let target = window.clone();
+        let _ = window.run_on_main_thread(move || {
+            if let Err(e) = relayout(&target) {
+                eprintln!("Failed to follow window resize: {}", e);
+            }
+        });
+    });
+}
+
+fn relayout(window: &Window) -> Result<(), String> {
+    let size = window_size(window)?;
+    let scale = window
+        .scale_factor()
+        .map_err(|e| format!("Failed to get scale factor: {}", e))?;
+    for instance in window.state::<Players>().all() {
+        let mut player = instance.player.lock().unwrap();
+        if video_window::current(&player) != window.label() {
+            continue;
+        }
+        let Some(layout) = &player.video_layout else {
+            continue;
+        };
+
+        let area = layout.area_for(size);
+        apply_area(&instance, &player, &area, size, scale);
+        player.video_area = Some(area);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn area(x: i32, y: i32, width: i32, height: i32) -> VideoArea {
+        VideoArea {
+            x,
+            y,
+            width,
+            height,
+            units: AreaUnits::Logical,
+        }
+    }
+
+    fn bounds(area: &VideoArea) -> (i32, i32, i32, i32) {
+        (area.x, area.y, area.width, area.height)
+    }
+
+    #[test]
+    fn areas_are_trimmed_to_the_window() {
+        let size = (800.0, 600.0);
+        let inside = clamp_to_window(&area(10, 20, 300, 200), size).unwrap();
+        assert_eq!(bounds(&inside), (10, 20, 300, 200));
+        let over = clamp_to_window(&area(-50, 500, 1000, 300), size).unwrap();
+        assert_eq!(bounds(&over), (0, 500, 800, 100));
+
+        assert!(clamp_to_window(&area(0, 0, 0, 10), size).is_err());
+        assert!(clamp_to_window(&area(900, 0, 10, 10), size).is_err());
+    }
+
+    #[test]
+    fn extreme_areas_do_not_overflow() {
+        let size = (800.0, 600.0);
+        let huge = clamp_to_window(&area(i32::MAX, i32::MAX, i32::MAX, i32::MAX), size);
+        assert!(huge.is_err());
+        let everything = clamp_to_window(&area(i32::MIN, i32::MIN, i32::MAX, i32::MAX), size);
+        assert!(everything.is_err());
+        let wide = clamp_to_window(&area(100, 100, i32::MAX, i32::MAX), size).unwrap();
+        assert_eq!(bounds(&wide), (100, 100, 700, 500));
+    }
+}
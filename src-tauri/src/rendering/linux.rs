@@ -0,0 +1,237 @@
+// Video through mpv's render API, for when mpv isn't handed a window of its own: there
+// is no `wid` on Wayland, and on X11 a child window draws over the webview, hiding the
+// controls laid on top of the video. mpv renders into a GtkGLArea placed under the
+// webview in a GtkOverlay, GTK composites the two, and the page leaves the video area
+// transparent. As the GL area covers the whole webview, the video is placed with
+// margins rather than by moving a view. Everything here runs on the main thread, which
+// owns the GTK widgets.
+
+use std::cell::RefCell;
+use std::os::raw::{c_char, c_void};
+use std::rc::Rc;
+use std::sync::OnceLock;
+
+use gtk::prelude::*;
+use gtk::{gdk, glib};
+use tauri::window::Color;
+use tauri::Window;
+
+use crate::mpv::{GetProcAddress, GlDisplay, MpvClient, RenderContext};
+use crate::player::PlayerId;
+
+const GL_FRAMEBUFFER_BINDING: u32 = 0x8CA6;
+
+type GetIntegerv = unsafe extern "C" fn(name: u32, data: *mut i32);
+
+#[link(name = "EGL")]
+extern "C" {
+    fn eglGetProcAddress(name: *const c_char) -> *mut c_void;
+}
+
+#[link(name = "GL")]
+extern "C" {
+    fn glXGetProcAddressARB(name: *const c_char) -> *mut c_void;
+}
+
+extern "C" {
+    // From libgdk-3, which GTK already links
+    fn gdk_wayland_display_get_wl_display(display: *mut c_void) -> *mut c_void;
+    fn gdk_x11_display_get_xdisplay(display: *mut c_void) -> *mut c_void;
+}
+
+unsafe extern "C" fn egl_proc_address(name: *const c_char) -> *mut c_void {
+    eglGetProcAddress(name)
+}
+
+unsafe extern "C" fn glx_proc_address(name: *const c_char) -> *mut c_void {
+    glXGetProcAddressARB(name)
+}
+
+// GTK 3 draws through EGL on Wayland and GLX on X11
+fn get_proc_address() -> GetProcAddress {
+    if is_wayland() {
+        egl_proc_address
+    } else {
+        glx_proc_address
+    }
+}
+
+fn gl_display() -> Option<GlDisplay> {
+    let display = gdk::Display::default()?;
+    let wayland = is_wayland();
+    let raw = unsafe {
+        if wayland {
+            gdk_wayland_display_get_wl_display(display.as_ptr() as _)
+        } else {
+            gdk_x11_display_get_xdisplay(display.as_ptr() as _)
+        }
+    };
+    if raw.is_null() {
+        None
+    } else if wayland {
+        Some(GlDisplay::Wayland(raw))
+    } else {
+        Some(GlDisplay::X11(raw))
+    }
+}
+
+// The framebuffer GTK bound for the area; it isn't always 0
+fn current_framebuffer() -> i32 {
+    static GET_INTEGERV: OnceLock<Option<GetIntegerv>> = OnceLock::new();
+    let get_integerv = GET_INTEGERV.get_or_init(|| unsafe {
+        let function = get_proc_address()(c"glGetIntegerv".as_ptr());
+        (!function.is_null()).then(|| std::mem::transmute::<*mut c_void, GetIntegerv>(function))
+    });
+    let mut fbo = 0;
+    if let Some(get_integerv) = get_integerv {
+        unsafe { get_integerv(GL_FRAMEBUFFER_BINDING, &mut fbo) };
+    }
+    fbo
+}
+
+struct GlView {
+    // The player rendering into the area; only it may hide or take it
+    owner: PlayerId,
+    vbox: gtk::Box,
+    overlay: gtk::Overlay,
+    area: gtk::GLArea,
+    webview: gtk::Widget,
+}
+
+thread_local! {
+    static VIEW: RefCell<Option<GlView>> = const { RefCell::new(None) };
+}
+
+fn is_wayland() -> bool {
+    gdk::Display::default().is_some_and(|display| display.type_().name() == "GdkWaylandDisplay")
+}
+
+// Put a GL area under the window's webview and have player `owner`'s mpv render into it
+// through `client`. Does nothing if this player's is already there, and fails if another
+// player's is, as a window has room for one.
+pub fn attach(window: &Window, owner: PlayerId, client: MpvClient) -> Result<(), String> {
+    let current = VIEW.with(|view| view.borrow().as_ref().map(|view| view.owner));
+    match current {
+        Some(id) if id == owner => return Ok(()),
+        Some(_) => return Err("Another player is already rendering into the window".into()),
+        None => {}
+    }
+    let vbox = window
+        .default_vbox()
+        .map_err(|e| format!("Failed to get window contents: {}", e))?;
+    let webview = vbox
+        .children()
+        .into_iter()
+        .find(|child| child.type_().name() == "WebKitWebView")
+        .ok_or("No webview in the window")?;
+
+    let area = gtk::GLArea::new();
+    area.set_auto_render(false);
+    let render: Rc<RefCell<Option<RenderContext>>> = Rc::default();
+
+    // The GL context only exists once the area is realized, which happens once
+    let context = render.clone();
+    let client = RefCell::new(Some(client));
+    area.connect_realize(move |area| {
+        area.make_current();
+        if let Some(e) = area.error() {
+            eprintln!("No OpenGL context for video: {}", e);
+            return;
+        }
+        let Some(client) = client.borrow_mut().take() else {
+            return;
+        };
+        match RenderContext::opengl(client, get_proc_address(), gl_display()) {
+            Ok(mut renderer) => {
+                let area: glib::SendWeakRef<gtk::GLArea> = area.downgrade().into();
+                renderer.set_update_callback(move || {
+                    let area = area.clone();
+                    glib::idle_add_once(move || {
+                        if let Some(area) = area.upgrade() {
+                            area.queue_render();
+                        }
+                    });
+                });
+                *context.borrow_mut() = Some(renderer);
+            }
+            Err(e) => eprintln!("Failed to create mpv render context: {}", e),
+        }
+    });
+
+    let context = render.clone();
+    area.connect_render(move |area, _| {
+        if let Some(renderer) = context.borrow().as_ref() {
+            renderer.update();
+            let scale = area.scale_factor();
+            let (width, height) = (
+                area.allocated_width() * scale,
+                area.allocated_height() * scale,
+            );
+            if let Err(e) = renderer.render_opengl(current_framebuffer(), width, height, true) {
+                eprintln!("Failed to render video: {}", e);
+            }
+        }
+        glib::Propagation::Stop
+    });
+
+    // The render context uses the GL context, so it has to go while that still exists
+    area.connect_unrealize(move |area| {
+        area.make_current();
+        render.borrow_mut().take();
+    });
+
+    // Move the webview into an overlay above the video, and let the video show through
+    // where the page draws nothing. Holding `webview` keeps it alive while it moves.
+    for page in window.webviews() {
+        if let Err(e) = page.set_background_color(Some(Color(0, 0, 0, 0))) {
+            eprintln!("Failed to make the webview transparent: {}", e);
+        }
+    }
+    let overlay = gtk::Overlay::new();
+    vbox.remove(&webview);
+    overlay.add(&area);
+    overlay.add_overlay(&webview);
+    vbox.pack_start(&overlay, true, true, 0);
+    overlay.show_all();
+
+    VIEW.with(|view| {
+        *view.borrow_mut() = Some(GlView {
+            owner,
+            vbox,
+            overlay,
+            area,
+            webview,
+        })
+    });
+    Ok(())
+}
+
+// False if this player has no GL view to hide
+pub fn set_hidden(owner: PlayerId, hidden: bool) -> bool {
+    VIEW.with(|view| match view.borrow().as_ref() {
+        Some(view) if view.owner == owner => {
+            view.area.set_visible(!hidden);
+            true
+        }
+        _ => false,
+    })
+}
+
+// Put the webview back where it was and free the render context, which has to happen
+// before the mpv instance is destroyed
+pub fn detach(owner: PlayerId) {
+    let view = VIEW.with(|view| {
+        let mut view = view.borrow_mut();
+        match view.as_ref() {
+            Some(current) if current.owner == owner => view.take(),
+            _ => None,
+        }
+    });
+    let Some(view) = view else {
+        return;
+    };
+    view.overlay.remove(&view.webview);
+    // Taking the overlay out of the window unrealizes the area, freeing the render context
+    view.vbox.remove(&view.overlay);
+    view.vbox.pack_start(&view.webview, true, true, 0);
+}
@@ -0,0 +1,96 @@
+// The NSView mpv renders into, kept as a subview of the window's content view
+
+use objc::runtime::{Object, BOOL, NO, YES};
+use objc::{class, msg_send, sel, sel_impl};
+use std::os::raw::c_void;
+
+use super::VideoArea;
+
+#[allow(non_camel_case_types)]
+type id = *mut Object;
+const NIL: id = std::ptr::null_mut();
+
+// NSWindowOrderingMode
+const NS_WINDOW_ABOVE: isize = 1;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NSPoint {
+    x: f64,
+    y: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NSSize {
+    width: f64,
+    height: f64,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct NSRect {
+    origin: NSPoint,
+    size: NSSize,
+}
+
+// AppKit measures from the bottom-left unless the view is flipped; VideoArea is top-left
+unsafe fn frame_in(parent: id, area: &VideoArea) -> NSRect {
+    let bounds: NSRect = msg_send![parent, bounds];
+    let flipped: BOOL = msg_send![parent, isFlipped];
+    let y = if flipped == YES {
+        area.y as f64
+    } else {
+        bounds.size.height - area.y as f64 - area.height as f64
+    };
+    NSRect {
+        origin: NSPoint {
+            x: area.x as f64,
+            y,
+        },
+        size: NSSize {
+            width: area.width as f64,
+            height: area.height as f64,
+        },
+    }
+}
+
+// Add a video view above the webview, covering `area` of `content_view`. The returned
+// view is retained by us as well as its superview; hand it back to `destroy`.
+// Must run on the main thread, like all AppKit calls here.
+pub unsafe fn create(content_view: *mut c_void, area: &VideoArea) -> Result<*mut c_void, String> {
+    let parent = content_view as id;
+    let view: id = msg_send![class!(NSView), alloc];
+    let view: id = msg_send![view, initWithFrame: frame_in(parent, area)];
+    if view == NIL {
+        return Err("Failed to create video view".to_string());
+    }
+
+    // mpv's macOS backend attaches a CAMetalLayer/CAOpenGLLayer to the view
+    let _: () = msg_send![view, setWantsLayer: YES];
+    // We position it ourselves on every layout change
+    let _: () = msg_send![view, setAutoresizingMask: 0usize];
+    let _: () = msg_send![parent, addSubview: view positioned: NS_WINDOW_ABOVE relativeTo: NIL];
+    Ok(view as *mut c_void)
+}
+
+pub unsafe fn set_frame(view: *mut c_void, area: &VideoArea) {
+    let view = view as id;
+    let parent: id = msg_send![view, superview];
+    if parent == NIL {
+        return;
+    }
+    let _: () = msg_send![view, setFrame: frame_in(parent, area)];
+}
+
+// Hiding the view stops it compositing over the webview; mpv keeps playing regardless
+pub unsafe fn set_hidden(view: *mut c_void, hidden: bool) {
+    let _: () = msg_send![view as id, setHidden: if hidden { YES } else { NO }];
+}
+
+// Detach the view and drop our reference; mpv must no longer be rendering into it
+pub unsafe fn destroy(view: *mut c_void) {
+    let view = view as id;
+    let _: () = msg_send![view, removeFromSuperview];
+    let _: () = msg_send![view, release];
+}
@@ -0,0 +1,378 @@
+// Getting mpv's video onto the screen: embedded native views, the Linux GL surface,
+// or frames streamed to the page
+
+use serde::{Deserialize, Serialize};
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::cell::RefCell;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::collections::HashMap;
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use std::os::raw::c_void;
+use std::sync::Arc;
+use tauri::{Manager, Window};
+
+use crate::commands::on_main_thread;
+use crate::config;
+use crate::error::{Context, PlayerError};
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use crate::mpv::{MpvHandle, PlayerBackend};
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+use crate::player::PlayerId;
+use crate::player::PlayerInstance;
+
+pub mod frame_stream;
+#[cfg(target_os = "linux")]
+#[path = "linux.rs"]
+pub mod gl_view;
+pub mod layout;
+#[cfg(target_os = "macos")]
+#[path = "macos.rs"]
+pub mod native_view;
+#[cfg(target_os = "windows")]
+#[path = "windows.rs"]
+pub mod native_view;
+pub mod video_window;
+
+// Which pixels a VideoArea is measured in
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum AreaUnits {
+    // CSS pixels, as getBoundingClientRect reports them
+    #[default]
+    Logical,
+    // Device pixels, i.e. logical pixels times the window's scale factor
+    Physical,
+}
+
+// Video area coordinates from frontend
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct VideoArea {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+    #[serde(default)]
+    pub(crate) units: AreaUnits,
+}
+
+impl VideoArea {
+    // The same rectangle in logical pixels for a window at `scale`
+    pub(crate) fn to_logical(&self, scale: f64) -> VideoArea {
+        match self.units {
+            AreaUnits::Logical => self.clone(),
+            AreaUnits::Physical => VideoArea {
+                x: (self.x as f64 / scale).round() as i32,
+                y: (self.y as f64 / scale).round() as i32,
+                width: (self.width as f64 / scale).round() as i32,
+                height: (self.height as f64 / scale).round() as i32,
+                units: AreaUnits::Logical,
+            },
+        }
+    }
+}
+
+// The NSView or child HWND mpv renders into
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct NativeView(pub(crate) *mut c_void);
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+impl NativeView {
+    // Remove the view from its window. Main thread only.
+    pub(crate) unsafe fn destroy(self) {
+        native_view::destroy(self.0)
+    }
+}
+
+// Views may only be touched on the main thread, so that is where they're kept
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+thread_local! {
+    // The view each player's mpv renders into
+    static NATIVE_VIEWS: RefCell<HashMap<PlayerId, NativeView>> = RefCell::default();
+    // Views a player's video was moved out of, until mpv has let go of them
+    static RETIRED_VIEWS: RefCell<Vec<(PlayerId, NativeView)>> = RefCell::default();
+}
+
+// The view player `id` renders into, on the main thread
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub(crate) fn native_view(id: PlayerId) -> Option<NativeView> {
+    NATIVE_VIEWS.with_borrow(|views| views.get(&id).copied())
+}
+
+// Set `id`'s view aside while mpv may still be drawing into it, so setting up rendering
+// again makes a new one
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub(crate) fn retire_native_view(id: PlayerId) {
+    if let Some(view) = NATIVE_VIEWS.with_borrow_mut(|views| views.remove(&id)) {
+        RETIRED_VIEWS.with_borrow_mut(|retired| retired.push((id, view)));
+    }
+}
+
+// Destroy the views retire_native_view set aside for `id`
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub(crate) fn destroy_retired_views(id: PlayerId) {
+    let views = RETIRED_VIEWS.with_borrow_mut(|retired| {
+        let (views, kept) = retired.drain(..).partition(|(owner, _)| *owner == id);
+        *retired = kept;
+        views
+    });
+    for (_, view) in views {
+        unsafe { view.destroy() };
+    }
+}
+
+// Destroy all of `id`'s views, once its mpv instance is gone
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+pub(crate) fn destroy_native_views(id: PlayerId) {
+    retire_native_view(id);
+    destroy_retired_views(id);
+}
+
+// Native rendering setup: mpv renders into a child view (macOS) or child window
+// (Windows) covering `video_area`. Returns the child's window ID for mpv, or None if
+// the player already had one, which is moved instead. On the main thread;
+// attach_native_view then points mpv at it.
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn setup_native_view(
+    window: &Window,
+    id: PlayerId,
+    video_area: &VideoArea,
+) -> Result<Option<i64>, PlayerError> {
+    use raw_window_handle::{HasWindowHandle, RawWindowHandle};
+
+    if let Some(view) = native_view(id) {
+        unsafe { native_view::set_frame(view.0, video_area) };
+        return Ok(None);
+    }
+
+    let view = unsafe {
+        // Get the raw window handle from Tauri
+        let raw_handle = window
+            .window_handle()
+            .map_err(|e| PlayerError::Other(format!("Failed to get window handle: {}", e)))?
+            .as_raw();
+
+        let parent: *mut c_void = match raw_handle {
+            #[cfg(target_os = "macos")]
+            RawWindowHandle::AppKit(appkit_handle) => appkit_handle.ns_view.as_ptr(),
+            #[cfg(target_os = "windows")]
+            RawWindowHandle::Win32(win32_handle) => win32_handle.hwnd.get() as *mut c_void,
+            _ => {
+                return Err(PlayerError::UnsupportedPlatform(
+                    "Unsupported window handle type".to_string(),
+                ))
+            }
+        };
+        NativeView(native_view::create(parent, video_area)?)
+    };
+    NATIVE_VIEWS.with_borrow_mut(|views| views.insert(id, view));
+    Ok(Some(view.0 as i64))
+}
+
+// Have mpv render into the view `view_id`, on the event thread
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+fn attach_native_view(mpv_handle: &MpvHandle, view_id: i64) -> Result<(), PlayerError> {
+    // Set the window ID for MPV to render into
+    mpv_handle
+        .set("wid", view_id)
+        .map_err(|e| PlayerError::Mpv {
+            action: Some("Failed to set window ID".to_string()),
+            error: e,
+        })?;
+
+    // Force MPV to use the embedded view
+    if let Err(e) = mpv_handle.set("force-window", "yes") {
+        eprintln!("Failed to set force-window: {}", e);
+    }
+
+    println!("MPV configured to render into view: {}", view_id);
+    Ok(())
+}
+
+// Have mpv draw into the window itself with `backend`, returning the success message.
+// Views are made on the main thread, and mpv is pointed at them on the event thread.
+async fn setup_embedded_video(
+    backend: config::VideoBackend,
+    window: &Window,
+    instance: &Arc<PlayerInstance>,
+    video_area: &VideoArea,
+    size: (f64, f64),
+    scale: f64,
+) -> Result<String, PlayerError> {
+    let app = window.app_handle();
+    let id = instance.id;
+    match backend {
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        config::VideoBackend::Native => {
+            let (target, area) = (window.clone(), video_area.clone());
+            let created =
+                on_main_thread(app, move || setup_native_view(&target, id, &area)).await?;
+            if let Some(view_id) = created {
+                let attached = instance
+                    .run(move |_, handle| attach_native_view(handle, view_id))
+                    .await;
+                if let Err(e) = attached {
+                    on_main_thread(app, move || {
+                        destroy_native_views(id);
+                        Ok(())
+                    })
+                    .await?;
+                    return Err(e);
+                }
+            }
+
+            let mut player = instance.player.lock().unwrap();
+            player.video_layout = Some(layout::VideoLayout::new(
+                video_area.clone(),
+                size,
+                layout::LayoutRule::default(),
+            ));
+            let _ = scale;
+            Ok(format!(
+                "✅ Video will render in app (no popup) at {}x{}",
+                video_area.width, video_area.height
+            ))
+        }
+
+        #[cfg(target_os = "linux")]
+        config::VideoBackend::OpenGl => {
+            let hidden = !instance.player.lock().unwrap().video_layer_visible;
+            // The render context gets a client of mpv's of its own, for the main thread
+            let client = instance
+                .run(|_, handle| {
+                    handle
+                        .create_client()
+                        .context("Failed to create an mpv client")
+                })
+                .await?;
+            let target = window.clone();
+            on_main_thread(app, move || {
+                gl_view::attach(&target, id, client)
+                    .map_err(|e| PlayerError::Other(format!("Failed to setup rendering: {}", e)))?;
+                if hidden {
+                    gl_view::set_hidden(id, true);
+                }
+                Ok(())
+            })
+            .await?;
+
+            let mut player = instance.player.lock().unwrap();
+            layout::apply_area(instance, &player, video_area, size, scale);
+            player.video_layout = Some(layout::VideoLayout::new(
+                video_area.clone(),
+                size,
+                layout::LayoutRule::default(),
+            ));
+            Ok(format!(
+                "✅ Video will render in app through the mpv render API at {}x{}",
+                video_area.width, video_area.height
+            ))
+        }
+
+        _ => {
+            let _ = (app, id, instance, size, scale);
+            Err(PlayerError::UnsupportedPlatform(
+                "Video rendering not yet implemented for this platform".to_string(),
+            ))
+        }
+    }
+}
+
+// The backend `setting` means on this platform, or why it can't be used here. Linux
+// has no native embedding yet, and the GL view is built on GTK so it is Linux only.
+pub(crate) fn resolve_video_backend(
+    setting: config::VideoBackend,
+) -> Result<config::VideoBackend, PlayerError> {
+    use config::VideoBackend;
+
+    let native = cfg!(any(target_os = "macos", target_os = "windows"));
+    let opengl = cfg!(target_os = "linux");
+    match setting {
+        VideoBackend::Auto if opengl => Ok(VideoBackend::OpenGl),
+        VideoBackend::Auto => Ok(VideoBackend::Native),
+        VideoBackend::Native if !native => Err(PlayerError::UnsupportedPlatform(
+            "Native video embedding isn't available on this platform".to_string(),
+        )),
+        VideoBackend::OpenGl if !opengl => Err(PlayerError::UnsupportedPlatform(
+            "OpenGL video rendering isn't available on this platform yet".to_string(),
+        )),
+        backend => Ok(backend),
+    }
+}
+
+// Result of placing the video; `area` is what was actually applied after clamping
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoPlacement {
+    pub(crate) message: String,
+    pub(crate) area: VideoArea,
+    // With software, the page draws the frames from mpvframe:// itself
+    pub(crate) backend: config::VideoBackend,
+}
+
+// Have mpv draw into `window` at `video_area`: embedded with the configured backend if it
+// can be, and streamed to the page if not
+pub(crate) async fn place_video(
+    window: &Window,
+    instance: &Arc<PlayerInstance>,
+    video_area: VideoArea,
+    size: (f64, f64),
+    scale: f64,
+) -> Result<VideoPlacement, PlayerError> {
+    let (setting, streaming) = {
+        let player = instance.player.lock().unwrap();
+        (
+            player.config.values.video_backend,
+            player.frame_stream.is_some(),
+        )
+    };
+    let backend = resolve_video_backend(setting)?;
+    // Once frames are streamed, a repeated setup only moves the area
+    if backend != config::VideoBackend::Software && !streaming {
+        match setup_embedded_video(backend, window, instance, &video_area, size, scale).await {
+            Ok(message) => {
+                instance.player.lock().unwrap().video_backend = Some(backend);
+                return Ok(VideoPlacement {
+                    message,
+                    area: video_area,
+                    backend,
+                });
+            }
+            Err(e) => eprintln!("{}; streaming frames to the page instead", e),
+        }
+    }
+
+    let (area, frames) = (video_area.clone(), instance.frames.clone());
+    instance
+        .run(move |player, handle| {
+            if player.frame_stream.is_none() {
+                // Windows starts mpv with vo=gpu for its own window
+                #[cfg(target_os = "windows")]
+                if let Err(e) = handle.set("vo", "libmpv") {
+                    eprintln!("Failed to switch to vo=libmpv: {}", e);
+                }
+                let client = handle
+                    .create_client()
+                    .context("Failed to create an mpv client")?;
+                player.frame_stream = Some(frame_stream::FrameStream::start(client, frames)?);
+            }
+            player.video_layout = Some(layout::VideoLayout::new(
+                area.clone(),
+                size,
+                layout::LayoutRule::default(),
+            ));
+            if let Some(stream) = &player.frame_stream {
+                layout::size_stream(stream, &area, scale);
+            }
+            player.video_backend = Some(config::VideoBackend::Software);
+            Ok(())
+        })
+        .await?;
+    Ok(VideoPlacement {
+        message: format!(
+            "✅ Video will stream to the page at {}x{}",
+            video_area.width, video_area.height
+        ),
+        area: video_area,
+        backend: config::VideoBackend::Software,
+    })
+}
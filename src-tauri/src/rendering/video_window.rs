@@ -0,0 +1,207 @@
+// The video popped out into a window of its own, so it can sit on one monitor while the
+// controls stay on another. mpv keeps playing throughout: what it draws into is released
+// in one window and set up again in the other, and closing the pop-out brings the video
+// back to where the main page last put it.
+
+use std::sync::Arc;
+use tauri::{Emitter, Manager, WebviewUrl, WebviewWindowBuilder, Window};
+
+use super::{layout, place_video, VideoArea, VideoPlacement};
+use crate::commands::on_main_thread;
+use crate::error::PlayerError;
+use crate::mpv::PlayerBackend;
+use crate::player::{self, MpvPlayer, PlayerId, PlayerInstance};
+
+pub const MAIN_WINDOW: &str = "main";
+const LABEL_PREFIX: &str = "video-";
+// For when the video has no area yet to take the size from
+const DEFAULT_SIZE: (f64, f64) = (960.0, 540.0);
+const MIN_SIZE: (f64, f64) = (320.0, 180.0);
+
+pub fn label(id: PlayerId) -> String {
+    format!("{}{}", LABEL_PREFIX, id)
+}
+
+// The player whose pop-out window `label` is, if it is one
+pub fn player_for(label: &str) -> Option<PlayerId> {
+    label.strip_prefix(LABEL_PREFIX)?.parse().ok()
+}
+
+// The window `player`'s video is in
+pub fn current(player: &MpvPlayer) -> &str {
+    player.video_window.as_deref().unwrap_or(MAIN_WINDOW)
+}
+
+// Open the pop-out window, sized like the area the video leaves. It loads the app like
+// any other window; the page sees from its label that it only shows the video.
+pub fn open(app: &tauri::AppHandle, instance: &PlayerInstance) -> Result<Window, PlayerError> {
+    let label = label(instance.id);
+    if app.get_webview_window(&label).is_some() {
+        return Err(PlayerError::Other(
+            "The video is already in its own window".to_string(),
+        ));
+    }
+    let (width, height) = {
+        let player = instance.player.lock().unwrap();
+        if !player.running() {
+            return Err(PlayerError::NotInitialized);
+        }
+        if player.video_layout.is_none() {
+            return Err(PlayerError::RenderingNotSetUp);
+        }
+        player
+            .video_area
+            .as_ref()
+            .map_or(DEFAULT_SIZE, |area| (area.width as f64, area.height as f64))
+    };
+
+    let window = WebviewWindowBuilder::new(app, &label, WebviewUrl::default())
+        .title("Video")
+        .inner_size(width.max(MIN_SIZE.0), height.max(MIN_SIZE.1))
+        .min_inner_size(MIN_SIZE.0, MIN_SIZE.1)
+        .build()
+        .map_err(|e| PlayerError::Other(format!("Failed to open the video window: {}", e)))?;
+    Ok(window.as_ref().window())
+}
+
+// Move the video into the pop-out `window`, filling it
+pub async fn detach(
+    window: &Window,
+    instance: &Arc<PlayerInstance>,
+) -> Result<VideoPlacement, PlayerError> {
+    let Some(docked) = instance.player.lock().unwrap().video_layout.clone() else {
+        return Err(PlayerError::RenderingNotSetUp);
+    };
+    let size = layout::window_size(window)?;
+    // A control bar of no height: the video fills the window as it resizes
+    let rule = layout::LayoutRule::ControlBar { height: 0.0 };
+    let filling = layout::VideoLayout::new(VideoArea::default(), size, rule);
+    let placement = move_video(window, instance, filling, size).await?;
+    let mut player = instance.player.lock().unwrap();
+    player.docked_layout = Some(docked);
+    player.video_window = Some(window.label().to_string());
+    Ok(placement)
+}
+
+// Bring the video back to the main window and close the pop-out, which goes even if
+// docking fails
+pub async fn close(
+    app: &tauri::AppHandle,
+    instance: &Arc<PlayerInstance>,
+) -> Result<VideoPlacement, PlayerError> {
+    let docked = match app.get_webview_window(MAIN_WINDOW) {
+        Some(main) => dock(&main.as_ref().window(), instance).await,
+        None => Err(PlayerError::NotFound(
+            "No main window to dock the video in".to_string(),
+        )),
+    };
+    if let Some(window) = app.get_webview_window(&label(instance.id)) {
+        if let Err(e) = window.destroy() {
+            eprintln!("Failed to close the video window: {}", e);
+        }
+    }
+    docked
+}
+
+// Put the video back at the area the main page last asked for
+async fn dock(
+    main: &Window,
+    instance: &Arc<PlayerInstance>,
+) -> Result<VideoPlacement, PlayerError> {
+    let docked = {
+        let mut player = instance.player.lock().unwrap();
+        if player.video_window.is_none() {
+            return Err(PlayerError::Other(
+                "The video isn't in its own window".to_string(),
+            ));
+        }
+        let Some(docked) = player.docked_layout.take() else {
+            return Err(PlayerError::RenderingNotSetUp);
+        };
+        player.video_window = None;
+        docked
+    };
+    let size = layout::window_size(main)?;
+    let placement = move_video(main, instance, docked, size).await?;
+    // The pop-out can also be closed from the window manager, not just by the page
+    let event = player::event_name(instance.id, "video-window://docked");
+    if let Err(e) = main.emit(&event, &placement) {
+        eprintln!("Failed to emit video window docked event: {}", e);
+    }
+    Ok(placement)
+}
+
+// Release what mpv draws into and set it up again in `window`, following `video_layout`.
+// Streamed frames aren't tied to a window, so for them only the area changes; an
+// embedded vo is switched off while its view changes under it.
+async fn move_video(
+    window: &Window,
+    instance: &Arc<PlayerInstance>,
+    video_layout: layout::VideoLayout,
+    size: (f64, f64),
+) -> Result<VideoPlacement, PlayerError> {
+    let scale = window
+        .scale_factor()
+        .map_err(|e| PlayerError::Other(format!("Failed to get scale factor: {}", e)))?;
+    let area = layout::clamp_to_window(&video_layout.area_for(size), size)?;
+
+    let vid = instance
+        .run(|player, handle| {
+            if player.frame_stream.is_some() {
+                return Ok(None);
+            }
+            let vid = handle.get::<String>("vid").ok();
+            if let Err(e) = handle.set("vid", "no") {
+                eprintln!("Failed to switch the video off while moving it: {}", e);
+            }
+            Ok(vid)
+        })
+        .await?;
+    // The old view goes once mpv draws into the new one
+    let (app, id) = (window.app_handle(), instance.id);
+    on_main_thread(app, move || {
+        #[cfg(any(target_os = "macos", target_os = "windows"))]
+        super::retire_native_view(id);
+        #[cfg(target_os = "linux")]
+        super::gl_view::detach(id);
+        Ok(())
+    })
+    .await?;
+
+    let placed = place_video(window, instance, area, size, scale).await;
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    on_main_thread(app, move || {
+        super::destroy_retired_views(id);
+        Ok(())
+    })
+    .await?;
+    if let Some(vid) = vid {
+        let restored = instance.channel.post(move |handle| {
+            if let Err(e) = handle.set("vid", &vid) {
+                eprintln!("Failed to switch the video back on: {}", e);
+            }
+        });
+        if let Err(e) = restored {
+            eprintln!("Failed to switch the video back on: {}", e);
+        }
+    }
+
+    // place_video lays the area out proportionally; keep the rule it's meant to follow
+    let placement = placed?;
+    let mut player = instance.player.lock().unwrap();
+    player.video_layout = Some(video_layout);
+    player.video_area = Some(placement.area.clone());
+    Ok(placement)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pop_out_windows_are_told_apart_by_label() {
+        assert_eq!(player_for(&label(3)), Some(3));
+        assert_eq!(player_for(MAIN_WINDOW), None);
+        assert_eq!(player_for("video-main"), None);
+    }
+}
@@ -0,0 +1,113 @@
+// The child window mpv renders into on Windows, kept above the WebView2 window.
+// Same interface as the macOS native_view; rendering/mod.rs picks one by target.
+
+use std::os::raw::c_void;
+
+use super::VideoArea;
+
+type Hwnd = *mut c_void;
+
+const WS_CHILD: u32 = 0x4000_0000;
+const WS_VISIBLE: u32 = 0x1000_0000;
+const WS_CLIPSIBLINGS: u32 = 0x0400_0000;
+const SWP_NOACTIVATE: u32 = 0x0010;
+const SW_HIDE: i32 = 0;
+const SW_SHOWNA: i32 = 8;
+const HWND_TOP: Hwnd = std::ptr::null_mut();
+const USER_DEFAULT_SCREEN_DPI: f64 = 96.0;
+
+#[link(name = "user32")]
+extern "system" {
+    #[allow(clippy::too_many_arguments)]
+    fn CreateWindowExW(
+        ex_style: u32,
+        class_name: *const u16,
+        window_name: *const u16,
+        style: u32,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        parent: Hwnd,
+        menu: *mut c_void,
+        instance: *mut c_void,
+        param: *mut c_void,
+    ) -> Hwnd;
+    fn SetWindowPos(
+        hwnd: Hwnd,
+        insert_after: Hwnd,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        flags: u32,
+    ) -> i32;
+    fn ShowWindow(hwnd: Hwnd, command: i32) -> i32;
+    fn DestroyWindow(hwnd: Hwnd) -> i32;
+    fn GetDpiForWindow(hwnd: Hwnd) -> u32;
+}
+
+fn wide(value: &str) -> Vec<u16> {
+    value.encode_utf16().chain(std::iter::once(0)).collect()
+}
+
+// VideoArea is in logical pixels; child windows are placed in device pixels
+unsafe fn physical(hwnd: Hwnd, area: &VideoArea) -> (i32, i32, i32, i32) {
+    let dpi = GetDpiForWindow(hwnd);
+    let scale = if dpi == 0 {
+        1.0
+    } else {
+        dpi as f64 / USER_DEFAULT_SCREEN_DPI
+    };
+    let scaled = |value: i32| (value as f64 * scale).round() as i32;
+    (
+        scaled(area.x),
+        scaled(area.y),
+        scaled(area.width),
+        scaled(area.height),
+    )
+}
+
+// Add a video window above the webview, covering `area` of `parent`'s client area.
+// mpv creates its own window inside this one when given it as `wid`.
+// Must run on the main thread, which owns the window and pumps its messages.
+pub unsafe fn create(parent: *mut c_void, area: &VideoArea) -> Result<*mut c_void, String> {
+    // A STATIC control needs no window class of our own, and passes clicks to the parent
+    let class = wide("STATIC");
+    let (x, y, width, height) = physical(parent, area);
+    let view = CreateWindowExW(
+        0,
+        class.as_ptr(),
+        std::ptr::null(),
+        WS_CHILD | WS_VISIBLE | WS_CLIPSIBLINGS,
+        x,
+        y,
+        width,
+        height,
+        parent,
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+        std::ptr::null_mut(),
+    );
+    if view.is_null() {
+        return Err("Failed to create video window".to_string());
+    }
+    // Sibling order decides what draws on top; the webview was created first
+    SetWindowPos(view, HWND_TOP, x, y, width, height, SWP_NOACTIVATE);
+    Ok(view)
+}
+
+pub unsafe fn set_frame(view: *mut c_void, area: &VideoArea) {
+    let (x, y, width, height) = physical(view, area);
+    SetWindowPos(view, HWND_TOP, x, y, width, height, SWP_NOACTIVATE);
+}
+
+// Hiding the window stops it covering the webview; mpv keeps playing regardless
+pub unsafe fn set_hidden(view: *mut c_void, hidden: bool) {
+    ShowWindow(view, if hidden { SW_HIDE } else { SW_SHOWNA });
+}
+
+// Destroy the window; mpv must no longer be rendering into it
+pub unsafe fn destroy(view: *mut c_void) {
+    DestroyWindow(view);
+}
@@ -0,0 +1,117 @@
+// Where playback of each file got to, so it can pick up there next time (see
+// LoadOptions::resume and the resume-available event). mpv's watch_later files do the
+// same, but the page can't read those.
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::file_settings::path_digest;
+
+const POSITIONS_DIR: &str = "positions";
+
+// Closer to the start than this isn't worth resuming from
+const MIN_POSITION: f64 = 10.0;
+
+// Past this share of the file, what's left is usually credits; count it as watched
+pub const WATCHED_FRACTION: f64 = 0.95;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedPosition {
+    pub path: String,
+    // Seconds into the file
+    pub position: f64,
+    pub duration: f64,
+    // Seconds since the Unix epoch
+    pub saved_at: u64,
+}
+
+// What to do with the position a file was left at
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Progress {
+    // Barely started; keep whatever was saved before
+    Started,
+    Resumable,
+    // Watched to the end, so next time starts from the beginning
+    Finished,
+}
+
+impl SavedPosition {
+    pub fn new(path: String, position: f64, duration: f64) -> Self {
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        Self {
+            path,
+            position,
+            duration,
+            saved_at,
+        }
+    }
+
+    pub fn progress(&self) -> Progress {
+        if self.position >= self.duration * WATCHED_FRACTION {
+            Progress::Finished
+        } else if self.position < MIN_POSITION {
+            Progress::Started
+        } else {
+            Progress::Resumable
+        }
+    }
+}
+
+fn position_file<R: Runtime>(app: &AppHandle<R>, path: &str) -> Option<PathBuf> {
+    let dir = app.path().app_data_dir().ok()?.join(POSITIONS_DIR);
+    Some(dir.join(format!("{}.json", path_digest(path))))
+}
+
+pub fn load<R: Runtime>(app: &AppHandle<R>, path: &str) -> Option<SavedPosition> {
+    let contents = std::fs::read_to_string(position_file(app, path)?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+// The position to start `path` from, if it was left somewhere worth resuming
+pub fn resume_from<R: Runtime>(app: &AppHandle<R>, path: &str) -> Option<f64> {
+    load(app, path)
+        .filter(|saved| saved.progress() == Progress::Resumable)
+        .map(|saved| saved.position)
+}
+
+pub fn save<R: Runtime>(app: &AppHandle<R>, saved: &SavedPosition) -> Result<(), String> {
+    let file = position_file(app, &saved.path).ok_or("No app data directory")?;
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create positions directory: {}", e))?;
+    }
+    let contents = serde_json::to_string_pretty(saved)
+        .map_err(|e| format!("Failed to serialize the position: {}", e))?;
+    std::fs::write(file, contents).map_err(|e| format!("Failed to write the position: {}", e))
+}
+
+// Returns whether anything was stored for `path`
+pub fn clear<R: Runtime>(app: &AppHandle<R>, path: &str) -> Result<bool, String> {
+    let file = position_file(app, path).ok_or("No app data directory")?;
+    match std::fs::remove_file(file) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!("Failed to clear the position: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_middle_of_a_file_is_resumable() {
+        let at = |position| SavedPosition::new("/videos/a.mkv".to_string(), position, 1200.0);
+        assert_eq!(at(3.0).progress(), Progress::Started);
+        assert_eq!(at(MIN_POSITION).progress(), Progress::Resumable);
+        assert_eq!(at(1100.0).progress(), Progress::Resumable);
+        assert_eq!(at(1150.0).progress(), Progress::Finished);
+        // A short clip is over before there's anything to resume
+        let clip = SavedPosition::new("/videos/clip.mp4".to_string(), 9.6, 10.0);
+        assert_eq!(clip.progress(), Progress::Finished);
+    }
+}
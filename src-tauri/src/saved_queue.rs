@@ -0,0 +1,107 @@
+// The main player's queue as of the last save, so it can be picked up again after the
+// app restarts (see AppConfig::restore_queue)
+
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::error::{Context, PlayerError};
+use crate::loadfile::LoadOptions;
+use crate::mpv::{MpvHandle, PlayerBackend};
+use crate::playlist;
+
+const QUEUE_FILE: &str = "queue.json";
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedQueue {
+    pub entries: Vec<String>,
+    // The entry that was playing, and how far into it
+    pub index: Option<usize>,
+    pub position: Option<f64>,
+}
+
+impl SavedQueue {
+    pub fn read(handle: &MpvHandle) -> Self {
+        let entries = (0..playlist::count(handle))
+            .filter_map(|i| {
+                handle
+                    .get::<String>(&format!("playlist/{}/filename", i))
+                    .ok()
+            })
+            .collect();
+        let index = handle
+            .get::<i64>("playlist-pos")
+            .ok()
+            .and_then(|i| usize::try_from(i).ok());
+        Self {
+            entries,
+            index,
+            position: index.and(handle.get::<f64>("time-pos").ok()),
+        }
+    }
+
+    // Replace the playlist with this one. The entry that was playing is loaded first, so
+    // it's the only one that starts at `position`, and the ones before it are moved in
+    // ahead of it.
+    pub fn restore(&self, handle: &MpvHandle) -> Result<(), PlayerError> {
+        let index = self.index.filter(|&i| i < self.entries.len()).unwrap_or(0);
+        let Some(current) = self.entries.get(index) else {
+            return Ok(());
+        };
+        let options = LoadOptions {
+            start: self.position,
+            ..LoadOptions::default()
+        }
+        .encode()?;
+        handle
+            .load(
+                current,
+                Some(&options).filter(|o| !o.is_empty()).map(String::as_str),
+            )
+            .context(format!("Failed to load {}", current))?;
+
+        for (i, path) in self.entries.iter().enumerate() {
+            if i == index {
+                continue;
+            }
+            handle
+                .command(&["loadfile", path, "append"])
+                .context(format!("Failed to queue {}", path))?;
+            if i < index {
+                let last = (playlist::count(handle) - 1).to_string();
+                handle
+                    .command(&["playlist-move", &last, &i.to_string()])
+                    .context(format!("Failed to queue {}", path))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn queue_file<R: Runtime>(app: &AppHandle<R>) -> Option<PathBuf> {
+    Some(app.path().app_data_dir().ok()?.join(QUEUE_FILE))
+}
+
+pub fn load<R: Runtime>(app: &AppHandle<R>) -> Option<SavedQueue> {
+    let contents = std::fs::read_to_string(queue_file(app)?).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+pub fn save<R: Runtime>(app: &AppHandle<R>, queue: &SavedQueue) -> Result<(), String> {
+    let file = queue_file(app).ok_or("No app data directory")?;
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create data directory: {}", e))?;
+    }
+    let contents = serde_json::to_string_pretty(queue)
+        .map_err(|e| format!("Failed to serialize the queue: {}", e))?;
+    std::fs::write(file, contents).map_err(|e| format!("Failed to write the queue: {}", e))
+}
+
+pub fn clear<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(file) = queue_file(app) {
+        // Nothing saved is fine too
+        let _ = std::fs::remove_file(file);
+    }
+}
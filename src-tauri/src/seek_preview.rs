@@ -0,0 +1,249 @@
+// Frames for the seekbar's hover preview. When a local video loads, ffmpeg pulls a
+// low-res frame every few seconds from its keyframes, in the background, into the app's
+// cache; get_preview_thumbnail then answers with the frame nearest the hovered time.
+// They're kept as separate small JPEGs rather than one sprite sheet, since the page
+// can't load files itself and a hover shouldn't send the whole sheet each time.
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter, Runtime};
+
+use crate::cover_art::data_url;
+use crate::file_settings::path_digest;
+
+pub const PREVIEWS_DIR: &str = "previews";
+
+// Enough for a smooth preview along a wide seekbar; longer files space them out more
+const MAX_FRAMES: f64 = 200.0;
+const MIN_INTERVAL: f64 = 2.0;
+
+const WIDTH: u32 = 160;
+
+// Written last, so a folder with it is complete
+const INDEX_FILE: &str = "index.json";
+
+// How often a running ffmpeg checks whether another file has been loaded since
+const CANCEL_POLL: Duration = Duration::from_millis(200);
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Index {
+    // Seconds between frames; frame n is at n * interval
+    interval: f64,
+    count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PreviewThumbnail {
+    // Where the frame is from, which can be up to half an interval off the time asked for
+    pub time: f64,
+    pub data_url: String,
+}
+
+// Payload of seek-preview://ready
+#[derive(Debug, Clone, Serialize)]
+struct PreviewsReady {
+    path: String,
+}
+
+pub struct SeekPreviews {
+    // None without a cache dir
+    dir: Option<PathBuf>,
+    // Bumped for each file loaded; a generation that sees it change gives up
+    generation: Arc<AtomicU64>,
+}
+
+impl SeekPreviews {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            generation: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    // Stops making previews for the previous file
+    pub fn cancel(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // Called by the event thread as a video loads; only ever works on the latest one
+    pub fn prepare<R: Runtime>(&self, app: &AppHandle<R>, path: String, duration: f64) {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let Some(dir) = self.dir.clone() else {
+            return;
+        };
+        if duration <= 0.0 {
+            return;
+        }
+        let app = app.clone();
+        let current = self.generation.clone();
+        std::thread::spawn(move || {
+            // A stream would have to be downloaded in full to read through it
+            let Some(frames) = file_cache_dir(&dir, Path::new(&path)) else {
+                return;
+            };
+            if frames.join(INDEX_FILE).exists() {
+                return;
+            }
+            let cancelled = || current.load(Ordering::SeqCst) != generation;
+            match generate(&frames, &path, duration, cancelled) {
+                Ok(true) => {
+                    if let Err(e) = app.emit("seek-preview://ready", PreviewsReady { path }) {
+                        eprintln!("Failed to emit seek-preview://ready: {}", e);
+                    }
+                }
+                Ok(false) => {}
+                Err(e) => eprintln!("No seek previews for {}: {}", path, e),
+            }
+        });
+    }
+
+    // None while they're still being made, or if the file can't have any
+    pub fn frame(&self, path: &str, time: f64) -> Result<Option<PreviewThumbnail>, String> {
+        let Some(frames) = self
+            .dir
+            .as_deref()
+            .and_then(|dir| file_cache_dir(dir, Path::new(path)))
+        else {
+            return Ok(None);
+        };
+        let Ok(contents) = std::fs::read_to_string(frames.join(INDEX_FILE)) else {
+            return Ok(None);
+        };
+        let index: Index = serde_json::from_str(&contents)
+            .map_err(|e| format!("Failed to read the seek preview index: {}", e))?;
+        let Some(n) = index.nearest(time) else {
+            return Ok(None);
+        };
+        let bytes = std::fs::read(frame_file(&frames, n))
+            .map_err(|e| format!("Failed to read the seek preview: {}", e))?;
+        Ok(Some(PreviewThumbnail {
+            time: n as f64 * index.interval,
+            data_url: data_url("image/jpeg", &bytes),
+        }))
+    }
+}
+
+impl Index {
+    fn nearest(&self, time: f64) -> Option<usize> {
+        let last = self.count.checked_sub(1)?;
+        Some(((time.max(0.0) / self.interval).round() as usize).min(last))
+    }
+}
+
+fn interval(duration: f64) -> f64 {
+    (duration / MAX_FRAMES).max(MIN_INTERVAL).ceil()
+}
+
+// A folder of `dir` for `path`, named after the file's path, size and modification
+// time so an edited file gets new frames; None if it isn't a local file. Chapter
+// thumbnails are kept the same way.
+pub fn file_cache_dir(dir: &Path, path: &Path) -> Option<PathBuf> {
+    let metadata = std::fs::metadata(path).ok().filter(|m| m.is_file())?;
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs());
+    let key = format!("{}\n{}\n{}", path.display(), metadata.len(), mtime);
+    Some(dir.join(path_digest(&key)))
+}
+
+// ffmpeg numbers its output from 1
+fn frame_file(frames: &Path, n: usize) -> PathBuf {
+    frames.join(format!("{:04}.jpg", n + 1))
+}
+
+// Returns false if it was cancelled
+fn generate(
+    frames: &Path,
+    path: &str,
+    duration: f64,
+    cancelled: impl Fn() -> bool,
+) -> Result<bool, String> {
+    let partial = frames.with_extension("part");
+    let _ = std::fs::remove_dir_all(&partial);
+    std::fs::create_dir_all(&partial)
+        .map_err(|e| format!("Failed to create the previews directory: {}", e))?;
+
+    let interval = interval(duration);
+    let mut child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostdin", "-loglevel", "error", "-y"])
+        // Decoding only keyframes is what makes this quick enough to do on every load
+        .args(["-skip_frame", "nokey"])
+        .args(["-i", path])
+        .args(["-an", "-sn", "-vsync", "vfr"])
+        .args([
+            "-vf",
+            &format!("fps=1/{},scale={}:-2", interval, WIDTH),
+            "-q:v",
+            "5",
+        ])
+        .arg(partial.join("%04d.jpg"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    let status = loop {
+        if cancelled() {
+            let _ = child.kill();
+            let _ = child.wait();
+            let _ = std::fs::remove_dir_all(&partial);
+            return Ok(false);
+        }
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => std::thread::sleep(CANCEL_POLL),
+            Err(e) => return Err(format!("ffmpeg did not finish: {}", e)),
+        }
+    };
+
+    let count = (0..)
+        .take_while(|&n| frame_file(&partial, n).exists())
+        .count();
+    if !status.success() || count == 0 {
+        let _ = std::fs::remove_dir_all(&partial);
+        return Err("ffmpeg failed".to_string());
+    }
+    let index = serde_json::to_string(&Index { interval, count })
+        .map_err(|e| format!("Failed to serialize the seek preview index: {}", e))?;
+    std::fs::write(partial.join(INDEX_FILE), index)
+        .map_err(|e| format!("Failed to write the seek preview index: {}", e))?;
+    let _ = std::fs::remove_dir_all(frames);
+    std::fs::rename(&partial, frames)
+        .map_err(|e| format!("Failed to save seek previews: {}", e))?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hovered_times_map_to_the_nearest_frame() {
+        // Short files get a frame every MIN_INTERVAL, long ones at most MAX_FRAMES
+        assert_eq!(interval(90.0), MIN_INTERVAL);
+        assert_eq!(interval(7200.0), 36.0);
+
+        let index = Index {
+            interval: 10.0,
+            count: 5,
+        };
+        assert_eq!(index.nearest(0.0), Some(0));
+        assert_eq!(index.nearest(14.9), Some(1));
+        assert_eq!(index.nearest(15.0), Some(2));
+        assert_eq!(index.nearest(-3.0), Some(0));
+        // Past the last frame (the file's tail end) stays on it
+        assert_eq!(index.nearest(900.0), Some(4));
+        let empty = Index {
+            interval: 10.0,
+            count: 0,
+        };
+        assert_eq!(empty.nearest(5.0), None);
+    }
+}
@@ -0,0 +1,56 @@
+// Audio passthrough (bitstreaming): AC3, DTS and TrueHD tracks go to the receiver still
+// encoded, through audio-spdif, so it decodes the surround itself rather than getting
+// mpv's PCM. mpv reopens the decoder when the list changes, so it applies to the track
+// that's playing; codecs not in the list, and everything with passthrough off, are
+// decoded as usual.
+
+use crate::config::AudioPassthrough;
+use crate::error::{Context, PlayerError};
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// audio-spdif's value: the codecs to pass through, or none
+fn codecs(settings: &AudioPassthrough) -> String {
+    if !settings.enabled {
+        return String::new();
+    }
+    [
+        (settings.ac3, "ac3"),
+        (settings.eac3, "eac3"),
+        (settings.dts, "dts"),
+        (settings.dts_hd, "dts-hd"),
+        (settings.truehd, "truehd"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, codec)| enabled.then_some(codec))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+pub fn apply(handle: &MpvHandle, settings: &AudioPassthrough) -> Result<(), PlayerError> {
+    handle
+        .set("audio-spdif", &codecs(settings))
+        .context("Failed to set audio passthrough")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_enabled_codecs_are_passed_through() {
+        let defaults = AudioPassthrough::default();
+        assert_eq!(codecs(&defaults), "");
+        let enabled = AudioPassthrough {
+            enabled: true,
+            ..defaults
+        };
+        assert_eq!(codecs(&enabled), "ac3,dts");
+        let hdmi = AudioPassthrough {
+            eac3: true,
+            dts_hd: true,
+            truehd: true,
+            ..enabled
+        };
+        assert_eq!(codecs(&hdmi), "ac3,eac3,dts,dts-hd,truehd");
+    }
+}
@@ -0,0 +1,149 @@
+// The subtitle text mpv is currently showing, for frontends that render it themselves
+// (e.g. with clickable words) instead of letting mpv burn it into the video
+
+use serde::Serialize;
+
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// Observed by the event thread; sub-start/sub-end change together with sub-text
+pub const OBSERVED_PROPERTIES: &[&str] = &["sub-text", "secondary-sub-text"];
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SubtitleText {
+    // None when no line is showing, or the track is image-based (DVD/Blu-ray)
+    pub primary: Option<String>,
+    pub secondary: Option<String>,
+    // Display window of the primary line, in seconds of playback time
+    pub start: Option<f64>,
+    pub end: Option<f64>,
+}
+
+impl SubtitleText {
+    // `raw` keeps ASS override tags such as {\i1} and escapes such as \N
+    pub fn read(handle: &MpvHandle, raw: bool) -> Self {
+        let primary = read_text(handle, "sub-text", raw);
+        Self {
+            start: primary
+                .as_ref()
+                .and_then(|_| handle.get::<f64>("sub-start").ok()),
+            end: primary
+                .as_ref()
+                .and_then(|_| handle.get::<f64>("sub-end").ok()),
+            primary,
+            secondary: read_text(handle, "secondary-sub-text", raw),
+        }
+    }
+}
+
+// The "/ass" sub-property has the event text as written; older mpv versions lack it for
+// secondary subtitles, so fall back to the plain property
+fn read_text(handle: &MpvHandle, name: &str, raw: bool) -> Option<String> {
+    let text = handle
+        .get::<String>(&format!("{}/ass", name))
+        .or_else(|_| handle.get::<String>(name))
+        .ok()?;
+    let text = if raw { text } else { strip_ass_tags(&text) };
+    if text.trim().is_empty() {
+        None
+    } else {
+        Some(text)
+    }
+}
+
+// Plain text of an ASS event: override blocks are dropped, \N and \n become line
+// breaks and \h a space. Vector drawings (between {\p1} and {\p0}) aren't text at all.
+pub fn strip_ass_tags(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut drawing = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' => {
+                let mut block = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    block.push(c);
+                }
+                if !closed {
+                    // libass shows an unterminated brace literally
+                    if !drawing {
+                        plain.push('{');
+                        plain.push_str(&block);
+                    }
+                } else if let Some(level) = drawing_level(&block) {
+                    drawing = level > 0;
+                }
+            }
+            '\\' if matches!(chars.peek(), Some('N' | 'n' | 'h')) => {
+                let escape = chars.next();
+                if !drawing {
+                    plain.push(if escape == Some('h') { ' ' } else { '\n' });
+                }
+            }
+            c if !drawing => plain.push(c),
+            _ => {}
+        }
+    }
+    plain
+}
+
+// The last \p<level> tag in an override block, if any (not \pos, \pbo and friends)
+fn drawing_level(block: &str) -> Option<u32> {
+    block
+        .rsplit('\\')
+        .find_map(|tag| tag.trim().strip_prefix('p')?.parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain_text_is_unchanged() {
+        assert_eq!(strip_ass_tags("Hello, world!"), "Hello, world!");
+        assert_eq!(strip_ass_tags(""), "");
+    }
+
+    #[test]
+    fn override_blocks_are_removed() {
+        assert_eq!(
+            strip_ass_tags(r"{\an8}{\i1}Where{\i0} are you {\c&H00FFFF&\fs40}going?"),
+            "Where are you going?"
+        );
+        assert_eq!(strip_ass_tags(r"{\pos(320,50)\fad(200,200)}Title"), "Title");
+        // Comments are override blocks too
+        assert_eq!(strip_ass_tags("{translator's note}Hi"), "Hi");
+    }
+
+    #[test]
+    fn escapes_become_line_breaks_and_spaces() {
+        assert_eq!(
+            strip_ass_tags(r"First line\NSecond line"),
+            "First line\nSecond line"
+        );
+        assert_eq!(strip_ass_tags(r"soft\nbreak"), "soft\nbreak");
+        assert_eq!(strip_ass_tags(r"50\hkm"), "50 km");
+        // Other backslashes are literal text
+        assert_eq!(strip_ass_tags(r"C:\path"), r"C:\path");
+    }
+
+    #[test]
+    fn drawings_are_dropped() {
+        assert_eq!(
+            strip_ass_tags(r"{\p1}m 0 0 l 100 0 100 100 0 100{\p0}Caption"),
+            "Caption"
+        );
+        // \pos and \pbo don't start or end a drawing
+        assert_eq!(strip_ass_tags(r"{\pos(10,10)\pbo0}Text"), "Text");
+    }
+
+    #[test]
+    fn unterminated_blocks_are_kept() {
+        assert_eq!(strip_ass_tags(r"Smile {\i1"), r"Smile {\i1");
+    }
+}
@@ -0,0 +1,154 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::mpv::{MpvHandle, PlayerBackend};
+
+// One entry of mpv's track-list. Ids are only unique within a type, and are what aid,
+// sid and vid take.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Track {
+    pub id: i64,
+    // "video", "audio" or "sub"
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub language: Option<String>,
+    pub title: Option<String>,
+    pub codec: Option<String>,
+    pub default: bool,
+    pub forced: bool,
+    // Loaded from a separate file, e.g. with sub-add
+    pub external: bool,
+    pub selected: bool,
+}
+
+// Read field by field, like the chapter list, rather than parsing the whole node
+pub fn read_tracks(handle: &MpvHandle) -> Vec<Track> {
+    let count = handle.get::<i64>("track-list/count").unwrap_or(0);
+    (0..count.max(0))
+        .filter_map(|i| {
+            let string = |field: &str| {
+                handle
+                    .get::<String>(&format!("track-list/{}/{}", i, field))
+                    .ok()
+            };
+            let flag = |field: &str| {
+                handle
+                    .get::<bool>(&format!("track-list/{}/{}", i, field))
+                    .unwrap_or(false)
+            };
+            Some(Track {
+                id: handle.get::<i64>(&format!("track-list/{}/id", i)).ok()?,
+                kind: string("type")?,
+                language: string("lang"),
+                title: string("title"),
+                codec: string("codec"),
+                default: flag("default"),
+                forced: flag("forced"),
+                external: flag("external"),
+                selected: flag("selected"),
+            })
+        })
+        .collect()
+}
+
+// The property that selects tracks of `kind`
+fn selector(kind: &str) -> &'static str {
+    match kind {
+        "video" => "vid",
+        "audio" => "aid",
+        _ => "sid",
+    }
+}
+
+// The id of the playing track of `kind`; None when that kind is switched off
+pub fn selected(handle: &MpvHandle, kind: &str) -> Option<i64> {
+    handle.get::<String>(selector(kind)).ok()?.parse().ok()
+}
+
+// Play track `id` of `kind`, or with None switch that kind off
+pub fn select(handle: &MpvHandle, kind: &str, id: Option<i64>) -> Result<(), String> {
+    let property = selector(kind);
+    let value = match id {
+        Some(id) => {
+            if !read_tracks(handle)
+                .iter()
+                .any(|t| t.kind == kind && t.id == id)
+            {
+                return Err(format!("No {} track with id {}", kind, id));
+            }
+            id.to_string()
+        }
+        None => "no".to_string(),
+    };
+    handle
+        .set(property, &value)
+        .map_err(|e| format!("Failed to set {}: {}", property, e))
+}
+
+// Move to the next track of `kind`, passing through "off" after the last, and return
+// the new selection
+pub fn cycle(handle: &MpvHandle, kind: &str) -> Result<Option<i64>, String> {
+    let property = selector(kind);
+    handle
+        .command(&["cycle", property])
+        .map_err(|e| format!("Failed to cycle {}: {}", property, e))?;
+    Ok(selected(handle, kind))
+}
+
+// How sub-add treats the file: select plays it, auto only lists it, and cached selects
+// the copy attached earlier instead of loading the file twice
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SubtitleFlag {
+    #[default]
+    Select,
+    Auto,
+    Cached,
+}
+
+impl SubtitleFlag {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Select => "select",
+            Self::Auto => "auto",
+            Self::Cached => "cached",
+        }
+    }
+}
+
+// Attach an external subtitle file to the current file and return its track. mpv names
+// the track after the file unless given a title.
+pub fn add_subtitle(
+    handle: &MpvHandle,
+    path: &str,
+    flag: SubtitleFlag,
+    title: Option<&str>,
+    language: Option<&str>,
+) -> Result<Track, String> {
+    // The title comes before the language, so spell out mpv's default to pass only one
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+    let mut args = vec!["sub-add", path, flag.as_str()];
+    if let Some(title) = title.or(language.and(file_name.as_deref())) {
+        args.push(title);
+    }
+    if let Some(language) = language {
+        args.push(language);
+    }
+    handle
+        .command(&args)
+        .map_err(|e| format!("Failed to load subtitles: {}", e))?;
+
+    // Tracks are appended, and unless added with auto the new one is also selected
+    let mut subs = read_tracks(handle).into_iter().filter(|t| t.kind == "sub");
+    let track = match flag {
+        SubtitleFlag::Auto => subs.next_back(),
+        _ => {
+            let id = selected(handle, "sub");
+            subs.find(|t| Some(t.id) == id)
+        }
+    };
+    track.ok_or_else(|| format!("Subtitles from {} didn't show up in the track list", path))
+}
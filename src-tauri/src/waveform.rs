@@ -0,0 +1,140 @@
+// Peak waveforms for a waveform seekbar: ffmpeg decodes the audio to mono 8 kHz PCM,
+// the loudest sample of each 10 ms is kept while it streams in, and those are reduced
+// to as many points as the page asked for. Decoding audio alone is quick, a few
+// seconds for an hour-long podcast.
+
+use serde::Serialize;
+use std::io::{BufReader, Read};
+use std::process::{Command, Stdio};
+
+use crate::clip::ffmpeg_available;
+
+const SAMPLE_RATE: usize = 8000;
+
+// Samples per window: 10 ms
+const WINDOW: usize = SAMPLE_RATE / 100;
+
+pub const DEFAULT_POINTS: usize = 1000;
+pub const MAX_POINTS: usize = 20_000;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Waveform {
+    pub path: String,
+    // Seconds of audio the peaks cover
+    pub duration: f64,
+    // 0.0 (silence) to 1.0 (full scale), evenly spaced over the duration
+    pub peaks: Vec<f32>,
+}
+
+pub fn extract(path: &str, points: usize) -> Result<Waveform, String> {
+    if !ffmpeg_available() {
+        return Err("Waveforms need ffmpeg on PATH".to_string());
+    }
+    let mut child = Command::new("ffmpeg")
+        .args(["-hide_banner", "-nostdin", "-loglevel", "error"])
+        .args(["-i", path])
+        .args([
+            "-vn",
+            "-sn",
+            "-dn",
+            "-ac",
+            "1",
+            "-ar",
+            &SAMPLE_RATE.to_string(),
+        ])
+        .args(["-f", "s16le", "-acodec", "pcm_s16le", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
+
+    let windows = match child.stdout.take() {
+        Some(stdout) => window_peaks(BufReader::new(stdout)),
+        None => Vec::new(),
+    };
+    let output = child
+        .wait_with_output()
+        .map_err(|e| format!("ffmpeg did not finish: {}", e))?;
+    if !output.status.success() || windows.is_empty() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = stderr.lines().last().unwrap_or("the file has no audio");
+        return Err(format!("ffmpeg failed: {}", reason));
+    }
+    Ok(Waveform {
+        path: path.to_string(),
+        duration: windows.len() as f64 * WINDOW as f64 / SAMPLE_RATE as f64,
+        peaks: downsample(&windows, points),
+    })
+}
+
+// The loudest sample of each WINDOW of little-endian 16-bit PCM, the last one partial
+fn window_peaks(mut pcm: impl Read) -> Vec<f32> {
+    let mut peaks = Vec::new();
+    let mut buffer = [0u8; WINDOW * 2];
+    loop {
+        let mut filled = 0;
+        while filled < buffer.len() {
+            match pcm.read(&mut buffer[filled..]) {
+                Ok(0) | Err(_) => break,
+                Ok(n) => filled += n,
+            }
+        }
+        if filled < 2 {
+            return peaks;
+        }
+        let peak = buffer[..filled]
+            .chunks_exact(2)
+            .map(|sample| i16::from_le_bytes([sample[0], sample[1]]).unsigned_abs())
+            .max()
+            .unwrap_or(0);
+        peaks.push(f32::from(peak) / 32768.0);
+        if filled < buffer.len() {
+            return peaks;
+        }
+    }
+}
+
+// `points` peaks, each the loudest of the windows it spans; fewer if there aren't as many
+// windows
+fn downsample(windows: &[f32], points: usize) -> Vec<f32> {
+    let points = points.min(windows.len());
+    (0..points)
+        .map(|i| {
+            let start = i * windows.len() / points;
+            let end = ((i + 1) * windows.len() / points).max(start + 1);
+            windows[start..end].iter().copied().fold(0.0, f32::max)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn peaks_are_the_loudest_sample_of_each_window() {
+        let mut pcm = Vec::new();
+        for window in 0..3i16 {
+            for i in 0..WINDOW as i16 {
+                let sample = if i == 7 { -8192 * (window + 1) } else { 100 };
+                pcm.extend_from_slice(&sample.to_le_bytes());
+            }
+        }
+        // Half a window more, with a full-scale sample in it
+        for _ in 0..WINDOW / 2 {
+            pcm.extend_from_slice(&i16::MIN.to_le_bytes());
+        }
+        assert_eq!(window_peaks(&pcm[..]), [0.25, 0.5, 0.75, 1.0]);
+        assert!(window_peaks(&[][..]).is_empty());
+    }
+
+    #[test]
+    fn downsampling_keeps_the_loudest() {
+        let windows = [0.1, 0.9, 0.2, 0.3, 0.5, 0.4];
+        assert_eq!(downsample(&windows, 3), [0.9, 0.3, 0.5]);
+        assert_eq!(downsample(&windows, 2), [0.9, 0.5]);
+        assert_eq!(downsample(&windows, 100), windows);
+        assert!(downsample(&[], 10).is_empty());
+    }
+}
@@ -0,0 +1,61 @@
+import { convertFileSrc } from "@tauri-apps/api/core";
+
+// Software rendering: poll for frames newer than the last one drawn. Each is a
+// 16-byte header (width, height as u32, sequence as u64, little-endian) then RGBA.
+// Until the first frame arrives it only asks a few times a second, as a page that
+// doesn't know the backend may be polling for frames that never come. Returns a
+// function that stops the stream.
+const IDLE_POLL_MS = 250;
+
+export const streamFrames = (canvasRef, { playerId, onFirstFrame } = {}) => {
+  const frameUrl = convertFileSrc(
+    playerId === undefined ? "frame" : String(playerId),
+    "mpvframe",
+  );
+  let lastFrame = 0n;
+  let pending = null;
+  let stopped = false;
+
+  const schedule = () => {
+    if (stopped) return;
+    if (lastFrame === 0n) {
+      pending = setTimeout(drawNext, IDLE_POLL_MS);
+    } else {
+      pending = requestAnimationFrame(drawNext);
+    }
+  };
+
+  const drawNext = async () => {
+    try {
+      const response = await fetch(`${frameUrl}?since=${lastFrame}`);
+      const canvas = canvasRef.value;
+      if (response.status === 200 && canvas) {
+        const data = await response.arrayBuffer();
+        const header = new DataView(data, 0, 16);
+        const width = header.getUint32(0, true);
+        const height = header.getUint32(4, true);
+        const first = lastFrame === 0n;
+        lastFrame = header.getBigUint64(8, true);
+        if (canvas.width !== width || canvas.height !== height) {
+          canvas.width = width;
+          canvas.height = height;
+        }
+        const pixels = new Uint8ClampedArray(data, 16, width * height * 4);
+        canvas
+          .getContext("2d")
+          .putImageData(new ImageData(pixels, width, height), 0, 0);
+        if (first) onFirstFrame?.();
+      }
+    } catch (error) {
+      console.error("Failed to fetch video frame:", error);
+    }
+    schedule();
+  };
+
+  pending = requestAnimationFrame(drawNext);
+  return () => {
+    stopped = true;
+    cancelAnimationFrame(pending);
+    clearTimeout(pending);
+  };
+};
@@ -0,0 +1,5 @@
+// Commands reject with { kind, message } (see src-tauri/src/error.rs); anything else,
+// e.g. an Error thrown on this side, is shown as it is.
+export const errorMessage = (error) => error?.message ?? String(error);
+
+export const isErrorKind = (error, kind) => error?.kind === kind;